@@ -0,0 +1,40 @@
+/*
+ * C interface of yaml-config, built with the `ffi` feature.
+ *
+ * Handles returned by yaml_config_load are released with yaml_config_free. Strings returned
+ * by the library, including error messages, are released with yaml_config_string_free.
+ * Strings passed to the library are only borrowed for the duration of the call.
+ */
+#ifndef YAML_CONFIG_H
+#define YAML_CONFIG_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define YAML_CONFIG_OK 0
+#define YAML_CONFIG_NOT_FOUND 1
+#define YAML_CONFIG_WRONG_TYPE 2
+#define YAML_CONFIG_INVALID_ARGUMENT 3
+
+typedef struct YamlConfig YamlConfig;
+
+/* Returns NULL on failure and, if error is not NULL, stores a message in it. */
+YamlConfig *yaml_config_load(const char *path, char **error);
+void yaml_config_free(YamlConfig *config);
+void yaml_config_string_free(char *s);
+
+/* Each getter returns a YAML_CONFIG_* status and only writes *out on YAML_CONFIG_OK. */
+int yaml_config_get_str(const YamlConfig *config, const char *key, char **out);
+int yaml_config_get_i64(const YamlConfig *config, const char *key, int64_t *out);
+int yaml_config_get_f64(const YamlConfig *config, const char *key, double *out);
+int yaml_config_get_bool(const YamlConfig *config, const char *key, bool *out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
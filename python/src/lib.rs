@@ -0,0 +1,104 @@
+//! Python bindings, built into the `yaml_config` module with `maturin build` in this directory.
+//!
+//! The module loads files with the same flattening and environment resolution as the Rust
+//! crate, so Python services read a configuration exactly as Rust services do:
+//!
+//! ```python
+//! import yaml_config
+//! config = yaml_config.load("config.yaml", prefer_env=True)
+//! config["DATABASE_PORT"]
+//! yaml_config.flattened_key(["database", "port"])  # "DATABASE_PORT"
+//! ```
+//!
+//! Failures raise `yaml_config.ConfigError`.
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString};
+use yaml_config::error::ParseError;
+use yaml_config::{LoadOptions, Preference, Value};
+
+create_exception!(yaml_config, ConfigError, PyException);
+
+fn config_error(e: ParseError) -> PyErr {
+    ConfigError::new_err(e.to_string())
+}
+
+fn options(prefer_env: bool) -> LoadOptions {
+    LoadOptions {
+        preference: if prefer_env {
+            Preference::PreferEnv
+        } else {
+            Preference::PreferYaml
+        },
+        ..Default::default()
+    }
+}
+
+fn to_py<'py>(py: Python<'py>, val: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match val {
+        Value::I32(v) => PyInt::new(py, *v).into_any(),
+        Value::I64(v) => PyInt::new(py, *v).into_any(),
+        Value::F32(v) => PyFloat::new(py, f64::from(*v)).into_any(),
+        Value::F64(v) => PyFloat::new(py, *v).into_any(),
+        Value::String(v) => PyString::new(py, v).into_any(),
+        Value::Bool(v) => PyBool::new(py, *v).to_owned().into_any(),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(to_py(py, item)?)?;
+            }
+            list.into_any()
+        }
+        Value::Map(map) => to_dict(py, map)?.into_any(),
+        Value::Duration(v) => v.into_pyobject(py)?.into_any(),
+        Value::Null => py.None().into_bound(py),
+    })
+}
+
+fn to_dict<'py>(
+    py: Python<'py>,
+    map: &IndexMap<String, Value, FxBuildHasher>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    for (key, val) in map {
+        dict.set_item(key, to_py(py, val)?)?;
+    }
+    Ok(dict)
+}
+
+/// Loads the YAML file at `path` into a dict of flattened keys.
+#[pyfunction]
+#[pyo3(signature = (path, prefer_env = false))]
+fn load(py: Python<'_>, path: &str, prefer_env: bool) -> PyResult<Py<PyDict>> {
+    let config = yaml_config::load_with(path, &options(prefer_env)).map_err(config_error)?;
+    Ok(to_dict(py, &config)?.unbind())
+}
+
+/// Parses a YAML document held in a string into a dict of flattened keys.
+#[pyfunction]
+#[pyo3(signature = (source, prefer_env = false))]
+fn loads(py: Python<'_>, source: &str, prefer_env: bool) -> PyResult<Py<PyDict>> {
+    let config = yaml_config::try_parse_bytes(source.as_bytes(), &options(prefer_env))
+        .map_err(config_error)?;
+    Ok(to_dict(py, &config)?.unbind())
+}
+
+/// Returns the flattened key of a YAML path, which is also the environment variable consulted
+/// for it.
+#[pyfunction]
+fn flattened_key(path: Vec<String>) -> String {
+    yaml_config::flattened_key(&path)
+}
+
+#[pymodule]
+#[pyo3(name = "yaml_config")]
+fn yaml_config_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("ConfigError", m.py().get_type::<ConfigError>())?;
+    m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_function(wrap_pyfunction!(loads, m)?)?;
+    m.add_function(wrap_pyfunction!(flattened_key, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,174 @@
+//! YAML annotated with the origin of every value.
+//!
+//! `Config::annotated_yaml` re-emits the resolved configuration as nested YAML with a trailing
+//! comment on each value naming the layer it came from and whether an environment variable
+//! overrode it, ready to attach to a support ticket:
+//!
+//! ```yaml
+//! db:
+//!   host: "localhost"  # from config/base.yaml
+//!   port: 6543  # from environment variable DB_PORT (config/base.yaml)
+//! ```
+//!
+//! Keys nest as they did in the file that provided them. Keys that weren't read from a YAML
+//! document, such as those of custom sources or overrides, are written at the top level under
+//! their flattened name. Values are written as JSON, which YAML accepts, and secret values are
+//! masked.
+use crate::debug::MASK;
+use crate::json::{write_str, write_value};
+use crate::schema::Visibility;
+use crate::{normalize_key, Config};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+
+/// The keys of one level of the output, by normalized name, with their spelling in the file.
+type Level<'a> = IndexMap<String, (String, Node<'a>), FxBuildHasher>;
+
+enum Node<'a> {
+    /// A value, identified by its flattened key.
+    Leaf(&'a str),
+    Branch(Level<'a>),
+}
+
+/// Places `key` at `path` in `level`, returning `false` if another key already occupies it.
+fn insert<'a>(level: &mut Level<'a>, path: &[String], key: &'a str) -> bool {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    let normalized = normalize_key(segment);
+    if rest.is_empty() {
+        if level.contains_key(&normalized) {
+            return false;
+        }
+        level.insert(normalized, (segment.clone(), Node::Leaf(key)));
+        return true;
+    }
+    let (_, node) = level
+        .entry(normalized)
+        .or_insert_with(|| (segment.clone(), Node::Branch(IndexMap::default())));
+    match node {
+        Node::Branch(children) => insert(children, rest, key),
+        Node::Leaf(_) => false,
+    }
+}
+
+/// Writes `key` as a plain scalar when YAML reads it back unchanged, and quoted otherwise.
+fn write_key(key: &str, out: &mut String) {
+    let plain = !key.is_empty()
+        && !key.starts_with('-')
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.');
+    if plain {
+        out.push_str(key);
+    } else {
+        write_str(key, out);
+    }
+}
+
+/// Describes where the value of `key` came from.
+fn provenance(config: &Config, key: &str) -> String {
+    let origin = match config.origin(key) {
+        Some(origin) => origin,
+        None => return "set by an override".to_string(),
+    };
+    let location = &config.layers()[origin.layer].location;
+    if origin.from_env {
+        format!("from environment variable {} ({})", key, location)
+    } else {
+        format!("from {}", location)
+    }
+}
+
+fn write_level(config: &Config, level: &Level, indent: usize, out: &mut String) {
+    for (name, node) in level.values() {
+        out.push_str(&" ".repeat(indent));
+        write_key(name, out);
+        out.push(':');
+        match node {
+            Node::Branch(children) => {
+                out.push('\n');
+                write_level(config, children, indent + 2, out);
+            }
+            Node::Leaf(key) => {
+                out.push(' ');
+                if config.schema().visibility_of(key) == Visibility::Secret {
+                    write_str(MASK, out);
+                } else {
+                    write_value(&config.values()[*key], out);
+                }
+                out.push_str("  # ");
+                out.push_str(&provenance(config, key));
+                out.push('\n');
+            }
+        }
+    }
+}
+
+/// Renders `config` as YAML annotated with the origin of every value.
+pub fn render(config: &Config) -> String {
+    let mut root = Level::default();
+    for key in config.values().keys() {
+        let placed = match config.origin(key).and_then(|origin| origin.path.as_ref()) {
+            Some(path) => insert(&mut root, path, key),
+            None => false,
+        };
+        if !placed {
+            root.insert(key.clone(), (key.clone(), Node::Leaf(key)));
+        }
+    }
+
+    let mut out = String::new();
+    write_level(config, &root, 0, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ConfigBuilder, KeySpec, Schema, Visibility};
+    use envtestkit::lock::lock_test;
+    use envtestkit::set_env;
+    use std::ffi::OsString;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn values_are_annotated_with_their_origin() {
+        let _lock = lock_test();
+        let _port = set_env(OsString::from("ANNOTATE_DB_PORT"), "6543");
+        let dir = tempdir().unwrap();
+        let base = dir.path().join("base.yaml");
+        fs::write(
+            &base,
+            "annotate:\n  db:\n    host: localhost\n    port: null\n    password: hunter2\n  \
+             my key: [1, 2]\n",
+        )
+        .unwrap();
+        let prod = dir.path().join("prod.yaml");
+        fs::write(&prod, "annotate:\n  db:\n    host: db.internal\n").unwrap();
+        let (base, prod) = (base.to_str().unwrap(), prod.to_str().unwrap());
+
+        let config = ConfigBuilder::new()
+            .with_schema(Schema::new().key(
+                "ANNOTATE_DB_PASSWORD",
+                KeySpec::new().visibility(Visibility::Secret),
+            ))
+            .add_file(base)
+            .add_file(prod)
+            .build()
+            .unwrap()
+            .overridden([("DEBUG", true)]);
+
+        assert_eq!(
+            config.annotated_yaml(),
+            format!(
+                "annotate:\n  db:\n    host: \"db.internal\"  # from {prod}\n    \
+                 port: 6543  # from environment variable ANNOTATE_DB_PORT ({base})\n    \
+                 password: \"********\"  # from {base}\n  \
+                 \"my key\": [1,2]  # from {base}\n\
+                 DEBUG: true  # set by an override\n",
+            )
+        );
+    }
+}
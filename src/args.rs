@@ -0,0 +1,176 @@
+//! Passing a configuration on the command line.
+//!
+//! `Config::to_args` renders the effective configuration as `--set KEY=VALUE` pairs so a
+//! supervisor can respawn workers with exactly its own configuration, and `parse_set_args`
+//! reads them back in the worker. Values are written as YAML flow scalars: strings are always
+//! quoted, so `"8080"` stays a string while `8080` is an integer.
+use crate::json::write_str;
+use crate::resolvers::Resolvers;
+use crate::types::duration;
+use crate::{loader, yaml_to_value, ParseError, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+
+/// The flag preceding each `KEY=VALUE` pair.
+pub const SET_FLAG: &str = "--set";
+
+fn write_f64(f: f64, out: &mut String) {
+    if f.is_nan() {
+        out.push_str(".nan");
+    } else if f.is_infinite() {
+        out.push_str(if f > 0.0 { ".inf" } else { "-.inf" });
+    } else {
+        out.push_str(&format!("{:?}", f));
+    }
+}
+
+/// Appends `val` to `out` in a form `parse_set_args` reads back as the same value.
+pub(crate) fn write_value(val: &Value, out: &mut String) {
+    match val {
+        Value::I32(v) => out.push_str(&v.to_string()),
+        Value::I64(v) => out.push_str(&v.to_string()),
+        Value::F32(v) => write_f64(f64::from(*v), out),
+        Value::F64(v) => write_f64(*v, out),
+        // JSON escapes are a subset of YAML's double-quoted escapes.
+        Value::String(v) => write_str(v, out),
+        Value::Bool(v) => out.push_str(&v.to_string()),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_value(item, out);
+            }
+            out.push(']');
+        }
+        Value::Map(map) => {
+            out.push('{');
+            for (i, (key, item)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_str(key, out);
+                out.push_str(": ");
+                write_value(item, out);
+            }
+            out.push('}');
+        }
+        Value::Duration(v) => out.push_str(&duration::format(*v)),
+        // Only written inside sequences and maps; `Config::to_args` leaves out null keys.
+        Value::Null => out.push_str("null"),
+    }
+}
+
+fn args_error(message: String) -> ParseError {
+    ParseError::new("config::args", message)
+}
+
+/// Parses the value of one `KEY=VALUE` pair.
+fn parse_pair(pair: &str) -> Result<(String, Value), ParseError> {
+    let (key, raw) = match pair.split_once('=') {
+        Some((key, raw)) if !key.is_empty() => (key, raw),
+        _ => {
+            return Err(args_error(format!(
+                "Expected KEY=VALUE, found \"{}\".",
+                pair
+            )))
+        }
+    };
+    if raw.is_empty() {
+        return Ok((key.to_string(), Value::String(String::new())));
+    }
+
+    let docs = loader::load_from_str(raw, &Resolvers::default())?;
+    match docs.first() {
+        Some(doc) if !doc.is_null() && !doc.is_badvalue() => {
+            Ok((key.to_string(), yaml_to_value(key, doc)?))
+        }
+        _ => Err(args_error(format!("{} has no value.", key))),
+    }
+}
+
+/// Collects every `--set KEY=VALUE` (or `--set=KEY=VALUE`) pair in `args`. Other arguments are
+/// ignored so the pairs can be mixed with an application's own flags.
+///
+/// # Examples
+///
+/// ```rust
+/// use yaml_config::args::parse_set_args;
+/// let values = parse_set_args(std::env::args().skip(1)).unwrap();
+/// ```
+pub fn parse_set_args<I, S>(args: I) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let arg = arg.as_ref();
+        let pair = if arg == SET_FLAG {
+            match args.next() {
+                Some(pair) => parse_pair(pair.as_ref())?,
+                None => return Err(args_error(format!("{} is missing a value.", SET_FLAG))),
+            }
+        } else if let Some(pair) = arg.strip_prefix("--set=") {
+            parse_pair(pair)?
+        } else {
+            continue;
+        };
+        values.insert(pair.0, pair.1);
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::args::parse_set_args;
+    use crate::config::Config;
+    use crate::schema::Schema;
+    use crate::Value;
+    use fxhash::FxBuildHasher;
+    use indexmap::IndexMap;
+
+    #[test]
+    fn args_round_trip() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert("PORT".to_string(), Value::I64(8080));
+        values.insert("PORT_STR".to_string(), Value::String("8080".to_string()));
+        values.insert("RATIO".to_string(), Value::F64(1.0));
+        values.insert("LIMIT".to_string(), Value::F64(f64::INFINITY));
+        values.insert("DEBUG".to_string(), Value::Bool(false));
+        values.insert(
+            "NAME".to_string(),
+            Value::String("a \"quoted\"\nname: with, [flow]".to_string()),
+        );
+        values.insert("EMPTY".to_string(), Value::String(String::new()));
+        values.insert(
+            "HOSTS".to_string(),
+            Value::Array(vec![Value::String("a".to_string()), Value::I64(1)]),
+        );
+        let config = Config::new(values.clone(), Vec::new(), Schema::new());
+
+        let args = config.to_args();
+        assert_eq!(args[0], "--set");
+        assert_eq!(args[1], "PORT=8080");
+        assert_eq!(args[3], "PORT_STR=\"8080\"");
+
+        assert_eq!(parse_set_args(&args).unwrap(), values);
+    }
+
+    #[test]
+    fn other_arguments_are_ignored() {
+        let values = parse_set_args(["worker", "--verbose", "--set=A=1", "--set", "B=x"]).unwrap();
+
+        assert_eq!(values.len(), 2);
+        assert_eq!(values["A"], Value::I64(1));
+        assert_eq!(values["B"], Value::String("x".to_string()));
+
+        assert!(parse_set_args(["--set"]).is_err());
+        assert!(parse_set_args(["--set", "=1"]).is_err());
+        assert!(parse_set_args(["--set", "A"]).is_err());
+    }
+}
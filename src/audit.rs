@@ -0,0 +1,88 @@
+//! An audit log of applied configuration changes.
+//!
+//! `SharedConfig::with_audit_log` appends one JSON object per line to a file for every change
+//! it applies, whether by `replace`, `reload`, or `rollback`:
+//!
+//! ```json
+//! {"at":1700000000,"source":"sighup","fingerprint":"9c1f0e3b7a4d2c65","previous_fingerprint":"0b5e8f2d1c3a7946","diff":"- PORT = 80\n+ PORT = 8080\n"}
+//! ```
+//!
+//! `source` names who or what made the change (see `SharedConfig::reload_from`) and `diff`
+//! lists the changed lines of `snapshot::render`, so secret values are masked. A change that
+//! can't be written to the log is not applied.
+use crate::json::write_str;
+use crate::ParseError;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A change about to be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub at: SystemTime,
+    /// Who or what made the change.
+    pub source: String,
+    /// `Config::fingerprint` of the new snapshot.
+    pub fingerprint: u64,
+    /// `Config::fingerprint` of the replaced snapshot.
+    pub previous_fingerprint: u64,
+    /// The changed lines, in the format of `snapshot::render` with unchanged lines omitted.
+    pub diff: String,
+}
+
+impl AuditEntry {
+    /// Renders the entry as a single line of JSON, without the trailing newline.
+    pub fn to_json(&self) -> String {
+        let at = self.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut out = format!("{{\"at\":{},\"source\":", at.as_secs());
+        write_str(&self.source, &mut out);
+        out.push_str(&format!(
+            ",\"fingerprint\":\"{:016x}\",\"previous_fingerprint\":\"{:016x}\",\"diff\":",
+            self.fingerprint, self.previous_fingerprint
+        ));
+        write_str(&self.diff, &mut out);
+        out.push('}');
+        out
+    }
+}
+
+/// Appends entries to a JSON Lines file, creating it if needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        AuditLog {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The file entries are appended to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `entry`. The file is opened for every entry, so it can be rotated at any time.
+    pub fn append(&self, entry: &AuditEntry) -> Result<(), ParseError> {
+        let audit_error = |e: std::io::Error| {
+            ParseError::new(
+                "config::audit",
+                format!(
+                    "Cannot write to the audit log {}: {}.",
+                    self.path.display(),
+                    e
+                ),
+            )
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(audit_error)?;
+        file.write_all(format!("{}\n", entry.to_json()).as_bytes())
+            .map_err(audit_error)
+    }
+}
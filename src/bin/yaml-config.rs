@@ -0,0 +1,41 @@
+//! Command line tools for configuration files.
+//!
+//! ```text
+//! yaml-config lint FILE...
+//! ```
+//!
+//! `lint` checks the files as layers merged in the given order and exits with status 1 when an
+//! error is found.
+use std::env;
+use std::process::ExitCode;
+use yaml_config::lint::{lint_layers, Severity};
+use yaml_config::messages;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.split_first() {
+        Some((command, files)) if command == "lint" && !files.is_empty() => {
+            let files: Vec<&str> = files.iter().map(String::as_str).collect();
+            match lint_layers(&files) {
+                Ok(findings) => {
+                    for finding in &findings {
+                        println!("{}", finding);
+                    }
+                    if findings.iter().any(|f| f.severity == Severity::Error) {
+                        ExitCode::FAILURE
+                    } else {
+                        ExitCode::SUCCESS
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        _ => {
+            eprintln!("{}", messages::text("cli.usage", &[]));
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,509 @@
+//! A binary cache of resolved configurations for fast cold starts.
+//!
+//! A command-line tool that loads the same files on every invocation spends most of its startup
+//! parsing YAML. `BinaryCache::load` stores the resolved values in a compact binary file next to
+//! a fingerprint of their sources and, on the next start, returns them straight from that file
+//! as long as the sources are unchanged. Otherwise the files are loaded as by `load_layered` and
+//! the cache is rewritten.
+//!
+//! The fingerprint covers the contents of every file, of the files they `!include`, and of the
+//! secret files named by `_FILE` variables, the `LoadOptions`, the crate version, and the value
+//! of every environment variable the configuration is bound to (see `Config::env_bindings`), so
+//! setting or changing an override also invalidates the cache.
+//! Values produced by custom tag resolvers, such as `!keyring`, are cached as they were when the
+//! cache was written.
+//!
+//! The cache holds the values in plain text, secrets included. On Unix it is created readable
+//! by its owner only; keep it somewhere only the application can read, such as its cache
+//! directory. A cache that can't be read, is corrupt, or was written by another version is
+//! simply rebuilt, and failing to write it is not an error.
+//!
+//! **Examples**
+//!
+//! ```rust,no_run
+//! use yaml_config::binary_cache::BinaryCache;
+//! use yaml_config::LoadOptions;
+//! let cache = BinaryCache::new("/var/cache/my-cli/config.bin", LoadOptions::default());
+//! let config = cache.load(&["/etc/my-cli/config.yaml", "/etc/my-cli/local.yaml"])?;
+//! # Ok::<(), yaml_config::error::ParseError>(())
+//! ```
+use crate::loader::MAX_DEPTH;
+use crate::{read_counted, secret_files, ConfigBuilder, LoadOptions, ParseError, Reads, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Identifies a cache file. The last byte is the version of the format.
+const MAGIC: &[u8; 8] = b"YCFGBIN\x02";
+
+const I32: u8 = 0;
+const I64: u8 = 1;
+const F32: u8 = 2;
+const F64: u8 = 3;
+const STRING: u8 = 4;
+const BOOL: u8 = 5;
+const ARRAY: u8 = 6;
+const MAP: u8 = 7;
+const NULL: u8 = 8;
+const DURATION: u8 = 9;
+
+/// Caches the values resolved from a set of files in a binary file.
+#[derive(Debug, Clone)]
+pub struct BinaryCache {
+    path: PathBuf,
+    options: LoadOptions,
+}
+
+impl BinaryCache {
+    /// Creates a cache stored at `path`, loading files with `options`.
+    pub fn new<P: Into<PathBuf>>(path: P, options: LoadOptions) -> Self {
+        BinaryCache {
+            path: path.into(),
+            options,
+        }
+    }
+
+    /// Returns the values resolved from `file_paths`, merged in order as by `load_layered`.
+    /// They are read from the cache if it was written for the same sources, and loaded from the
+    /// files otherwise, in which case the cache is rewritten.
+    pub fn load(
+        &self,
+        file_paths: &[&str],
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        let fingerprint = self.fingerprint(file_paths)?;
+        if let Some(values) = fs::read(&self.path)
+            .ok()
+            .and_then(|bytes| decode(&bytes, fingerprint, &self.options))
+        {
+            return Ok(values);
+        }
+
+        let config = file_paths
+            .iter()
+            .fold(
+                ConfigBuilder::new().with_options(self.options.clone()),
+                |builder, path| builder.add_file(path),
+            )
+            .build()?;
+        let mut vars: Vec<String> = config.env_bindings().values().cloned().collect();
+        if self.options.secret_files {
+            let file_vars: Vec<String> = vars.iter().map(|var| format!("{}_FILE", var)).collect();
+            vars.extend(file_vars);
+        }
+        let vars: Vec<&str> = vars.iter().map(String::as_str).collect();
+        let secret_files = secret_files(&config, &self.options);
+        let included: Vec<&Path> = config
+            .layers()
+            .iter()
+            .flat_map(|layer| layer.included.iter())
+            .chain(&secret_files)
+            .map(PathBuf::as_path)
+            .collect();
+        let bytes = encode(
+            fingerprint,
+            &self.options,
+            &vars,
+            &included,
+            config.values(),
+        );
+        // The cache only saves time, so the configuration is returned even if it can't be saved.
+        let _ = write(&self.path, &bytes);
+        Ok(config.into_values())
+    }
+
+    /// Hashes the contents of `file_paths` together with everything else that shapes the
+    /// values loaded from them, except the environment.
+    fn fingerprint(&self, file_paths: &[&str]) -> Result<u64, ParseError> {
+        // The environment is a `HashMap`, so it is hashed in sorted order rather than as its
+        // `Debug` output, whose order changes from run to run.
+        let options = LoadOptions {
+            env: None,
+            ..self.options.clone()
+        };
+        let mut env: Option<Vec<(&String, &String)>> =
+            self.options.env.as_ref().map(|env| env.iter().collect());
+        if let Some(env) = &mut env {
+            env.sort();
+        }
+        let mut sources = vec![
+            env!("CARGO_PKG_VERSION").to_string(),
+            format!("{:?}", options),
+            format!("{:?}", env),
+        ];
+        // The files are read under the options, so the cache never serves values for files the
+        // options wouldn't load.
+        let mut reads = Reads::default();
+        for path in file_paths {
+            let contents = read_counted(Path::new(path), &self.options, &mut reads)?;
+            sources.push(path.to_string());
+            sources.push(contents);
+        }
+        Ok(fxhash::hash64(&sources))
+    }
+}
+
+/// Hashes the current values of the environment variables `vars`, as `options` read them.
+fn env_fingerprint<S: AsRef<str>>(options: &LoadOptions, vars: &[S]) -> u64 {
+    let values: Vec<Option<OsString>> = vars
+        .iter()
+        .map(|var| options.var_os(var.as_ref()))
+        .collect();
+    fxhash::hash64(&values)
+}
+
+/// Hashes the current contents of the included files `paths`. A file that can't be read hashes
+/// differently from any contents.
+fn includes_fingerprint<P: AsRef<Path>>(paths: &[P]) -> u64 {
+    let contents: Vec<Option<Vec<u8>>> = paths.iter().map(|path| fs::read(path).ok()).collect();
+    fxhash::hash64(&contents)
+}
+
+/// Writes `bytes` to a temporary file and moves it over `path`, so a reader never sees half a
+/// cache.
+fn write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp)?;
+    file.write_all(bytes)?;
+    drop(file);
+    fs::rename(&tmp, path)
+}
+
+fn write_varint(mut n: u64, out: &mut Vec<u8>) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_signed(n: i64, out: &mut Vec<u8>) {
+    write_varint(((n << 1) ^ (n >> 63)) as u64, out);
+}
+
+fn write_str(s: &str, out: &mut Vec<u8>) {
+    write_varint(s.len() as u64, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_value(val: &Value, out: &mut Vec<u8>) {
+    match val {
+        Value::I32(v) => {
+            out.push(I32);
+            write_signed(i64::from(*v), out);
+        }
+        Value::I64(v) => {
+            out.push(I64);
+            write_signed(*v, out);
+        }
+        Value::F32(v) => {
+            out.push(F32);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::F64(v) => {
+            out.push(F64);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::String(v) => {
+            out.push(STRING);
+            write_str(v, out);
+        }
+        Value::Bool(v) => out.extend_from_slice(&[BOOL, u8::from(*v)]),
+        Value::Array(items) => {
+            out.push(ARRAY);
+            write_varint(items.len() as u64, out);
+            for item in items {
+                write_value(item, out);
+            }
+        }
+        Value::Map(map) => {
+            out.push(MAP);
+            write_map(map, out);
+        }
+        Value::Duration(v) => {
+            out.push(DURATION);
+            write_varint(v.as_secs(), out);
+            write_varint(u64::from(v.subsec_nanos()), out);
+        }
+        Value::Null => out.push(NULL),
+    }
+}
+
+fn write_map(map: &IndexMap<String, Value, FxBuildHasher>, out: &mut Vec<u8>) {
+    write_varint(map.len() as u64, out);
+    for (key, val) in map {
+        write_str(key, out);
+        write_value(val, out);
+    }
+}
+
+/// Encodes `values` with the fingerprint of their sources and the environment variables and
+/// included files they depend on.
+fn encode(
+    fingerprint: u64,
+    options: &LoadOptions,
+    vars: &[&str],
+    included: &[&Path],
+    values: &IndexMap<String, Value, FxBuildHasher>,
+) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&fingerprint.to_le_bytes());
+    write_varint(vars.len() as u64, &mut out);
+    for var in vars {
+        write_str(var, &mut out);
+    }
+    out.extend_from_slice(&env_fingerprint(options, vars).to_le_bytes());
+    write_varint(included.len() as u64, &mut out);
+    for path in included {
+        write_str(&path.to_string_lossy(), &mut out);
+    }
+    out.extend_from_slice(&includes_fingerprint(included).to_le_bytes());
+    write_map(values, &mut out);
+    out
+}
+
+/// Reads a cache file. Every method returns `None` once the input is exhausted or malformed.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.bytes.len() {
+            return None;
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Some(head)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)?.try_into().ok().map(u64::from_le_bytes)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            n |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Some(n);
+            }
+        }
+        None
+    }
+
+    fn signed(&mut self) -> Option<i64> {
+        let n = self.varint()?;
+        Some((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+
+    /// Reads a count, which can't exceed the bytes left since every item takes at least one.
+    fn count(&mut self) -> Option<usize> {
+        usize::try_from(self.varint()?)
+            .ok()
+            .filter(|n| *n <= self.bytes.len())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.count()?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn value(&mut self, depth: usize) -> Option<Value> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        Some(match self.byte()? {
+            I32 => Value::I32(i32::try_from(self.signed()?).ok()?),
+            I64 => Value::I64(self.signed()?),
+            F32 => Value::F32(f32::from_le_bytes(self.take(4)?.try_into().ok()?)),
+            F64 => Value::F64(f64::from_le_bytes(self.take(8)?.try_into().ok()?)),
+            STRING => Value::String(self.string()?),
+            BOOL => Value::Bool(self.byte()? != 0),
+            ARRAY => {
+                let len = self.count()?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.value(depth + 1)?);
+                }
+                Value::Array(items)
+            }
+            MAP => Value::Map(self.map(depth + 1)?),
+            NULL => Value::Null,
+            DURATION => Value::Duration(Duration::new(
+                self.varint()?,
+                u32::try_from(self.varint()?).ok()?,
+            )),
+            _ => return None,
+        })
+    }
+
+    fn map(&mut self, depth: usize) -> Option<IndexMap<String, Value, FxBuildHasher>> {
+        let len = self.count()?;
+        let mut map = IndexMap::with_capacity_and_hasher(len, FxBuildHasher::default());
+        for _ in 0..len {
+            let key = self.string()?;
+            map.insert(key, self.value(depth)?);
+        }
+        Some(map)
+    }
+}
+
+/// Decodes a cache file, returning `None` unless it is intact and was written for sources with
+/// `fingerprint`, the current values of its environment variables, and the current contents of
+/// its included files.
+fn decode(
+    bytes: &[u8],
+    fingerprint: u64,
+    options: &LoadOptions,
+) -> Option<IndexMap<String, Value, FxBuildHasher>> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC || reader.u64()? != fingerprint {
+        return None;
+    }
+    let mut vars = Vec::new();
+    for _ in 0..reader.count()? {
+        vars.push(reader.string()?);
+    }
+    if reader.u64()? != env_fingerprint(options, &vars) {
+        return None;
+    }
+    let mut included = Vec::new();
+    for _ in 0..reader.count()? {
+        included.push(reader.string()?);
+    }
+    if reader.u64()? != includes_fingerprint(&included) {
+        return None;
+    }
+    let values = reader.map(0)?;
+    reader.bytes.is_empty().then_some(values)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binary_cache::{decode, encode, BinaryCache};
+    use crate::{LoadOptions, Value};
+    use envtestkit::lock::lock_test;
+    use envtestkit::set_env;
+    use fxhash::FxBuildHasher;
+    use indexmap::IndexMap;
+    use std::collections::HashMap;
+    use std::ffi::OsString;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn values_round_trip() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert("A".to_string(), Value::I32(-7));
+        values.insert("B".to_string(), Value::I64(i64::MIN));
+        values.insert("C".to_string(), Value::F32(0.5));
+        values.insert("D".to_string(), Value::F64(-1e300));
+        values.insert("E".to_string(), Value::String("héllo".to_string()));
+        values.insert("F".to_string(), Value::Bool(true));
+        values.insert("G".to_string(), Value::Null);
+        values.insert(
+            "H".to_string(),
+            Value::Array(vec![Value::Map(values.clone()), Value::Array(Vec::new())]),
+        );
+
+        let options = LoadOptions::default();
+        let bytes = encode(42, &options, &[], &[], &values);
+        assert_eq!(decode(&bytes, 42, &options), Some(values));
+        assert_eq!(decode(&bytes, 43, &options), None);
+        assert_eq!(decode(&bytes[..bytes.len() - 1], 42, &options), None);
+        assert_eq!(decode(b"garbage", 42, &options), None);
+    }
+
+    #[test]
+    fn the_cache_is_rebuilt_when_a_source_changes() {
+        let _lock = lock_test();
+        let dir = tempdir().unwrap();
+        let config = dir.path().join("config.yaml");
+        fs::write(&config, "binary_cache:\n  port: 80\n  token: null\n").unwrap();
+        let cache_path = dir.path().join("config.bin");
+        let cache = BinaryCache::new(&cache_path, LoadOptions::default());
+        let paths = [config.to_str().unwrap()];
+
+        let token = set_env(OsString::from("BINARY_CACHE_TOKEN"), "a");
+        assert_eq!(
+            cache.load(&paths).unwrap()["BINARY_CACHE_PORT"],
+            Value::I64(80)
+        );
+        assert!(cache_path.exists());
+
+        // A forged cache for the same sources is trusted, which shows it is being read.
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert("CACHED".to_string(), Value::Bool(true));
+        let fingerprint = cache.fingerprint(&paths).unwrap();
+        fs::write(
+            &cache_path,
+            encode(
+                fingerprint,
+                &LoadOptions::default(),
+                &["BINARY_CACHE_TOKEN"],
+                &[],
+                &values,
+            ),
+        )
+        .unwrap();
+        assert!(cache.load(&paths).unwrap().contains_key("CACHED"));
+
+        drop(token);
+        let _token = set_env(OsString::from("BINARY_CACHE_TOKEN"), "b");
+        let loaded = cache.load(&paths).unwrap();
+        assert_eq!(loaded["BINARY_CACHE_TOKEN"], Value::String("b".to_string()));
+
+        fs::write(&config, "binary_cache:\n  port: 8080\n  token: null\n").unwrap();
+        assert_eq!(
+            cache.load(&paths).unwrap()["BINARY_CACHE_PORT"],
+            Value::I64(8080)
+        );
+        assert_eq!(
+            cache.load(&paths).unwrap()["BINARY_CACHE_PORT"],
+            Value::I64(8080)
+        );
+    }
+
+    #[test]
+    fn the_fingerprint_is_stable_for_an_injected_environment() {
+        let dir = tempdir().unwrap();
+        let config = dir.path().join("config.yaml");
+        fs::write(&config, "token: null\n").unwrap();
+        let paths = [config.to_str().unwrap()];
+        let cache = |token: &str| {
+            let mut env: HashMap<String, String> = (0..32)
+                .map(|i| (format!("VAR_{}", i), i.to_string()))
+                .collect();
+            env.insert("TOKEN".to_string(), token.to_string());
+            let options = LoadOptions {
+                env: Some(env),
+                ..Default::default()
+            };
+            BinaryCache::new(dir.path().join("config.bin"), options)
+        };
+
+        // Each map iterates in its own order, which must not change the fingerprint.
+        assert_eq!(
+            cache("a").fingerprint(&paths).unwrap(),
+            cache("a").fingerprint(&paths).unwrap()
+        );
+        assert_eq!(cache("a").load(&paths).unwrap()["TOKEN"], Value::from("a"));
+        assert_eq!(cache("b").load(&paths).unwrap()["TOKEN"], Value::from("b"));
+    }
+}
@@ -0,0 +1,1265 @@
+//! Layered configuration assembly.
+//!
+//! A `ConfigBuilder` holds an ordered stack of sources. Each source is loaded into its own
+//! flattened map and the maps are merged key-by-key, with later sources overriding earlier ones.
+//! The resulting `Config` remembers the stack so tooling can show how it was assembled.
+//!
+//! Besides files, the builder takes YAML held in memory (`add_str`), environment variables
+//! sharing a prefix (`add_env_prefix`), and values given in code (`add_defaults`), so the usual
+//! defaults, base file, and environment overlay are one stack:
+//!
+//! ```rust,no_run
+//! use yaml_config::ConfigBuilder;
+//! let config = ConfigBuilder::new()
+//!     .add_defaults([("DB_PORT", 5432)])
+//!     .add_str("db:\n  host: localhost\n")
+//!     .add_file("config/base.yaml")
+//!     .add_env_prefix("APP_")
+//!     .build();
+//! ```
+use crate::config::Config;
+use crate::loader::{self, Definition};
+use crate::missing::{MissingKey, MissingReport};
+use crate::permissions::{self, PermissionPolicy};
+use crate::policy::Policies;
+use crate::schema::{Schema, Visibility};
+use crate::{
+    infer_value, load_traced, load_with, parse_document, parse_str, read_document, ErrorCode,
+    LoadOptions, OnMissing, ParseError, Reads, Trace, Value,
+};
+use fxhash::{FxBuildHasher, FxHashMap, FxHashSet};
+use indexmap::{IndexMap, IndexSet};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The kind of source a layer was loaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SourceKind {
+    /// A YAML file on disk.
+    File,
+    /// A YAML document held in memory.
+    Str,
+    /// Environment variables sharing a prefix.
+    Env,
+    /// Values given in code.
+    Defaults,
+    /// A user-provided source, identified by name.
+    Custom(String),
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceKind::File => write!(f, "file"),
+            SourceKind::Str => write!(f, "string"),
+            SourceKind::Env => write!(f, "env"),
+            SourceKind::Defaults => write!(f, "defaults"),
+            SourceKind::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A source of flattened configuration values.
+///
+/// Implement this trait to plug an additional kind of source into a `ConfigBuilder` through
+/// `ConfigBuilder::add_source`.
+pub trait Source {
+    /// The kind of the source, reported by `Config::layers`.
+    fn kind(&self) -> SourceKind;
+
+    /// A human readable location for the source such as a path or URL.
+    fn location(&self) -> String;
+
+    /// Loads the source into a flattened map.
+    fn load(
+        &self,
+        options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError>;
+}
+
+/// A YAML file source.
+struct FileSource {
+    path: String,
+}
+
+impl Source for FileSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::File
+    }
+
+    fn location(&self) -> String {
+        self.path.clone()
+    }
+
+    fn load(
+        &self,
+        options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        load_with(&self.path, options)
+    }
+}
+
+/// Describes one layer of an assembled `Config`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layer {
+    /// The kind of source the layer was loaded from.
+    pub kind: SourceKind,
+    /// The path, URL, or name of the source.
+    pub location: String,
+    /// The absolute path of a file layer with every symbolic link resolved.
+    pub canonical_path: Option<PathBuf>,
+    /// The canonical paths of the files the layer spliced in with `!include`, in the order they
+    /// were read.
+    pub included: Vec<PathBuf>,
+    /// The number of keys the layer provided.
+    pub key_count: usize,
+    /// The position of the layer in the stack. Higher priorities override lower ones.
+    pub priority: usize,
+    /// When `true` no later layer may override the keys provided by this layer.
+    pub read_only: bool,
+}
+
+/// A YAML document held in memory.
+struct StrSource {
+    yaml: String,
+}
+
+impl Source for StrSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Str
+    }
+
+    fn location(&self) -> String {
+        "<string>".to_string()
+    }
+
+    fn load(
+        &self,
+        options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        parse_str(&self.yaml, options)
+    }
+}
+
+/// The environment variables starting with a prefix, keyed by the rest of their name.
+struct EnvSource {
+    prefix: String,
+}
+
+impl Source for EnvSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Env
+    }
+
+    fn location(&self) -> String {
+        format!("{}*", self.prefix)
+    }
+
+    fn load(
+        &self,
+        options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        for (key, val) in options.vars() {
+            match key.strip_prefix(&self.prefix) {
+                Some(key) if !key.is_empty() => {
+                    values.insert(key.to_string(), infer_value(val));
+                }
+                _ => {}
+            }
+        }
+        // The environment has no order of its own, so make the layer deterministic.
+        values.sort_keys();
+        Ok(values)
+    }
+}
+
+/// Values given in code.
+struct DefaultsSource {
+    values: IndexMap<String, Value, FxBuildHasher>,
+}
+
+impl Source for DefaultsSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Defaults
+    }
+
+    fn location(&self) -> String {
+        "defaults".to_string()
+    }
+
+    fn load(
+        &self,
+        _options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        Ok(self.values.clone())
+    }
+}
+
+/// The values a source loaded when a configuration was built, which `Config::resolve_with`
+/// reuses instead of loading the source again.
+struct LoadedSource {
+    kind: SourceKind,
+    location: String,
+    values: IndexMap<String, Value, FxBuildHasher>,
+}
+
+impl Source for LoadedSource {
+    fn kind(&self) -> SourceKind {
+        self.kind.clone()
+    }
+
+    fn location(&self) -> String {
+        self.location.clone()
+    }
+
+    fn load(
+        &self,
+        _options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        Ok(self.values.clone())
+    }
+}
+
+/// A YAML document the builder parses itself, so that the origin of each of its keys is known.
+#[derive(Debug, Clone)]
+enum Document {
+    File(String),
+    Str(String),
+    /// A file whose contents are given rather than read, by `Config::resolve_with`.
+    Contents {
+        path: String,
+        contents: String,
+    },
+}
+
+/// How `Config::resolve_with` loads a layer again.
+#[derive(Debug, Clone)]
+struct ReplayLayer {
+    kind: SourceKind,
+    location: String,
+    document: Option<Document>,
+    env_prefix: Option<String>,
+    /// The values of a layer loaded from any other source.
+    values: IndexMap<String, Value, FxBuildHasher>,
+    read_only: bool,
+    optional: bool,
+    secret: bool,
+}
+
+/// The options and layers a `Config` was built from.
+#[derive(Debug, Clone)]
+pub(crate) struct Recipe {
+    options: LoadOptions,
+    layers: Vec<ReplayLayer>,
+    /// The document of each layer of the `Config`, in the order of `Config::layers`.
+    documents: Vec<Option<Document>>,
+}
+
+impl Recipe {
+    /// The options every layer was loaded with.
+    pub(crate) fn options(&self) -> &LoadOptions {
+        &self.options
+    }
+
+    /// Finds where the key at the YAML `path` of the document of layer `layer` is written.
+    /// Files are read again, so lines may be off if a file changed since it was loaded.
+    pub(crate) fn definition(&self, layer: usize, path: &[String]) -> Option<Definition> {
+        let (file, contents) = match self.documents.get(layer)?.as_ref()? {
+            Document::File(file) => (
+                Some(file.as_str()),
+                Cow::Owned(read_document(file, &self.options).ok()?),
+            ),
+            Document::Str(yaml) => (None, Cow::Borrowed(yaml.as_str())),
+            Document::Contents { path, contents } => {
+                (Some(path.as_str()), Cow::Borrowed(contents.as_str()))
+            }
+        };
+        Some(loader::definition(&contents, path, file, &self.options))
+    }
+
+    /// Returns `true` if `path` is the location of a file layer.
+    pub(crate) fn has_file(&self, path: &str) -> bool {
+        self.layers
+            .iter()
+            .any(|layer| layer.kind == SourceKind::File && layer.location == path)
+    }
+
+    /// Returns a builder for the same layers, with `env` set on top of the environment the
+    /// configuration was loaded from and the file layers named in `files` holding the given
+    /// contents. Other sources provide the values they loaded the first time.
+    pub(crate) fn builder(
+        &self,
+        env: &HashMap<String, String>,
+        files: &HashMap<String, String>,
+    ) -> ConfigBuilder {
+        let mut options = self.options.clone();
+        let mut vars: HashMap<String, String> = options.vars().into_iter().collect();
+        vars.extend(env.iter().map(|(var, val)| (var.clone(), val.clone())));
+        options.env = Some(vars);
+
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| {
+                let document = match (&layer.document, files.get(&layer.location)) {
+                    (Some(Document::File(path)), Some(contents))
+                    | (Some(Document::Contents { path, .. }), Some(contents)) => {
+                        Some(Document::Contents {
+                            path: path.clone(),
+                            contents: contents.clone(),
+                        })
+                    }
+                    (document, _) => document.clone(),
+                };
+                let source: Box<dyn Source> = match &layer.env_prefix {
+                    Some(prefix) => Box::new(EnvSource {
+                        prefix: prefix.clone(),
+                    }),
+                    None => Box::new(LoadedSource {
+                        kind: layer.kind.clone(),
+                        location: layer.location.clone(),
+                        values: layer.values.clone(),
+                    }),
+                };
+                PendingLayer {
+                    source,
+                    document,
+                    env_prefix: layer.env_prefix.clone(),
+                    read_only: layer.read_only,
+                    optional: layer.optional,
+                    secret: layer.secret,
+                }
+            })
+            .collect();
+        ConfigBuilder {
+            options,
+            layers,
+            ..Default::default()
+        }
+    }
+}
+
+/// What provided the value of a key, as reported by `Config::source_of`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValueSourceKind {
+    /// A YAML document, either a file or one held in memory.
+    Yaml,
+    /// A file spliced into a YAML document with `!include`.
+    Include,
+    /// An environment variable.
+    Env,
+    /// A default, given to `add_defaults` or declared in the schema.
+    Default,
+    /// A user-provided source, identified by name.
+    Custom(String),
+    /// A value set on the `Config` itself, e.g. by `Config::overridden`.
+    Override,
+}
+
+/// Where the value of a key came from, as reported by `Config::source_of`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueSource {
+    pub kind: ValueSourceKind,
+    /// The file the value is written in, if it was read from one.
+    pub file: Option<String>,
+    /// The line the value is on in `file`, starting at 1, when it can be found.
+    pub line: Option<usize>,
+}
+
+impl ValueSource {
+    pub(crate) fn new(kind: ValueSourceKind) -> Self {
+        ValueSource {
+            kind,
+            file: None,
+            line: None,
+        }
+    }
+}
+
+/// Where the value of a key of an assembled `Config` came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Origin {
+    /// The index in `Config::layers` of the layer that provided the value.
+    pub layer: usize,
+    /// The YAML path of the key in that layer, if it was read from a YAML document.
+    pub path: Option<Vec<String>>,
+    /// Whether the value was taken from the environment variable named after the key.
+    pub from_env: bool,
+}
+
+/// A source waiting to be loaded by `ConfigBuilder::build`.
+struct PendingLayer {
+    source: Box<dyn Source>,
+    document: Option<Document>,
+    /// The prefix of an `add_env_prefix` layer.
+    env_prefix: Option<String>,
+    read_only: bool,
+    optional: bool,
+    secret: bool,
+}
+
+impl PendingLayer {
+    fn load(
+        &self,
+        options: &LoadOptions,
+        trace: &mut Trace,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        match &self.document {
+            Some(Document::File(path)) => load_traced(path, options, trace),
+            Some(Document::Str(yaml)) => parse_document(yaml, options, None, trace),
+            Some(Document::Contents { path, contents }) => {
+                parse_document(contents, options, Some(Path::new(path)), trace)
+            }
+            None => self.source.load(options),
+        }
+    }
+}
+
+/// Builds a `Config` from an ordered stack of sources.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::ConfigBuilder;
+/// let config = ConfigBuilder::new()
+///     .add_file("path/to/base.yaml")
+///     .read_only()
+///     .add_file("path/to/overrides.yaml")
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct ConfigBuilder {
+    options: LoadOptions,
+    schema: Schema,
+    policies: Policies,
+    permissions: Option<PermissionPolicy>,
+    layers: Vec<PendingLayer>,
+}
+
+impl ConfigBuilder {
+    /// Creates a builder with default `LoadOptions` and no sources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the options used to load every source.
+    pub fn with_options(mut self, options: LoadOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets the schema attached to the resulting `Config`. Building fails if the loaded values
+    /// don't satisfy it; see `Config::mount_schema`.
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Sets the policies evaluated against the resulting `Config`. See the `policy` module.
+    pub fn with_policies(mut self, policies: Policies) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    /// Checks the permissions and ownership of every file layer before loading it. See the
+    /// `permissions` module.
+    pub fn with_permission_policy(mut self, policy: PermissionPolicy) -> Self {
+        self.permissions = Some(policy);
+        self
+    }
+
+    /// Adds a YAML file on top of the current stack.
+    pub fn add_file(mut self, path: &str) -> Self {
+        self = self.add_source(FileSource {
+            path: path.to_string(),
+        });
+        if let Some(layer) = self.layers.last_mut() {
+            layer.document = Some(Document::File(path.to_string()));
+        }
+        self
+    }
+
+    /// Adds a YAML document held in memory on top of the current stack, such as defaults
+    /// embedded with `include_str!`.
+    pub fn add_str(mut self, yaml: &str) -> Self {
+        self = self.add_source(StrSource {
+            yaml: yaml.to_string(),
+        });
+        if let Some(layer) = self.layers.last_mut() {
+            layer.document = Some(Document::Str(yaml.to_string()));
+        }
+        self
+    }
+
+    /// Adds every environment variable whose name starts with `prefix` on top of the current
+    /// stack, keyed by the rest of its name. With the prefix `APP_`, `APP_DB_PORT` provides
+    /// `DB_PORT`. Values are typed as for a `null` YAML value.
+    pub fn add_env_prefix(mut self, prefix: &str) -> Self {
+        self = self.add_source(EnvSource {
+            prefix: prefix.to_string(),
+        });
+        if let Some(layer) = self.layers.last_mut() {
+            layer.env_prefix = Some(prefix.to_string());
+        }
+        self
+    }
+
+    /// Adds values given in code on top of the current stack. Added first, they provide
+    /// defaults every other layer may override. Unlike `Config::register_default`, they are
+    /// part of the layer stack and so are reported by `Config::layers` and checked against
+    /// read-only layers.
+    pub fn add_defaults<I, K, V>(self, defaults: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.add_source(DefaultsSource {
+            values: defaults
+                .into_iter()
+                .map(|(key, val)| (key.into(), val.into()))
+                .collect(),
+        })
+    }
+
+    /// Adds a YAML file of secrets on top of the current stack, such as a `secrets.yaml`
+    /// kept apart from `config.yaml`. Every key it provides is declared
+    /// `Visibility::Secret` in the schema, and a warning is recorded in `Config::warnings`
+    /// when the file is readable by anyone on the host.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new()
+    ///     .add_file("path/to/config.yaml")
+    ///     .add_secrets_file("path/to/secrets.yaml")
+    ///     .build();
+    /// ```
+    pub fn add_secrets_file(mut self, path: &str) -> Self {
+        self = self.add_file(path);
+        if let Some(layer) = self.layers.last_mut() {
+            layer.secret = true;
+        }
+        self
+    }
+
+    /// Adds an arbitrary source on top of the current stack.
+    pub fn add_source<S: Source + 'static>(mut self, source: S) -> Self {
+        self.layers.push(PendingLayer {
+            source: Box::new(source),
+            document: None,
+            env_prefix: None,
+            read_only: false,
+            optional: false,
+            secret: false,
+        });
+        self
+    }
+
+    /// Marks the most recently added source as read-only. Building fails if a later source
+    /// tries to override one of its keys.
+    pub fn read_only(mut self) -> Self {
+        if let Some(layer) = self.layers.last_mut() {
+            layer.read_only = true;
+        }
+        self
+    }
+
+    /// Marks the most recently added source as optional. If it isn't available, because a local
+    /// override file doesn't exist or a remote source fails with
+    /// `ErrorCode::SourceUnreachable`, it is skipped and the failure is recorded in
+    /// `Config::warnings` instead of failing the build. Other failures, such as a syntax error
+    /// in a file that exists, still fail the build.
+    ///
+    /// Sources are required by default.
+    pub fn optional(mut self) -> Self {
+        if let Some(layer) = self.layers.last_mut() {
+            layer.optional = true;
+        }
+        self
+    }
+
+    /// Loads every source in order and merges them into a `Config`.
+    pub fn build(self) -> Result<Config, ParseError> {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        let mut layers = Vec::with_capacity(self.layers.len());
+        // Maps keys owned by a read-only layer to that layer's location.
+        let mut locked: FxHashMap<String, String> = FxHashMap::default();
+        let mut origins = FxHashMap::default();
+        // The priority of the last layer other than an environment prefix providing each key,
+        // and the prefix layers in order.
+        let mut providers: FxHashMap<String, usize> = FxHashMap::default();
+        let mut env_prefixes = Vec::new();
+        // Keys left null with no variable to provide them and no earlier layer providing them.
+        let mut missing: IndexSet<String, FxBuildHasher> = IndexSet::default();
+        // Keys a layer left null with no variable to provide them, when that is an error, and
+        // their YAML paths. Those no other layer provides are reported once every layer is
+        // merged.
+        let mut unresolved: IndexMap<String, Option<Vec<String>>, FxBuildHasher> =
+            IndexMap::default();
+        let mut warnings: Vec<String> = self.options.check_env()?.into_iter().collect();
+        let mut replay = Vec::with_capacity(self.layers.len());
+        let mut documents = Vec::with_capacity(self.layers.len());
+        let mut schema = self.schema;
+        // The files read by every layer so far, which share one read budget.
+        let mut reads = Reads::default();
+
+        for (priority, pending) in self.layers.into_iter().enumerate() {
+            let location = pending.source.location();
+            let is_file = pending.source.kind() == SourceKind::File;
+            // The path the file is read from, relative to `base_dir` if set.
+            let resolved = match (&self.options.base_dir, is_file) {
+                (_, false) => None,
+                (Some(base_dir), true) => Some(base_dir.join(&location)),
+                (None, true) => Some(PathBuf::from(&location)),
+            };
+            let canonical_path = resolved
+                .as_ref()
+                .and_then(|path| fs::canonicalize(path).ok());
+            // An optional file that doesn't exist is skipped, so there is nothing to check.
+            let absent = pending.optional && canonical_path.is_none();
+            if let (Some(path), Some(policy), false) = (&resolved, &self.permissions, absent) {
+                warnings.extend(policy.check(path)?);
+            }
+            let read_before = reads.files.len();
+            let mut trace = Trace {
+                reads: reads.clone(),
+                ..Trace::default()
+            };
+            let loaded = pending.load(&self.options, &mut trace);
+            let replayed = pending.document.is_some() || pending.env_prefix.is_some();
+            let loaded = match loaded {
+                Ok(loaded) => loaded,
+                Err(e) if pending.optional && unavailable(&e) => {
+                    warnings.push(format!("Skipped optional layer {}: {}", location, e));
+                    // A document may load in a what-if, while other sources have nothing to
+                    // replay.
+                    if replayed {
+                        replay.push(ReplayLayer {
+                            kind: pending.source.kind(),
+                            location,
+                            document: pending.document,
+                            env_prefix: pending.env_prefix,
+                            values: IndexMap::default(),
+                            read_only: pending.read_only,
+                            optional: true,
+                            secret: pending.secret,
+                        });
+                    }
+                    continue;
+                }
+                Err(ParseError::MissingEnv { .. } | ParseError::MissingKeys { .. })
+                    if pending.document.is_some() =>
+                {
+                    let options = LoadOptions {
+                        allow_missing: OnMissing::Null,
+                        ..self.options.clone()
+                    };
+                    trace = Trace {
+                        reads: reads.clone(),
+                        ..Trace::default()
+                    };
+                    let loaded = pending.load(&options, &mut trace)?;
+                    for key in &trace.missing {
+                        unresolved
+                            .entry(key.clone())
+                            .or_insert_with(|| trace.paths.get(key).cloned());
+                    }
+                    loaded
+                }
+                Err(e) => return Err(e),
+            };
+            replay.push(ReplayLayer {
+                kind: pending.source.kind(),
+                location: location.clone(),
+                document: pending.document.clone(),
+                env_prefix: pending.env_prefix.clone(),
+                values: if replayed {
+                    IndexMap::default()
+                } else {
+                    loaded.clone()
+                },
+                read_only: pending.read_only,
+                optional: pending.optional,
+                secret: pending.secret,
+            });
+
+            // A missing key leaves the value of an earlier layer in place rather than replacing it.
+            let layer_missing: FxHashSet<String> = trace.missing.drain(..).collect();
+            for key in loaded.keys().filter(|key| !layer_missing.contains(*key)) {
+                if let Some(owner) = locked.get(key) {
+                    return Err(ParseError::with_code(
+                        ErrorCode::ReadOnlyOverride,
+                        "config::builder",
+                        format!(
+                            "{} cannot override {} provided by read-only layer {}.",
+                            location, key, owner
+                        ),
+                    ));
+                }
+            }
+
+            reads = mem::take(&mut trace.reads);
+            let included = reads.files[read_before..]
+                .iter()
+                .filter(|path| Some(*path) != canonical_path.as_ref())
+                .cloned()
+                .collect();
+            documents.push(pending.document.clone());
+            layers.push(Layer {
+                kind: pending.source.kind(),
+                location: location.clone(),
+                canonical_path,
+                included,
+                key_count: loaded.len(),
+                priority,
+                read_only: pending.read_only,
+            });
+
+            if pending.secret {
+                if resolved.as_deref().is_some_and(permissions::world_readable) {
+                    warnings.push(format!(
+                        "The secrets file {} is readable by every user on the host.",
+                        location
+                    ));
+                }
+                for key in loaded.keys() {
+                    let spec = schema.get(key).cloned().unwrap_or_default();
+                    schema = schema.key(key, spec.visibility(Visibility::Secret));
+                }
+            }
+
+            match &pending.env_prefix {
+                Some(prefix) => env_prefixes.push((priority, prefix.clone())),
+                None => providers.extend(loaded.keys().map(|key| (key.clone(), priority))),
+            }
+
+            let env_keys: FxHashSet<String> = trace.env_keys.into_iter().collect();
+            for key in &layer_missing {
+                if !values.contains_key(key) {
+                    missing.insert(key.clone());
+                }
+            }
+            for (key, val) in loaded {
+                if !layer_missing.contains(&key) {
+                    missing.shift_remove(&key);
+                } else if values.contains_key(&key) {
+                    continue;
+                }
+                if pending.read_only {
+                    locked.insert(key.clone(), location.clone());
+                }
+                origins.insert(
+                    key.clone(),
+                    Origin {
+                        layer: layers.len() - 1,
+                        path: trace.paths.remove(&key),
+                        from_env: env_keys.contains(&key),
+                    },
+                );
+                values.insert(key, val);
+            }
+        }
+
+        unresolved.retain(|key, _| missing.contains(key));
+        if !unresolved.is_empty() {
+            return Err(missing_keys(unresolved, &schema, &values, &env_prefixes));
+        }
+
+        let prefer_env = self.options.preference.prefers_env();
+        let mut env_bindings = IndexMap::with_hasher(FxBuildHasher::default());
+        for key in values.keys() {
+            let provider = providers.get(key);
+            // An environment prefix layer above every other layer providing the key wins.
+            let prefixed = env_prefixes
+                .iter()
+                .rev()
+                .find(|(priority, _)| provider.is_none_or(|provider| priority > provider));
+            let binding = match prefixed {
+                Some((_, prefix)) => Some(format!("{}{}", prefix, key)),
+                // Otherwise a YAML document consults the variable named after the key if the
+                // options prefer the environment, or if the document left the value null.
+                None => origins
+                    .get(key)
+                    .filter(|origin: &&Origin| {
+                        origin.path.is_some()
+                            && (prefer_env || origin.from_env || missing.contains(key))
+                    })
+                    .map(|_| key.clone()),
+            };
+            if let Some(binding) = binding {
+                env_bindings.insert(key.clone(), binding);
+            }
+        }
+
+        let mut config = Config::new(values, layers, Schema::new())
+            .with_origins(origins)
+            .with_env_bindings(env_bindings)
+            .with_missing(missing.into_iter().collect())
+            .with_resolvers(self.options.resolvers.clone())
+            .with_warnings(warnings)
+            .with_recipe(Arc::new(Recipe {
+                options: self.options,
+                layers: replay,
+                documents,
+            }));
+        config.mount_schema(schema)?;
+        config.with_policies(self.policies)
+    }
+}
+
+/// The error for the keys layers left null with no variable to provide them, listing the
+/// required keys of `schema` no layer provides as well when there are several.
+/// Returns `true` if `error` means a source isn't there, rather than that it is broken.
+fn unavailable(error: &ParseError) -> bool {
+    match error {
+        ParseError::Io { source } => source.kind() == io::ErrorKind::NotFound,
+        error => error.code() == ErrorCode::SourceUnreachable,
+    }
+}
+
+fn missing_keys(
+    unresolved: IndexMap<String, Option<Vec<String>>, FxBuildHasher>,
+    schema: &Schema,
+    values: &IndexMap<String, Value, FxBuildHasher>,
+    env_prefixes: &[(usize, String)],
+) -> ParseError {
+    let example = |key: &str| schema.get(key).and_then(|spec| spec.get_example()).cloned();
+    let mut keys: Vec<MissingKey> = unresolved
+        .into_iter()
+        .map(|(key, path)| MissingKey {
+            path,
+            env_var: Some(key.clone()),
+            example: example(&key),
+            key,
+        })
+        .collect();
+    for (key, spec) in schema.iter() {
+        let provided = values.get(key).is_some_and(|val| !val.is_null());
+        if spec.is_required()
+            && !provided
+            && spec.get_default().is_none()
+            && !keys.iter().any(|missing| missing.key == *key)
+        {
+            keys.push(MissingKey {
+                key: key.clone(),
+                path: None,
+                // Only a prefix layer reads variables for keys no document mentions.
+                env_var: env_prefixes
+                    .last()
+                    .map(|(_, prefix)| format!("{}{}", prefix, key)),
+                example: spec.get_example().cloned(),
+            });
+        }
+    }
+
+    if keys.len() == 1 {
+        let key = keys.remove(0).key;
+        return ParseError::MissingEnv { key };
+    }
+    ParseError::MissingKeys {
+        report: MissingReport::new(keys),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::{SourceKind, ValueSourceKind};
+    use crate::schema::Visibility;
+    use crate::{
+        ConfigBuilder, KeySpec, LoadOptions, OnMissing, ParseError, ReadBudget, Schema, Value,
+    };
+    use envtestkit::lock::lock_test;
+    use envtestkit::set_env;
+    use std::ffi::OsString;
+    use std::fs::{self, File};
+    use std::io::Write;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    #[test]
+    fn the_source_of_every_value_is_reported() {
+        let _lock = lock_test();
+        let _host = set_env(OsString::from("PROVENANCE_TEST_DB_HOST"), "db.internal");
+        let dir = tempdir().unwrap();
+        let app = dir.path().join("app.yaml");
+        fs::write(&app, "name: app\ndb: !include db.yaml\n").unwrap();
+        fs::write(
+            dir.path().join("db.yaml"),
+            "host: localhost\n\nport: 5432\n",
+        )
+        .unwrap();
+
+        let config = ConfigBuilder::new()
+            .add_defaults([("TIMEOUT", 30)])
+            .add_file(app.to_str().unwrap())
+            .add_env_prefix("PROVENANCE_TEST_")
+            .build()
+            .unwrap()
+            .overridden([("DEBUG", true)]);
+
+        let name = config.source_of("NAME").unwrap();
+        assert_eq!(name.kind, ValueSourceKind::Yaml);
+        assert_eq!(name.file.as_deref(), app.to_str());
+        assert_eq!(name.line, Some(1));
+        let port = config.source_of("DB_PORT").unwrap();
+        assert_eq!(port.kind, ValueSourceKind::Include);
+        assert!(port.file.unwrap().ends_with("db.yaml"));
+        assert_eq!(port.line, Some(3));
+        assert_eq!(
+            config.source_of("DB_HOST").unwrap().kind,
+            ValueSourceKind::Env
+        );
+        assert_eq!(
+            config.source_of("TIMEOUT").unwrap().kind,
+            ValueSourceKind::Default
+        );
+        assert_eq!(
+            config.source_of("DEBUG").unwrap().kind,
+            ValueSourceKind::Override
+        );
+        assert_eq!(config.source_of("MISSING"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn includes_of_linked_files_are_found() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("real")).unwrap();
+        fs::write(dir.path().join("real/app.yaml"), "db: !include db.yaml\n").unwrap();
+        fs::write(dir.path().join("real/db.yaml"), "port: 5432\n").unwrap();
+        let link = dir.path().join("app.yaml");
+        std::os::unix::fs::symlink(dir.path().join("real/app.yaml"), &link).unwrap();
+
+        let config = ConfigBuilder::new()
+            .add_file(link.to_str().unwrap())
+            .build()
+            .unwrap();
+
+        let port = config.source_of("DB_PORT").unwrap();
+        assert_eq!(port.kind, ValueSourceKind::Include);
+        assert!(port.file.unwrap().ends_with("real/db.yaml"));
+        assert_eq!(port.line, Some(1));
+    }
+
+    #[test]
+    fn every_missing_key_is_reported_at_once() {
+        let _lock = lock_test();
+        let schema = Schema::new()
+            .key("DB_PASSWORD", KeySpec::new().example("hunter2"))
+            .key("SERVER_PORT", KeySpec::new().required().example(8080_i64));
+        let error = ConfigBuilder::new()
+            .with_schema(schema)
+            .add_str("db:\n  host: ~\n  port: 5432\n  password: ~\n")
+            .add_env_prefix("MISSING_TEST_UNSET_")
+            .build()
+            .unwrap_err();
+
+        match &error {
+            ParseError::MissingKeys { report } => assert_eq!(report.keys().len(), 3),
+            error => panic!("unexpected error {:?}", error),
+        }
+        assert_eq!(
+            error.message(),
+            "3 required keys are missing.\n\n\
+             db\n  \
+             DB_HOST at db.host: set DB_HOST\n  \
+             DB_PASSWORD at db.password: set DB_PASSWORD, e.g. \"hunter2\"\n\
+             server\n  \
+             SERVER_PORT: set MISSING_TEST_UNSET_SERVER_PORT, e.g. 8080"
+        );
+
+        let error = ConfigBuilder::new()
+            .add_str("db:\n  host: ~\n")
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, ParseError::MissingEnv { key } if key == "DB_HOST"));
+
+        // Only keys no layer provides are reported.
+        let error = ConfigBuilder::new()
+            .add_str("db:\n  host: localhost\n  user: ~\n")
+            .add_str("db:\n  host: ~\n  port: ~\n  user: admin\n")
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, ParseError::MissingEnv { key } if key == "DB_PORT"));
+    }
+
+    #[test]
+    fn missing_keys_leave_earlier_layers_in_place() {
+        let options = LoadOptions {
+            allow_missing: OnMissing::Null,
+            ..Default::default()
+        };
+        let mut config = ConfigBuilder::new()
+            .with_options(options)
+            .add_str("builder_missing:\n  host: localhost\n  user: null\n")
+            .add_str("builder_missing:\n  host: null\n  token: null\n  user: admin\n")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            config.get("BUILDER_MISSING_HOST"),
+            Some(&Value::from("localhost"))
+        );
+        assert_eq!(config.get("BUILDER_MISSING_TOKEN"), Some(&Value::Null));
+        assert_eq!(
+            config.missing_keys(),
+            &["BUILDER_MISSING_TOKEN".to_string()]
+        );
+        assert_eq!(
+            config
+                .env_bindings()
+                .get("BUILDER_MISSING_TOKEN")
+                .map(String::as_str),
+            Some("BUILDER_MISSING_TOKEN")
+        );
+
+        config.register_default("BUILDER_MISSING_TOKEN", "anonymous");
+        assert_eq!(
+            config.get("BUILDER_MISSING_TOKEN"),
+            Some(&Value::from("anonymous"))
+        );
+        let config = config.overridden([("BUILDER_MISSING_TOKEN", "secret")]);
+        assert!(config.missing_keys().is_empty());
+    }
+
+    #[test]
+    fn defaults_strings_files_and_environment_are_layered() {
+        let _lock = lock_test();
+        let _port = set_env(OsString::from("BUILDER_TEST_DB_PORT"), "6543");
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("base.yaml");
+        fs::write(&path, "db:\n  host: db.internal\n").unwrap();
+
+        let config = ConfigBuilder::new()
+            .add_defaults([("DB_HOST", "localhost"), ("DB_NAME", "app")])
+            .add_str("db:\n  host: db.local\n  port: 5432\n")
+            .add_file(path.to_str().unwrap())
+            .add_env_prefix("BUILDER_TEST_")
+            .build()
+            .unwrap();
+
+        assert_eq!(config.get("DB_HOST"), Some(&Value::from("db.internal")));
+        assert_eq!(config.get("DB_NAME"), Some(&Value::from("app")));
+        assert_eq!(config.get("DB_PORT"), Some(&Value::I64(6543)));
+        let kinds: Vec<SourceKind> = config.layers().iter().map(|l| l.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SourceKind::Defaults,
+                SourceKind::Str,
+                SourceKind::File,
+                SourceKind::Env
+            ]
+        );
+        assert_eq!(config.layers()[3].location, "BUILDER_TEST_*");
+        assert_eq!(config.layers()[3].key_count, 1);
+    }
+
+    #[test]
+    fn later_layers_override_earlier_layers() {
+        let dir = tempdir().unwrap();
+        let base_path = dir.path().join("base.yaml");
+        let mut base = File::create(&base_path).unwrap();
+        writeln!(base, "a: 1\nb: 2").unwrap();
+        let prod_path = dir.path().join("prod.yaml");
+        let mut prod = File::create(&prod_path).unwrap();
+        writeln!(prod, "b: 3").unwrap();
+
+        let config = ConfigBuilder::new()
+            .add_file(base_path.to_str().unwrap())
+            .add_file(prod_path.to_str().unwrap())
+            .build()
+            .unwrap();
+
+        assert_eq!(*config.get("A").unwrap().as_i64().unwrap(), 1);
+        assert_eq!(*config.get("B").unwrap().as_i64().unwrap(), 3);
+
+        let layers = config.layers();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].kind, SourceKind::File);
+        assert_eq!(layers[0].location, base_path.to_str().unwrap());
+        assert_eq!(
+            layers[0].canonical_path,
+            Some(fs::canonicalize(&base_path).unwrap())
+        );
+        assert_eq!(layers[0].key_count, 2);
+        assert_eq!(layers[1].key_count, 1);
+        assert_eq!(layers[1].priority, 1);
+        assert!(!layers[1].read_only);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn read_only_layers_cannot_be_overridden() {
+        let dir = tempdir().unwrap();
+        let base_path = dir.path().join("base.yaml");
+        let mut base = File::create(&base_path).unwrap();
+        writeln!(base, "a: 1").unwrap();
+        let prod_path = dir.path().join("prod.yaml");
+        let mut prod = File::create(&prod_path).unwrap();
+        writeln!(prod, "a: 2").unwrap();
+
+        let res = ConfigBuilder::new()
+            .add_file(base_path.to_str().unwrap())
+            .read_only()
+            .add_file(prod_path.to_str().unwrap())
+            .build();
+
+        assert!(res.is_err());
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn optional_layers_are_skipped_when_they_fail() {
+        let dir = tempdir().unwrap();
+        let base_path = dir.path().join("base.yaml");
+        let mut base = File::create(&base_path).unwrap();
+        writeln!(base, "a: 1").unwrap();
+        let local_path = dir.path().join("config.local.yaml");
+
+        let config = ConfigBuilder::new()
+            .add_file(base_path.to_str().unwrap())
+            .add_file(local_path.to_str().unwrap())
+            .optional()
+            .build()
+            .unwrap();
+
+        assert_eq!(*config.get("A").unwrap().as_i64().unwrap(), 1);
+        assert_eq!(config.layers().len(), 1);
+        assert_eq!(config.warnings().len(), 1);
+        assert!(config.warnings()[0].contains("config.local.yaml"));
+
+        let res = ConfigBuilder::new()
+            .add_file(local_path.to_str().unwrap())
+            .build();
+        assert!(res.is_err());
+
+        // A file that exists but is broken is an error even if optional.
+        fs::write(&local_path, "a: [1\n").unwrap();
+        let err = ConfigBuilder::new()
+            .add_file(base_path.to_str().unwrap())
+            .add_file(local_path.to_str().unwrap())
+            .optional()
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ParseError::Scan { .. }));
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn keys_of_secrets_files_are_secret() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        fs::write(&config_path, "db:\n  host: localhost\n").unwrap();
+        let secrets_path = dir.path().join("secrets.yaml");
+        fs::write(&secrets_path, "db:\n  password: hunter2\n").unwrap();
+        #[cfg(unix)]
+        fs::set_permissions(&secrets_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let config = ConfigBuilder::new()
+            .add_file(config_path.to_str().unwrap())
+            .add_secrets_file(secrets_path.to_str().unwrap())
+            .build()
+            .unwrap();
+
+        let schema = config.schema();
+        assert_eq!(schema.visibility_of("DB_PASSWORD"), Visibility::Secret);
+        assert_eq!(schema.visibility_of("DB_HOST"), Visibility::Internal);
+        #[cfg(unix)]
+        assert!(config.warnings()[0].contains("readable by every user"));
+
+        // A file relative to `base_dir` is checked where it is read from.
+        let config = ConfigBuilder::new()
+            .with_options(LoadOptions {
+                base_dir: Some(dir.path().to_path_buf()),
+                ..Default::default()
+            })
+            .add_secrets_file("secrets.yaml")
+            .build()
+            .unwrap();
+        #[cfg(unix)]
+        assert!(config.warnings()[0].contains("readable by every user"));
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn read_budgets_cover_every_file() {
+        let dir = tempdir().unwrap();
+        let mut paths = Vec::new();
+        for name in ["a.yaml", "b.yaml", "c.yaml"] {
+            let path = dir.path().join(name);
+            fs::write(&path, "key: 12345\n").unwrap();
+            paths.push(path.to_str().unwrap().to_string());
+        }
+        let build = |budget: ReadBudget| {
+            let options = LoadOptions {
+                read_budget: budget,
+                ..Default::default()
+            };
+            paths
+                .iter()
+                .fold(ConfigBuilder::new().with_options(options), |b, path| {
+                    b.add_file(path)
+                })
+                .build()
+        };
+
+        assert!(build(ReadBudget {
+            max_files: Some(3),
+            max_bytes: Some(33),
+        })
+        .is_ok());
+        assert!(build(ReadBudget {
+            max_files: Some(2),
+            max_bytes: None,
+        })
+        .unwrap_err()
+        .message()
+        .ends_with("exceeds the read budget of 2 files."));
+        assert!(build(ReadBudget {
+            max_files: None,
+            max_bytes: Some(32),
+        })
+        .unwrap_err()
+        .message()
+        .ends_with("exceeds the read budget of 10 bytes."));
+
+        // Included files count toward the same budget.
+        fs::write(dir.path().join("c.yaml"), "key: !include d.yaml\n").unwrap();
+        fs::write(dir.path().join("d.yaml"), "12345\n").unwrap();
+        let config = build(ReadBudget {
+            max_files: Some(4),
+            max_bytes: None,
+        })
+        .unwrap();
+        assert_eq!(
+            config.layers()[2].included,
+            vec![dir.path().join("d.yaml").canonicalize().unwrap()]
+        );
+        assert!(build(ReadBudget {
+            max_files: Some(3),
+            max_bytes: None,
+        })
+        .unwrap_err()
+        .message()
+        .ends_with("exceeds the read budget of 3 files."));
+
+        dir.close().unwrap();
+    }
+}
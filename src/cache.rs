@@ -0,0 +1,245 @@
+//! A cache of configurations composed from the same files.
+//!
+//! Services that assemble a configuration from a set of fragments again and again, such as a
+//! multi-tenant gateway loading a tenant's files on every request, can load through a
+//! `ConfigCache`. It keys each merged `Config` by the paths and contents of its files, so a
+//! cached configuration is only reused while none of its files, nor any file they `!include` or
+//! secret file it read, has changed. Changed files
+//! produce a new entry, and the least recently used entries are dropped once the cache is full.
+//! Environment variables are only read when a configuration is first built.
+//!
+//! **Examples**
+//!
+//! ```rust,no_run
+//! use yaml_config::cache::ConfigCache;
+//! use yaml_config::LoadOptions;
+//! let cache = ConfigCache::new(LoadOptions::default());
+//! let config = cache.load(&["tenants/base.yaml", "tenants/acme.yaml"])?;
+//! # Ok::<(), yaml_config::error::ParseError>(())
+//! ```
+use crate::{read_counted, secret_files, Config, ConfigBuilder, LoadOptions, ParseError, Reads};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The number of configurations kept unless set with `ConfigCache::with_capacity`.
+pub const CAPACITY: usize = 64;
+
+/// The paths and contents a configuration was composed from.
+type Fragments = Vec<(String, String)>;
+
+/// The files a configuration included or read secrets from, with their contents when it was
+/// built.
+type Included = Vec<(PathBuf, String)>;
+
+/// Cached configurations keyed by the hash of their fragments, least recently used first. The
+/// fragments are kept to tell apart sets of files whose hashes collide.
+type Entries = IndexMap<u64, (Fragments, Included, Arc<Config>), FxBuildHasher>;
+
+/// Caches merged configurations by the contents of their files.
+#[derive(Debug)]
+pub struct ConfigCache {
+    options: LoadOptions,
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl ConfigCache {
+    /// Creates an empty cache loading files with `options`.
+    pub fn new(options: LoadOptions) -> Self {
+        ConfigCache {
+            options,
+            capacity: CAPACITY,
+            entries: Mutex::new(IndexMap::with_hasher(FxBuildHasher::default())),
+        }
+    }
+
+    /// Keeps at most `capacity` configurations. A capacity of zero is treated as one.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Returns the configuration composed from `paths`, merged in order as by
+    /// `ConfigBuilder::add_file`, building it only if no cached configuration was composed from
+    /// files with the same contents.
+    pub fn load(&self, paths: &[&str]) -> Result<Arc<Config>, ParseError> {
+        // The files are read under the options before they are hashed, so a cached
+        // configuration is never handed out for files the options wouldn't load.
+        let mut reads = Reads::default();
+        let fragments = paths
+            .iter()
+            .map(|path| {
+                let contents = read_counted(Path::new(path), &self.options, &mut reads)?;
+                Ok((path.to_string(), contents))
+            })
+            .collect::<Result<Fragments, ParseError>>()?;
+        let hash = fxhash::hash64(&fragments);
+
+        let mut entries = self.lock();
+        if let Some((cached, included, config)) = entries.shift_remove(&hash) {
+            let unchanged = included
+                .iter()
+                .all(|(path, contents)| read_to_string(path).ok().as_ref() == Some(contents));
+            if cached == fragments && unchanged {
+                let config = Arc::clone(&config);
+                entries.insert(hash, (cached, included, Arc::clone(&config)));
+                return Ok(config);
+            }
+        }
+        drop(entries);
+
+        let config = Arc::new(
+            paths
+                .iter()
+                .fold(
+                    ConfigBuilder::new().with_options(self.options.clone()),
+                    |builder, path| builder.add_file(path),
+                )
+                .build()?,
+        );
+
+        let included = config
+            .layers()
+            .iter()
+            .flat_map(|layer| &layer.included)
+            .chain(&secret_files(&config, &self.options))
+            .map(|path| Ok((path.clone(), read_to_string(path)?)))
+            .collect::<Result<Included, ParseError>>()?;
+
+        let mut entries = self.lock();
+        entries.insert(hash, (fragments, included, Arc::clone(&config)));
+        while entries.len() > self.capacity {
+            entries.shift_remove_index(0);
+        }
+        Ok(config)
+    }
+
+    /// The number of cached configurations.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no configuration is cached.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Drops every cached configuration.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        // The map is consistent between operations, so a poisoned lock is still usable.
+        match self.entries.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cache::ConfigCache;
+    use crate::{LoadOptions, ReadBudget, Value};
+    use std::fs;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn configurations_are_reused_until_a_file_changes() {
+        let dir = tempdir().unwrap();
+        let base = dir.path().join("base.yaml");
+        let tenant = dir.path().join("tenant.yaml");
+        fs::write(&base, "port: 80\n").unwrap();
+        fs::write(&tenant, "port: 8080\n").unwrap();
+        let paths = [base.to_str().unwrap(), tenant.to_str().unwrap()];
+        let cache = ConfigCache::new(LoadOptions::default()).with_capacity(1);
+
+        let first = cache.load(&paths).unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.load(&paths).unwrap()));
+        assert_eq!(first.get("PORT"), Some(&Value::I64(8080)));
+
+        fs::write(&tenant, "port: 9090\n").unwrap();
+        let changed = cache.load(&paths).unwrap();
+        assert_eq!(changed.get("PORT"), Some(&Value::I64(9090)));
+        assert_eq!(cache.len(), 1);
+
+        let base_only = cache.load(&paths[..1]).unwrap();
+        assert_eq!(base_only.get("PORT"), Some(&Value::I64(80)));
+        assert_eq!(cache.len(), 1);
+
+        fs::write(&tenant, "db: !include db.yaml\n").unwrap();
+        fs::write(dir.path().join("db.yaml"), "port: 5432\n").unwrap();
+        let included = cache.load(&paths).unwrap();
+        assert!(Arc::ptr_eq(&included, &cache.load(&paths).unwrap()));
+        fs::write(dir.path().join("db.yaml"), "port: 6432\n").unwrap();
+        assert_eq!(
+            cache.load(&paths).unwrap().get("DB_PORT"),
+            Some(&Value::I64(6432))
+        );
+    }
+
+    #[test]
+    fn configurations_are_rebuilt_when_a_secret_file_changes() {
+        let dir = tempdir().unwrap();
+        let config = dir.path().join("config.yaml");
+        let secret = dir.path().join("password");
+        fs::write(&config, "password: null\n").unwrap();
+        fs::write(&secret, "a\n").unwrap();
+        let options = LoadOptions {
+            env: Some(
+                [(
+                    "PASSWORD_FILE".to_string(),
+                    secret.to_str().unwrap().to_string(),
+                )]
+                .into_iter()
+                .collect(),
+            ),
+            secret_files: true,
+            ..Default::default()
+        };
+        let cache = ConfigCache::new(options);
+        let paths = [config.to_str().unwrap()];
+
+        let first = cache.load(&paths).unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.load(&paths).unwrap()));
+        fs::write(&secret, "b\n").unwrap();
+        assert_eq!(
+            cache.load(&paths).unwrap().get("PASSWORD"),
+            Some(&Value::from("b"))
+        );
+    }
+
+    #[test]
+    fn files_are_read_under_the_options() {
+        let dir = tempdir().unwrap();
+        let config = dir.path().join("config.yaml");
+        fs::write(&config, "port: 80\n").unwrap();
+        let paths = [config.to_str().unwrap()];
+
+        let confined = ConfigCache::new(LoadOptions {
+            base_dir: Some(dir.path().join("config")),
+            ..Default::default()
+        });
+        fs::create_dir(dir.path().join("config")).unwrap();
+        assert!(confined.load(&paths).is_err());
+        assert!(confined.is_empty());
+
+        let budgeted = ConfigCache::new(LoadOptions {
+            read_budget: ReadBudget {
+                max_files: None,
+                max_bytes: Some(4),
+            },
+            ..Default::default()
+        });
+        assert!(budgeted
+            .load(&paths)
+            .unwrap_err()
+            .message()
+            .ends_with("exceeds the read budget of 4 bytes."));
+    }
+}
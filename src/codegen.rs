@@ -0,0 +1,219 @@
+//! Rust constants generated from a configuration at build time.
+//!
+//! Some settings must be baked into the binary, yet are easier to review and share as YAML.
+//! Call `generate` from a build script to load a file and write a Rust module holding one
+//! typed constant per flattened key, then `include!` it:
+//!
+//! ```rust,no_run
+//! // build.rs, in `fn main`
+//! use yaml_config::LoadOptions;
+//! let out_dir = std::env::var("OUT_DIR").unwrap();
+//! let out = std::path::Path::new(&out_dir).join("defaults.rs");
+//! yaml_config::codegen::generate("config/defaults.yaml", &out, &LoadOptions::default()).unwrap();
+//! ```
+//!
+//! ```rust,ignore
+//! // src/main.rs
+//! mod defaults {
+//!     include!(concat!(env!("OUT_DIR"), "/defaults.rs"));
+//! }
+//! let pool = Pool::new(defaults::DB_POOL_SIZE);
+//! ```
+//!
+//! Integers become `i64`, floats `f64`, strings `&str`, booleans `bool`, and sequences whose
+//! elements share one of those types a slice. Other values, such as maps inside a sequence, have
+//! no constant form and are reported as errors. Keys starting with a digit are prefixed with an
+//! underscore. The file is loaded as by `load_with`, so `null` values are read from the
+//! environment of the build.
+use crate::{load_traced, LoadOptions, ParseError, Trace, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::fs;
+use std::path::Path;
+
+fn codegen_error(message: String) -> ParseError {
+    ParseError::new("config::codegen", message)
+}
+
+/// Returns the Rust type and literal of a scalar, or `None` if it isn't one.
+fn scalar(val: &Value) -> Option<(&'static str, String)> {
+    let float = |f: f64| {
+        if f.is_nan() {
+            "f64::NAN".to_string()
+        } else if f.is_infinite() {
+            if f > 0.0 {
+                "f64::INFINITY"
+            } else {
+                "f64::NEG_INFINITY"
+            }
+            .to_string()
+        } else {
+            // `{:?}` always writes a decimal point or an exponent.
+            format!("{:?}", f)
+        }
+    };
+    match val {
+        Value::I32(v) => Some(("i64", v.to_string())),
+        Value::I64(v) => Some(("i64", v.to_string())),
+        Value::F32(v) => Some(("f64", float(f64::from(*v)))),
+        Value::F64(v) => Some(("f64", float(*v))),
+        Value::String(v) => Some(("&str", format!("{:?}", v))),
+        Value::Bool(v) => Some(("bool", v.to_string())),
+        _ => None,
+    }
+}
+
+/// Returns the Rust type and expression of `val`.
+fn constant(key: &str, val: &Value) -> Result<(String, String), ParseError> {
+    if let Some((ty, literal)) = scalar(val) {
+        return Ok((ty.to_string(), literal));
+    }
+    let items = match val {
+        Value::Array(items) if !items.is_empty() => items,
+        Value::Array(_) => {
+            return Err(codegen_error(format!(
+                "{} is an empty sequence, whose element type is unknown.",
+                key
+            )))
+        }
+        val => {
+            return Err(codegen_error(format!(
+                "{} is {}, which has no constant form.",
+                key,
+                val.describe()
+            )))
+        }
+    };
+
+    let mut element_ty = None;
+    let mut literals = Vec::with_capacity(items.len());
+    for item in items {
+        let (ty, literal) = scalar(item).ok_or_else(|| {
+            codegen_error(format!(
+                "{} holds {}, which has no constant form.",
+                key,
+                item.describe()
+            ))
+        })?;
+        if element_ty.is_some_and(|element_ty| element_ty != ty) {
+            return Err(codegen_error(format!(
+                "{} mixes elements of different types.",
+                key
+            )));
+        }
+        element_ty = Some(ty);
+        literals.push(literal);
+    }
+    Ok((
+        format!("&[{}]", element_ty.unwrap_or_default()),
+        format!("&[{}]", literals.join(", ")),
+    ))
+}
+
+/// Renders `values` as a Rust module body with one `pub const` per key, in order.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::{codegen, load_from_str};
+/// let values = load_from_str("db:\n  host: localhost\n  port: 5432\n", None).unwrap();
+/// assert_eq!(
+///     codegen::to_rust(&values).unwrap(),
+///     "pub const DB_HOST: &str = \"localhost\";\npub const DB_PORT: i64 = 5432;\n"
+/// );
+/// ```
+pub fn to_rust(values: &IndexMap<String, Value, FxBuildHasher>) -> Result<String, ParseError> {
+    let mut out = String::new();
+    for (key, val) in values {
+        let (ty, expr) = constant(key, val)?;
+        let prefix = if key.starts_with(|c: char| c.is_ascii_digit()) {
+            "_"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "pub const {}{}: {} = {};\n",
+            prefix, key, ty, expr
+        ));
+    }
+    Ok(out)
+}
+
+/// Loads the YAML file at `yaml_path` with `options` and writes its values to `out_path` as Rust
+/// constants.
+///
+/// Meant for build scripts: it also tells Cargo to run the script again when the file or any file
+/// it includes changes.
+pub fn generate<P: AsRef<Path>>(
+    yaml_path: &str,
+    out_path: P,
+    options: &LoadOptions,
+) -> Result<(), ParseError> {
+    let mut trace = Trace::default();
+    let values = load_traced(yaml_path, options, &mut trace)?;
+    let module = format!(
+        "// Generated by yaml_config::codegen from {}. Do not edit.\n\n{}",
+        yaml_path,
+        to_rust(&values)?
+    );
+    fs::write(out_path, module)?;
+    for path in &trace.reads.files {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codegen::{generate, to_rust};
+    use crate::{load_from_str, LoadOptions};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn rust(doc: &str) -> Result<String, String> {
+        to_rust(&load_from_str(doc, None).unwrap()).map_err(|e| e.message().into_owned())
+    }
+
+    #[test]
+    fn values_become_typed_constants() {
+        assert_eq!(
+            rust(
+                "name: \"say \\\"hi\\\"\\n\"\nratio: 1.0\nlimits: [1, 2]\ndebug: false\n\
+                 tags: [a, b]\n404: page\nnan: .nan\n"
+            )
+            .unwrap(),
+            "pub const NAME: &str = \"say \\\"hi\\\"\\n\";\n\
+             pub const RATIO: f64 = 1.0;\n\
+             pub const LIMITS: &[i64] = &[1, 2];\n\
+             pub const DEBUG: bool = false;\n\
+             pub const TAGS: &[&str] = &[\"a\", \"b\"];\n\
+             pub const _404: &str = \"page\";\n\
+             pub const NAN: f64 = f64::NAN;\n"
+        );
+    }
+
+    #[test]
+    fn values_without_a_constant_form_are_errors() {
+        assert_eq!(
+            rust("mixed: [1, a]\n").unwrap_err(),
+            "MIXED mixes elements of different types."
+        );
+        assert!(rust("servers:\n  - host: a\n").is_err());
+        assert!(rust("empty: []\n").is_err());
+    }
+
+    #[test]
+    fn generated_modules_follow_includes() {
+        let dir = tempdir().unwrap();
+        let yaml = dir.path().join("defaults.yaml");
+        fs::write(&yaml, "db: !include db.yaml\n").unwrap();
+        fs::write(dir.path().join("db.yaml"), "pool_size: 16\n").unwrap();
+        let out = dir.path().join("defaults.rs");
+
+        generate(yaml.to_str().unwrap(), &out, &LoadOptions::default()).unwrap();
+
+        assert!(fs::read_to_string(&out)
+            .unwrap()
+            .ends_with("\n\npub const DB_POOL_SIZE: i64 = 16;\n"));
+    }
+}
@@ -0,0 +1,1356 @@
+//! The assembled configuration.
+//!
+//! `Config` is produced by `ConfigBuilder::build`. It holds the merged flattened values along
+//! with a description of every layer that contributed to them.
+use crate::annotate;
+use crate::args;
+use crate::builder::{Layer, Origin, Recipe, SourceKind, ValueSource, ValueSourceKind};
+use crate::footprint::{self, MemoryFootprint};
+use crate::messages;
+use crate::policy::{ComplianceReport, Policies};
+use crate::resolvers::Resolvers;
+use crate::schema::{Schema, Visibility};
+use crate::snapshot;
+use crate::whatif::{self, WhatIf};
+use crate::{coerce_env, normalize_key, ErrorCode, LoadOptions, Mismatch, ParseError, Value};
+use fxhash::{FxBuildHasher, FxHashMap};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// A configuration assembled from one or more layers.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::ConfigBuilder;
+/// let config = ConfigBuilder::new().add_file("path/to/yaml/file.yaml").build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    values: IndexMap<String, Value, FxBuildHasher>,
+    /// The indices of `values` in key order, built on first use by `iter_range` and
+    /// `iter_prefix`.
+    sorted: OnceLock<Vec<usize>>,
+    layers: Vec<Layer>,
+    origins: FxHashMap<String, Origin>,
+    env_bindings: IndexMap<String, String, FxBuildHasher>,
+    missing: Vec<String>,
+    schema: Schema,
+    resolvers: Resolvers,
+    warnings: Vec<String>,
+    defaults: IndexMap<String, Value, FxBuildHasher>,
+    policies: Policies,
+    compliance: ComplianceReport,
+    /// How `ConfigBuilder::build` assembled the configuration, for `resolve_with`.
+    recipe: Option<Arc<Recipe>>,
+}
+
+impl Config {
+    pub(crate) fn new(
+        values: IndexMap<String, Value, FxBuildHasher>,
+        layers: Vec<Layer>,
+        schema: Schema,
+    ) -> Self {
+        Config {
+            values,
+            sorted: OnceLock::new(),
+            layers,
+            origins: FxHashMap::default(),
+            env_bindings: IndexMap::with_hasher(FxBuildHasher::default()),
+            missing: Vec::new(),
+            schema,
+            resolvers: Resolvers::default(),
+            warnings: Vec::new(),
+            defaults: IndexMap::with_hasher(FxBuildHasher::default()),
+            policies: Policies::default(),
+            compliance: ComplianceReport::default(),
+            recipe: None,
+        }
+    }
+
+    pub(crate) fn with_recipe(mut self, recipe: Arc<Recipe>) -> Self {
+        self.recipe = Some(recipe);
+        self
+    }
+
+    pub(crate) fn with_origins(mut self, origins: FxHashMap<String, Origin>) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    pub(crate) fn with_env_bindings(
+        mut self,
+        env_bindings: IndexMap<String, String, FxBuildHasher>,
+    ) -> Self {
+        self.env_bindings = env_bindings;
+        self
+    }
+
+    pub(crate) fn with_missing(mut self, missing: Vec<String>) -> Self {
+        self.missing = missing;
+        self
+    }
+
+    pub(crate) fn with_resolvers(mut self, resolvers: Resolvers) -> Self {
+        self.resolvers = resolvers;
+        self
+    }
+
+    pub(crate) fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Attaches `policies` and evaluates them, failing if enforced policies are violated.
+    pub(crate) fn with_policies(mut self, policies: Policies) -> Result<Self, ParseError> {
+        self.compliance = policies.check(&self)?;
+        self.policies = policies;
+        Ok(self)
+    }
+
+    /// Sets or, with `None`, removes the loaded value of `key`.
+    pub(crate) fn set_value(&mut self, key: &str, val: Option<Value>) {
+        match val {
+            Some(val) => {
+                self.values.insert(key.to_string(), val);
+            }
+            None => {
+                self.values.shift_remove(key);
+            }
+        }
+        self.sorted.take();
+    }
+
+    /// Where the loaded value of `key` came from, unless it was set by an override.
+    pub(crate) fn origin(&self, key: &str) -> Option<&Origin> {
+        self.origins.get(key)
+    }
+
+    /// Lists, for every key an environment variable can override, the name of that variable.
+    ///
+    /// The names follow the options and layers the configuration was built with. A key provided
+    /// by a YAML layer is bound to the variable named after it, e.g. `DB_PORT`, when the options
+    /// prefer the environment or the YAML value is `null`. A layer added with
+    /// `add_env_prefix("APP_")` binds `APP_DB_PORT` instead, unless a later layer provides
+    /// `DB_PORT`. Keys that no variable can override are left out.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust,no_run
+    /// use yaml_config::{ConfigBuilder, LoadOptions, Preference};
+    /// let config = ConfigBuilder::new()
+    ///     .with_options(LoadOptions { preference: Preference::PreferEnv, ..Default::default() })
+    ///     .add_file("config/base.yaml")
+    ///     .build()
+    ///     .unwrap();
+    /// for (key, var) in config.env_bindings() {
+    ///     println!("{} <- ${}", key, var);
+    /// }
+    /// ```
+    pub fn env_bindings(&self) -> &IndexMap<String, String, FxBuildHasher> {
+        &self.env_bindings
+    }
+
+    /// Reports where the value `get` returns for `key` came from: a YAML document, a file it
+    /// includes, an environment variable, a default, or another source. For values read from
+    /// a file, the file and the line of the value are included. Returns `None` for keys without
+    /// a value.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::builder::ValueSourceKind;
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new()
+    ///     .add_defaults([("DB_PORT", 5432)])
+    ///     .add_str("db:\n  host: localhost\n")
+    ///     .build()
+    ///     .unwrap();
+    /// let source = config.source_of("DB_HOST").unwrap();
+    /// assert_eq!((source.kind, source.line), (ValueSourceKind::Yaml, Some(2)));
+    /// assert_eq!(config.source_of("DB_PORT").unwrap().kind, ValueSourceKind::Default);
+    /// ```
+    pub fn source_of(&self, key: &str) -> Option<ValueSource> {
+        let val = self.values.get(key);
+        if matches!(val, Some(Value::Null) | None) && self.defaults.contains_key(key) {
+            return Some(ValueSource::new(ValueSourceKind::Default));
+        }
+        val?;
+        let origin = match self.origins.get(key) {
+            Some(origin) => origin,
+            None => return Some(ValueSource::new(ValueSourceKind::Override)),
+        };
+        let layer = &self.layers[origin.layer];
+        Some(match &layer.kind {
+            _ if origin.from_env => ValueSource::new(ValueSourceKind::Env),
+            SourceKind::Env => ValueSource::new(ValueSourceKind::Env),
+            SourceKind::Defaults => ValueSource::new(ValueSourceKind::Default),
+            SourceKind::Custom(name) => ValueSource::new(ValueSourceKind::Custom(name.clone())),
+            SourceKind::File | SourceKind::Str => {
+                let definition = match (&self.recipe, &origin.path) {
+                    (Some(recipe), Some(path)) => recipe.definition(origin.layer, path),
+                    _ => None,
+                }
+                .unwrap_or_default();
+                let file = match layer.kind {
+                    SourceKind::File => definition.file.or_else(|| Some(layer.location.clone())),
+                    _ => definition.file,
+                };
+                ValueSource {
+                    kind: if definition.included {
+                        ValueSourceKind::Include
+                    } else {
+                        ValueSourceKind::Yaml
+                    },
+                    file,
+                    line: definition.line,
+                }
+            }
+        })
+    }
+
+    /// Lists the keys that were left `null` with no environment variable to provide them, when
+    /// `LoadOptions::allow_missing` let the configuration load anyway. Such keys hold
+    /// `Value::Null` or, with `OnMissing::Skip`, are absent. A key a later layer provided isn't
+    /// listed.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, LoadOptions, OnMissing};
+    /// let options = LoadOptions { allow_missing: OnMissing::Null, ..Default::default() };
+    /// let config = ConfigBuilder::new()
+    ///     .with_options(options)
+    ///     .add_str("smtp:\n  password: null\n")
+    ///     .build()
+    ///     .unwrap();
+    /// for key in config.missing_keys() {
+    ///     eprintln!("{} is not set; mail is disabled", key);
+    /// }
+    /// ```
+    pub fn missing_keys(&self) -> &[String] {
+        &self.missing
+    }
+
+    /// Returns the loaded value for `key`, falling back to a registered default when it wasn't
+    /// loaded or is missing.
+    fn lookup(&self, key: &str) -> Option<&Value> {
+        match self.values.get(key) {
+            Some(Value::Null) | None => self.defaults.get(key).or(self.values.get(key)),
+            val => val,
+        }
+    }
+
+    /// Returns the value for `key`, if present. Registered defaults are consulted for keys that
+    /// weren't loaded.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.lookup(key)
+    }
+
+    /// Returns the value of `key` converted to `T`, with an error naming the key if it isn't set
+    /// or holds a value of another type.
+    ///
+    /// `T` may be any type implementing `TryFrom<&Value>` whose error converts into a `Mismatch`,
+    /// which becomes a `ParseError::TypeMismatch`. A key that isn't set is a
+    /// `ParseError::NotSet`. The crate provides conversions to `i64`, `i32`, `f64`, `bool`,
+    /// `&str`, and `String`; `get_i64` and its siblings are shorthands for the common ones.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new().add_str("db:\n  port: 5432\n").build().unwrap();
+    /// assert_eq!(config.get_as::<i32>("DB_PORT").unwrap(), 5432);
+    /// assert_eq!(
+    ///     config.get_as::<bool>("DB_PORT").unwrap_err().message(),
+    ///     "DB_PORT: expected a boolean, found an integer."
+    /// );
+    /// ```
+    pub fn get_as<'a, T>(&'a self, key: &str) -> Result<T, ParseError>
+    where
+        T: TryFrom<&'a Value>,
+        T::Error: Into<Mismatch>,
+    {
+        let val = self.require(key)?;
+        T::try_from(val).map_err(|e| {
+            let Mismatch { expected, found } = e.into();
+            ParseError::TypeMismatch {
+                key: key.to_string(),
+                expected,
+                found,
+            }
+        })
+    }
+
+    /// Returns the value of `key`, or `ParseError::NotSet` if it isn't set.
+    fn require(&self, key: &str) -> Result<&Value, ParseError> {
+        self.lookup(key).ok_or_else(|| ParseError::NotSet {
+            key: key.to_string(),
+        })
+    }
+
+    /// Returns the value of `key` as an integer. See `get_as`.
+    pub fn get_i64(&self, key: &str) -> Result<i64, ParseError> {
+        self.get_as(key)
+    }
+
+    /// Returns the value of `key` as a number, widening integers. See `get_as`.
+    pub fn get_f64(&self, key: &str) -> Result<f64, ParseError> {
+        self.get_as(key)
+    }
+
+    /// Returns the value of `key` as a boolean. See `get_as`.
+    pub fn get_bool(&self, key: &str) -> Result<bool, ParseError> {
+        self.get_as(key)
+    }
+
+    /// Returns the value of `key` as a string. See `get_as`.
+    pub fn get_str(&self, key: &str) -> Result<&str, ParseError> {
+        self.get_as(key)
+    }
+
+    /// Registers `val` as the default for `key`, used by every accessor when `key` wasn't
+    /// loaded. Registering a default again replaces it.
+    ///
+    /// This lets a library embedded in an application contribute defaults for its own keys
+    /// without owning the load step. Defaults are not part of `values`, `len`, or `to_args`.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, Value};
+    /// let mut config = ConfigBuilder::new().build().unwrap();
+    /// config.register_default("CACHE_TTL", 300_i64);
+    /// assert_eq!(config.get("CACHE_TTL"), Some(&Value::I64(300)));
+    /// ```
+    pub fn register_default<V: Into<Value>>(&mut self, key: &str, val: V) {
+        self.defaults.insert(key.to_string(), val.into());
+    }
+
+    /// Returns the live value of the environment variable `key` if it is set, and the loaded
+    /// value otherwise, regardless of the `Preference` the configuration was loaded with.
+    ///
+    /// The environment is read on every call, so this suits keys operators toggle while the
+    /// process runs. It is read through the `LoadOptions` the configuration was built with and
+    /// the variable is parsed as the loader would parse it over the loaded value. A variable that
+    /// doesn't parse as that type leaves the loaded value in place.
+    pub fn get_env_first(&self, key: &str) -> Option<Value> {
+        self.live_env(key, self.lookup(key))
+            .or_else(|| self.lookup(key).cloned())
+    }
+
+    /// Mounts a component's schema: its defaults are registered, its declarations are added
+    /// to the configuration's schema, values of keys with a declared unit are converted to that
+    /// unit, and every declared key is checked.
+    ///
+    /// If a required key is missing or a validator rejects a value, nothing is mounted and the
+    /// error lists every problem found.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, KeySpec, Schema};
+    /// let mut config = ConfigBuilder::new().build().unwrap();
+    /// let cache = Schema::new().key("CACHE_TTL", KeySpec::new().default_value(300_i64));
+    /// config.mount_schema(cache).unwrap();
+    /// ```
+    pub fn mount_schema(&mut self, schema: Schema) -> Result<(), ParseError> {
+        let mut problems = Vec::new();
+        let mut converted = Vec::new();
+        for (key, spec) in schema.iter() {
+            let mut val = self.values.get(key).filter(|val| !val.is_null()).cloned();
+            if let (Some(unit), Some(loaded)) = (spec.get_unit(), &val) {
+                match unit.convert(loaded) {
+                    Ok(v) => {
+                        converted.push((key.clone(), v.clone()));
+                        val = Some(v);
+                    }
+                    Err(e) => {
+                        problems.push(messages::text(
+                            "schema.invalid",
+                            &[("key", key), ("problem", &e)],
+                        ));
+                        continue;
+                    }
+                }
+            }
+            let val = val.as_ref().or_else(|| spec.get_default());
+            if let Err(e) = spec.check(key, val.or_else(|| self.defaults.get(key))) {
+                problems.push(e);
+            }
+        }
+        if !problems.is_empty() {
+            return Err(ParseError::with_code(
+                ErrorCode::SchemaViolation,
+                "config::schema",
+                problems.join(" "),
+            ));
+        }
+
+        self.values.extend(converted);
+        for (key, spec) in schema.iter() {
+            if let Some(default) = spec.get_default() {
+                self.defaults.insert(key.clone(), default.clone());
+            }
+        }
+        self.schema = std::mem::take(&mut self.schema).merge(schema);
+        Ok(())
+    }
+
+    /// Returns the loaded value for `key` if present, then the live value of the environment
+    /// variable `key`, then the registered default.
+    pub fn get_yaml_first(&self, key: &str) -> Option<Value> {
+        match self.values.get(key) {
+            Some(val) if !val.is_null() => Some(val.clone()),
+            _ => self
+                .live_env(key, None)
+                .or_else(|| self.lookup(key).cloned()),
+        }
+    }
+
+    /// Reads the environment variable `key` through the options the configuration was built
+    /// with, converting it as the loader does over `like`.
+    fn live_env(&self, key: &str, like: Option<&Value>) -> Option<Value> {
+        let default;
+        let options = match &self.recipe {
+            Some(recipe) => recipe.options(),
+            None => {
+                default = LoadOptions::default();
+                &default
+            }
+        };
+        let raw = options.var_os(key)?.into_string().ok()?;
+        coerce_env(key, raw, like, options).ok().flatten()
+    }
+
+    /// Treats the value of `key` as an ordered fallback list and returns the first candidate
+    /// accepted by `check`.
+    ///
+    /// A `Value::Array` is tried element by element; any other value is the only candidate.
+    /// `check` typically parses the candidate, e.g. as a URL, and its output is returned. If
+    /// the key is missing or every candidate is rejected, the error lists each rejection.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new().build().unwrap();
+    /// // db_url: [primary:5432, replica1:5432, replica2:5432]
+    /// let addr = config.get_first_ok("DB_URL", |candidate| {
+    ///     candidate
+    ///         .as_string()
+    ///         .ok_or("not a string")
+    ///         .and_then(|s| s.parse::<std::net::SocketAddr>().map_err(|_| "not an address"))
+    /// });
+    /// assert!(addr.is_err());
+    /// ```
+    pub fn get_first_ok<T, E, F>(&self, key: &str, mut check: F) -> Result<T, ParseError>
+    where
+        E: fmt::Display,
+        F: FnMut(&Value) -> Result<T, E>,
+    {
+        let candidates = match self.lookup(key) {
+            Some(Value::Array(items)) => items.as_slice(),
+            Some(val) => std::slice::from_ref(val),
+            None => {
+                return Err(ParseError::NotSet {
+                    key: key.to_string(),
+                })
+            }
+        };
+
+        let mut rejections = Vec::with_capacity(candidates.len());
+        for (i, candidate) in candidates.iter().enumerate() {
+            match check(candidate) {
+                Ok(val) => return Ok(val),
+                Err(e) => rejections.push((i, e.to_string())),
+            }
+        }
+
+        Err(no_usable_candidate(key, rejections))
+    }
+
+    /// Treats the value of `key` as a weighted list and returns a candidate accepted by
+    /// `check`, picked at random in proportion to the weights.
+    ///
+    /// Each element of a `Value::Array` is either a candidate with a weight of 1 or a map
+    /// holding the candidate under `value` and a non-negative number under `weight`; any
+    /// other value is the only candidate. `random` is a number in `[0, 1)` supplied by the
+    /// caller's random number generator, as for `RetryPolicy::delay_with_jitter`. A rejected
+    /// candidate is set aside and the pick repeated among the rest with the same `random`, so
+    /// a usable candidate with a positive weight is always found. Failures are reported as by
+    /// `get_first_ok`.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new()
+    ///     .add_str("db_url: [{value: primary, weight: 3}, {value: replica, weight: 1}]\n")
+    ///     .build()
+    ///     .unwrap();
+    /// let pick = |random| {
+    ///     config.get_weighted_ok("DB_URL", random, |v| v.as_string().cloned().ok_or("?"))
+    /// };
+    /// assert_eq!(pick(0.5).unwrap(), "primary");
+    /// assert_eq!(pick(0.9).unwrap(), "replica");
+    /// ```
+    pub fn get_weighted_ok<T, E, F>(
+        &self,
+        key: &str,
+        random: f64,
+        mut check: F,
+    ) -> Result<T, ParseError>
+    where
+        E: fmt::Display,
+        F: FnMut(&Value) -> Result<T, E>,
+    {
+        let items = match self.lookup(key) {
+            Some(Value::Array(items)) => items.as_slice(),
+            Some(val) => std::slice::from_ref(val),
+            None => {
+                return Err(ParseError::NotSet {
+                    key: key.to_string(),
+                })
+            }
+        };
+
+        let mut rejections = Vec::new();
+        let mut candidates = Vec::with_capacity(items.len());
+        for (i, item) in items.iter().enumerate() {
+            let (candidate, weight) = match item {
+                Value::Map(map) if map.contains_key("VALUE") => match map.get("WEIGHT") {
+                    None => (&map["VALUE"], 1.0),
+                    Some(Value::I64(weight)) if *weight >= 0 => (&map["VALUE"], *weight as f64),
+                    Some(Value::F64(weight)) if weight.is_finite() && *weight >= 0.0 => {
+                        (&map["VALUE"], *weight)
+                    }
+                    Some(_) => {
+                        rejections.push((i, "the weight is not a non-negative number".to_string()));
+                        continue;
+                    }
+                },
+                item => (item, 1.0),
+            };
+            candidates.push((i, candidate, weight));
+        }
+
+        let random = random.clamp(0.0, 1.0);
+        loop {
+            let total: f64 = candidates.iter().map(|(_, _, weight)| weight).sum();
+            if total <= 0.0 {
+                break;
+            }
+            let mut target = random * total;
+            let picked = candidates
+                .iter()
+                .position(|(_, _, weight)| {
+                    let hit = *weight > 0.0 && target < *weight;
+                    target -= weight;
+                    hit
+                })
+                // Rounding can leave `target` just past the last weight.
+                .or_else(|| candidates.iter().rposition(|(_, _, weight)| *weight > 0.0))
+                .unwrap_or_default();
+            let (i, candidate, _) = candidates.remove(picked);
+            match check(candidate) {
+                Ok(val) => return Ok(val),
+                Err(e) => rejections.push((i, e.to_string())),
+            }
+        }
+
+        for (i, _, _) in candidates {
+            rejections.push((i, "the weight is 0".to_string()));
+        }
+        rejections.sort_by_key(|(i, _)| *i);
+        Err(no_usable_candidate(key, rejections))
+    }
+
+    /// Returns `true` if `key` is present or has a registered default.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.lookup(key).is_some()
+    }
+
+    /// The number of keys in the configuration.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if the configuration has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// A fingerprint of the loaded values that changes whenever a key or value does,
+    /// independent of the order keys were loaded in. It is not a cryptographic hash.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new().build().unwrap();
+    /// assert_ne!(config.fingerprint(), config.overridden([("A", 1_i64)]).fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        let mut entries: Vec<String> = self
+            .values
+            .iter()
+            .map(|(key, val)| format!("{}={:?}", key, val))
+            .collect();
+        entries.sort();
+        fxhash::hash64(&entries)
+    }
+
+    /// Estimates the memory held by the configuration, with a breakdown per layer. See the
+    /// `footprint` module.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut report = MemoryFootprint {
+            layers: vec![0; self.layers.len()],
+            ..Default::default()
+        };
+        for (key, val) in &self.values {
+            let (key_size, value_size) = (footprint::key_size(key), footprint::value_size(val));
+            report.keys += key_size;
+            report.values += value_size;
+            match self.origins.get(key).map(|origin| origin.layer) {
+                Some(layer) if layer < report.layers.len() => {
+                    report.layers[layer] += key_size + value_size
+                }
+                _ => report.unattributed += key_size + value_size,
+            }
+        }
+        report.defaults = self
+            .defaults
+            .iter()
+            .map(|(key, val)| footprint::key_size(key) + footprint::value_size(val))
+            .sum();
+        report.index = footprint::table_size(self.values.len() + self.defaults.len())
+            + self
+                .sorted
+                .get()
+                .map_or(0, |sorted| sorted.len() * size_of::<usize>());
+        report
+    }
+
+    /// Iterates over at most `limit` loaded keys in key order, starting at `start_key` or the
+    /// first key after it. Pass `""` to start at the first key.
+    ///
+    /// The keys are sorted once, on first use, so a debug UI can page through a configuration
+    /// of tens of thousands of keys without copying it. To fetch the next page, ask for one
+    /// more entry than shown and start the next page at the extra key.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new()
+    ///     .add_defaults([("C", 3), ("A", 1), ("B", 2)])
+    ///     .build()
+    ///     .unwrap();
+    /// let page: Vec<&str> = config.iter_range("B", 10).map(|(key, _)| key).collect();
+    /// assert_eq!(page, vec!["B", "C"]);
+    /// ```
+    pub fn iter_range<'a>(
+        &'a self,
+        start_key: &str,
+        limit: usize,
+    ) -> impl Iterator<Item = (&'a str, &'a Value)> + 'a {
+        let sorted = self.sorted();
+        let start = sorted.partition_point(|i| self.key_at(*i) < start_key);
+        self.entries(&sorted[start..]).take(limit)
+    }
+
+    /// Iterates over the loaded keys starting with `prefix`, in key order.
+    ///
+    /// Keys sharing a prefix are adjacent once sorted, so they are found by binary search in
+    /// the index `iter_range` uses and the query costs the number of matches rather than the
+    /// size of the configuration.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new()
+    ///     .add_str("tenants:\n  acme:\n    quota: 10\n  globex:\n    quota: 20\n")
+    ///     .build()
+    ///     .unwrap();
+    /// let keys: Vec<&str> = config.iter_prefix("TENANTS_ACME_").map(|(key, _)| key).collect();
+    /// assert_eq!(keys, vec!["TENANTS_ACME_QUOTA"]);
+    /// ```
+    pub fn iter_prefix<'a>(
+        &'a self,
+        prefix: &str,
+    ) -> impl Iterator<Item = (&'a str, &'a Value)> + 'a {
+        let sorted = self.sorted();
+        let start = sorted.partition_point(|i| self.key_at(*i) < prefix);
+        let len = sorted[start..].partition_point(|i| self.key_at(*i).starts_with(prefix));
+        self.entries(&sorted[start..start + len])
+    }
+
+    /// Returns the loaded keys under the section `name`, e.g. `DB_HOST` and `DB_PORT` for
+    /// `"db"`, keyed by the rest of their name. `name` is normalized like any other key, so
+    /// nested sections can be named as `"db.primary"`.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, Value};
+    /// let config = ConfigBuilder::new()
+    ///     .add_str("db:\n  host: localhost\n  port: 5432\ndebug: true\n")
+    ///     .build()
+    ///     .unwrap();
+    /// let db = config.section("db");
+    /// assert_eq!(db.len(), 2);
+    /// assert_eq!(db["PORT"], Value::I64(5432));
+    /// ```
+    pub fn section(&self, name: &str) -> IndexMap<String, Value, FxBuildHasher> {
+        let prefix = format!("{}_", normalize_key(name));
+        self.iter_prefix(&prefix)
+            .map(|(key, val)| (key[prefix.len()..].to_string(), val.clone()))
+            .collect()
+    }
+
+    /// The indices of `values` in key order, sorted on first use.
+    fn sorted(&self) -> &[usize] {
+        self.sorted.get_or_init(|| {
+            let mut sorted: Vec<usize> = (0..self.values.len()).collect();
+            sorted.sort_unstable_by_key(|i| self.key_at(*i));
+            sorted
+        })
+    }
+
+    fn key_at(&self, index: usize) -> &str {
+        self.values
+            .get_index(index)
+            .map_or("", |(key, _)| key.as_str())
+    }
+
+    fn entries<'a>(&'a self, indices: &'a [usize]) -> impl Iterator<Item = (&'a str, &'a Value)> {
+        indices
+            .iter()
+            .filter_map(move |i| self.values.get_index(*i))
+            .map(|(key, val)| (key.as_str(), val))
+    }
+
+    /// The merged flattened values.
+    pub fn values(&self) -> &IndexMap<String, Value, FxBuildHasher> {
+        &self.values
+    }
+
+    /// Consumes the configuration and returns the merged flattened values.
+    pub fn into_values(self) -> IndexMap<String, Value, FxBuildHasher> {
+        self.values
+    }
+
+    /// The layers the configuration was assembled from, ordered from lowest to highest priority.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Problems that didn't fail the build, such as optional layers that were skipped.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The outcome of the policies registered with `ConfigBuilder::with_policies`, evaluated
+    /// when the configuration was built.
+    pub fn compliance(&self) -> &ComplianceReport {
+        &self.compliance
+    }
+
+    /// The policies the configuration was built with.
+    pub fn policies(&self) -> &Policies {
+        &self.policies
+    }
+
+    /// The schema the configuration was built with.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Returns a copy of the configuration containing only the keys whose visibility is at
+    /// or below `visibility`.
+    ///
+    /// `config.view(Visibility::Public)` is the subset that is safe to expose publicly, for
+    /// example over a debug endpoint.
+    pub fn view(&self, visibility: Visibility) -> Config {
+        let visible = |map: &IndexMap<String, Value, FxBuildHasher>| {
+            map.iter()
+                .filter(|(key, _)| self.schema.visibility_of(key) <= visibility)
+                .map(|(key, val)| (key.clone(), val.clone()))
+                .collect()
+        };
+
+        let mut view = Config::new(
+            visible(&self.values),
+            self.layers.clone(),
+            self.schema.clone(),
+        )
+        .with_origins(self.origins.clone())
+        .with_env_bindings(
+            self.env_bindings
+                .iter()
+                .filter(|(key, _)| self.schema.visibility_of(key) <= visibility)
+                .map(|(key, var)| (key.clone(), var.clone()))
+                .collect(),
+        )
+        .with_missing(
+            self.missing
+                .iter()
+                .filter(|key| self.schema.visibility_of(key) <= visibility)
+                .cloned()
+                .collect(),
+        )
+        .with_resolvers(self.resolvers.clone())
+        .with_warnings(self.warnings.clone());
+        view.defaults = visible(&self.defaults);
+        view
+    }
+
+    /// Returns a copy of the configuration with `overrides` applied on top of the loaded values.
+    pub fn overridden<I, K, V>(&self, overrides: I) -> Config
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        let mut config = self.clone();
+        for (key, val) in overrides {
+            let key = key.into();
+            config.origins.remove(&key);
+            config.env_bindings.shift_remove(&key);
+            config.missing.retain(|missing| *missing != key);
+            config.values.insert(key, val.into());
+        }
+        config.sorted.take();
+        config
+    }
+
+    /// Runs `f` with a copy of the configuration that has `overrides` applied, leaving this
+    /// configuration untouched. This makes it easy to test a code path under different settings
+    /// without reloading files. To override a configuration read through a `SharedConfig`, see
+    /// `SharedConfig::scoped_overrides`.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, Value};
+    /// let config = ConfigBuilder::new().build().unwrap();
+    /// config.with_overrides([("FEATURE_ENABLED", true)], |config| {
+    ///     assert_eq!(config.get("FEATURE_ENABLED"), Some(&Value::Bool(true)));
+    /// });
+    /// assert!(config.get("FEATURE_ENABLED").is_none());
+    /// ```
+    pub fn with_overrides<I, K, V, F, R>(&self, overrides: I, f: F) -> R
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<Value>,
+        F: FnOnce(&Config) -> R,
+    {
+        f(&self.overridden(overrides))
+    }
+
+    /// Resolves the configuration again as if the environment variables `env_overrides` were
+    /// set and the file layers named in `file_overrides` held the given contents, and compares
+    /// the result with this configuration. Neither the process environment nor any file is
+    /// changed, which makes it suitable for "what would change if I set X?" tooling.
+    ///
+    /// Files are read again and the environment overrides are set on top of the environment
+    /// the configuration was loaded from. Sources other than files, YAML strings, and
+    /// environment prefixes provide the values they loaded the first time. The hypothetical
+    /// configuration is checked against the schema and policies like a reload would be.
+    ///
+    /// Only configurations built by `ConfigBuilder` can be resolved again, and overrides added
+    /// with `overridden` are not part of how they were built.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use yaml_config::{ConfigBuilder, LoadOptions, Preference, Value};
+    /// let config = ConfigBuilder::new()
+    ///     .with_options(LoadOptions { preference: Preference::PreferEnv, ..Default::default() })
+    ///     .add_str("whatif:\n  port: 8080\n")
+    ///     .build()
+    ///     .unwrap();
+    /// let env = HashMap::from([("WHATIF_PORT".to_string(), "9090".to_string())]);
+    /// let what_if = config.resolve_with(&env, &HashMap::new()).unwrap();
+    /// assert_eq!(what_if.changes[0].resolved, Some(Value::I64(9090)));
+    /// assert_eq!(config.get("WHATIF_PORT"), Some(&Value::I64(8080)));
+    /// ```
+    pub fn resolve_with(
+        &self,
+        env_overrides: &HashMap<String, String>,
+        file_overrides: &HashMap<String, String>,
+    ) -> Result<WhatIf, ParseError> {
+        let whatif_error = |message: String| ParseError::new("config::whatif", message);
+        let recipe = self.recipe.as_ref().ok_or_else(|| {
+            whatif_error("The configuration was not built by a ConfigBuilder.".to_string())
+        })?;
+        if let Some(path) = file_overrides.keys().find(|path| !recipe.has_file(path)) {
+            return Err(whatif_error(format!(
+                "{} is not a file layer of the configuration.",
+                path
+            )));
+        }
+        let resolved = recipe
+            .builder(env_overrides, file_overrides)
+            .with_schema(self.schema.clone())
+            .with_policies(self.policies.clone())
+            .build()?;
+        Ok(whatif::compare(self, resolved))
+    }
+
+    /// Renders the configuration as `--set KEY=VALUE` argument pairs, which
+    /// `args::parse_set_args` reads back into the same values.
+    ///
+    /// This lets a supervisor respawn a worker with exactly its own effective configuration.
+    /// Note that the arguments, secrets included, are visible to other processes on the host.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::with_capacity(self.values.len() * 2);
+        // A missing value has no `--set` form, so the child reports it missing as well.
+        for (key, val) in self.values.iter().filter(|(_, val)| !val.is_null()) {
+            let mut pair = format!("{}=", key);
+            args::write_value(val, &mut pair);
+            args.push(args::SET_FLAG.to_string());
+            args.push(pair);
+        }
+        args
+    }
+
+    /// Renders the configuration as nested YAML with a comment on every value naming the layer
+    /// it came from and whether an environment variable overrode it. Secret values are masked.
+    /// See the `annotate` module for the format.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust,no_run
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new().add_file("config/prod.yaml").build().unwrap();
+    /// eprintln!("{}", config.annotated_yaml());
+    /// ```
+    pub fn annotated_yaml(&self) -> String {
+        annotate::render(self)
+    }
+
+    /// Panics with a line diff unless the configuration matches the snapshot file at `path`.
+    ///
+    /// The snapshot is written instead when it doesn't exist yet or `UPDATE_SNAPSHOTS=1` is set.
+    /// Secret values are masked. See the `snapshot` module for the format.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust,no_run
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new().add_file("config/prod.yaml").build().unwrap();
+    /// config.assert_matches_snapshot("tests/snapshots/prod.snap");
+    /// ```
+    pub fn assert_matches_snapshot<P: AsRef<Path>>(&self, path: P) {
+        snapshot::assert_matches(self, path.as_ref());
+    }
+
+    /// Refetches every value cached by the resolvers the configuration was loaded with.
+    ///
+    /// Only the resolvers' caches are refreshed: the values of this `Config`, including those
+    /// its tags resolved to, are left unchanged. Rebuild the configuration, e.g. with
+    /// `SharedConfig::reload`, to pick up the refreshed values. See
+    /// `resolvers::cache::CachedResolver`.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust,no_run
+    /// use yaml_config::{ConfigBuilder, SharedConfig};
+    /// let build = || ConfigBuilder::new().add_file("config/prod.yaml").build();
+    /// let shared = SharedConfig::new(build()?);
+    /// // After a secret was rotated:
+    /// shared.snapshot().refresh_secrets()?;
+    /// shared.reload(build)?;
+    /// # Ok::<(), yaml_config::error::ParseError>(())
+    /// ```
+    pub fn refresh_secrets(&self) -> Result<(), ParseError> {
+        self.resolvers.refresh()
+    }
+}
+
+/// The error of `get_first_ok` and `get_weighted_ok` listing why each candidate was rejected.
+fn no_usable_candidate(key: &str, rejections: Vec<(usize, String)>) -> ParseError {
+    let rejections: Vec<String> = rejections
+        .iter()
+        .map(|(i, e)| format!("{}: {}", i, e))
+        .collect();
+    ParseError::new(
+        "config",
+        format!(
+            "No candidate for {} is usable ({}).",
+            key,
+            rejections.join("; ")
+        ),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::numbers::NumberFormat;
+    use crate::schema::{KeySpec, Schema, Visibility};
+    use crate::{ConfigBuilder, ErrorCode, LoadOptions, ParseError, Preference, Value};
+    use envtestkit::lock::lock_test;
+    use envtestkit::set_env;
+    use fxhash::FxBuildHasher;
+    use indexmap::IndexMap;
+    use std::ffi::OsString;
+
+    #[test]
+    fn view_filters_by_visibility() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert("LEVEL".to_string(), Value::String("INFO".to_string()));
+        values.insert("HOST".to_string(), Value::String("db".to_string()));
+        values.insert("PASSWORD".to_string(), Value::String("hunter2".to_string()));
+        let schema = Schema::new()
+            .key("LEVEL", KeySpec::new().visibility(Visibility::Public))
+            .key("PASSWORD", KeySpec::new().visibility(Visibility::Secret));
+        let config = Config::new(values, Vec::new(), schema);
+
+        let public = config.view(Visibility::Public);
+        assert_eq!(public.len(), 1);
+        assert!(public.contains_key("LEVEL"));
+
+        let internal = config.view(Visibility::Internal);
+        assert_eq!(internal.len(), 2);
+        assert!(!internal.contains_key("PASSWORD"));
+
+        assert_eq!(config.view(Visibility::Secret).len(), 3);
+    }
+
+    #[test]
+    fn prefixes_and_sections_use_the_sorted_index() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        for tenant in ["GLOBEX", "ACME", "ACME2", "INITECH"] {
+            values.insert(format!("TENANTS_{}_QUOTA", tenant), Value::I64(1));
+            values.insert(format!("TENANTS_{}_REGION", tenant), Value::I64(2));
+        }
+        values.insert("DEBUG".to_string(), Value::Bool(true));
+        let config = Config::new(values, Vec::new(), Schema::new());
+
+        let keys: Vec<&str> = config
+            .iter_prefix("TENANTS_ACME")
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "TENANTS_ACME2_QUOTA",
+                "TENANTS_ACME2_REGION",
+                "TENANTS_ACME_QUOTA",
+                "TENANTS_ACME_REGION"
+            ]
+        );
+        assert_eq!(config.iter_prefix("").count(), 9);
+        assert_eq!(config.iter_prefix("TENANTS_Z").count(), 0);
+
+        let acme = config.section("tenants.acme");
+        assert_eq!(acme.keys().collect::<Vec<_>>(), vec!["QUOTA", "REGION"]);
+        assert_eq!(config.section("tenants").len(), 8);
+        assert!(config.section("debug").is_empty());
+
+        let config = config.overridden([("TENANTS_ACME_ZONE", "eu")]);
+        assert_eq!(config.section("tenants.acme").len(), 3);
+    }
+
+    #[test]
+    fn typed_getters_convert_or_explain() {
+        let config = ConfigBuilder::new()
+            .add_str("db:\n  host: db\n  port: 5432\n  ratio: 0.5\n  tls: true\n")
+            .build()
+            .unwrap();
+
+        assert_eq!(config.get_str("DB_HOST").unwrap(), "db");
+        assert_eq!(config.get_i64("DB_PORT").unwrap(), 5432);
+        assert_eq!(config.get_f64("DB_PORT").unwrap(), 5432.0);
+        assert_eq!(config.get_f64("DB_RATIO").unwrap(), 0.5);
+        assert!(config.get_bool("DB_TLS").unwrap());
+        assert_eq!(config.get_as::<String>("DB_HOST").unwrap(), "db");
+
+        let err = config.get_i64("DB_HOST").unwrap_err();
+        assert_eq!(err.module(), "config");
+        assert_eq!(
+            err.message(),
+            "DB_HOST: expected an integer, found a string."
+        );
+        assert_eq!(
+            config.get_str("DB_NAME").unwrap_err().message(),
+            "DB_NAME is not set."
+        );
+        assert!(matches!(
+            config.get_as::<bool>("DB_PORT").unwrap_err(),
+            ParseError::TypeMismatch { expected, .. } if expected == "a boolean"
+        ));
+        assert_eq!(
+            config.get_i64("DB_NAME").unwrap_err().code(),
+            ErrorCode::NotSet
+        );
+    }
+
+    #[test]
+    fn ranges_are_iterated_in_key_order() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        for i in (0..100).rev() {
+            values.insert(format!("KEY_{:03}", i), Value::I64(i));
+        }
+        let config = Config::new(values, Vec::new(), Schema::new());
+
+        let page: Vec<(&str, &Value)> = config.iter_range("KEY_050", 3).collect();
+        assert_eq!(
+            page,
+            vec![
+                ("KEY_050", &Value::I64(50)),
+                ("KEY_051", &Value::I64(51)),
+                ("KEY_052", &Value::I64(52))
+            ]
+        );
+        assert_eq!(
+            config.iter_range("KEY_0505", 1).next().unwrap().0,
+            "KEY_051"
+        );
+        assert_eq!(config.iter_range("", 1000).count(), 100);
+        assert_eq!(config.iter_range("L", 10).count(), 0);
+
+        let config = config.overridden([("KEY_050A", 1_i64)]);
+        assert_eq!(
+            config.iter_range("KEY_050", 2).nth(1).unwrap().0,
+            "KEY_050A"
+        );
+    }
+
+    #[test]
+    fn env_bindings_follow_the_layers_and_options() {
+        let _lock = lock_test();
+        let _port = set_env(OsString::from("BINDINGS_PORT"), "9090");
+        let yaml = "bindings:\n  host: db\n  port: null\n";
+        let builder = || {
+            ConfigBuilder::new()
+                .add_defaults([("BINDINGS_LEVEL", "info")])
+                .add_str(yaml)
+        };
+
+        let config = builder().build().unwrap();
+        assert_eq!(
+            config.env_bindings().iter().collect::<Vec<_>>(),
+            vec![(&"BINDINGS_PORT".to_string(), &"BINDINGS_PORT".to_string())]
+        );
+
+        let config = builder()
+            .add_env_prefix("APP_")
+            .add_str("bindings:\n  host: db.internal\n")
+            .build()
+            .unwrap();
+        let bindings = config.env_bindings();
+        assert_eq!(bindings["BINDINGS_LEVEL"], "APP_BINDINGS_LEVEL");
+        assert_eq!(bindings["BINDINGS_PORT"], "APP_BINDINGS_PORT");
+        assert!(!bindings.contains_key("BINDINGS_HOST"));
+
+        let config = builder()
+            .with_options(LoadOptions {
+                preference: Preference::PreferEnv,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        assert_eq!(config.env_bindings()["BINDINGS_HOST"], "BINDINGS_HOST");
+        assert!(!config.env_bindings().contains_key("BINDINGS_LEVEL"));
+    }
+
+    #[test]
+    fn env_and_yaml_first_consult_the_live_environment() {
+        let _lock = lock_test();
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert("GET_FIRST_PORT".to_string(), Value::I64(8080));
+        values.insert("GET_FIRST_NAME".to_string(), Value::String("a".to_string()));
+        let config = Config::new(values, Vec::new(), Schema::new());
+
+        assert_eq!(
+            config.get_env_first("GET_FIRST_PORT"),
+            Some(Value::I64(8080))
+        );
+        assert_eq!(config.get_yaml_first("GET_FIRST_MISSING"), None);
+
+        let _port = set_env(OsString::from("GET_FIRST_PORT"), "9090");
+        let _name = set_env(OsString::from("GET_FIRST_NAME"), "1");
+        let _missing = set_env(OsString::from("GET_FIRST_MISSING"), "true");
+
+        assert_eq!(
+            config.get_env_first("GET_FIRST_PORT"),
+            Some(Value::I64(9090))
+        );
+        assert_eq!(
+            config.get_env_first("GET_FIRST_NAME"),
+            Some(Value::String("1".to_string()))
+        );
+        assert_eq!(
+            config.get_yaml_first("GET_FIRST_PORT"),
+            Some(Value::I64(8080))
+        );
+        assert_eq!(
+            config.get_yaml_first("GET_FIRST_MISSING"),
+            Some(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn live_values_are_read_like_loaded_ones() {
+        let env = [
+            ("LIVE_DEBUG", "true"),
+            ("LIVE_RATIO", "1,5"),
+            ("LIVE_PORT", "x"),
+        ];
+        let config = ConfigBuilder::new()
+            .with_options(LoadOptions {
+                env: Some(env.map(|(k, v)| (k.to_string(), v.to_string())).into()),
+                number_format: NumberFormat::Localized {
+                    thousands: '.',
+                    decimal: ',',
+                },
+                ..Default::default()
+            })
+            .add_str("live:\n  debug: false\n  ratio: 0.5\n  port: 80\n")
+            .build()
+            .unwrap();
+
+        assert_eq!(config.get_env_first("LIVE_DEBUG"), Some(Value::Bool(true)));
+        assert_eq!(config.get_env_first("LIVE_RATIO"), Some(Value::F64(1.5)));
+        assert_eq!(config.get_env_first("LIVE_PORT"), Some(Value::I64(80)));
+    }
+
+    #[test]
+    fn first_ok_returns_the_first_accepted_candidate() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert(
+            "DB_URL".to_string(),
+            Value::Array(vec![
+                Value::String("primary".to_string()),
+                Value::String("replica1:5432".to_string()),
+                Value::String("replica2:5432".to_string()),
+            ]),
+        );
+        values.insert("PORT".to_string(), Value::I64(80));
+        let config = Config::new(values, Vec::new(), Schema::new());
+        let has_port = |val: &Value| match val.as_string() {
+            Some(s) if s.contains(':') => Ok(s.clone()),
+            _ => Err("missing port"),
+        };
+
+        assert_eq!(
+            config.get_first_ok("DB_URL", has_port).unwrap(),
+            "replica1:5432"
+        );
+        assert_eq!(
+            config
+                .get_first_ok("PORT", |v| v.as_i64().copied().ok_or("x"))
+                .unwrap(),
+            80
+        );
+
+        let error = config.get_first_ok("PORT", has_port).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "No candidate for PORT is usable (0: missing port)."
+        );
+        assert!(config.get_first_ok("MISSING", has_port).is_err());
+    }
+
+    #[test]
+    fn weighted_ok_picks_in_proportion_to_the_weights() {
+        let config = ConfigBuilder::new()
+            .add_str(
+                "db_url:\n  - {value: 'a:1', weight: 1}\n  - {value: b, weight: 2}\n  \
+                 - {value: 'c:1', weight: 1}\n  - {value: 'd:1', weight: 0}\nport: 80\n",
+            )
+            .build()
+            .unwrap();
+        let has_port = |val: &Value| match val.as_string() {
+            Some(s) if s.contains(':') => Ok(s.clone()),
+            _ => Err("missing port"),
+        };
+        let pick = |random| config.get_weighted_ok("DB_URL", random, has_port);
+
+        assert_eq!(pick(0.0).unwrap(), "a:1");
+        assert_eq!(pick(0.9).unwrap(), "c:1");
+        // `b` is rejected, so the pick is repeated between `a` and `c`.
+        assert_eq!(pick(0.5).unwrap(), "c:1");
+        assert_eq!(
+            config
+                .get_weighted_ok("PORT", 0.3, |v| v.as_i64().copied().ok_or("x"))
+                .unwrap(),
+            80
+        );
+        assert_eq!(
+            config
+                .get_weighted_ok("DB_URL", 0.5, |_| Err::<(), _>("down"))
+                .unwrap_err()
+                .message(),
+            "No candidate for DB_URL is usable (0: down; 1: down; 2: down; 3: the weight is 0)."
+        );
+    }
+
+    #[test]
+    fn registered_defaults_fill_in_missing_keys() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert("CACHE_TTL".to_string(), Value::I64(60));
+        let mut config = Config::new(values, Vec::new(), Schema::new());
+
+        config.register_default("CACHE_TTL", 300_i64);
+        config.register_default("CACHE_NAME", "main");
+
+        assert_eq!(config.get("CACHE_TTL"), Some(&Value::I64(60)));
+        assert_eq!(
+            config.get("CACHE_NAME"),
+            Some(&Value::String("main".to_string()))
+        );
+        assert!(config.contains_key("CACHE_NAME"));
+        assert_eq!(config.len(), 1);
+        assert_eq!(
+            config.view(Visibility::Internal).get("CACHE_NAME"),
+            Some(&Value::String("main".to_string()))
+        );
+    }
+
+    #[test]
+    fn mounted_schemas_are_checked() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert("CACHE_SIZE".to_string(), Value::I64(-1));
+        let mut config = Config::new(values, Vec::new(), Schema::new());
+        let positive = |val: &Value| match val.as_i64() {
+            Some(v) if *v > 0 => Ok(()),
+            _ => Err("must be positive".to_string()),
+        };
+
+        let cache = Schema::new()
+            .key("CACHE_SIZE", KeySpec::new().validator(positive))
+            .key("CACHE_HOST", KeySpec::new().required())
+            .key("CACHE_TTL", KeySpec::new().default_value(300_i64));
+        let error = config.mount_schema(cache).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "CACHE_SIZE: must be positive CACHE_HOST is required."
+        );
+        assert!(config.get("CACHE_TTL").is_none());
+
+        let cache = Schema::new()
+            .key(
+                "CACHE_TTL",
+                KeySpec::new().default_value(300_i64).validator(positive),
+            )
+            .key("CACHE_SIZE", KeySpec::new().visibility(Visibility::Public));
+        config.mount_schema(cache).unwrap();
+        assert_eq!(config.get("CACHE_TTL"), Some(&Value::I64(300)));
+        assert_eq!(
+            config.schema().visibility_of("CACHE_SIZE"),
+            Visibility::Public
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Deserializing configurations into your own types with serde.
+//!
+//! `from_file` loads a file with `load_nested`, so environment variables apply exactly as they
+//! do for `load`, and deserializes the tree into any type implementing `Deserialize`. Struct
+//! fields are matched against keys after both are normalized, so a field `max_connections`
+//! reads `max-connections`, `MAX_CONNECTIONS`, or `max.connections` from the file. Keys of maps
+//! deserialized into a `HashMap` or similar keep their normalized form, e.g. `MAX_CONNECTIONS`.
+//!
+//! `Value` implements `Deserializer` as well, so a single value of a `Config` can be converted
+//! with `from_value`.
+//!
+//! **Examples**
+//!
+//! ```rust,no_run
+//! use serde::Deserialize;
+//! use yaml_config::Preference;
+//!
+//! #[derive(Deserialize)]
+//! struct DbConfig {
+//!     host: String,
+//!     port: u16,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct AppConfig {
+//!     db: DbConfig,
+//!     debug: bool,
+//! }
+//!
+//! let config: AppConfig = yaml_config::from_file("config.yaml", Some(Preference::PreferEnv))?;
+//! # Ok::<(), yaml_config::error::ParseError>(())
+//! ```
+use crate::{load_nested, normalize_key, LoadOptions, ParseError, Preference, Value};
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use std::fmt::Display;
+
+impl serde::de::Error for ParseError {
+    fn custom<T: Display>(msg: T) -> Self {
+        ParseError::new("config::de", msg.to_string())
+    }
+}
+
+/// Loads the YAML file at `file_path` and deserializes it into `T`. `preference` is handled as
+/// by `load`.
+pub fn from_file<T: DeserializeOwned>(
+    file_path: &str,
+    preference: Option<Preference>,
+) -> Result<T, ParseError> {
+    let options = LoadOptions {
+        preference: preference.unwrap_or_default(),
+        ..Default::default()
+    };
+    from_file_with(file_path, &options)
+}
+
+/// Loads the YAML file at `file_path` with `options` and deserializes it into `T`.
+pub fn from_file_with<T: DeserializeOwned>(
+    file_path: &str,
+    options: &LoadOptions,
+) -> Result<T, ParseError> {
+    from_value(Value::Map(load_nested(file_path, options)?))
+}
+
+/// Deserializes `value` into `T`.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::de::from_value;
+/// use yaml_config::Value;
+/// let ports: Vec<u16> = from_value(Value::Array(vec![Value::I64(80), Value::I64(443)]))?;
+/// assert_eq!(ports, vec![80, 443]);
+/// # Ok::<(), yaml_config::error::ParseError>(())
+/// ```
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, ParseError> {
+    T::deserialize(value)
+}
+
+impl<'de> IntoDeserializer<'de, ParseError> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = ParseError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParseError> {
+        match self {
+            Value::I32(v) => visitor.visit_i32(v),
+            Value::I64(v) => visitor.visit_i64(v),
+            Value::F32(v) => visitor.visit_f32(v),
+            Value::F64(v) => visitor.visit_f64(v),
+            Value::String(v) => visitor.visit_string(v),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Array(items) => {
+                let mut seq = SeqDeserializer::new(items.into_iter());
+                let val = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(val)
+            }
+            Value::Map(map) => {
+                let mut entries = MapDeserializer::new(map.into_iter());
+                let val = visitor.visit_map(&mut entries)?;
+                entries.end()?;
+                Ok(val)
+            }
+            // As `std::time::Duration` is deserialized from a sequence.
+            Value::Duration(v) => {
+                let secs = i64::try_from(v.as_secs()).unwrap_or(i64::MAX);
+                let parts = vec![Value::I64(secs), Value::I64(i64::from(v.subsec_nanos()))];
+                Value::Array(parts).deserialize_any(visitor)
+            }
+            Value::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParseError> {
+        match self {
+            Value::Null => visitor.visit_none(),
+            val => visitor.visit_some(val),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ParseError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Presents the fields under the names the struct expects, followed by any keys that match
+    /// no field.
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ParseError> {
+        let mut map = match self {
+            Value::Map(map) => map,
+            other => return other.deserialize_any(visitor),
+        };
+        let mut entries = Vec::with_capacity(map.len());
+        for field in fields {
+            if let Some(val) = map.shift_remove(&normalize_key(field)) {
+                entries.push((field.to_string(), val));
+            }
+        }
+        entries.extend(map);
+
+        let mut entries = MapDeserializer::new(entries.into_iter());
+        let val = visitor.visit_map(&mut entries)?;
+        entries.end()?;
+        Ok(val)
+    }
+
+    /// Unit variants are read from strings, and other variants from a map with a single key
+    /// naming the variant.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ParseError> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Map(map) if map.len() == 1 => visitor.visit_enum(MapAccessDeserializer::new(
+                MapDeserializer::new(map.into_iter()),
+            )),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::de::{from_file, from_value};
+    use crate::{Preference, Value};
+    use envtestkit::lock::lock_test;
+    use envtestkit::set_env;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+    use std::ffi::OsString;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Level {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Db {
+        host: String,
+        port: u16,
+        max_connections: Option<u32>,
+        timeout: Option<f64>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct App {
+        db: Db,
+        replicas: Vec<Db>,
+        level: Level,
+        labels: HashMap<String, String>,
+    }
+
+    #[test]
+    fn files_are_deserialized_with_environment_overrides() {
+        let _lock = lock_test();
+        let _port = set_env(OsString::from("DE_TEST_DB_PORT"), "6543");
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(
+            &path,
+            "de-test:\n  db:\n    host: localhost\n    port: 5432\n    max-connections: 10\n  \
+             replicas:\n    - host: a\n      port: 5433\n  level: info\n  labels:\n    team: core\n",
+        )
+        .unwrap();
+
+        #[derive(Debug, Deserialize)]
+        struct Root {
+            de_test: App,
+        }
+        let root: Root = from_file(path.to_str().unwrap(), Some(Preference::PreferEnv)).unwrap();
+
+        assert_eq!(
+            root.de_test,
+            App {
+                db: Db {
+                    host: "localhost".to_string(),
+                    port: 6543,
+                    max_connections: Some(10),
+                    timeout: None,
+                },
+                replicas: vec![Db {
+                    host: "a".to_string(),
+                    port: 5433,
+                    max_connections: None,
+                    timeout: None,
+                }],
+                level: Level::Info,
+                labels: HashMap::from([("TEAM".to_string(), "core".to_string())]),
+            }
+        );
+    }
+
+    #[test]
+    fn mismatched_values_are_errors() {
+        let err = from_value::<Db>(Value::String("db".to_string())).unwrap_err();
+        assert_eq!(err.module(), "config::de");
+
+        assert_eq!(
+            from_value::<Level>(Value::String("debug".to_string())).unwrap(),
+            Level::Debug
+        );
+        assert!(from_value::<u8>(Value::I64(300)).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Debug rendering of the resolved configuration.
+//!
+//! `render` produces the JSON document served at `/debug/config`, and `render_health` the
+//! one served at `/health/config`. With the `web` feature enabled, `router` returns a
+//! ready-made `axum` router serving both from a `SharedConfig`.
+use crate::json::{write_str, write_value};
+use crate::schema::Visibility;
+use crate::{Config, SharedConfig};
+use std::time::UNIX_EPOCH;
+
+/// The replacement shown for secret values.
+pub const MASK: &str = "********";
+
+/// Renders `config` and its layer stack as JSON.
+///
+/// Only keys whose visibility is at or below `visibility` are included, and values of keys
+/// tagged `Visibility::Secret` are always replaced with `MASK`.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::debug::render;
+/// use yaml_config::{ConfigBuilder, Visibility};
+/// let config = ConfigBuilder::new().build().unwrap();
+/// assert_eq!(render(&config, Visibility::Public), "{\"config\":{},\"layers\":[]}");
+/// ```
+pub fn render(config: &Config, visibility: Visibility) -> String {
+    let view = config.view(visibility);
+    let mut out = String::from("{\"config\":{");
+    for (i, (key, val)) in view.values().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_str(key, &mut out);
+        out.push(':');
+        if config.schema().visibility_of(key) == Visibility::Secret {
+            write_str(MASK, &mut out);
+        } else {
+            write_value(val, &mut out);
+        }
+    }
+    out.push_str("},\"layers\":[");
+    for (i, layer) in config.layers().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"kind\":");
+        write_str(&layer.kind.to_string(), &mut out);
+        out.push_str(",\"location\":");
+        write_str(&layer.location, &mut out);
+        out.push_str(&format!(
+            ",\"key_count\":{},\"priority\":{},\"read_only\":{}}}",
+            layer.key_count, layer.priority, layer.read_only
+        ));
+    }
+    out.push_str("]}");
+    out
+}
+
+/// Renders the reload health of `shared` as JSON.
+///
+/// The status is `ok` unless the most recent reload was rejected, in which case it is
+/// `degraded` and the error, the diff that was not applied, and the time of the rejection in
+/// seconds since the Unix epoch are included. Like `render`, the diff only lists keys whose
+/// visibility is at or below `visibility`.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::debug::render_health;
+/// use yaml_config::{ConfigBuilder, SharedConfig, Visibility};
+/// let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+/// assert_eq!(render_health(&shared, Visibility::Public), "{\"status\":\"ok\"}");
+/// ```
+pub fn render_health(shared: &SharedConfig, visibility: Visibility) -> String {
+    let (failure, snapshot) = shared.failure_and_snapshot();
+    let failure = match failure {
+        Some(failure) => failure,
+        None => return "{\"status\":\"ok\"}".to_string(),
+    };
+    let mut out = String::from("{\"status\":\"degraded\",\"error\":");
+    write_str(&failure.error, &mut out);
+    out.push_str(",\"rejected_diff\":");
+    // Each line of the diff reads `- KEY = value` or `+ KEY = value`.
+    let diff: String = failure
+        .diff
+        .lines()
+        .filter(|line| {
+            let key = line.get(2..).and_then(|rest| rest.split(" = ").next());
+            key.is_some_and(|key| snapshot.schema().visibility_of(key) <= visibility)
+        })
+        .map(|line| format!("{}\n", line))
+        .collect();
+    write_str(&diff, &mut out);
+    let at = failure.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    out.push_str(&format!(",\"rejected_at\":{}}}", at.as_secs()));
+    out
+}
+
+/// Returns an `axum` router serving `render` of the live snapshot at `/debug/config` and
+/// `render_health` at `/health/config`. The health endpoint answers `503 Service Unavailable`
+/// while the most recent reload is rejected.
+///
+/// The snapshot is read on every request, so reloads are reflected immediately.
+#[cfg(feature = "web")]
+pub fn router(shared: SharedConfig, visibility: Visibility) -> axum::Router {
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+
+    let health = shared.clone();
+    axum::Router::new()
+        .route(
+            "/debug/config",
+            get(move || {
+                let body = render(&shared.snapshot(), visibility);
+                async move { ([(CONTENT_TYPE, "application/json")], body) }
+            }),
+        )
+        .route(
+            "/health/config",
+            get(move || {
+                let status = match health.last_reload_failure() {
+                    Some(_) => StatusCode::SERVICE_UNAVAILABLE,
+                    None => StatusCode::OK,
+                };
+                let body = render_health(&health, visibility);
+                async move { (status, [(CONTENT_TYPE, "application/json")], body) }
+            }),
+        )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::debug::{render, render_health};
+    use crate::schema::{KeySpec, Schema, Visibility};
+    use crate::{ConfigBuilder, ParseError, SharedConfig};
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn secrets_are_masked_and_filtered() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.yaml");
+        let mut file = File::create(&file_path).unwrap();
+        writeln!(file, "level: \"INFO\"\npassword: \"hunter2\"").unwrap();
+        let schema = Schema::new()
+            .key("LEVEL", KeySpec::new().visibility(Visibility::Public))
+            .key("PASSWORD", KeySpec::new().visibility(Visibility::Secret));
+        let config = ConfigBuilder::new()
+            .with_schema(schema)
+            .add_file(file_path.to_str().unwrap())
+            .build()
+            .unwrap();
+
+        let public = render(&config, Visibility::Public);
+        assert!(
+            public.starts_with("{\"config\":{\"LEVEL\":\"INFO\"},\"layers\":[{\"kind\":\"file\"")
+        );
+
+        let secret = render(&config, Visibility::Secret);
+        assert!(secret.contains("\"PASSWORD\":\"********\""));
+        assert!(!secret.contains("hunter2"));
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn rejected_reloads_are_reported() {
+        let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+        let _ = shared.reload(|| Err(ParseError::new("test", "unreadable")));
+
+        let health = render_health(&shared, Visibility::Public);
+
+        assert!(health.starts_with(
+            "{\"status\":\"degraded\",\"error\":\"[YC9000] test: unreadable\",\
+             \"rejected_diff\":\"\""
+        ));
+    }
+
+    #[test]
+    fn rejected_diffs_are_filtered() {
+        let schema = Schema::new()
+            .key("LEVEL", KeySpec::new().visibility(Visibility::Public))
+            .key(
+                "PORT",
+                KeySpec::new().validator(|val| match val.as_i64() {
+                    Some(0) => Err("must not be 0".to_string()),
+                    _ => Ok(()),
+                }),
+            );
+        let current = ConfigBuilder::new()
+            .with_schema(schema)
+            .add_str("level: INFO\nport: 80\n")
+            .build()
+            .unwrap();
+        let shared = SharedConfig::new(current);
+        // The candidate builds, but the current schema rejects it.
+        let _ = shared.reload(|| {
+            ConfigBuilder::new()
+                .add_str("level: DEBUG\nport: 0\n")
+                .build()
+        });
+
+        let public = render_health(&shared, Visibility::Public);
+        assert!(public.contains("\"rejected_diff\":\"- LEVEL = \\\"INFO\\\"\\n+ LEVEL"));
+        assert!(!public.contains("PORT = "));
+        assert!(render_health(&shared, Visibility::Internal).contains("+ PORT = 0"));
+    }
+}
@@ -1,60 +1,472 @@
 //! Errors returned by config.
 //!
-//! The primary error returned will be `ParseError`. `ParseError` wraps a number of different
-//! types of sub-errors that give more information.
+//! The primary error returned will be `ParseError`. It is an enum of the kinds of failure
+//! callers may want to handle differently, such as a missing environment variable or a syntax
+//! error at a given line, plus `ParseError::Other` for everything else. Every variant names the
+//! module it came from and a message through `module` and `message`, and a stable `ErrorCode`
+//! through `code`, such as `YC1001` for a missing environment variable. It displays as
+//! `[code] module: message`, so logs can be searched and alerted on by code.
 //!
+//! **Examples**
 //!
+//! ```rust
+//! use yaml_config::{load_from_str, ParseError};
+//! match load_from_str("db:\n  password: null\n", None) {
+//!     Err(ParseError::MissingEnv { key }) => eprintln!("set {} to start the service", key),
+//!     Err(e) => eprintln!("{}", e),
+//!     Ok(values) => println!("{:?}", values),
+//! }
+//! ```
+use crate::messages;
+use crate::missing::MissingReport;
+use std::borrow::Cow;
 use std::env::VarError;
 use std::fmt;
 use std::io::Error;
 use yaml_rust::scanner::ScanError;
+
 /// Defines a ParseError.
 ///
-/// `ParseError` is a wrapper around several different kinds of sub-errors that may occur. The goal
-/// is to give the user what they need without overburdening them with match statements.
-///
 /// **Examples**
 ///
 /// ```rust
 /// use yaml_config::error::ParseError;
-/// let error = ParseError { module: "some_mod".to_string(), message: "something broke!".to_string() };
+/// let error = ParseError::new("some_mod", "something broke!");
+/// assert_eq!(error.to_string(), "[YC9000] some_mod: something broke!");
 /// ```
 #[derive(Debug)]
-pub struct ParseError {
-    pub module: String,
-    pub message: String,
+#[non_exhaustive]
+pub enum ParseError {
+    /// Reading a file or another resource failed.
+    Io { source: Error },
+    /// The document is not valid YAML. `line` and `col` locate the problem, starting at 1 and
+    /// 0 respectively as reported by the scanner.
+    Scan {
+        line: usize,
+        col: usize,
+        source: ScanError,
+    },
+    /// A `null` value has no environment variable providing it.
+    MissingEnv { key: String },
+    /// An environment variable is not valid unicode, or otherwise can't be read.
+    Env {
+        key: Option<String>,
+        source: VarError,
+    },
+    /// The process environment can't be read and `LoadOptions::on_env_unavailable` is
+    /// `OnEnvUnavailable::Error`.
+    EnvUnavailable,
+    /// The environment variable overriding `key` doesn't parse as the type of its YAML value.
+    /// `location` is where that value is in the document, when known.
+    InvalidEnv {
+        key: String,
+        value: String,
+        expected: &'static str,
+        location: Option<Location>,
+    },
+    /// A value has another type than the one asked for.
+    TypeMismatch {
+        key: String,
+        expected: String,
+        found: String,
+    },
+    /// A key asked for by `Config::get_as` or its siblings isn't set.
+    NotSet { key: String },
+    /// More than one required key is missing. The report lists all of them.
+    MissingKeys { report: MissingReport },
+    /// The map at `key` is nested deeper than `LoadOptions::max_depth` levels. `location` is
+    /// where the map is in the document, when known.
+    DepthExceeded {
+        key: String,
+        max_depth: usize,
+        location: Option<Location>,
+    },
+    /// A YAML node can't be represented as a value. `location` is where the node is in the
+    /// document, when known.
+    UnsupportedStructure {
+        key: String,
+        location: Option<Location>,
+    },
+    /// The document holds no YAML nodes and `LoadOptions::on_empty` is `OnEmpty::Error`.
+    EmptyDocument,
+    /// Any other failure, described by the module it came from and a message. `code` tells the
+    /// families of failure apart, such as `ErrorCode::CircularInclude`, and is `ErrorCode::Other`
+    /// for the rest.
+    Other {
+        module: String,
+        message: String,
+        code: ErrorCode,
+    },
+}
+
+/// A stable code identifying the kind of a `ParseError`.
+///
+/// Codes never change meaning once released. The first digit groups them: `1` for the
+/// environment, `2` for the document, `3` for values, `4` for I/O, and `9` for everything else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// `YC1001`, `ParseError::MissingEnv`.
+    MissingEnvVar,
+    /// `YC1002`, `ParseError::Env`.
+    EnvUnreadable,
+    /// `YC1003`, `ParseError::InvalidEnv`.
+    InvalidEnvValue,
+    /// `YC1004`, `ParseError::EnvUnavailable`.
+    EnvUnavailable,
+    /// `YC2001`, `ParseError::Scan`.
+    Syntax,
+    /// `YC2002`, `ParseError::DepthExceeded`.
+    DepthExceeded,
+    /// `YC2003`, `ParseError::UnsupportedStructure`.
+    UnsupportedStructure,
+    /// `YC2004`, an `!include` that includes itself, directly or not.
+    CircularInclude,
+    /// `YC2005`, `ParseError::EmptyDocument`.
+    EmptyDocument,
+    /// `YC2006`, two keys of the document that flatten to the same key.
+    KeyCollision,
+    /// `YC2007`, a tag no resolver is registered for.
+    UnknownTag,
+    /// `YC3001`, `ParseError::TypeMismatch`.
+    TypeMismatch,
+    /// `YC3002`, `ParseError::MissingKeys`.
+    MissingKeys,
+    /// `YC3003`, values that break the schema.
+    SchemaViolation,
+    /// `YC3004`, values that break an enforced policy.
+    PolicyViolation,
+    /// `YC3005`, a layer overriding a key of a read-only layer.
+    ReadOnlyOverride,
+    /// `YC3006`, `ParseError::NotSet`.
+    NotSet,
+    /// `YC4001`, `ParseError::Io`.
+    Io,
+    /// `YC4002`, reading more files or bytes than `LoadOptions::read_budget` allows.
+    ReadBudgetExceeded,
+    /// `YC4003`, a `{VAR}_FILE` variable naming a file that can't be read.
+    SecretFileUnreadable,
+    /// `YC4004`, a file denied by the `PermissionPolicy` of a `ConfigBuilder`.
+    UnsafePermissions,
+    /// `YC4005`, a remote source that can't be reached.
+    SourceUnreachable,
+    /// `YC4006`, a path refused by `LoadOptions::symlinks` or `LoadOptions::base_dir`.
+    PathDenied,
+    /// `YC9000`, `ParseError::Other`.
+    Other,
+}
+
+impl ErrorCode {
+    /// Every code, in numeric order.
+    pub const ALL: [ErrorCode; 24] = [
+        ErrorCode::MissingEnvVar,
+        ErrorCode::EnvUnreadable,
+        ErrorCode::InvalidEnvValue,
+        ErrorCode::EnvUnavailable,
+        ErrorCode::Syntax,
+        ErrorCode::DepthExceeded,
+        ErrorCode::UnsupportedStructure,
+        ErrorCode::CircularInclude,
+        ErrorCode::EmptyDocument,
+        ErrorCode::KeyCollision,
+        ErrorCode::UnknownTag,
+        ErrorCode::TypeMismatch,
+        ErrorCode::MissingKeys,
+        ErrorCode::SchemaViolation,
+        ErrorCode::PolicyViolation,
+        ErrorCode::ReadOnlyOverride,
+        ErrorCode::NotSet,
+        ErrorCode::Io,
+        ErrorCode::ReadBudgetExceeded,
+        ErrorCode::SecretFileUnreadable,
+        ErrorCode::UnsafePermissions,
+        ErrorCode::SourceUnreachable,
+        ErrorCode::PathDenied,
+        ErrorCode::Other,
+    ];
+
+    /// The code as printed, e.g. `YC1001`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::MissingEnvVar => "YC1001",
+            ErrorCode::EnvUnreadable => "YC1002",
+            ErrorCode::InvalidEnvValue => "YC1003",
+            ErrorCode::EnvUnavailable => "YC1004",
+            ErrorCode::Syntax => "YC2001",
+            ErrorCode::DepthExceeded => "YC2002",
+            ErrorCode::UnsupportedStructure => "YC2003",
+            ErrorCode::CircularInclude => "YC2004",
+            ErrorCode::EmptyDocument => "YC2005",
+            ErrorCode::KeyCollision => "YC2006",
+            ErrorCode::UnknownTag => "YC2007",
+            ErrorCode::TypeMismatch => "YC3001",
+            ErrorCode::MissingKeys => "YC3002",
+            ErrorCode::SchemaViolation => "YC3003",
+            ErrorCode::PolicyViolation => "YC3004",
+            ErrorCode::ReadOnlyOverride => "YC3005",
+            ErrorCode::NotSet => "YC3006",
+            ErrorCode::Io => "YC4001",
+            ErrorCode::ReadBudgetExceeded => "YC4002",
+            ErrorCode::SecretFileUnreadable => "YC4003",
+            ErrorCode::UnsafePermissions => "YC4004",
+            ErrorCode::SourceUnreachable => "YC4005",
+            ErrorCode::PathDenied => "YC4006",
+            ErrorCode::Other => "YC9000",
+        }
+    }
+
+    /// The name of the code, e.g. `MissingEnvVar`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::MissingEnvVar => "MissingEnvVar",
+            ErrorCode::EnvUnreadable => "EnvUnreadable",
+            ErrorCode::InvalidEnvValue => "InvalidEnvValue",
+            ErrorCode::EnvUnavailable => "EnvUnavailable",
+            ErrorCode::Syntax => "Syntax",
+            ErrorCode::DepthExceeded => "DepthExceeded",
+            ErrorCode::UnsupportedStructure => "UnsupportedStructure",
+            ErrorCode::CircularInclude => "CircularInclude",
+            ErrorCode::EmptyDocument => "EmptyDocument",
+            ErrorCode::KeyCollision => "KeyCollision",
+            ErrorCode::UnknownTag => "UnknownTag",
+            ErrorCode::TypeMismatch => "TypeMismatch",
+            ErrorCode::MissingKeys => "MissingKeys",
+            ErrorCode::SchemaViolation => "SchemaViolation",
+            ErrorCode::PolicyViolation => "PolicyViolation",
+            ErrorCode::ReadOnlyOverride => "ReadOnlyOverride",
+            ErrorCode::NotSet => "NotSet",
+            ErrorCode::Io => "Io",
+            ErrorCode::ReadBudgetExceeded => "ReadBudgetExceeded",
+            ErrorCode::SecretFileUnreadable => "SecretFileUnreadable",
+            ErrorCode::UnsafePermissions => "UnsafePermissions",
+            ErrorCode::SourceUnreachable => "SourceUnreachable",
+            ErrorCode::PathDenied => "PathDenied",
+            ErrorCode::Other => "Other",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Where a node is in a YAML document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The file the document was read from, if any.
+    pub file: Option<String>,
+    /// The line, starting at 1.
+    pub line: usize,
+    /// The column, starting at 1.
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}:{}", file, self.line, self.column),
+            None => write!(f, "line {}, column {}", self.line, self.column),
+        }
+    }
+}
+
+impl ParseError {
+    /// Creates a `ParseError::Other` with the code `ErrorCode::Other`.
+    pub fn new<M: Into<String>, S: Into<String>>(module: M, message: S) -> Self {
+        ParseError::with_code(ErrorCode::Other, module, message)
+    }
+
+    /// Creates a `ParseError::Other` with `code`.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::error::{ErrorCode, ParseError};
+    /// let error = ParseError::with_code(ErrorCode::SchemaViolation, "app", "PORT is required.");
+    /// assert_eq!(error.to_string(), "[YC3003] app: PORT is required.");
+    /// ```
+    pub fn with_code<M: Into<String>, S: Into<String>>(
+        code: ErrorCode,
+        module: M,
+        message: S,
+    ) -> Self {
+        ParseError::Other {
+            module: module.into(),
+            message: message.into(),
+            code,
+        }
+    }
+
+    /// The module the error came from, such as `config` or `yaml_rust::scanner`.
+    pub fn module(&self) -> &str {
+        match self {
+            ParseError::Io { .. } => "std::io",
+            ParseError::Scan { .. } => "yaml_rust::scanner",
+            ParseError::MissingEnv { .. } | ParseError::Env { .. } | ParseError::EnvUnavailable => {
+                "std::env"
+            }
+            ParseError::InvalidEnv { .. }
+            | ParseError::TypeMismatch { .. }
+            | ParseError::NotSet { .. }
+            | ParseError::MissingKeys { .. }
+            | ParseError::DepthExceeded { .. }
+            | ParseError::UnsupportedStructure { .. }
+            | ParseError::EmptyDocument => "config",
+            ParseError::Other { module, .. } => module,
+        }
+    }
+
+    /// The stable code of the kind of failure.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ParseError::Io { .. } => ErrorCode::Io,
+            ParseError::Scan { .. } => ErrorCode::Syntax,
+            ParseError::MissingEnv { .. } => ErrorCode::MissingEnvVar,
+            ParseError::Env { .. } => ErrorCode::EnvUnreadable,
+            ParseError::EnvUnavailable => ErrorCode::EnvUnavailable,
+            ParseError::InvalidEnv { .. } => ErrorCode::InvalidEnvValue,
+            ParseError::TypeMismatch { .. } => ErrorCode::TypeMismatch,
+            ParseError::NotSet { .. } => ErrorCode::NotSet,
+            ParseError::MissingKeys { .. } => ErrorCode::MissingKeys,
+            ParseError::DepthExceeded { .. } => ErrorCode::DepthExceeded,
+            ParseError::UnsupportedStructure { .. } => ErrorCode::UnsupportedStructure,
+            ParseError::EmptyDocument => ErrorCode::EmptyDocument,
+            ParseError::Other { code, .. } => *code,
+        }
+    }
+
+    /// A description of the failure, without the module.
+    pub fn message(&self) -> Cow<'_, str> {
+        match self {
+            ParseError::Io { source } => Cow::Owned(source.to_string()),
+            ParseError::Scan { source, .. } => Cow::Owned(source.to_string()),
+            ParseError::MissingEnv { key } => {
+                Cow::Owned(messages::text("error.missing_env", &[("key", key)]))
+            }
+            ParseError::Env {
+                key: Some(key),
+                source: VarError::NotUnicode(_),
+            } => Cow::Owned(messages::text("error.env_not_unicode", &[("key", key)])),
+            ParseError::Env { source, .. } => Cow::Owned(source.to_string()),
+            ParseError::EnvUnavailable => Cow::Owned(messages::text("error.env_unavailable", &[])),
+            ParseError::InvalidEnv {
+                key,
+                value,
+                expected,
+                location,
+            } => Cow::Owned(messages::text(
+                "error.invalid_env",
+                &[
+                    ("key", key),
+                    ("value", value),
+                    ("expected", expected),
+                    ("at", &at(location)),
+                ],
+            )),
+            ParseError::TypeMismatch {
+                key,
+                expected,
+                found,
+            } => Cow::Owned(messages::text(
+                "error.type_mismatch",
+                &[("key", key), ("expected", expected), ("found", found)],
+            )),
+            ParseError::NotSet { key } => {
+                Cow::Owned(messages::text("error.not_set", &[("key", key)]))
+            }
+            ParseError::MissingKeys { report } => Cow::Owned(report.to_string()),
+            ParseError::DepthExceeded {
+                key,
+                max_depth,
+                location,
+            } => Cow::Owned(messages::text(
+                "error.depth_exceeded",
+                &[
+                    ("key", key),
+                    ("max_depth", &max_depth.to_string()),
+                    ("at", &at(location)),
+                ],
+            )),
+            ParseError::UnsupportedStructure { key, location } => Cow::Owned(messages::text(
+                "error.unsupported_structure",
+                &[("key", key), ("at", &at(location))],
+            )),
+            ParseError::EmptyDocument => Cow::Owned(messages::text("error.empty_document", &[])),
+            ParseError::Other { message, .. } => Cow::Borrowed(message),
+        }
+    }
+
+    /// Where in the document the error occurred, if known.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            ParseError::InvalidEnv { location, .. }
+            | ParseError::DepthExceeded { location, .. }
+            | ParseError::UnsupportedStructure { location, .. } => location.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Sets the location of an error about a node that doesn't have one yet.
+    pub(crate) fn located(mut self, found: Location) -> Self {
+        if let ParseError::InvalidEnv { location, .. }
+        | ParseError::DepthExceeded { location, .. }
+        | ParseError::UnsupportedStructure { location, .. } = &mut self
+        {
+            location.get_or_insert(found);
+        }
+        self
+    }
+}
+
+/// Formats an optional location as a suffix of a message.
+fn at(location: &Option<Location>) -> String {
+    match location {
+        Some(location) => messages::text("error.at", &[("location", &location.to_string())]),
+        None => String::new(),
+    }
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}: {}", self.module, self.message)
+        write!(f, "[{}] {}: {}", self.code(), self.module(), self.message())
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io { source } => Some(source),
+            ParseError::Scan { source, .. } => Some(source),
+            ParseError::Env { source, .. } => Some(source),
+            _ => None,
+        }
     }
 }
 
 impl From<ScanError> for ParseError {
     fn from(error: ScanError) -> Self {
-        ParseError {
-            module: String::from("yaml_rust::scanner"),
-            message: error.to_string(),
+        ParseError::Scan {
+            line: error.marker().line(),
+            col: error.marker().col(),
+            source: error,
         }
     }
 }
 
 impl From<VarError> for ParseError {
     fn from(error: VarError) -> Self {
-        ParseError {
-            module: String::from("std::env"),
-            message: error.to_string(),
+        ParseError::Env {
+            key: None,
+            source: error,
         }
     }
 }
 
 impl From<Error> for ParseError {
     fn from(error: Error) -> Self {
-        ParseError {
-            module: String::from("std::io"),
-            message: error.to_string(),
-        }
+        ParseError::Io { source: error }
     }
 }
 
@@ -66,11 +478,21 @@ mod test {
 
     #[test]
     fn test_display_trait() {
-        let error = ParseError {
-            module: "test::test".to_string(),
-            message: "test error".to_string(),
-        };
-        assert_eq!(format!("{}", error), "test::test: test error")
+        let error = ParseError::new("test::test", "test error");
+        assert_eq!(format!("{}", error), "[YC9000] test::test: test error")
+    }
+
+    #[test]
+    fn codes_are_unique_and_ordered() {
+        use crate::error::ErrorCode;
+
+        let error = ParseError::with_code(ErrorCode::ReadOnlyOverride, "test", "locked");
+        assert_eq!(error.to_string(), "[YC3005] test: locked");
+        let codes: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+        let mut sorted = codes.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(codes, sorted);
     }
 
     // ScanError cant be tested due to private fields.
@@ -80,13 +502,35 @@ mod test {
         let error = ParseError::from(VarError::NotPresent);
         assert_eq!(
             format!("{}", error),
-            "std::env: environment variable not found"
+            "[YC1002] std::env: environment variable not found"
         );
     }
 
     #[test]
     fn test_error() {
         let error = ParseError::from(Error::new(std::io::ErrorKind::Unsupported, "bad news"));
-        assert_eq!(format!("{}", error), "std::io: bad news");
+        assert_eq!(format!("{}", error), "[YC4001] std::io: bad news");
+    }
+
+    #[test]
+    fn kinds_can_be_matched() {
+        use crate::load_from_str;
+        use std::error::Error as _;
+
+        match load_from_str("a: [1\n", None).unwrap_err() {
+            ParseError::Scan { line, .. } => assert_eq!(line, 2),
+            e => panic!("unexpected error {:?}", e),
+        }
+        let error = load_from_str("error_test_unset: null\n", None).unwrap_err();
+        assert!(
+            matches!(&error, ParseError::MissingEnv { key } if key == "ERROR_TEST_UNSET"),
+            "{:?}",
+            error
+        );
+        assert!(error.source().is_none());
+        assert_eq!(error.code().as_str(), "YC1001");
+        assert_eq!(error.code().name(), "MissingEnvVar");
+        let error = ParseError::from(Error::new(std::io::ErrorKind::NotFound, "gone"));
+        assert_eq!(error.source().unwrap().to_string(), "gone");
     }
 }
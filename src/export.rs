@@ -0,0 +1,96 @@
+//! Exports of the resolved configuration for infrastructure tooling.
+//!
+//! `tfvars_json` renders a Terraform `.tfvars.json` file, with every flattened key lower-cased
+//! into a Terraform variable name (`DB_PORT` becomes `db_port`), and `json_vars` a generic JSON
+//! object keyed by the flattened keys. Both write values with their types, so numbers and
+//! booleans stay numbers and booleans, and sequences and maps become JSON arrays and objects.
+//!
+//! Only keys whose visibility is at or below `visibility` are exported. Values are written as
+//! they are, since infrastructure needs them, so export secrets only to files handled like the
+//! secrets themselves.
+//!
+//! **Examples**
+//!
+//! ```rust,no_run
+//! use yaml_config::{export, ConfigBuilder, Visibility};
+//! let config = ConfigBuilder::new().add_file("config/prod.yaml").build().unwrap();
+//! std::fs::write(
+//!     "infra/app.auto.tfvars.json",
+//!     export::tfvars_json(&config, Visibility::Internal),
+//! )
+//! .unwrap();
+//! ```
+use crate::json::{write_str, write_value};
+use crate::schema::Visibility;
+use crate::Config;
+
+fn render<F>(config: &Config, visibility: Visibility, name: F) -> String
+where
+    F: Fn(&str) -> String,
+{
+    let view = config.view(visibility);
+    if view.is_empty() {
+        return "{}\n".to_string();
+    }
+    let mut out = String::from("{\n");
+    for (i, (key, val)) in view.values().iter().enumerate() {
+        if i > 0 {
+            out.push_str(",\n");
+        }
+        out.push_str("  ");
+        write_str(&name(key), &mut out);
+        out.push_str(": ");
+        write_value(val, &mut out);
+    }
+    out.push_str("\n}\n");
+    out
+}
+
+/// Renders the keys of `config` visible at `visibility` as a Terraform `.tfvars.json` file.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::{export, ConfigBuilder, Visibility};
+/// let config = ConfigBuilder::new().add_str("db:\n  port: 5432\n").build().unwrap();
+/// assert_eq!(
+///     export::tfvars_json(&config, Visibility::Internal),
+///     "{\n  \"db_port\": 5432\n}\n"
+/// );
+/// ```
+pub fn tfvars_json(config: &Config, visibility: Visibility) -> String {
+    render(config, visibility, str::to_lowercase)
+}
+
+/// Renders the keys of `config` visible at `visibility` as a JSON object keyed by their
+/// flattened keys.
+pub fn json_vars(config: &Config, visibility: Visibility) -> String {
+    render(config, visibility, str::to_string)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::export::{json_vars, tfvars_json};
+    use crate::{ConfigBuilder, KeySpec, Schema, Visibility};
+
+    #[test]
+    fn visible_keys_are_exported_with_their_types() {
+        let config = ConfigBuilder::new()
+            .with_schema(
+                Schema::new().key("DB_PASSWORD", KeySpec::new().visibility(Visibility::Secret)),
+            )
+            .add_str("db:\n  host: db\n  port: 5432\n  password: hunter2\nzones: [a, b]\n")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            tfvars_json(&config, Visibility::Internal),
+            "{\n  \"db_host\": \"db\",\n  \"db_port\": 5432,\n  \"zones\": [\"a\",\"b\"]\n}\n"
+        );
+        assert!(json_vars(&config, Visibility::Secret).contains("\"DB_PASSWORD\": \"hunter2\""));
+        assert_eq!(
+            json_vars(&ConfigBuilder::new().build().unwrap(), Visibility::Public),
+            "{}\n"
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Facts about the system the configuration is loaded on.
+//!
+//! Facts are detected once per process, the first time they are needed, and are available
+//! through `Config::facts` and as variables of `!expr` expressions (see
+//! `resolvers::expr`).
+//!
+//! Inside a container, `cpu_count` and `memory_bytes` reflect the cgroup CPU quota and memory
+//! limit rather than the node, so pools sized from them fit the container. The node's values
+//! remain available as `host_cpu_count` and `host_memory_bytes`.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::facts::Facts;
+//! let facts = Facts::current();
+//! assert!(facts.cpu_count >= 1);
+//! ```
+use crate::Config;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::{env, fs, thread};
+
+/// Facts detected about the running system.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Facts {
+    /// The host name, if it is valid Unicode.
+    pub hostname: Option<String>,
+    /// The number of CPUs available to the process, taking a cgroup CPU quota into account.
+    pub cpu_count: usize,
+    /// The memory available to the process in bytes, taking a cgroup memory limit into
+    /// account, where the platform reports it.
+    pub memory_bytes: Option<u64>,
+    /// The number of CPUs of the machine.
+    pub host_cpu_count: usize,
+    /// The total memory of the machine in bytes, where the platform reports it.
+    pub host_memory_bytes: Option<u64>,
+    /// The cgroup CPU quota in CPUs, e.g. `1.5`, when one is set.
+    pub cpu_limit: Option<f64>,
+    /// The cgroup memory limit in bytes, when one is set.
+    pub memory_limit: Option<u64>,
+    /// The operating system, as in `std::env::consts::OS`.
+    pub os: &'static str,
+    /// The CPU architecture, as in `std::env::consts::ARCH`.
+    pub arch: &'static str,
+    /// Whether the process appears to run in a container.
+    pub container: bool,
+}
+
+/// Reads `MemTotal` from `/proc/meminfo`.
+fn memory_bytes() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemTotal:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    kib.checked_mul(1024)
+}
+
+/// Counts the processors listed in `/proc/cpuinfo`.
+fn host_cpu_count() -> Option<usize> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    let count = cpuinfo
+        .lines()
+        .filter(|l| l.starts_with("processor"))
+        .count();
+    (count > 0).then_some(count)
+}
+
+/// Memory limits at or above this many bytes mean "unlimited" in cgroup v1, which reports
+/// the largest page-aligned `i64` instead of `max`.
+const UNLIMITED_MEMORY: u64 = 1 << 62;
+
+/// Parses cgroup v2 `cpu.max` (`"150000 100000"` or `"max 100000"`) into a number of CPUs.
+fn parse_cpu_max(cpu_max: &str) -> Option<f64> {
+    let mut fields = cpu_max.split_whitespace();
+    let quota: f64 = fields.next()?.parse().ok()?;
+    let period: f64 = fields.next().unwrap_or("100000").parse().ok()?;
+    (quota > 0.0 && period > 0.0).then(|| quota / period)
+}
+
+/// Parses cgroup v2 `memory.max` or v1 `memory.limit_in_bytes`.
+fn parse_memory_max(memory_max: &str) -> Option<u64> {
+    memory_max
+        .trim()
+        .parse()
+        .ok()
+        .filter(|limit| *limit < UNLIMITED_MEMORY)
+}
+
+/// Reads the CPU quota and memory limit of the cgroup mounted at `root`, trying the unified
+/// (v2) hierarchy first and the v1 controllers second.
+fn cgroup_limits(root: &Path) -> (Option<f64>, Option<u64>) {
+    let read = |path: &str| fs::read_to_string(root.join(path)).ok();
+
+    let cpu = read("cpu.max").and_then(|v| parse_cpu_max(&v)).or_else(|| {
+        let quota: f64 = read("cpu/cpu.cfs_quota_us")?.trim().parse().ok()?;
+        let period = read("cpu/cpu.cfs_period_us")?;
+        parse_cpu_max(&format!("{} {}", quota, period.trim()))
+    });
+    let memory = read("memory.max")
+        .or_else(|| read("memory/memory.limit_in_bytes"))
+        .and_then(|v| parse_memory_max(&v));
+    (cpu, memory)
+}
+
+/// Looks for the marker files and variables container runtimes leave behind.
+fn in_container() -> bool {
+    if Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+        || env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+    {
+        return true;
+    }
+    fs::read_to_string("/proc/1/cgroup").is_ok_and(|cgroup| {
+        ["docker", "kubepods", "containerd", "libpod", "lxc"]
+            .iter()
+            .any(|runtime| cgroup.contains(runtime))
+    })
+}
+
+impl Facts {
+    /// Detects the facts of the running system.
+    pub fn detect() -> Self {
+        let available = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_limits(
+            host_cpu_count().unwrap_or(available).max(available),
+            available,
+            memory_bytes(),
+            cgroup_limits(Path::new("/sys/fs/cgroup")),
+        )
+    }
+
+    /// Builds the facts from the host's resources, the CPUs the process may be scheduled on,
+    /// and the cgroup limits.
+    fn with_limits(
+        host_cpu_count: usize,
+        available: usize,
+        host_memory_bytes: Option<u64>,
+        (cpu_limit, memory_limit): (Option<f64>, Option<u64>),
+    ) -> Self {
+        // A quota of 1.5 CPUs still lets two threads make progress.
+        let cpu_count = match cpu_limit {
+            Some(limit) => (limit.ceil() as usize).clamp(1, available),
+            None => available,
+        };
+        let memory_bytes = match (host_memory_bytes, memory_limit) {
+            (Some(host), Some(limit)) => Some(host.min(limit)),
+            (host, limit) => host.or(limit),
+        };
+        Facts {
+            hostname: gethostname::gethostname().into_string().ok(),
+            cpu_count,
+            memory_bytes,
+            host_cpu_count,
+            host_memory_bytes,
+            cpu_limit,
+            memory_limit,
+            os: env::consts::OS,
+            arch: env::consts::ARCH,
+            container: in_container(),
+        }
+    }
+
+    /// The facts detected for this process, detecting them on first use.
+    pub fn current() -> &'static Facts {
+        static FACTS: OnceLock<Facts> = OnceLock::new();
+        FACTS.get_or_init(Facts::detect)
+    }
+
+    /// The numeric facts by the names expressions use: `cpu_count`, `host_cpu_count`,
+    /// `container` as 1 or 0, and `memory_bytes`, `host_memory_bytes`, `cpu_limit`, and
+    /// `memory_limit` when known.
+    pub fn vars(&self) -> Vec<(&'static str, f64)> {
+        let mut vars = vec![
+            ("cpu_count", self.cpu_count as f64),
+            ("host_cpu_count", self.host_cpu_count as f64),
+            ("container", if self.container { 1.0 } else { 0.0 }),
+        ];
+        let optional = [
+            ("memory_bytes", self.memory_bytes.map(|b| b as f64)),
+            (
+                "host_memory_bytes",
+                self.host_memory_bytes.map(|b| b as f64),
+            ),
+            ("cpu_limit", self.cpu_limit),
+            ("memory_limit", self.memory_limit.map(|b| b as f64)),
+        ];
+        vars.extend(
+            optional
+                .into_iter()
+                .filter_map(|(name, val)| val.map(|val| (name, val))),
+        );
+        vars
+    }
+}
+
+impl Config {
+    /// The facts detected about the system the configuration was loaded on.
+    pub fn facts(&self) -> &'static Facts {
+        Facts::current()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::facts::{cgroup_limits, Facts};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn facts_are_detected_once() {
+        let facts = Facts::current();
+
+        assert!(std::ptr::eq(facts, Facts::current()));
+        assert!(facts.cpu_count >= 1);
+        assert_eq!(facts.os, std::env::consts::OS);
+        assert_eq!(facts.vars()[0], ("cpu_count", facts.cpu_count as f64));
+    }
+
+    #[test]
+    fn cgroup_v2_limits_are_read() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("cpu.max"), "150000 100000\n").unwrap();
+        fs::write(dir.path().join("memory.max"), "536870912\n").unwrap();
+
+        let limits = cgroup_limits(dir.path());
+        let facts = Facts::with_limits(16, 16, Some(64 << 30), limits);
+
+        assert_eq!(limits, (Some(1.5), Some(512 << 20)));
+        assert_eq!(facts.cpu_count, 2);
+        assert_eq!(facts.host_cpu_count, 16);
+        assert_eq!(facts.memory_bytes, Some(512 << 20));
+        assert_eq!(facts.host_memory_bytes, Some(64 << 30));
+    }
+
+    #[test]
+    fn unlimited_cgroups_use_host_values() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("cpu")).unwrap();
+        fs::create_dir(dir.path().join("memory")).unwrap();
+        fs::write(dir.path().join("cpu/cpu.cfs_quota_us"), "-1\n").unwrap();
+        fs::write(dir.path().join("cpu/cpu.cfs_period_us"), "100000\n").unwrap();
+        fs::write(
+            dir.path().join("memory/memory.limit_in_bytes"),
+            "9223372036854771712\n",
+        )
+        .unwrap();
+
+        let limits = cgroup_limits(dir.path());
+        let facts = Facts::with_limits(4, 4, Some(8 << 30), limits);
+
+        assert_eq!(limits, (None, None));
+        assert_eq!(facts.cpu_count, 4);
+        assert_eq!(facts.memory_bytes, Some(8 << 30));
+        assert_eq!(cgroup_limits(&dir.path().join("missing")), (None, None));
+    }
+}
@@ -0,0 +1,272 @@
+//! A C ABI for embedding the loader in C and C++ services.
+//!
+//! The functions are declared in `include/yaml_config.h`. Build a shared or static library with
+//! the `ffi` feature, e.g. `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+//! Ownership rules:
+//!
+//! * `yaml_config_load` returns a handle owned by the caller, released with `yaml_config_free`.
+//! * Strings returned by the library, including error messages, are owned by the caller and
+//!   released with `yaml_config_string_free`.
+//! * Strings passed to the library are borrowed for the duration of the call only.
+//!
+//! Getters return one of the `YAML_CONFIG_*` status codes and write the value through their
+//! out-pointer only on `YAML_CONFIG_OK`. Keys are flattened keys such as `DATABASE_PORT`, and
+//! registered defaults apply as they do for `Config::get`.
+use crate::{Config, ConfigBuilder, Value};
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+
+/// The value was found and written.
+pub const YAML_CONFIG_OK: c_int = 0;
+/// The key is not present.
+pub const YAML_CONFIG_NOT_FOUND: c_int = 1;
+/// The value has another type than the getter returns.
+pub const YAML_CONFIG_WRONG_TYPE: c_int = 2;
+/// A pointer argument is null or a string is not valid UTF-8.
+pub const YAML_CONFIG_INVALID_ARGUMENT: c_int = 3;
+
+/// An opaque handle to a loaded configuration.
+pub struct YamlConfig {
+    config: Config,
+}
+
+/// Converts `s` into a string the caller owns, replacing interior NUL bytes.
+fn to_c_string(s: &str) -> *mut c_char {
+    CString::new(s.replace('\0', "\\0")).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Borrows `s` as UTF-8.
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string valid for the lifetime `'a`.
+unsafe fn borrow_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// Looks up `key` in `config`, mapping failures to a status code.
+///
+/// # Safety
+///
+/// `config` must be null or a handle returned by `yaml_config_load`, and `key` null or a
+/// NUL-terminated string.
+unsafe fn lookup<'a>(config: *const YamlConfig, key: *const c_char) -> Result<&'a Value, c_int> {
+    let key = borrow_str(key).ok_or(YAML_CONFIG_INVALID_ARGUMENT)?;
+    let config = config.as_ref().ok_or(YAML_CONFIG_INVALID_ARGUMENT)?;
+    config.config.get(key).ok_or(YAML_CONFIG_NOT_FOUND)
+}
+
+/// Writes the value selected by `select` through `out`.
+///
+/// # Safety
+///
+/// As for `lookup`, and `out` must be null or valid for writes.
+unsafe fn get<T, F>(config: *const YamlConfig, key: *const c_char, out: *mut T, select: F) -> c_int
+where
+    F: FnOnce(&Value) -> Option<T>,
+{
+    if out.is_null() {
+        return YAML_CONFIG_INVALID_ARGUMENT;
+    }
+    match lookup(config, key) {
+        Ok(val) => match select(val) {
+            Some(val) => {
+                out.write(val);
+                YAML_CONFIG_OK
+            }
+            None => YAML_CONFIG_WRONG_TYPE,
+        },
+        Err(status) => status,
+    }
+}
+
+/// Loads the YAML file at `path` as `ConfigBuilder::add_file` would. Returns null on failure
+/// and, when `error` is not null, stores a message the caller must release with
+/// `yaml_config_string_free` in it.
+///
+/// # Safety
+///
+/// `path` must be null or a NUL-terminated string, and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn yaml_config_load(
+    path: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut YamlConfig {
+    let result = match borrow_str(path) {
+        Some(path) => ConfigBuilder::new()
+            .add_file(path)
+            .build()
+            .map_err(|e| e.to_string()),
+        None => Err("The path is null or not valid UTF-8.".to_string()),
+    };
+    match result {
+        Ok(config) => Box::into_raw(Box::new(YamlConfig { config })),
+        Err(message) => {
+            if !error.is_null() {
+                error.write(to_c_string(&message));
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Releases a handle returned by `yaml_config_load`. Null is ignored.
+///
+/// # Safety
+///
+/// `config` must be null or a handle returned by `yaml_config_load` that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn yaml_config_free(config: *mut YamlConfig) {
+    if !config.is_null() {
+        drop(Box::from_raw(config));
+    }
+}
+
+/// Releases a string returned by the library. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by the library that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn yaml_config_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Stores a copy of the string value of `key` in `out`, to be released with
+/// `yaml_config_string_free`.
+///
+/// # Safety
+///
+/// `config` must be null or a live handle, `key` null or a NUL-terminated string, and `out`
+/// null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn yaml_config_get_str(
+    config: *const YamlConfig,
+    key: *const c_char,
+    out: *mut *mut c_char,
+) -> c_int {
+    get(config, key, out, |val| {
+        val.as_string().map(|s| to_c_string(s))
+    })
+}
+
+/// Stores the integer value of `key` in `out`.
+///
+/// # Safety
+///
+/// As for `yaml_config_get_str`.
+#[no_mangle]
+pub unsafe extern "C" fn yaml_config_get_i64(
+    config: *const YamlConfig,
+    key: *const c_char,
+    out: *mut i64,
+) -> c_int {
+    get(config, key, out, |val| match val {
+        Value::I32(v) => Some(i64::from(*v)),
+        Value::I64(v) => Some(*v),
+        _ => None,
+    })
+}
+
+/// Stores the numeric value of `key` in `out`. Integers are converted.
+///
+/// # Safety
+///
+/// As for `yaml_config_get_str`.
+#[no_mangle]
+pub unsafe extern "C" fn yaml_config_get_f64(
+    config: *const YamlConfig,
+    key: *const c_char,
+    out: *mut f64,
+) -> c_int {
+    get(config, key, out, |val| match val {
+        Value::I32(v) => Some(f64::from(*v)),
+        Value::I64(v) => Some(*v as f64),
+        Value::F32(v) => Some(f64::from(*v)),
+        Value::F64(v) => Some(*v),
+        _ => None,
+    })
+}
+
+/// Stores the boolean value of `key` in `out`.
+///
+/// # Safety
+///
+/// As for `yaml_config_get_str`.
+#[no_mangle]
+pub unsafe extern "C" fn yaml_config_get_bool(
+    config: *const YamlConfig,
+    key: *const c_char,
+    out: *mut bool,
+) -> c_int {
+    get(config, key, out, |val| val.as_bool().copied())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ffi::*;
+    use std::ffi::{CStr, CString};
+    use std::fs;
+    use std::ptr;
+    use tempfile::tempdir;
+
+    #[test]
+    fn values_are_read_through_the_c_abi() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(&path, "db:\n  host: localhost\n  port: 5432\ndebug: true\n").unwrap();
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let config = yaml_config_load(path.as_ptr(), ptr::null_mut());
+            assert!(!config.is_null());
+
+            let mut host = ptr::null_mut();
+            let key = CString::new("DB_HOST").unwrap();
+            assert_eq!(
+                yaml_config_get_str(config, key.as_ptr(), &mut host),
+                YAML_CONFIG_OK
+            );
+            assert_eq!(CStr::from_ptr(host).to_str().unwrap(), "localhost");
+            yaml_config_string_free(host);
+
+            let mut port = 0;
+            let key = CString::new("DB_PORT").unwrap();
+            assert_eq!(
+                yaml_config_get_i64(config, key.as_ptr(), &mut port),
+                YAML_CONFIG_OK
+            );
+            assert_eq!(port, 5432);
+            let mut debug = false;
+            assert_eq!(
+                yaml_config_get_bool(config, key.as_ptr(), &mut debug),
+                YAML_CONFIG_WRONG_TYPE
+            );
+            let key = CString::new("MISSING").unwrap();
+            assert_eq!(
+                yaml_config_get_f64(config, key.as_ptr(), &mut 0.0),
+                YAML_CONFIG_NOT_FOUND
+            );
+
+            yaml_config_free(config);
+        }
+    }
+
+    #[test]
+    fn load_errors_are_returned() {
+        let path = CString::new("/does/not/exist.yaml").unwrap();
+        let mut error = ptr::null_mut();
+
+        unsafe {
+            assert!(yaml_config_load(path.as_ptr(), &mut error).is_null());
+            assert!(!CStr::from_ptr(error).to_str().unwrap().is_empty());
+            yaml_config_string_free(error);
+        }
+    }
+}
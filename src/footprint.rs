@@ -0,0 +1,128 @@
+//! Estimates of the memory held by a configuration.
+//!
+//! `Config::memory_footprint` adds up the bytes held by every key and value, including the heap
+//! allocations of strings, sequences, and maps, and splits them by the layer that provided each
+//! key. The figures are estimates: they follow the capacity of each allocation but not the
+//! allocator's own overhead, so they are best used to track growth over time, e.g. by exporting
+//! `MemoryFootprint::total` as a gauge.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::ConfigBuilder;
+//! let config = ConfigBuilder::new().add_str("db:\n  port: 5432\n").build().unwrap();
+//! let footprint = config.memory_footprint();
+//! assert!(footprint.total() > 0);
+//! println!("{}", footprint);
+//! ```
+use crate::Value;
+use std::fmt;
+use std::mem::size_of;
+
+/// The bookkeeping an `IndexMap` keeps per entry besides the key and value: the cached hash and
+/// the slot in its hash table.
+const ENTRY_OVERHEAD: usize = 2 * size_of::<usize>();
+
+/// The estimated memory held by a `Config`, in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryFootprint {
+    /// The flattened keys.
+    pub keys: usize,
+    /// The loaded values.
+    pub values: usize,
+    /// The defaults registered with `Config::register_default` or a mounted schema.
+    pub defaults: usize,
+    /// The hash tables and sorted index over the keys.
+    pub index: usize,
+    /// The keys and values provided by each layer, in the order of `Config::layers`.
+    pub layers: Vec<usize>,
+    /// The keys and values that no layer provided, such as overrides.
+    pub unattributed: usize,
+}
+
+impl MemoryFootprint {
+    /// The estimated number of bytes held in total.
+    pub fn total(&self) -> usize {
+        self.keys + self.values + self.defaults + self.index
+    }
+}
+
+impl fmt::Display for MemoryFootprint {
+    /// Writes the total and its breakdown, e.g. `12345 bytes (keys 2345, values 9000, ...)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes (keys {}, values {}, defaults {}, index {})",
+            self.total(),
+            self.keys,
+            self.values,
+            self.defaults,
+            self.index
+        )
+    }
+}
+
+/// The bytes held by `key` as a map key.
+pub(crate) fn key_size(key: &str) -> usize {
+    size_of::<String>() + key.len()
+}
+
+/// The bytes held by `val`, including everything it owns.
+pub(crate) fn value_size(val: &Value) -> usize {
+    size_of::<Value>() + heap_size(val)
+}
+
+fn heap_size(val: &Value) -> usize {
+    match val {
+        Value::String(s) => s.capacity(),
+        Value::Array(items) => {
+            (items.capacity() - items.len()) * size_of::<Value>()
+                + items.iter().map(value_size).sum::<usize>()
+        }
+        Value::Map(map) => map
+            .iter()
+            .map(|(key, val)| key_size(key) + value_size(val) + ENTRY_OVERHEAD)
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// The bytes held by the hash table of a map with `len` entries.
+pub(crate) fn table_size(len: usize) -> usize {
+    len * ENTRY_OVERHEAD
+}
+
+#[cfg(test)]
+mod test {
+    use crate::footprint::{key_size, value_size};
+    use crate::{ConfigBuilder, Value};
+
+    #[test]
+    fn footprints_are_split_by_layer() {
+        let config = ConfigBuilder::new()
+            .add_str("a: 1\n")
+            .add_str("b: [x, yyyy]\nc: hello\n")
+            .build()
+            .unwrap()
+            .overridden([("D", true)]);
+
+        let footprint = config.memory_footprint();
+        assert_eq!(footprint.layers.len(), 2);
+        assert_eq!(
+            footprint.layers[0],
+            key_size("A") + value_size(&Value::I64(1))
+        );
+        assert!(footprint.layers[1] > footprint.layers[0]);
+        assert_eq!(
+            footprint.unattributed,
+            key_size("D") + value_size(&Value::Bool(true))
+        );
+        assert_eq!(
+            footprint.keys + footprint.values,
+            footprint.layers.iter().sum::<usize>() + footprint.unattributed
+        );
+        assert!(footprint
+            .to_string()
+            .starts_with(&format!("{} bytes", footprint.total())));
+    }
+}
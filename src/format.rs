@@ -0,0 +1,147 @@
+//! Formats a configuration file can be written in.
+//!
+//! JSON and TOML files are read into the same tree as YAML ones, then flattened, resolved
+//! against the environment, and validated exactly like them, so `{"db": {"port": 5432}}` and
+//! `[db]\nport = 5432` both load as `DB_PORT`. The format of a file is detected from its
+//! extension, `.json` and `.toml`, with anything else read as YAML. `LoadOptions::format` picks
+//! one explicitly, e.g. for documents held in memory.
+//!
+//! JSON needs no extra dependency. TOML requires the `toml` feature; TOML dates and times are
+//! loaded as strings. Custom tags such as `!include` only exist in YAML.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::{try_parse_bytes, Format, LoadOptions, Value};
+//! let options = LoadOptions {
+//!     format: Some(Format::Json),
+//!     ..Default::default()
+//! };
+//! let values = try_parse_bytes(br#"{"db": {"port": 5432}}"#, &options).unwrap();
+//! assert_eq!(values["DB_PORT"], Value::I64(5432));
+//! ```
+use crate::ParseError;
+use std::path::Path;
+use yaml_rust::Yaml;
+
+/// The format of a configuration document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Yaml,
+    Json,
+    /// Requires the `toml` feature.
+    Toml,
+}
+
+impl Format {
+    /// Detects the format of the file at `path` from its extension, defaulting to YAML.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::Format;
+    /// assert_eq!(Format::from_path("config/app.JSON"), Format::Json);
+    /// assert_eq!(Format::from_path("config/app.yml"), Format::Yaml);
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Format {
+        let extension = path.as_ref().extension().and_then(|e| e.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("json") => Format::Json,
+            Some("toml") => Format::Toml,
+            _ => Format::Yaml,
+        }
+    }
+}
+
+/// Parses the TOML document `source` into a YAML tree.
+#[cfg(feature = "toml")]
+pub(crate) fn toml_to_yaml(source: &str) -> Result<Yaml, ParseError> {
+    let table: toml::Table = source.parse().map_err(|e: toml::de::Error| {
+        ParseError::new("config::toml", e.to_string().trim_end().to_string())
+    })?;
+    Ok(from_toml(toml::Value::Table(table)))
+}
+
+#[cfg(not(feature = "toml"))]
+pub(crate) fn toml_to_yaml(_source: &str) -> Result<Yaml, ParseError> {
+    Err(ParseError::new(
+        "config::toml",
+        "Reading TOML requires the `toml` feature.",
+    ))
+}
+
+#[cfg(feature = "toml")]
+fn from_toml(val: toml::Value) -> Yaml {
+    match val {
+        toml::Value::String(s) => Yaml::String(s),
+        toml::Value::Integer(v) => Yaml::Integer(v),
+        // `Yaml::Real` holds the text of the float, which YAML spells differently for these.
+        toml::Value::Float(f) if f.is_nan() => Yaml::Real(".nan".to_string()),
+        toml::Value::Float(f) if f.is_infinite() && f > 0.0 => Yaml::Real(".inf".to_string()),
+        toml::Value::Float(f) if f.is_infinite() => Yaml::Real("-.inf".to_string()),
+        toml::Value::Float(f) => Yaml::Real(format!("{:?}", f)),
+        toml::Value::Boolean(v) => Yaml::Boolean(v),
+        toml::Value::Datetime(dt) => Yaml::String(dt.to_string()),
+        toml::Value::Array(items) => Yaml::Array(items.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Yaml::Hash(
+            table
+                .into_iter()
+                .map(|(key, val)| (Yaml::String(key), from_toml(val)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_str, Format, LoadOptions, Value};
+
+    fn parse_as(doc: &str, format: Format) -> Vec<(String, Value)> {
+        let options = LoadOptions {
+            format: Some(format),
+            ..Default::default()
+        };
+        parse_str(doc, &options).unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn formats_flatten_alike() {
+        let expected = vec![
+            ("DB_HOST".to_string(), Value::from("localhost")),
+            ("DB_PORT".to_string(), Value::I64(5432)),
+            ("DB_RATIO".to_string(), Value::F64(0.5)),
+            (
+                "ZONES".to_string(),
+                Value::Array(vec![Value::from("a"), Value::from("b")]),
+            ),
+        ];
+        assert_eq!(
+            parse_as(
+                "db:\n  host: localhost\n  port: 5432\n  ratio: 0.5\nzones: [a, b]\n",
+                Format::Yaml
+            ),
+            expected
+        );
+        assert_eq!(
+            parse_as(
+                r#"{"db": {"host": "localhost", "port": 5432, "ratio": 0.5}, "zones": ["a", "b"]}"#,
+                Format::Json
+            ),
+            expected
+        );
+        #[cfg(feature = "toml")]
+        assert_eq!(
+            parse_as(
+                "zones = [\"a\", \"b\"]\n[db]\nhost = \"localhost\"\nport = 5432\nratio = 0.5\n",
+                Format::Toml
+            ),
+            vec![
+                expected[3].clone(),
+                expected[0].clone(),
+                expected[1].clone(),
+                expected[2].clone()
+            ]
+        );
+    }
+}
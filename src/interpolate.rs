@@ -0,0 +1,266 @@
+//! Interpolation of `${...}` references inside string values.
+//!
+//! With `LoadOptions::interpolate` set, every string value read from the YAML document may
+//! refer to other keys and to environment variables:
+//!
+//! ```yaml
+//! db:
+//!   host: db.internal
+//!   port: 5432
+//! url: postgres://${db.host}:${DB_PORT}/${DB_NAME:-app}
+//! log_dir: ${HOME}/logs
+//! ```
+//!
+//! A reference `${NAME}` names a key, written either as its flattened key or as a dotted path,
+//! or else an environment variable. `${NAME:-default}` falls back to `default` when neither
+//! exists; without a default that is an error. References to keys are resolved after those keys
+//! are themselves interpolated, in any order, and a chain of references leading back to itself
+//! is reported as a cycle. A value that is a single reference to a key, such as
+//! `${DB_PORT}`, takes that key's value with its type. Write `$${` for a literal `${`.
+//!
+//! Values taken from environment variables are used as they are, without interpolation.
+use crate::{normalize_key, LoadOptions, ParseError, Value};
+use fxhash::{FxBuildHasher, FxHashMap, FxHashSet};
+use indexmap::IndexMap;
+
+fn interpolate_error(message: String) -> ParseError {
+    ParseError::new("config::interpolate", message)
+}
+
+/// Returns `true` if `val` holds a string with a reference in it.
+fn has_reference(val: &Value) -> bool {
+    match val {
+        Value::String(s) => s.contains("${"),
+        Value::Array(items) => items.iter().any(has_reference),
+        Value::Map(map) => map.values().any(has_reference),
+        _ => false,
+    }
+}
+
+/// The text a value takes when it is interpolated into a string.
+fn text(key: &str, val: &Value) -> Result<String, String> {
+    match val {
+        Value::String(s) => Ok(s.clone()),
+        Value::I32(v) => Ok(v.to_string()),
+        Value::I64(v) => Ok(v.to_string()),
+        Value::F32(v) => Ok(v.to_string()),
+        Value::F64(v) => Ok(v.to_string()),
+        Value::Bool(v) => Ok(v.to_string()),
+        val => Err(format!(
+            "{} is {} and can't be interpolated into a string",
+            key,
+            val.describe()
+        )),
+    }
+}
+
+struct Resolver<'a> {
+    values: &'a IndexMap<String, Value, FxBuildHasher>,
+    options: &'a LoadOptions,
+    /// Keys whose values are used as they are.
+    verbatim: FxHashSet<&'a str>,
+    resolved: FxHashMap<String, Value>,
+    /// The keys being resolved, innermost last, to detect cycles.
+    stack: Vec<String>,
+}
+
+impl Resolver<'_> {
+    fn key(&mut self, key: &str) -> Result<Value, ParseError> {
+        if let Some(val) = self.resolved.get(key) {
+            return Ok(val.clone());
+        }
+        if let Some(start) = self.stack.iter().position(|k| k == key) {
+            let mut cycle = self.stack[start..].to_vec();
+            cycle.push(key.to_string());
+            return Err(interpolate_error(format!(
+                "Interpolation cycle: {}.",
+                cycle.join(" -> ")
+            )));
+        }
+        let val = &self.values[key];
+        if self.verbatim.contains(key) || !has_reference(val) {
+            return Ok(val.clone());
+        }
+
+        self.stack.push(key.to_string());
+        let resolved = self.value(key, val)?;
+        self.stack.pop();
+        self.resolved.insert(key.to_string(), resolved.clone());
+        Ok(resolved)
+    }
+
+    fn value(&mut self, key: &str, val: &Value) -> Result<Value, ParseError> {
+        Ok(match val {
+            Value::String(s) => self.string(key, s)?,
+            Value::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.value(key, item))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Map(map) => Value::Map(
+                map.iter()
+                    .map(|(k, item)| Ok((k.clone(), self.value(key, item)?)))
+                    .collect::<Result<_, ParseError>>()?,
+            ),
+            val => val.clone(),
+        })
+    }
+
+    /// Interpolates the string `s` found in the value of `key`.
+    fn string(&mut self, key: &str, s: &str) -> Result<Value, ParseError> {
+        // A lone reference to a key keeps the type of its value.
+        if let Some(name) = s.strip_prefix("${").and_then(|rest| rest.strip_suffix('}')) {
+            let target = normalize_key(name);
+            if !name.contains(['{', '}', ':']) && self.values.contains_key(&target) {
+                return self.key(&target);
+            }
+        }
+
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                out.push_str(&rest[..start - 1]);
+                out.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            out.push_str(&rest[..start]);
+            let end = rest[start..].find('}').ok_or_else(|| {
+                interpolate_error(format!("{} has an unterminated \"${{\".", key))
+            })?;
+            let reference = &rest[start + 2..start + end];
+            out.push_str(&self.reference(key, reference)?);
+            rest = &rest[start + end + 1..];
+        }
+        out.push_str(rest);
+        Ok(Value::String(out))
+    }
+
+    /// Looks up `reference`, the text between `${` and `}`, as a key, then as an environment
+    /// variable, then falls back to its default.
+    fn reference(&mut self, key: &str, reference: &str) -> Result<String, ParseError> {
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        let target = normalize_key(name);
+        if self.values.contains_key(&target) {
+            let val = self.key(&target)?;
+            return text(&target, &val).map_err(|e| interpolate_error(format!("{}: {}.", key, e)));
+        }
+        if let Some(val) = self.options.var_os(name).and_then(|v| v.into_string().ok()) {
+            return Ok(val);
+        }
+        default.map(str::to_string).ok_or_else(|| {
+            interpolate_error(format!(
+                "{} refers to ${{{}}}, which is neither a key nor an environment variable.",
+                key, name
+            ))
+        })
+    }
+}
+
+/// Interpolates the references in every value of `values`, except those of `verbatim` keys,
+/// reading environment variables through `options`.
+pub(crate) fn resolve(
+    values: &mut IndexMap<String, Value, FxBuildHasher>,
+    verbatim: &[String],
+    options: &LoadOptions,
+) -> Result<(), ParseError> {
+    let pending: Vec<String> = values
+        .iter()
+        .filter(|(_, val)| has_reference(val))
+        .map(|(key, _)| key.clone())
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let mut resolver = Resolver {
+        values,
+        options,
+        verbatim: verbatim.iter().map(String::as_str).collect(),
+        resolved: FxHashMap::default(),
+        stack: Vec::new(),
+    };
+    for key in &pending {
+        resolver.key(key)?;
+    }
+    let resolved = resolver.resolved;
+    for (key, val) in resolved {
+        values.insert(key, val);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpolate::resolve;
+    use crate::{parse_str, LoadOptions, Value};
+    use envtestkit::lock::lock_test;
+    use envtestkit::set_env;
+    use std::ffi::OsString;
+
+    fn interpolated(doc: &str) -> Result<Vec<(String, Value)>, String> {
+        let options = LoadOptions {
+            interpolate: true,
+            ..Default::default()
+        };
+        parse_str(doc, &options)
+            .map(|values| values.into_iter().collect())
+            .map_err(|e| e.message().into_owned())
+    }
+
+    #[test]
+    fn references_are_resolved_from_keys_and_the_environment() {
+        let _lock = lock_test();
+        let _user = set_env(OsString::from("INTERPOLATE_USER"), "admin");
+        let values = interpolated(
+            "url: postgres://${INTERPOLATE_USER}@${db.host}:${DB_PORT}/${DB_NAME:-app}\n\
+             db:\n  host: ${INTERPOLATE_HOST:-localhost}\n  port: 5432\n\
+             port: ${DB_PORT}\nhosts: [\"${DB_HOST}\", \"$${literal}\"]\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            values,
+            vec![
+                (
+                    "URL".to_string(),
+                    Value::from("postgres://admin@localhost:5432/app")
+                ),
+                ("DB_HOST".to_string(), Value::from("localhost")),
+                ("DB_PORT".to_string(), Value::I64(5432)),
+                ("PORT".to_string(), Value::I64(5432)),
+                (
+                    "HOSTS".to_string(),
+                    Value::Array(vec![Value::from("localhost"), Value::from("${literal}")])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn cycles_and_unknown_references_are_errors() {
+        assert_eq!(
+            interpolated("a: ${B}\nb: x${C}\nc: ${A}\n").unwrap_err(),
+            "Interpolation cycle: A -> B -> C -> A."
+        );
+        assert_eq!(
+            interpolated("a: ${INTERPOLATE_UNSET}\n").unwrap_err(),
+            "A refers to ${INTERPOLATE_UNSET}, which is neither a key nor an environment variable."
+        );
+        assert!(interpolated("a: ${B\n").is_err());
+        assert!(interpolated("a: x${B}\nb: [1]\n").is_err());
+        assert_eq!(
+            parse_str("a: ${B}\n", &LoadOptions::default()).unwrap()["A"],
+            Value::from("${B}")
+        );
+
+        let mut values = parse_str("a: ${B}\nb: 1\n", &LoadOptions::default()).unwrap();
+        resolve(&mut values, &["A".to_string()], &LoadOptions::default()).unwrap();
+        assert_eq!(values["A"], Value::from("${B}"));
+    }
+}
@@ -0,0 +1,397 @@
+//! A minimal JSON writer for flattened values, and a reader for JSON configuration files.
+//!
+//! The reader produces the same YAML tree as the loader, so JSON files are flattened exactly like
+//! YAML ones. Both are small enough that the crate avoids pulling in a serialization framework.
+use crate::loader::MAX_DEPTH;
+use crate::types::duration;
+use crate::{ParseError, Value};
+use linked_hash_map::LinkedHashMap;
+use yaml_rust::Yaml;
+
+/// Appends `s` to `out` as a quoted, escaped JSON string.
+pub(crate) fn write_str(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Appends a float to `out`. JSON has no representation for NaN or infinity so they become `null`.
+fn write_f64(f: f64, out: &mut String) {
+    if f.is_finite() {
+        out.push_str(&format!("{:?}", f));
+    } else {
+        out.push_str("null");
+    }
+}
+
+/// Appends `val` to `out` as JSON.
+pub(crate) fn write_value(val: &Value, out: &mut String) {
+    match val {
+        Value::I32(v) => out.push_str(&v.to_string()),
+        Value::I64(v) => out.push_str(&v.to_string()),
+        Value::F32(v) => write_f64(f64::from(*v), out),
+        Value::F64(v) => write_f64(*v, out),
+        Value::String(v) => write_str(v, out),
+        Value::Bool(v) => out.push_str(&v.to_string()),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(item, out);
+            }
+            out.push(']');
+        }
+        Value::Map(map) => {
+            out.push('{');
+            for (i, (key, item)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_str(key, out);
+                out.push(':');
+                write_value(item, out);
+            }
+            out.push('}');
+        }
+        Value::Duration(v) => write_str(&duration::format(*v), out),
+        Value::Null => out.push_str("null"),
+    }
+}
+
+/// Parses the JSON document `source` into a YAML tree. Object keys keep their order, and a key
+/// repeated in an object keeps its last value.
+pub(crate) fn parse(source: &str) -> Result<Yaml, ParseError> {
+    let mut reader = Reader {
+        source,
+        pos: 0,
+        depth: 0,
+    };
+    reader.skip_whitespace();
+    let doc = reader.value()?;
+    reader.skip_whitespace();
+    if reader.pos < source.len() {
+        return Err(reader.error("Unexpected text after the document"));
+    }
+    Ok(doc)
+}
+
+struct Reader<'a> {
+    source: &'a str,
+    // Always on a character boundary.
+    pos: usize,
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, what: &str) -> ParseError {
+        let before = &self.source[..self.pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        ParseError::new(
+            "config::json",
+            format!(
+                "{} at line {}, column {}.",
+                what,
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1
+            ),
+        )
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Yaml, ParseError> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Yaml::String(self.string()?)),
+            Some(b't') => self.literal("true", Yaml::Boolean(true)),
+            Some(b'f') => self.literal("false", Yaml::Boolean(false)),
+            Some(b'n') => self.literal("null", Yaml::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of document")),
+        }
+    }
+
+    fn literal(&mut self, word: &str, val: Yaml) -> Result<Yaml, ParseError> {
+        if !self.source[self.pos..].starts_with(word) {
+            return Err(self.error("Unexpected character"));
+        }
+        self.pos += word.len();
+        Ok(val)
+    }
+
+    /// Enters an object or array, bounding the nesting depth like the YAML loader does.
+    fn enter(&mut self) -> Result<(), ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(&format!("Nesting deeper than {} levels", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        self.pos += 1;
+        self.skip_whitespace();
+        Ok(())
+    }
+
+    /// Consumes the separator after an element, returning `true` at the closing `close`.
+    fn next_or_close(&mut self, close: u8) -> Result<bool, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b',') => {
+                self.pos += 1;
+                self.skip_whitespace();
+                Ok(false)
+            }
+            Some(c) if c == close => {
+                self.pos += 1;
+                self.depth -= 1;
+                Ok(true)
+            }
+            _ => Err(self.error(&format!("Expected ',' or '{}'", close as char))),
+        }
+    }
+
+    fn object(&mut self) -> Result<Yaml, ParseError> {
+        self.enter()?;
+        let mut hash = LinkedHashMap::new();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(Yaml::Hash(hash));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error("Expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.error("Expected ':'"));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let val = self.value()?;
+            hash.insert(Yaml::String(key), val);
+            if self.next_or_close(b'}')? {
+                return Ok(Yaml::Hash(hash));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Yaml, ParseError> {
+        self.enter()?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(Yaml::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            if self.next_or_close(b']')? {
+                return Ok(Yaml::Array(items));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        // The opening quote.
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            match self.source[self.pos..].chars().next() {
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    out.push(self.escape()?);
+                }
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("Unescaped control character in a string"))
+                }
+                Some(c) => {
+                    out.push(c);
+                    self.pos += c.len_utf8();
+                }
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                return self.unicode();
+            }
+            _ => return Err(self.error("Invalid escape")),
+        };
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn hex4(&mut self) -> Result<u32, ParseError> {
+        let digits = self
+            .source
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("Invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap_or_default())
+    }
+
+    /// Reads the rest of a `\u` escape, joining a surrogate pair into one character.
+    fn unicode(&mut self) -> Result<char, ParseError> {
+        let mut code = self.hex4()?;
+        if (0xD800..0xDC00).contains(&code) {
+            if !self.source[self.pos..].starts_with("\\u") {
+                return Err(self.error("Unpaired surrogate"));
+            }
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("Unpaired surrogate"));
+            }
+            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+        }
+        char::from_u32(code).ok_or_else(|| self.error("Unpaired surrogate"))
+    }
+
+    fn digits(&mut self) -> Result<(), ParseError> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("Invalid number"));
+        }
+        Ok(())
+    }
+
+    /// Reads a number as an integer when it has no fraction or exponent and fits an `i64`, and
+    /// as a float otherwise.
+    fn number(&mut self) -> Result<Yaml, ParseError> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else {
+            self.digits()?;
+        }
+        let mut integer = true;
+        if self.peek() == Some(b'.') {
+            integer = false;
+            self.pos += 1;
+            self.digits()?;
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            integer = false;
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            self.digits()?;
+        }
+        let text = &self.source[start..self.pos];
+        match text.parse::<i64>() {
+            Ok(v) if integer => Ok(Yaml::Integer(v)),
+            _ => Ok(Yaml::Real(text.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::json::{parse, write_str, write_value};
+    use crate::Value;
+    use linked_hash_map::LinkedHashMap;
+    use yaml_rust::Yaml;
+
+    #[test]
+    fn strings_are_escaped() {
+        let mut out = String::new();
+        write_str("a \"b\"\n\u{1}", &mut out);
+        assert_eq!(out, "\"a \\\"b\\\"\\n\\u0001\"");
+    }
+
+    #[test]
+    fn values_are_written() {
+        let mut out = String::new();
+        write_value(
+            &Value::Array(vec![
+                Value::I64(1),
+                Value::F64(1.5),
+                Value::F64(f64::NAN),
+                Value::Bool(true),
+            ]),
+            &mut out,
+        );
+        assert_eq!(out, "[1,1.5,null,true]");
+    }
+
+    #[test]
+    fn documents_are_read_as_yaml_trees() {
+        let doc = parse(
+            "{\n\t\"a\": {\"b\": [1, -2.5e1, \"x\\/\\u00e9\\ud83d\\ude00\", null, true]},\n\t\"c\": {}\n}",
+        )
+        .unwrap();
+        let mut inner = LinkedHashMap::new();
+        inner.insert(
+            Yaml::String("b".to_string()),
+            Yaml::Array(vec![
+                Yaml::Integer(1),
+                Yaml::Real("-2.5e1".to_string()),
+                Yaml::String("x/\u{e9}\u{1f600}".to_string()),
+                Yaml::Null,
+                Yaml::Boolean(true),
+            ]),
+        );
+        let mut outer = LinkedHashMap::new();
+        outer.insert(Yaml::String("a".to_string()), Yaml::Hash(inner));
+        outer.insert(
+            Yaml::String("c".to_string()),
+            Yaml::Hash(LinkedHashMap::new()),
+        );
+        assert_eq!(doc, Yaml::Hash(outer));
+
+        assert_eq!(
+            parse("{\"a\": 1,\n \"b\" 2}").unwrap_err().message(),
+            "Expected ':' at line 2, column 6."
+        );
+        for bad in ["[1,]", "01", "{\"a\": tru}", "\"\\ud800\"", "[] []", ""] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+        assert!(parse(&"[".repeat(100_000)).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Kubernetes manifests and Helm values generated from a configuration.
+//!
+//! Every key with an entry in `Config::env_bindings` is emitted under the name of the
+//! environment variable that overrides it, so the manifests can be mounted with `envFrom`.
+//! Keys tagged `Visibility::Secret` go to a `Secret` and the rest to a `ConfigMap`. Keys no
+//! variable can override are left out; build the configuration with `Preference::PreferEnv`
+//! or an `add_env_prefix` layer to bind every key.
+//!
+//! The `Secret` holds the secret values in plain text, as `stringData`, so treat the output
+//! like the secrets themselves.
+//!
+//! **Examples**
+//!
+//! ```rust,no_run
+//! use yaml_config::{k8s, ConfigBuilder};
+//! let config = ConfigBuilder::new()
+//!     .add_file("config/base.yaml")
+//!     .add_secrets_file("config/secrets.yaml")
+//!     .add_env_prefix("APP_")
+//!     .build()
+//!     .unwrap();
+//! std::fs::write("deploy/config.yaml", k8s::manifests(&config, "my-service")).unwrap();
+//! ```
+use crate::json::{write_str, write_value};
+use crate::schema::Visibility;
+use crate::{Config, Value};
+
+/// Variable names and their values as quoted strings.
+type Entries<'a> = Vec<(&'a str, String)>;
+
+/// The bound variables and their values, split into plain and secret ones.
+fn entries(config: &Config) -> (Entries<'_>, Entries<'_>) {
+    let mut plain = Vec::new();
+    let mut secret = Vec::new();
+    for (key, var) in config.env_bindings() {
+        let val = match config.values().get(key) {
+            Some(val) => val,
+            None => continue,
+        };
+        // Values of a ConfigMap or Secret are always strings.
+        let mut text = String::new();
+        match val {
+            Value::String(s) => text.push_str(s),
+            val => write_value(val, &mut text),
+        }
+        let mut quoted = String::new();
+        write_str(&text, &mut quoted);
+        if config.schema().visibility_of(key) == Visibility::Secret {
+            secret.push((var.as_str(), quoted));
+        } else {
+            plain.push((var.as_str(), quoted));
+        }
+    }
+    (plain, secret)
+}
+
+/// Writes `entries` as a YAML map indented by `indent` spaces, or `{}` when empty.
+fn write_map(entries: &[(&str, String)], indent: usize, out: &mut String) {
+    if entries.is_empty() {
+        out.push_str(" {}\n");
+        return;
+    }
+    out.push('\n');
+    for (var, val) in entries {
+        out.push_str(&format!("{}{}: {}\n", " ".repeat(indent), var, val));
+    }
+}
+
+fn manifest(kind: &str, name: &str, field: &str, entries: &[(&str, String)]) -> String {
+    let mut out = format!(
+        "apiVersion: v1\nkind: {}\nmetadata:\n  name: {}\n{}:",
+        kind, name, field
+    );
+    write_map(entries, 2, &mut out);
+    out
+}
+
+/// Renders a `ConfigMap` named `name` holding the bound keys that aren't secret.
+pub fn config_map(config: &Config, name: &str) -> String {
+    manifest("ConfigMap", name, "data", &entries(config).0)
+}
+
+/// Renders a `Secret` named `name` holding the bound secret keys.
+pub fn secret(config: &Config, name: &str) -> String {
+    manifest("Secret", name, "stringData", &entries(config).1)
+}
+
+/// Renders the `ConfigMap` and, if any bound key is secret, the `Secret`, as one multi-document
+/// YAML file.
+pub fn manifests(config: &Config, name: &str) -> String {
+    let (plain, secret) = entries(config);
+    let mut out = manifest("ConfigMap", name, "data", &plain);
+    if !secret.is_empty() {
+        out.push_str("---\n");
+        out.push_str(&manifest("Secret", name, "stringData", &secret));
+    }
+    out
+}
+
+/// Renders a Helm values snippet with the bound keys under `configMap` and the secret ones
+/// under `secret`.
+pub fn helm_values(config: &Config) -> String {
+    let (plain, secret) = entries(config);
+    let mut out = String::from("configMap:");
+    write_map(&plain, 2, &mut out);
+    out.push_str("secret:");
+    write_map(&secret, 2, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::k8s::{config_map, helm_values, manifests};
+    use crate::{ConfigBuilder, KeySpec, Schema, Visibility};
+
+    #[test]
+    fn bound_keys_are_split_into_config_maps_and_secrets() {
+        let config = ConfigBuilder::new()
+            .with_schema(
+                Schema::new().key("DB_PASSWORD", KeySpec::new().visibility(Visibility::Secret)),
+            )
+            .add_str("db:\n  host: db.internal\n  port: 5432\n  password: hunter2\n")
+            .add_env_prefix("K8S_TEST_UNSET_")
+            .add_str("debug: true\n")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            manifests(&config, "app"),
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app\ndata:\n  \
+             K8S_TEST_UNSET_DB_HOST: \"db.internal\"\n  K8S_TEST_UNSET_DB_PORT: \"5432\"\n\
+             ---\napiVersion: v1\nkind: Secret\nmetadata:\n  name: app\nstringData:\n  \
+             K8S_TEST_UNSET_DB_PASSWORD: \"hunter2\"\n"
+        );
+        assert_eq!(
+            helm_values(&config),
+            "configMap:\n  K8S_TEST_UNSET_DB_HOST: \"db.internal\"\n  \
+             K8S_TEST_UNSET_DB_PORT: \"5432\"\nsecret:\n  K8S_TEST_UNSET_DB_PASSWORD: \"hunter2\"\n"
+        );
+
+        let unbound = ConfigBuilder::new().add_str("a: 1\n").build().unwrap();
+        assert_eq!(
+            config_map(&unbound, "app"),
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app\ndata: {}\n"
+        );
+    }
+}
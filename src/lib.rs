@@ -1,21 +1,104 @@
+pub mod annotate;
+pub mod args;
+pub mod audit;
+#[cfg(feature = "binary-cache")]
+pub mod binary_cache;
+pub mod builder;
+pub mod cache;
+pub mod codegen;
+pub mod config;
+pub mod database;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod debug;
 pub mod error;
+pub mod export;
+#[cfg(feature = "facts")]
+pub mod facts;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod footprint;
+pub mod format;
+pub mod interpolate;
+pub(crate) mod json;
+pub mod k8s;
+pub mod lint;
+mod loader;
+pub mod logging;
+pub mod messages;
+pub mod migrate;
+pub mod missing;
+pub mod numbers;
+pub mod options;
+pub mod overlay;
+pub mod paths;
+pub mod permissions;
+pub mod policy;
+pub mod proxy;
+pub mod ratelimit;
+pub mod resolvers;
+pub mod retry;
+pub mod schema;
+pub mod shared;
+pub mod snapshot;
+pub mod sources;
+pub mod types;
+pub mod units;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watch;
+pub mod whatif;
+pub mod wizard;
 
-pub use crate::error::ParseError;
+pub use crate::builder::ConfigBuilder;
+pub use crate::config::Config;
+#[cfg(feature = "serde")]
+pub use crate::de::from_file;
+pub use crate::error::{ErrorCode, Location, ParseError};
+pub use crate::format::Format;
+pub use crate::options::{LoadOptions, OnEmpty, OnEnvUnavailable, OnMissing, ReadBudget};
+pub use crate::schema::{KeySpec, Schema, Visibility};
+pub use crate::shared::{
+    HistoryEntry, OverrideGuard, ReadGuard, ReloadFailure, ReloadOutcome, SharedConfig,
+};
+pub use crate::validate::{validate, validate_with_schema, ValidationReport};
 
+use crate::missing::{MissingKey, MissingReport};
+use crate::types::duration;
 use enum_as_inner::EnumAsInner;
-use fxhash::FxBuildHasher;
+use fxhash::{FxBuildHasher, FxHashMap};
 use indexmap::IndexMap;
 use linked_hash_map::LinkedHashMap;
-use std::env;
-use std::fs::read_to_string;
-use yaml_rust::{Yaml, YamlLoader};
+use std::borrow::Cow;
+use std::env::VarError;
+use std::fmt;
+use std::fs::{read_to_string, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use yaml_rust::Yaml;
 
 /// Defines the preference for loading of a configuration when a variable exists in the
 /// YAML and also along the same path in the environment.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Preference {
+    /// Use the YAML value, consulting the environment only for `null` values.
+    #[default]
     PreferYaml,
+    /// Use the environment variable when it is set. A value that doesn't parse as the type of
+    /// the YAML value is an error.
     PreferEnv,
+    /// Like `PreferEnv`, but keep the YAML value when the environment variable doesn't parse
+    /// as its type.
+    PreferEnvFallback,
+}
+
+impl Preference {
+    /// Returns `true` if set environment variables override YAML values.
+    pub(crate) fn prefers_env(self) -> bool {
+        self != Preference::PreferYaml
+    }
 }
 
 /// A wrapped type enum useful for allowing polymorphic returns from
@@ -31,7 +114,7 @@ pub enum Preference {
 /// let val = *x.as_i32().unwrap();
 /// ```
 /// }
-#[derive(Debug, EnumAsInner)]
+#[derive(Debug, Clone, PartialEq, EnumAsInner)]
 pub enum Value {
     I32(i32),
     I64(i64),
@@ -39,158 +122,637 @@ pub enum Value {
     F64(f64),
     String(String),
     Bool(bool),
+    Array(Vec<Value>),
+    /// A map inside a sequence, such as each entry of a list of servers, or a level of the tree
+    /// returned by `load_nested`. Its keys are normalized like flattened keys.
+    Map(IndexMap<String, Value, FxBuildHasher>),
+    /// A length of time, such as a default set with `Value::from(Duration::from_secs(30))`.
+    /// Documents and the environment hold durations as strings or numbers, which
+    /// `Config::get_duration` parses.
+    Duration(Duration),
+    /// A key left `null` in the YAML with no environment variable to fill it, kept when
+    /// `LoadOptions::allow_missing` is `OnMissing::Null`.
+    Null,
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::I32(v)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::I64(v)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::F32(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::F64(v)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::String(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::String(v.to_string())
+    }
+}
+
+impl From<Duration> for Value {
+    fn from(v: Duration) -> Self {
+        Value::Duration(v)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(v: Vec<Value>) -> Self {
+        Value::Array(v)
+    }
+}
+
+impl Value {
+    /// Describes the type of the value for error messages, e.g. "an integer".
+    pub(crate) fn describe(&self) -> &'static str {
+        match self {
+            Value::I32(_) | Value::I64(_) => "an integer",
+            Value::F32(_) | Value::F64(_) => "a float",
+            Value::String(_) => "a string",
+            Value::Bool(_) => "a boolean",
+            Value::Array(_) => "a sequence",
+            Value::Map(_) => "a map",
+            Value::Duration(_) => "a duration",
+            Value::Null => "null",
+        }
+    }
+}
+
+/// The error of converting a `Value` to a type it doesn't hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// What the conversion accepts, e.g. "an integer".
+    pub expected: String,
+    /// What the value is, e.g. "a string".
+    pub found: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+fn mismatch(expected: &str, val: &Value) -> Mismatch {
+    Mismatch {
+        expected: expected.to_string(),
+        found: val.describe().to_string(),
+    }
+}
+
+impl TryFrom<&Value> for i64 {
+    type Error = Mismatch;
+
+    fn try_from(val: &Value) -> Result<Self, Mismatch> {
+        match val {
+            Value::I32(v) => Ok(*v as i64),
+            Value::I64(v) => Ok(*v),
+            val => Err(mismatch("an integer", val)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for i32 {
+    type Error = Mismatch;
+
+    fn try_from(val: &Value) -> Result<Self, Mismatch> {
+        let v = i64::try_from(val)?;
+        i32::try_from(v).map_err(|_| Mismatch {
+            expected: "a 32-bit integer".to_string(),
+            found: v.to_string(),
+        })
+    }
+}
+
+/// Integers are widened, so `5` reads as `5.0`.
+impl TryFrom<&Value> for f64 {
+    type Error = Mismatch;
+
+    fn try_from(val: &Value) -> Result<Self, Mismatch> {
+        match val {
+            Value::I32(v) => Ok(*v as f64),
+            Value::I64(v) => Ok(*v as f64),
+            Value::F32(v) => Ok(*v as f64),
+            Value::F64(v) => Ok(*v),
+            val => Err(mismatch("a number", val)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for bool {
+    type Error = Mismatch;
+
+    fn try_from(val: &Value) -> Result<Self, Mismatch> {
+        match val {
+            Value::Bool(v) => Ok(*v),
+            val => Err(mismatch("a boolean", val)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a str {
+    type Error = Mismatch;
+
+    fn try_from(val: &'a Value) -> Result<Self, Mismatch> {
+        match val {
+            Value::String(v) => Ok(v),
+            val => Err(mismatch("a string", val)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for String {
+    type Error = Mismatch;
+
+    fn try_from(val: &Value) -> Result<Self, Mismatch> {
+        <&str>::try_from(val).map(str::to_string)
+    }
 }
 
 /// Provides a simple way to allow question mark syntax in order to
 /// convert environment errors into ParseErrors.
-fn env_or_error(key: &str) -> Result<String, ParseError> {
-    match env::var_os(key) {
-        Some(v) => Ok(v
-            .into_string()
-            .expect("Could not convert OsString into string.")),
-        None => {
-            let msg = format!("Error parsing OS environment variable for {}", key);
-            Err(ParseError {
-                module: "std::env".to_string(),
-                message: msg,
-            })
+///
+/// When `key` isn't set but `{key}_FILE` is, and `LoadOptions::secret_files` is on, the value
+/// is read from the file it names, as with Docker and Kubernetes secrets.
+fn env_or_error(key: &str, options: &LoadOptions) -> Result<String, ParseError> {
+    let file_var = format!("{}_FILE", key);
+    match options.var_os(key) {
+        Some(v) => v.into_string().map_err(|v| ParseError::Env {
+            key: Some(key.to_string()),
+            source: VarError::NotUnicode(v),
+        }),
+        None => match options.var_os(&file_var).filter(|_| options.secret_files) {
+            Some(path) => read_secret_file(&file_var, Path::new(&path), options),
+            None => Err(ParseError::MissingEnv {
+                key: key.to_string(),
+            }),
+        },
+    }
+}
+
+/// Reads the secret in the file at `path`, named by the variable `file_var`, without the line
+/// break most editors and `echo` end files with. The file is read like a document, so `base_dir`,
+/// the symlink policy, and the read budget apply to it.
+fn read_secret_file(
+    file_var: &str,
+    path: &Path,
+    options: &LoadOptions,
+) -> Result<String, ParseError> {
+    let mut secret =
+        read_counted(path, options, &mut Reads::default()).map_err(|error| match error {
+            ParseError::Io { source } => ParseError::with_code(
+                ErrorCode::SecretFileUnreadable,
+                "config",
+                format!(
+                    "{} names {}, which can't be read: {}.",
+                    file_var,
+                    path.display(),
+                    source
+                ),
+            ),
+            error => error,
+        })?;
+    if secret.ends_with('\n') {
+        secret.pop();
+        if secret.ends_with('\r') {
+            secret.pop();
         }
     }
+    Ok(secret)
+}
+
+/// The files `config` may have read secrets from through `{VAR}_FILE` variables, for caches to
+/// tell when one of them is rotated.
+pub(crate) fn secret_files(config: &Config, options: &LoadOptions) -> Vec<PathBuf> {
+    if !options.secret_files {
+        return Vec::new();
+    }
+    config
+        .env_bindings()
+        .values()
+        .filter(|var| options.var_os(var).is_none())
+        .filter_map(|var| options.var_os(&format!("{}_FILE", var)))
+        .map(|path| match &options.base_dir {
+            Some(base_dir) => base_dir.join(path),
+            None => PathBuf::from(path),
+        })
+        .collect()
+}
+
+/// Parses the environment value `val` of `key` as the type of the YAML value it overrides.
+fn parse_env<T: std::str::FromStr>(
+    key: &str,
+    val: &str,
+    expected: &'static str,
+) -> Result<T, ParseError> {
+    val.parse::<T>().map_err(|_| ParseError::InvalidEnv {
+        key: key.to_string(),
+        value: val.to_string(),
+        expected,
+        location: None,
+    })
+}
+
+/// Parses the environment value `val` of `key` like `parse_env`, after normalizing numbers.
+///
+/// With `Preference::PreferEnvFallback` a value that doesn't parse yields `None`, so the YAML
+/// value is kept.
+fn parse_env_or_fallback<T: std::str::FromStr>(
+    key: &str,
+    val: String,
+    expected: &'static str,
+    options: &LoadOptions,
+) -> Result<Option<T>, ParseError> {
+    let parsed = numbers::normalize(key, val, options.number_format)
+        .and_then(|val| parse_env::<T>(key, &val, expected));
+    match parsed {
+        Ok(val) => Ok(Some(val)),
+        Err(_e) if options.preference == Preference::PreferEnvFallback => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                target: "yaml_config",
+                key,
+                error = %_e.message(),
+                "keeping YAML value since the environment variable doesn't parse"
+            );
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses a raw string into the most specific `Value` possible.
+///
+/// Integers are tried first, then floats, then booleans. Anything else is kept as a string.
+pub(crate) fn infer_value(val_str: String) -> Value {
+    match val_str.parse::<i64>() {
+        Ok(v) => Value::I64(v),
+        Err(_) => match val_str.parse::<f64>() {
+            Ok(v) => Value::F64(v),
+            Err(_) => match val_str.parse::<bool>() {
+                Ok(v) => Value::Bool(v),
+                Err(_) => Value::String(val_str),
+            },
+        },
+    }
+}
+
+/// Converts the environment value `raw` of `key` as the loader does when it overrides `like`,
+/// or infers its type when there is nothing to override.
+///
+/// With `Preference::PreferEnvFallback` a value that doesn't parse as the type of `like` yields
+/// `None`, so `like` is kept.
+pub(crate) fn coerce_env(
+    key: &str,
+    raw: String,
+    like: Option<&Value>,
+    options: &LoadOptions,
+) -> Result<Option<Value>, ParseError> {
+    let val = match like {
+        Some(Value::String(_)) => Some(Value::String(raw)),
+        Some(Value::I32(_)) => {
+            parse_env_or_fallback::<i32>(key, raw, "a 32-bit integer", options)?.map(Value::I32)
+        }
+        Some(Value::I64(_)) => {
+            parse_env_or_fallback::<i64>(key, raw, "an integer", options)?.map(Value::I64)
+        }
+        Some(Value::Bool(_)) => {
+            parse_env_or_fallback::<bool>(key, raw, "a boolean", options)?.map(Value::Bool)
+        }
+        Some(Value::F32(_)) => {
+            parse_env_or_fallback::<f32>(key, raw, "a float", options)?.map(Value::F32)
+        }
+        Some(Value::F64(_)) => {
+            parse_env_or_fallback::<f64>(key, raw, "a float", options)?.map(Value::F64)
+        }
+        Some(Value::Duration(_)) => match duration::parse(&Value::String(raw.clone())) {
+            Ok(v) => Some(Value::Duration(v)),
+            Err(_) if options.preference == Preference::PreferEnvFallback => None,
+            Err(_) => {
+                return Err(ParseError::InvalidEnv {
+                    key: key.to_string(),
+                    value: raw,
+                    expected: "a duration",
+                    location: None,
+                })
+            }
+        },
+        _ => Some(infer_value(numbers::normalize_untyped(
+            key,
+            raw,
+            options.number_format,
+        )?)),
+    };
+    Ok(val)
 }
 
 /// Takes a key and a Yaml reference, parses it, and sets the key.
 ///
 /// In addition to doing the initial parsing it will also do environment finding. If a given
-/// key is null, or `prefer_env` is true, then it will search the environment for the given
-/// key string and attempt to use that key string's value.
+/// key is null, or `options` prefer the environment, then it will search the environment for
+/// the given key string and attempt to use that key string's value.
+///
+/// Returns `true` when the value was taken from the environment.
 ///
 fn maybe_yaml_to_value(
     key: &str,
     maybe_val: &Yaml,
-    prefer_env: bool,
+    options: &LoadOptions,
     map: &mut IndexMap<String, Value, FxBuildHasher>,
-) -> Result<(), ParseError> {
+) -> Result<bool, ParseError> {
     if maybe_val.is_null() {
         // Because the value is null we have to attempt a full parse of whatever is coming back
         // from the user's environment since we don't have an indicator from the YAML itself.
-        let val_str = env_or_error(key)?;
-
-        let val = match val_str.parse::<i64>() {
-            Ok(v) => Value::I64(v),
-            Err(_) => match val_str.parse::<f64>() {
-                Ok(v) => Value::F64(v),
-                Err(_) => match val_str.parse::<bool>() {
-                    Ok(v) => Value::Bool(v),
-                    Err(_) => Value::String(val_str),
-                },
-            },
+        let raw = match (env_or_error(key, options), options.allow_missing) {
+            (Ok(v), _) => v,
+            (Err(e), OnMissing::Error) => return Err(e),
+            (Err(_), OnMissing::Null) => {
+                map.insert(key.to_string(), Value::Null);
+                return Ok(false);
+            }
+            (Err(_), OnMissing::Skip) => return Ok(false),
         };
 
-        map.insert(key.to_string(), val);
-        return Ok(());
+        if let Some(val) = coerce_env(key, raw, None, options)? {
+            map.insert(key.to_string(), val);
+        }
+        return Ok(true);
     }
 
-    if maybe_val.as_str().is_some() {
-        if prefer_env {
-            match env_or_error(key) {
-                Ok(v) => {
-                    map.insert(key.to_string(), Value::String(v));
-                }
-                Err(_) => {
-                    map.insert(
-                        key.to_string(),
-                        Value::String(maybe_val.as_str().unwrap().to_string()),
-                    );
-                }
-            };
-        } else {
-            map.insert(
-                key.to_string(),
-                Value::String(maybe_val.as_str().unwrap().to_string()),
-            );
+    let yaml_val = match maybe_val {
+        Yaml::String(s) => Value::String(s.clone()),
+        Yaml::Integer(i) => Value::I64(*i),
+        Yaml::Boolean(b) => Value::Bool(*b),
+        Yaml::Real(_) if maybe_val.as_f64().is_some() => Value::F64(maybe_val.as_f64().unwrap()),
+        _ => {
+            return Err(ParseError::UnsupportedStructure {
+                key: key.to_string(),
+                location: None,
+            })
         }
+    };
 
-        return Ok(());
+    if options.preference.prefers_env() {
+        if let Ok(raw) = env_or_error(key, options) {
+            if let Some(val) = coerce_env(key, raw, Some(&yaml_val), options)? {
+                map.insert(key.to_string(), val);
+                return Ok(true);
+            }
+        }
     }
+    map.insert(key.to_string(), yaml_val);
+    Ok(false)
+}
 
-    if maybe_val.as_i64().is_some() {
-        if prefer_env {
-            match env_or_error(key) {
-                Ok(v) => {
-                    let e_val = v.parse::<i64>().unwrap();
-                    map.insert(key.to_string(), Value::I64(e_val));
-                }
-                Err(_) => {
-                    map.insert(key.to_string(), Value::I64(maybe_val.as_i64().unwrap()));
+/// Converts a single YAML node into a `Value` without consulting the environment.
+///
+/// This is used for sequences and their elements, which have no flattened key of their own
+/// and so cannot be overridden from the environment. Maps inside a sequence become a
+/// `Value::Map`, and null elements become `Value::Null`.
+pub(crate) fn yaml_to_value(key: &str, val: &Yaml) -> Result<Value, ParseError> {
+    match val {
+        Yaml::String(s) => Ok(Value::String(s.clone())),
+        Yaml::Integer(i) => Ok(Value::I64(*i)),
+        Yaml::Boolean(b) => Ok(Value::Bool(*b)),
+        Yaml::Real(_) if val.as_f64().is_some() => Ok(Value::F64(val.as_f64().unwrap())),
+        Yaml::Null => Ok(Value::Null),
+        Yaml::Array(items) => Ok(Value::Array(
+            items
+                .iter()
+                .map(|item| yaml_to_value(key, item))
+                .collect::<Result<Vec<Value>, ParseError>>()?,
+        )),
+        Yaml::Hash(hash) => {
+            let mut map = IndexMap::with_hasher(FxBuildHasher::default());
+            for (k, v) in hash {
+                let normalized = normalize_key(&key_string(k)?);
+                let val = yaml_to_value(&format!("{}_{}", key, normalized), v)?;
+                if map.insert(normalized.clone(), val).is_some() {
+                    return Err(ParseError::with_code(
+                        ErrorCode::KeyCollision,
+                        "config",
+                        format!("Two keys of a map in {} normalize to {}.", key, normalized),
+                    ));
                 }
-            };
-        } else {
-            map.insert(key.to_string(), Value::I64(maybe_val.as_i64().unwrap()));
+            }
+            Ok(Value::Map(map))
         }
+        _ => Err(ParseError::UnsupportedStructure {
+            key: key.to_string(),
+            location: None,
+        }),
+    }
+}
 
-        return Ok(());
+/// Converts a YAML key into a string for processing.
+///
+/// Non-string keys are stringified deterministically so that files produced by other tools
+/// (for example maps keyed by port number) can be loaded:
+///
+/// * Integers are written in decimal, so `8080` becomes `"8080"`.
+/// * Booleans become `"true"` or `"false"`.
+/// * Reals are kept exactly as written in the file, so `1.50` stays `"1.50"`.
+///
+/// Any other kind of key (null, sequence, or map) is an error.
+fn key_string(key: &Yaml) -> Result<String, ParseError> {
+    match key {
+        Yaml::String(s) => Ok(s.clone()),
+        Yaml::Integer(i) => Ok(i.to_string()),
+        Yaml::Boolean(b) => Ok(b.to_string()),
+        Yaml::Real(r) => Ok(r.clone()),
+        _ => Err(ParseError::new(
+            "config",
+            format!("Could not convert key {:?} into String.", key),
+        )),
     }
+}
 
-    if maybe_val.as_bool().is_some() {
-        if prefer_env {
-            match env_or_error(key) {
-                Ok(v) => {
-                    let e_val = v.parse::<bool>().unwrap();
-                    map.insert(key.to_string(), Value::Bool(e_val));
-                }
-                Err(_) => {
-                    map.insert(key.to_string(), Value::Bool(maybe_val.as_bool().unwrap()));
-                }
-            };
+/// Normalizes a single key segment into the flattened naming convention.
+///
+/// Letters and digits are upper-cased (using Unicode case mapping, so `größe` becomes `GRÖSSE`).
+/// Every other character, including spaces, hyphens, and dots, is replaced with an underscore.
+/// For example `my-key.name` becomes `MY_KEY_NAME`.
+pub(crate) fn normalize_key(key: &str) -> String {
+    let mut normalized = String::with_capacity(key.len());
+    for c in key.chars() {
+        if c.is_alphanumeric() {
+            normalized.extend(c.to_uppercase());
         } else {
-            map.insert(key.to_string(), Value::Bool(maybe_val.as_bool().unwrap()));
+            normalized.push('_');
         }
+    }
+    normalized
+}
 
-        return Ok(());
+/// Returns the flattened key of the YAML path `path`, which is also the name of the environment
+/// variable consulted for it.
+///
+/// # Examples
+///
+/// ```rust
+/// use yaml_config::flattened_key;
+/// assert_eq!(flattened_key(&["database", "max-connections"]), "DATABASE_MAX_CONNECTIONS");
+/// ```
+pub fn flattened_key<S: AsRef<str>>(path: &[S]) -> String {
+    path.iter()
+        .map(|segment| normalize_key(segment.as_ref()))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Turns a sequence into a map keyed by the index of each element.
+fn indexed(items: &[Yaml]) -> LinkedHashMap<Yaml, Yaml> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| (Yaml::Integer(i as i64), item.clone()))
+        .collect()
+}
+
+/// What the loader records about the keys of a document besides their values.
+#[derive(Debug, Default)]
+pub(crate) struct Trace {
+    /// Every flattened key whose value was taken from the environment.
+    pub env_keys: Vec<String>,
+    /// The YAML path of every flattened key read from the document, one segment per level.
+    pub paths: FxHashMap<String, Vec<String>>,
+    /// Every flattened key left `null` with no environment variable to provide it.
+    pub missing: Vec<String>,
+    /// The YAML path of the node whose conversion failed, if any.
+    pub failed_at: Option<Vec<String>>,
+    /// The files read, including those spliced in by `!include`.
+    pub reads: Reads,
+}
+
+/// The files read while loading, which count against `LoadOptions::read_budget` together.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Reads {
+    /// The canonical path of every file read, in order.
+    pub files: Vec<PathBuf>,
+    /// The number of bytes read from them.
+    pub bytes: u64,
+}
+
+impl Trace {
+    /// Records `path` as the node that failed if `result` is an error.
+    fn at<T>(&mut self, path: &[String], result: Result<T, ParseError>) -> Result<T, ParseError> {
+        if result.is_err() && self.failed_at.is_none() {
+            self.failed_at = Some(path.to_vec());
+        }
+        result
     }
 
-    if maybe_val.as_f64().is_some() {
-        if prefer_env {
-            match env_or_error(key) {
-                Ok(v) => {
-                    let e_val = v.parse::<f64>().unwrap();
-                    map.insert(key.to_string(), Value::F64(e_val));
-                }
-                Err(_) => {
-                    map.insert(key.to_string(), Value::F64(maybe_val.as_f64().unwrap()));
-                }
-            };
-        } else {
-            map.insert(key.to_string(), Value::F64(maybe_val.as_f64().unwrap()));
+    /// Adds the location of the node that failed in `doc_str`, read from `path`, to `e`.
+    fn locate(&mut self, e: ParseError, doc_str: &str, path: Option<&Path>) -> ParseError {
+        let marker = match self.failed_at.take() {
+            Some(failed_at) => loader::position(doc_str, &failed_at),
+            None => None,
+        };
+        match marker {
+            Some(marker) => e.located(Location {
+                file: path.map(|path| path.display().to_string()),
+                line: marker.line(),
+                column: marker.col() + 1,
+            }),
+            None => e,
         }
+    }
+}
 
-        Ok(())
-    } else {
-        let msg = format!("Failed to convert type for {}", key);
-        Err(ParseError {
-            module: "config".to_string(),
-            message: msg,
-        })
+/// Writes a YAML path as a JSON pointer, e.g. `/a/b`.
+fn pointer(path: &[String]) -> String {
+    path.iter().map(|segment| format!("/{}", segment)).collect()
+}
+
+/// Fails if another YAML path already flattened to `key_str`.
+fn check_unique(key_str: &str, path: &[String], trace: &Trace) -> Result<(), ParseError> {
+    match trace.paths.get(key_str) {
+        Some(previous) => Err(ParseError::with_code(
+            ErrorCode::KeyCollision,
+            "config::build_map",
+            format!(
+                "Key {} is defined by both {} and {}.",
+                key_str,
+                pointer(previous),
+                pointer(path)
+            ),
+        )),
+        None => Ok(()),
     }
 }
 
-/// Converts a YAML key into a string for processing.
-fn key_string(key: &Yaml) -> Result<&str, ParseError> {
-    match key.as_str() {
-        Some(s) => Ok(s),
-        None => Err(ParseError {
-            module: "config".to_string(),
-            message: format!("Could not convert key {:?} into String.", key),
-        }),
+/// The keys and values of a map, or the indices and elements of a sequence, to flatten.
+type Entries<'a> = Vec<(Cow<'a, Yaml>, &'a Yaml)>;
+
+/// A map being flattened by `build_map`.
+struct Level<'a> {
+    entries: std::vec::IntoIter<(Cow<'a, Yaml>, &'a Yaml)>,
+    key_str: Option<String>,
+    path: Vec<String>,
+    // The original spelling of each normalized key at this level, so that two sibling keys
+    // which normalize to the same name (e.g. `my-key` and `my_key`) are reported.
+    seen: FxHashMap<String, String>,
+}
+
+impl<'a> Level<'a> {
+    fn new(entries: Entries<'a>, key_str: Option<String>, path: Vec<String>) -> Self {
+        Level {
+            entries: entries.into_iter(),
+            key_str,
+            path,
+            seen: FxHashMap::default(),
+        }
     }
 }
 
-/// Recursive map builder.
+fn entries(map: &LinkedHashMap<Yaml, Yaml>) -> Entries<'_> {
+    map.iter().map(|(k, v)| (Cow::Borrowed(k), v)).collect()
+}
+
+/// The elements of `items` keyed by their index, like `indexed` without copying them.
+fn indexed_entries(items: &[Yaml]) -> Entries<'_> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| (Cow::Owned(Yaml::Integer(i as i64)), item))
+        .collect()
+}
+
+/// Map builder.
 ///
-/// Given a "root" of the yaml file it will generate a configuration recursively. Due
-/// to it's use of recursion the actual depth of the YAML file is limited to the depth of
-/// the stack. But given most (arguably 99.9%) of YAML files are not even 5 levels deep
-/// this seemed like an acceptable trade off for an easier to write algorithm.
+/// Given a "root" of the yaml file it will generate a configuration. Maps are visited depth
+/// first with an explicit stack of the maps being flattened rather than recursion, so the
+/// depth of the YAML file is limited by `LoadOptions::max_depth` instead of the call stack.
+/// A map nested deeper is reported as `ParseError::DepthExceeded`.
 ///
 /// Effectively, this performs a depth first search of the YAML file treating each top level
 /// feature as a tree with 1-to-N values. When a concrete (non-hash) value is arrived at
@@ -203,57 +765,147 @@ fn key_string(key: &Yaml) -> Result<&str, ParseError> {
 ///
 /// * `root` - The start of the YAML document as given by `yaml-rust`.
 /// * `config` - An IndexMap of String -> Value. It must use an FxBuilderHasher.
-/// * `prefer_env` - When `true` will return an environment variable matching the path string
-///                  regardless of whether the YAML contains a value for this key. It will prefer
-///                  the given value otherwise unless that value is `null`.
+/// * `options` - The load options. When they prefer the environment, an environment variable
+///   matching the path string is returned regardless of whether the YAML contains a value for
+///   this key. The given value is preferred otherwise unless that value is `null`.
 /// * `current_key_str` - An optional argument that stores the current string of the path.
+/// * `current_path` - The YAML path of `root`, one segment per level.
+/// * `trace` - Records the YAML path of every flattened key inserted, which is also used to
+///   report two distinct YAML paths that flatten to the same key, and every key whose value was
+///   taken from the environment.
 ///
 fn build_map(
     root: &LinkedHashMap<Yaml, Yaml>,
     config: &mut IndexMap<String, Value, FxBuildHasher>,
-    prefer_env: bool,
+    options: &LoadOptions,
     current_key_str: Option<&str>,
+    current_path: &[String],
+    trace: &mut Trace,
 ) -> Result<(), ParseError> {
-    // Recursively parse each root key to resolve.
-    for key in root.keys() {
-        let maybe_val = &root[key];
+    let mut stack = vec![Level::new(
+        entries(root),
+        current_key_str.map(str::to_string),
+        current_path.to_vec(),
+    )];
 
-        let key_str = match current_key_str {
+    while let Some(level) = stack.last_mut() {
+        let (key, maybe_val) = match level.entries.next() {
+            Some(entry) => entry,
+            None => {
+                stack.pop();
+                continue;
+            }
+        };
+        let raw_key = key_string(&key)?;
+        let normalized = normalize_key(&raw_key);
+
+        if let Some(previous) = level.seen.insert(normalized.clone(), raw_key.clone()) {
+            return Err(ParseError::with_code(
+                ErrorCode::KeyCollision,
+                "config::build_map",
+                format!(
+                    "Keys \"{}\" and \"{}\" both normalize to {}.",
+                    previous, raw_key, normalized
+                ),
+            ));
+        }
+
+        let key_str = match &level.key_str {
             Some(k) => {
                 // In this case we have a previous value.
                 // We need to construct the current depth-related key.
-                let mut next_key = k.to_uppercase().to_string();
+                let mut next_key = k.clone();
                 next_key.push('_');
-                next_key.push_str(&key_string(key)?.to_uppercase());
+                next_key.push_str(&normalized);
                 next_key
             }
-            None => key_string(key)?.to_uppercase().to_string(),
+            None => normalized,
         };
 
+        let mut path = level.path.clone();
+        path.push(raw_key);
+
+        // `{value: 30, unit: seconds}` is a single quantity rather than two keys.
+        let annotated = units::annotated_scalar(maybe_val);
+        let nested = match (maybe_val.as_vec(), options.flatten_arrays) {
+            // Each element is flattened under its index, as if the sequence were a map.
+            (Some(items), true) => Some(indexed_entries(items)),
+            _ if annotated.is_some() => None,
+            _ => maybe_val.as_hash().map(entries),
+        };
+        if let Some(nested) = nested {
+            if stack.len() >= options.max_depth {
+                let error = ParseError::DepthExceeded {
+                    key: key_str,
+                    max_depth: options.max_depth,
+                    location: None,
+                };
+                return trace.at(&path, Err(error));
+            }
+            // Now we need to construct the key for one layer deeper.
+            stack.push(Level::new(nested, Some(key_str), path));
+            continue;
+        }
+
         if maybe_val.is_array() {
-            return Err(ParseError {
-                module: "config::build_map".to_string(),
-                message: "Arrays are currently unsupported for configuration.".to_string(),
-            });
+            check_unique(&key_str, &path, trace)?;
+            let val = trace.at(&path, yaml_to_value(&key_str, maybe_val))?;
+            config.insert(key_str.clone(), val);
+            trace.paths.insert(key_str, path);
+            continue;
         }
 
-        if maybe_val.as_hash().is_none() {
-            // Base condition
-            maybe_yaml_to_value(&key_str.to_uppercase(), maybe_val, prefer_env, config)?;
-        } else {
-            // Now we need to construct the key for one layer deeper.
-            build_map(
-                maybe_val.as_hash().unwrap(),
-                config,
-                prefer_env,
-                Some(&key_str),
-            )?;
+        let maybe_val = annotated.as_ref().unwrap_or(maybe_val);
+
+        check_unique(&key_str, &path, trace)?;
+        let from_env = trace.at(
+            &path,
+            maybe_yaml_to_value(&key_str, maybe_val, options, config),
+        )?;
+        #[cfg(feature = "tracing")]
+        trace_override(&key_str, &pointer(&path), maybe_val, from_env, options);
+        if from_env {
+            trace.env_keys.push(key_str.clone());
+        } else if maybe_val.is_null() {
+            trace.missing.push(key_str.clone());
+            if !config.contains_key(&key_str) {
+                // Skipped, so the key has no path either.
+                continue;
+            }
         }
+        trace.paths.insert(key_str, path);
     }
 
     Ok(())
 }
 
+/// Emits a `tracing` event when a YAML value and an environment variable both provide `key`,
+/// naming the side that won. Values are deliberately not logged since they may be secrets.
+#[cfg(feature = "tracing")]
+fn trace_override(key: &str, path: &str, yaml_val: &Yaml, from_env: bool, options: &LoadOptions) {
+    if yaml_val.is_null() {
+        // The YAML explicitly defers to the environment, so nothing was overridden.
+        return;
+    }
+    if from_env {
+        tracing::info!(
+            target: "yaml_config",
+            key,
+            path,
+            source = "env",
+            "environment variable overrides YAML value"
+        );
+    } else if options.var_os(key).is_some() {
+        tracing::info!(
+            target: "yaml_config",
+            key,
+            path,
+            source = "yaml",
+            "YAML value overrides environment variable"
+        );
+    }
+}
+
 /// Loads a configuration file.
 ///
 /// The parser will first load the YAML file. It then re-organizes the YAML
@@ -264,12 +916,13 @@ fn build_map(
 ///   y: "value"
 /// ```
 ///
-/// The key will be `X_Y` and the value will be the string `"value"`.
+/// The key will be `X_Y` and the value will be the string `"value"`. Characters other than
+/// letters and digits are replaced with underscores, so `my-key.name` becomes `MY_KEY_NAME`.
 ///
 /// After loading, it investigates each value looking for nulls. In the
 /// case of a null, it will search the environment for the
 /// key (in the above example `X_Y`). If found, it replaces the value.
-/// If not found, it will error.
+/// If not found, it will error, unless `LoadOptions::allow_missing` says otherwise.
 ///
 /// In the event that a key in the environment matches a key that is
 /// provided in the YAML it will prefer the key in the YAML file. To
@@ -306,29 +959,439 @@ pub fn load(
     file_path: &str,
     preference: Option<Preference>,
 ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
-    let prefer_env = match preference {
-        Some(p) => p == Preference::PreferEnv,
-        None => false,
+    let options = LoadOptions {
+        preference: preference.unwrap_or_default(),
+        ..Default::default()
     };
-    let doc_str = read_to_string(file_path)?;
-    let yaml_docs = YamlLoader::load_from_str(&doc_str)?;
-    let base_config = &yaml_docs[0];
-    let user_config = match base_config.as_hash() {
-        Some(hash) => hash,
-        None => {
-            return Err(ParseError {
-                module: "config".to_string(),
-                message: "Failed to parse YAML as hashmap.".to_string(),
-            })
+
+    load_with(file_path, &options)
+}
+
+/// Loads a configuration from a YAML document held in memory, such as one embedded with
+/// `include_str!` or fetched over the network. It behaves exactly like `load` otherwise.
+///
+/// # Examples
+///
+/// ```rust
+/// use yaml_config::load_from_str;
+/// let configuration = load_from_str("server:\n  port: 8080\n", None).unwrap();
+/// assert_eq!(*configuration["SERVER_PORT"].as_i64().unwrap(), 8080);
+/// ```
+pub fn load_from_str(
+    doc_str: &str,
+    preference: Option<Preference>,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    let options = LoadOptions {
+        preference: preference.unwrap_or_default(),
+        ..Default::default()
+    };
+
+    parse_str(doc_str, &options)
+}
+
+/// Loads a configuration from everything `reader` yields, such as standard input. It behaves
+/// exactly like `load` otherwise.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use yaml_config::load_from_reader;
+/// let configuration = load_from_reader(std::io::stdin(), None);
+/// ```
+pub fn load_from_reader<R: Read>(
+    mut reader: R,
+    preference: Option<Preference>,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    let mut doc_str = String::new();
+    reader.read_to_string(&mut doc_str)?;
+    load_from_str(&doc_str, preference)
+}
+
+/// Loads several YAML files and merges them in order, so each file overrides the keys of the
+/// files before it. Keys are merged one by one: a later file only replaces the keys it defines
+/// and leaves its siblings alone, so a small overlay can sit on top of a shared base. Sequences
+/// are replaced as a whole.
+///
+/// Each file is resolved against the environment as by `load`. For sources other than files,
+/// read-only layers, or schemas use `ConfigBuilder`, which this is a shorthand for.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use yaml_config::load_layered;
+/// // base.yaml: { db: { host: localhost, port: 5432 } }
+/// // prod.yaml: { db: { host: db.internal } }
+/// let configuration = load_layered(&["base.yaml", "prod.yaml"], None).unwrap();
+/// assert_eq!(configuration["DB_HOST"].as_string().unwrap(), "db.internal");
+/// assert_eq!(*configuration["DB_PORT"].as_i64().unwrap(), 5432);
+/// ```
+pub fn load_layered(
+    file_paths: &[&str],
+    preference: Option<Preference>,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    let options = LoadOptions {
+        preference: preference.unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let builder = file_paths.iter().fold(
+        ConfigBuilder::new().with_options(options),
+        |builder, path| builder.add_file(path),
+    );
+    Ok(builder.build()?.into_values())
+}
+
+/// Loads a configuration file using the given `LoadOptions`.
+///
+/// This behaves exactly like `load` but exposes every loader option. See the documentation
+/// for `LoadOptions` for the available settings.
+///
+/// If the top level of the document is a sequence it is loaded as a single `Value::Array`
+/// stored under `LoadOptions::root_key`, or with `LoadOptions::flatten_arrays` as one key per
+/// element prefixed with it.
+///
+/// # Arguments
+///
+/// * `file_path` - A string representing the path to the YAML file.
+/// * `options` - The options used while loading.
+///
+/// # Examples
+///
+/// ```rust
+/// use yaml_config::{load_with, LoadOptions, OnEmpty};
+/// let options = LoadOptions { on_empty: OnEmpty::EmptyConfig, ..Default::default() };
+/// let configuration = load_with("path/to/yaml/file.yaml", &options);
+/// ```
+pub fn load_with(
+    file_path: &str,
+    options: &LoadOptions,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    load_traced(file_path, options, &mut Trace::default())
+}
+
+/// Loads a configuration file like `load_with`, recording the path of every key and whether its
+/// value was taken from the environment in `trace`.
+pub(crate) fn load_traced(
+    file_path: &str,
+    options: &LoadOptions,
+    trace: &mut Trace,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    let doc_str = read_counted(Path::new(file_path), options, &mut trace.reads)?;
+    parse_document(&doc_str, options, Some(Path::new(file_path)), trace)
+}
+
+/// Loads a configuration file as a tree instead of flattening it.
+///
+/// Every map of the document becomes a `Value::Map` whose keys are normalized as in `load`,
+/// so given:
+///
+/// ```yaml
+/// database:
+///   host: localhost
+/// ```
+///
+/// the result holds `DATABASE`, a map containing `HOST`. Values are resolved exactly as by
+/// `load_with`: each leaf is still looked up in the environment under its flattened key, here
+/// `DATABASE_HOST`. Keys that have no place in the document, such as those added by migrations
+/// or read from the environment alone, are kept at the top level.
+///
+/// # Examples
+///
+/// ```rust
+/// use yaml_config::{load_nested, LoadOptions};
+/// let configuration = load_nested("path/to/yaml/file.yaml", &LoadOptions::default());
+/// ```
+pub fn load_nested(
+    file_path: &str,
+    options: &LoadOptions,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    options.check_env()?;
+    let mut trace = Trace::default();
+    let doc_str = read_counted(Path::new(file_path), options, &mut trace.reads)?;
+    let yaml_docs = loader::load_document(
+        &doc_str,
+        options,
+        Some(Path::new(file_path)),
+        &mut trace.reads,
+    )?;
+    let mut flat = build_document(yaml_docs.first(), options, &mut trace)
+        .map_err(|e| trace.locate(e, &doc_str, Some(Path::new(file_path))))?;
+
+    let mut tree = IndexMap::with_hasher(FxBuildHasher::default());
+    if let Some(root) = yaml_docs.first().and_then(Yaml::as_hash) {
+        let overlaid = overlay::apply(root, &options.overlays)?;
+        nest(
+            overlaid.as_ref().unwrap_or(root),
+            options,
+            None,
+            &mut flat,
+            &mut tree,
+        );
+    }
+    tree.extend(flat);
+    Ok(tree)
+}
+
+/// Moves the values of `flat` into `tree` following the shape of the YAML map `root`.
+fn nest(
+    root: &LinkedHashMap<Yaml, Yaml>,
+    options: &LoadOptions,
+    current_key_str: Option<&str>,
+    flat: &mut IndexMap<String, Value, FxBuildHasher>,
+    tree: &mut IndexMap<String, Value, FxBuildHasher>,
+) {
+    for (key, val) in root {
+        // `build_map` already rejected keys that can't be converted.
+        let normalized = match key_string(key) {
+            Ok(raw_key) => normalize_key(&raw_key),
+            Err(_) => continue,
+        };
+        let key_str = match current_key_str {
+            Some(k) => format!("{}_{}", k, normalized),
+            None => normalized.clone(),
+        };
+
+        let annotated = units::annotated_scalar(val);
+        let val = annotated.as_ref().unwrap_or(val);
+        let children = match (val.as_vec(), val.as_hash()) {
+            (Some(items), _) if options.flatten_arrays => Some(Cow::Owned(indexed(items))),
+            (_, Some(hash)) => Some(Cow::Borrowed(hash)),
+            _ => None,
+        };
+
+        match children {
+            Some(children) => {
+                let mut branch = IndexMap::with_hasher(FxBuildHasher::default());
+                nest(&children, options, Some(&key_str), flat, &mut branch);
+                tree.insert(normalized, Value::Map(branch));
+            }
+            None => {
+                if let Some(val) = flat.shift_remove(&key_str) {
+                    tree.insert(normalized, val);
+                }
+            }
         }
+    }
+}
+
+/// Reads the file at `file_path` as restricted by `options`.
+fn read_document(file_path: &str, options: &LoadOptions) -> Result<String, ParseError> {
+    read_counted(Path::new(file_path), options, &mut Reads::default())
+}
+
+/// Reads the file at `file_path` as restricted by `options`, adding it to `reads`. The read
+/// budget covers the files already in `reads` as well.
+pub(crate) fn read_counted(
+    file_path: &Path,
+    options: &LoadOptions,
+    reads: &mut Reads,
+) -> Result<String, ParseError> {
+    let budget = options.read_budget;
+    if let Some(max_files) = budget.max_files.filter(|max| reads.files.len() >= *max) {
+        return Err(ParseError::with_code(
+            ErrorCode::ReadBudgetExceeded,
+            "config",
+            format!(
+                "Reading {} exceeds the read budget of {} files.",
+                file_path.display(),
+                max_files
+            ),
+        ));
+    }
+    let path = paths::check(file_path, options.symlinks, options.base_dir.as_deref())?;
+    // Each file may only use what the previous ones left of the byte budget.
+    let max_bytes = budget.max_bytes.map(|max| max.saturating_sub(reads.bytes));
+    let doc_str = read_bounded(&path, max_bytes)?;
+    reads.bytes += doc_str.len() as u64;
+    reads.files.push(path);
+    Ok(doc_str)
+}
+
+/// Reads the file at `path`, failing without reading further once it exceeds `max_bytes`.
+fn read_bounded<P: AsRef<Path>>(path: P, max_bytes: Option<u64>) -> Result<String, ParseError> {
+    let max_bytes = match max_bytes {
+        Some(max_bytes) => max_bytes,
+        None => return Ok(read_to_string(path)?),
     };
+    let mut doc_str = String::new();
+    File::open(&path)?
+        .take(max_bytes.saturating_add(1))
+        .read_to_string(&mut doc_str)?;
+    if doc_str.len() as u64 > max_bytes {
+        return Err(ParseError::with_code(
+            ErrorCode::ReadBudgetExceeded,
+            "config",
+            format!(
+                "{} exceeds the read budget of {} bytes.",
+                path.as_ref().display(),
+                max_bytes
+            ),
+        ));
+    }
+    Ok(doc_str)
+}
 
+/// Parses a YAML document held in memory. This is the shared implementation behind every
+/// loader entry point.
+pub(crate) fn parse_str(
+    doc_str: &str,
+    options: &LoadOptions,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    parse_document(doc_str, options, None, &mut Trace::default())
+}
+
+/// Parses a YAML document from raw bytes.
+///
+/// This never panics, whatever the input: invalid UTF-8, malformed YAML, excessive nesting,
+/// alias bombs, and environment values of the wrong type are all reported as a `ParseError`.
+/// That makes it suitable for fuzzing and for configuration uploaded by users. Custom tag
+/// resolvers and migrations registered in `options` are user code and are not covered by
+/// this guarantee.
+///
+/// # Examples
+///
+/// ```rust
+/// use yaml_config::{try_parse_bytes, LoadOptions};
+/// let configuration = try_parse_bytes(b"server:\n  port: 8080\n", &LoadOptions::default());
+/// assert!(configuration.is_ok());
+/// assert!(try_parse_bytes(b"\xff\xfe", &LoadOptions::default()).is_err());
+/// ```
+pub fn try_parse_bytes(
+    bytes: &[u8],
+    options: &LoadOptions,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    let doc_str = std::str::from_utf8(bytes)
+        .map_err(|e| ParseError::new("config", format!("Document is not valid UTF-8: {}", e)))?;
+    parse_str(doc_str, options)
+}
+
+/// Parses a YAML document held in memory, recording the path of every key and whether its value
+/// was taken from the environment in `trace`. `path` is the file the document was read from, if
+/// any, which `!include` paths are relative to.
+pub(crate) fn parse_document(
+    doc_str: &str,
+    options: &LoadOptions,
+    path: Option<&Path>,
+    trace: &mut Trace,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    options.check_env()?;
+    let yaml_docs = loader::load_document(doc_str, options, path, &mut trace.reads)?;
+    match build_document(yaml_docs.first(), options, trace) {
+        Err(e @ ParseError::MissingEnv { .. }) => {
+            Err(missing_keys(yaml_docs.first(), options).unwrap_or(e))
+        }
+        result => result.map_err(|e| trace.locate(e, doc_str, path)),
+    }
+}
+
+/// Flattens `doc` again without failing on missing keys, to report every one of them when there
+/// is more than one.
+fn missing_keys(doc: Option<&Yaml>, options: &LoadOptions) -> Option<ParseError> {
+    let options = LoadOptions {
+        allow_missing: OnMissing::Null,
+        ..options.clone()
+    };
+    let mut trace = Trace::default();
+    build_document(doc, &options, &mut trace).ok()?;
+    if trace.missing.len() < 2 {
+        return None;
+    }
+    let keys = trace
+        .missing
+        .into_iter()
+        .map(|key| MissingKey {
+            path: trace.paths.remove(&key),
+            env_var: Some(key.clone()),
+            key,
+            example: None,
+        })
+        .collect();
+    Some(ParseError::MissingKeys {
+        report: MissingReport::new(keys),
+    })
+}
+
+/// Flattens the parsed YAML document `doc`, recording the path of every key and whether its
+/// value was taken from the environment in `trace`.
+fn build_document(
+    doc: Option<&Yaml>,
+    options: &LoadOptions,
+    trace: &mut Trace,
+) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
     let mut config = IndexMap::with_hasher(FxBuildHasher::default());
 
-    build_map(user_config, &mut config, prefer_env, None)?;
+    let base_config = match doc {
+        Some(doc) if !doc.is_null() => doc,
+        _ => {
+            load_empty(options, &mut config)?;
+            if options.on_empty == OnEmpty::EnvOnly {
+                trace.env_keys.extend(config.keys().cloned());
+            }
+            return Ok(config);
+        }
+    };
+
+    let root_key = normalize_key(&options.root_key);
+    match base_config.as_vec() {
+        Some(items) if options.flatten_arrays => build_map(
+            &indexed(items),
+            &mut config,
+            options,
+            Some(&root_key),
+            std::slice::from_ref(&options.root_key),
+            trace,
+        )?,
+        Some(_) => {
+            // A top-level sequence has no key of its own, so it is stored under a synthetic one.
+            let val = yaml_to_value(&root_key, base_config)?;
+            trace
+                .paths
+                .insert(root_key.clone(), vec![options.root_key.clone()]);
+            config.insert(root_key, val);
+        }
+        None => {
+            let user_config = match base_config.as_hash() {
+                Some(hash) => hash,
+                None => {
+                    return Err(ParseError::new(
+                        "config",
+                        "Failed to parse YAML as hashmap.",
+                    ))
+                }
+            };
+
+            let overlaid = overlay::apply(user_config, &options.overlays)?;
+            let user_config = overlaid.as_ref().unwrap_or(user_config);
+
+            build_map(user_config, &mut config, options, None, &[], trace)?;
+        }
+    }
+
+    if options.interpolate {
+        interpolate::resolve(&mut config, &trace.env_keys, options)?;
+    }
+
+    options.migrations.apply(&mut config)?;
 
     Ok(config)
 }
 
+/// Applies the `OnEmpty` policy for a document that contains no YAML nodes.
+fn load_empty(
+    options: &LoadOptions,
+    config: &mut IndexMap<String, Value, FxBuildHasher>,
+) -> Result<(), ParseError> {
+    match options.on_empty {
+        OnEmpty::Error => Err(ParseError::EmptyDocument),
+        OnEmpty::EmptyConfig => Ok(()),
+        OnEmpty::EnvOnly => {
+            for (key, val) in options.vars() {
+                config.insert(key, infer_value(val));
+            }
+            Ok(())
+        }
+    }
+}
+
 #[cfg(test)]
 mod test;
@@ -0,0 +1,375 @@
+//! Static checks for configuration files.
+//!
+//! `lint` inspects a single file and `lint_layers` a stack of files merged in order, reporting
+//! problems that load without error but are likely mistakes:
+//!
+//! * `Rule::DuplicateKey`: a map defines the same key twice, so one value is silently lost.
+//! * `Rule::ShadowedValue`: a later layer overrides a value set by an earlier one.
+//! * `Rule::PlaintextSecret`: a key named like a password or token holds a literal value
+//!   instead of a `null` taken from the environment or a tag such as `!keyring`.
+//! * `Rule::UnusedAnchor`: an anchor is defined but never referenced.
+//!
+//! With the `cli` feature the `yaml-config` binary runs these checks with
+//! `yaml-config lint FILE...`.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::lint::{lint_str, Rule};
+//! let findings = lint_str("db:\n  password: hunter2\n", "app.yaml").unwrap();
+//! assert_eq!(findings[0].rule, Rule::PlaintextSecret);
+//! assert_eq!(findings[0].location, "app.yaml:2:13");
+//! ```
+use crate::messages;
+use crate::{load_with, LoadOptions, OnMissing, ParseError};
+use fxhash::{FxHashMap, FxHashSet};
+use std::fmt;
+use std::fs::read_to_string;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle};
+
+/// Key names, matched against the last segment of a key, that hold credentials.
+const SECRET_NAMES: [&str; 8] = [
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "api_key",
+    "apikey",
+    "private_key",
+    "credentials",
+];
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let id = match self {
+            Severity::Info => "lint.info",
+            Severity::Warning => "lint.warning",
+            Severity::Error => "lint.error",
+        };
+        write!(f, "{}", messages::text(id, &[]))
+    }
+}
+
+/// The check that produced a finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Rule {
+    DuplicateKey,
+    ShadowedValue,
+    PlaintextSecret,
+    UnusedAnchor,
+}
+
+impl Rule {
+    /// The name of the rule as shown in reports, e.g. `duplicate-key`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::DuplicateKey => "duplicate-key",
+            Rule::ShadowedValue => "shadowed-value",
+            Rule::PlaintextSecret => "plaintext-secret",
+            Rule::UnusedAnchor => "unused-anchor",
+        }
+    }
+
+    /// The severity findings of this rule are reported with.
+    pub fn severity(self) -> Severity {
+        match self {
+            Rule::DuplicateKey => Severity::Error,
+            Rule::PlaintextSecret => Severity::Warning,
+            Rule::UnusedAnchor => Severity::Warning,
+            Rule::ShadowedValue => Severity::Info,
+        }
+    }
+}
+
+/// A problem found by a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: Rule,
+    pub severity: Severity,
+    /// Where the problem is, as `file:line:column`.
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} [{}] {}",
+            self.location,
+            self.severity,
+            self.rule.name(),
+            self.message
+        )
+    }
+}
+
+/// An open collection while walking the document.
+enum Frame {
+    Seq {
+        is_key: bool,
+    },
+    Map {
+        is_key: bool,
+        keys: FxHashSet<String>,
+        // The key whose value comes next, or `None` when a key comes next.
+        key: Option<String>,
+    },
+}
+
+struct Linter<'a> {
+    name: &'a str,
+    stack: Vec<Frame>,
+    anchors: FxHashMap<usize, Marker>,
+    used: FxHashSet<usize>,
+    findings: Vec<Finding>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, rule: Rule, mark: Marker, message: String) {
+        self.findings.push(Finding {
+            rule,
+            severity: rule.severity(),
+            location: format!("{}:{}:{}", self.name, mark.line(), mark.col() + 1),
+            message,
+        });
+    }
+
+    /// The dotted path of the value about to be read.
+    fn path(&self) -> String {
+        self.stack
+            .iter()
+            .filter_map(|frame| match frame {
+                Frame::Map { key, .. } => key.clone(),
+                Frame::Seq { .. } => None,
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Returns `true` when the next node is a map key.
+    fn expects_key(&self) -> bool {
+        matches!(self.stack.last(), Some(Frame::Map { key: None, .. }))
+    }
+
+    /// Records that a value has been read, so the parent map expects a key again.
+    fn value_done(&mut self) {
+        if let Some(Frame::Map { key, .. }) = self.stack.last_mut() {
+            *key = None;
+        }
+    }
+
+    fn anchor(&mut self, aid: usize, mark: Marker) {
+        if aid > 0 {
+            self.anchors.insert(aid, mark);
+        }
+    }
+
+    fn key(&mut self, name: String, mark: Marker) {
+        let duplicate = match self.stack.last_mut() {
+            Some(Frame::Map { keys, key, .. }) => {
+                *key = Some(name.clone());
+                !keys.insert(name.clone())
+            }
+            _ => false,
+        };
+        if duplicate {
+            let message = messages::text("lint.duplicate_key", &[("key", &self.path())]);
+            self.report(Rule::DuplicateKey, mark, message);
+        }
+    }
+
+    fn scalar(&mut self, value: &str, style: TScalarStyle, tagged: bool, mark: Marker) {
+        let name = self.path();
+        let last = name.rsplit('.').next().unwrap_or_default().to_lowercase();
+        let null = style == TScalarStyle::Plain && matches!(value, "" | "~" | "null");
+        if SECRET_NAMES.iter().any(|secret| last.contains(secret)) && !tagged && !null {
+            self.report(
+                Rule::PlaintextSecret,
+                mark,
+                messages::text("lint.plaintext_secret", &[("key", &name)]),
+            );
+        }
+    }
+}
+
+impl MarkedEventReceiver for Linter<'_> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::MappingStart(aid) | Event::SequenceStart(aid) => {
+                self.anchor(aid, mark);
+                let is_key = self.expects_key();
+                if is_key {
+                    // A collection used as a key has no name to report.
+                    self.key("?".to_string(), mark);
+                }
+                self.stack.push(match ev {
+                    Event::MappingStart(_) => Frame::Map {
+                        is_key,
+                        keys: FxHashSet::default(),
+                        key: None,
+                    },
+                    _ => Frame::Seq { is_key },
+                });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                if let Some(Frame::Map { is_key: false, .. } | Frame::Seq { is_key: false }) =
+                    self.stack.pop()
+                {
+                    self.value_done();
+                }
+            }
+            Event::Scalar(v, style, aid, tag) => {
+                self.anchor(aid, mark);
+                if self.expects_key() {
+                    self.key(v, mark);
+                } else {
+                    self.scalar(&v, style, tag.is_some(), mark);
+                    self.value_done();
+                }
+            }
+            Event::Alias(id) => {
+                self.used.insert(id);
+                if self.expects_key() {
+                    self.key("*".to_string(), mark);
+                } else {
+                    self.value_done();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lints `source`, naming it `name` in finding locations.
+pub fn lint_str(source: &str, name: &str) -> Result<Vec<Finding>, ParseError> {
+    let mut linter = Linter {
+        name,
+        stack: Vec::new(),
+        anchors: FxHashMap::default(),
+        used: FxHashSet::default(),
+        findings: Vec::new(),
+    };
+    let mut parser = Parser::new(source.chars());
+    parser.load(&mut linter, true)?;
+
+    let mut unused: Vec<(usize, Marker)> = linter
+        .anchors
+        .iter()
+        .filter(|(aid, _)| !linter.used.contains(aid))
+        .map(|(aid, mark)| (*aid, *mark))
+        .collect();
+    unused.sort_by_key(|(aid, _)| *aid);
+    for (_, mark) in unused {
+        linter.report(
+            Rule::UnusedAnchor,
+            mark,
+            messages::text("lint.unused_anchor", &[]),
+        );
+    }
+
+    Ok(linter.findings)
+}
+
+/// Lints the file at `path`.
+pub fn lint(path: &str) -> Result<Vec<Finding>, ParseError> {
+    lint_str(&read_to_string(path)?, path)
+}
+
+/// Lints every file in `paths` and reports values of earlier files overridden by later ones,
+/// as `ConfigBuilder` would merge them. `null` values without an environment variable and tags
+/// without a resolver are compared as written rather than failing the lint.
+pub fn lint_layers(paths: &[&str]) -> Result<Vec<Finding>, ParseError> {
+    let options = LoadOptions {
+        allow_missing: OnMissing::Null,
+        ..Default::default()
+    };
+    let mut findings = Vec::new();
+    let mut owners: FxHashMap<String, (usize, String)> = FxHashMap::default();
+    for (i, path) in paths.iter().enumerate() {
+        findings.extend(lint(path)?);
+        for (key, val) in load_with(path, &options)? {
+            let shown = format!("{:?}", val);
+            if let Some((owner, previous)) = owners.insert(key.clone(), (i, shown.clone())) {
+                if previous != shown {
+                    findings.push(Finding {
+                        rule: Rule::ShadowedValue,
+                        severity: Rule::ShadowedValue.severity(),
+                        location: path.to_string(),
+                        message: messages::text(
+                            "lint.shadowed_value",
+                            &[("key", &key), ("owner", paths[owner])],
+                        ),
+                    });
+                }
+            }
+        }
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lint::{lint_layers, lint_str, Rule, Severity};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn duplicate_keys_and_anchors_are_found() {
+        let source = "a:\n  b: 1\n  b: 2\nbase: &base {x: 1}\nunused: &unused 3\ncopy: *base\n";
+
+        let findings = lint_str(source, "f.yaml").unwrap();
+        let rules: Vec<Rule> = findings.iter().map(|f| f.rule).collect();
+
+        assert_eq!(rules, vec![Rule::DuplicateKey, Rule::UnusedAnchor]);
+        assert_eq!(findings[0].location, "f.yaml:3:3");
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert!(findings[0]
+            .message
+            .starts_with("a.b is defined more than once"));
+        assert_eq!(findings[1].location, "f.yaml:5:17");
+    }
+
+    #[test]
+    fn plaintext_secrets_are_found() {
+        let source = "db:\n  password: hunter2\n  api_token: !keyring app/token\n  secret: ~\n";
+
+        let findings = lint_str(source, "f.yaml").unwrap();
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, Rule::PlaintextSecret);
+        assert!(findings[0]
+            .to_string()
+            .starts_with("f.yaml:2:13: warning [plaintext-secret] db.password"));
+    }
+
+    #[test]
+    fn shadowed_values_are_found() {
+        let dir = tempdir().unwrap();
+        let base = dir.path().join("base.yaml");
+        let prod = dir.path().join("prod.yaml");
+        fs::write(&base, "port: 80\nhost: a\npassword: ~\n").unwrap();
+        fs::write(&prod, "port: 443\nhost: a\ntoken: !keyring app/token\n").unwrap();
+        let base = base.to_str().unwrap();
+        let prod = prod.to_str().unwrap();
+
+        let findings = lint_layers(&[base, prod]).unwrap();
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, Rule::ShadowedValue);
+        assert_eq!(
+            findings[0].message,
+            format!("PORT overrides the value set by {}.", base)
+        );
+    }
+}
@@ -0,0 +1,759 @@
+//! The YAML document loader.
+//!
+//! This mirrors `yaml_rust::YamlLoader` but keeps custom tags such as `!keyring` instead of
+//! discarding them. A tagged scalar is handed to the `Resolver` registered for its tag and
+//! replaced by the resolved value while the tree is being built.
+//!
+//! Unlike `YamlLoader` it also bounds the nesting depth and the number of nodes produced by
+//! aliases, so hostile input such as deeply nested flow sequences or "billion laughs" alias
+//! chains produces an error instead of exhausting the stack or memory.
+//!
+//! Documents read from a file may splice in other files with `!include path.yaml`, unless a
+//! resolver is registered for `!include`. The path is taken relative to the including file and
+//! read under the same `LoadOptions`, so `base_dir` and the symlink policy apply to it as well,
+//! and it counts toward the same read budget as the including file. Documents held in memory
+//! have no location to resolve paths from, so their includes are handled like any other custom
+//! tag.
+use crate::format::{self, Format};
+use crate::resolvers::Resolvers;
+use crate::types::duration;
+use crate::{
+    json, paths, read_counted, read_document, ErrorCode, LoadOptions, ParseError, Reads, Value,
+};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle, TokenType};
+use yaml_rust::yaml::Hash;
+use yaml_rust::Yaml;
+
+/// The deepest nesting of sequences and maps accepted.
+pub const MAX_DEPTH: usize = 512;
+
+/// The most nodes a single source may expand to, counting every copy made by an alias.
+pub(crate) const MAX_NODES: usize = 1 << 20;
+
+/// The longest chain of files including each other.
+pub(crate) const MAX_INCLUDE_DEPTH: usize = 32;
+
+/// Counts the nodes in `node` without recursing.
+fn node_count(node: &Yaml) -> usize {
+    let mut count = 0;
+    let mut pending = vec![node];
+    while let Some(node) = pending.pop() {
+        count += 1;
+        match node {
+            Yaml::Array(items) => pending.extend(items),
+            Yaml::Hash(h) => {
+                for (k, v) in h {
+                    pending.push(k);
+                    pending.push(v);
+                }
+            }
+            _ => {}
+        }
+    }
+    count
+}
+
+/// Converts a resolved `Value` back into a YAML node.
+fn value_to_yaml(val: Value) -> Yaml {
+    match val {
+        Value::I32(v) => Yaml::Integer(i64::from(v)),
+        Value::I64(v) => Yaml::Integer(v),
+        Value::F32(v) => Yaml::Real(v.to_string()),
+        Value::F64(v) => Yaml::Real(format!("{:?}", v)),
+        Value::String(v) => Yaml::String(v),
+        Value::Bool(v) => Yaml::Boolean(v),
+        Value::Array(items) => Yaml::Array(items.into_iter().map(value_to_yaml).collect()),
+        Value::Map(map) => Yaml::Hash(
+            map.into_iter()
+                .map(|(key, val)| (Yaml::String(key), value_to_yaml(val)))
+                .collect(),
+        ),
+        Value::Duration(v) => Yaml::String(duration::format(v)),
+        Value::Null => Yaml::Null,
+    }
+}
+
+/// Parses a scalar carrying one of the YAML core (`!!`) tags.
+fn core_tagged_scalar(v: String, suffix: &str) -> Yaml {
+    match suffix {
+        "bool" => match v.parse::<bool>() {
+            Ok(v) => Yaml::Boolean(v),
+            Err(_) => Yaml::BadValue,
+        },
+        "int" => match v.parse::<i64>() {
+            Ok(v) => Yaml::Integer(v),
+            Err(_) => Yaml::BadValue,
+        },
+        "float" => {
+            let real = Yaml::Real(v);
+            if real.as_f64().is_some() {
+                real
+            } else {
+                Yaml::BadValue
+            }
+        }
+        "null" => match v.as_ref() {
+            "~" | "null" => Yaml::Null,
+            _ => Yaml::BadValue,
+        },
+        _ => Yaml::String(v),
+    }
+}
+
+/// Resolves `!include` tags in a document read from a file.
+pub(crate) struct Includes<'a> {
+    options: &'a LoadOptions,
+    /// The canonical paths of the current file and the files including it, outermost first.
+    chain: Vec<PathBuf>,
+    /// The directory of the current file.
+    dir: PathBuf,
+    /// The files read so far, shared by every file of the chain.
+    reads: &'a RefCell<Reads>,
+}
+
+impl<'a> Includes<'a> {
+    fn new(options: &'a LoadOptions, chain: Vec<PathBuf>, reads: &'a RefCell<Reads>) -> Self {
+        let dir = chain
+            .last()
+            .and_then(|path| path.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Includes {
+            options,
+            chain,
+            dir,
+            reads,
+        }
+    }
+
+    /// Reads and parses the file included as `target`.
+    fn load(&self, target: &str) -> Result<Yaml, ParseError> {
+        let include_error = |message: String| ParseError::new("config::loader", message);
+        let including = self.chain.last().map(|path| path.display().to_string());
+        let including = including.unwrap_or_default();
+        if self.chain.len() >= MAX_INCLUDE_DEPTH {
+            return Err(include_error(format!(
+                "Includes are nested deeper than {} files in {}.",
+                MAX_INCLUDE_DEPTH, including
+            )));
+        }
+
+        let path = self.dir.join(target);
+        let canonical = fs::canonicalize(&path)
+            .map_err(|e| include_error(format!("{} can't include {}: {}", including, target, e)))?;
+        if let Some(start) = self.chain.iter().position(|p| *p == canonical) {
+            let cycle: Vec<String> = self.chain[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(ParseError::with_code(
+                ErrorCode::CircularInclude,
+                "config::loader",
+                format!("Circular include: {}.", cycle.join(" -> ")),
+            ));
+        }
+
+        let doc_str = read_counted(&path, self.options, &mut self.reads.borrow_mut())?;
+        let mut chain = self.chain.clone();
+        chain.push(canonical);
+        let nested = Includes::new(self.options, chain, self.reads);
+        let docs = parse(
+            &doc_str,
+            Format::from_path(&path),
+            &self.options.resolvers,
+            Some(&nested),
+        )?;
+        // An empty file includes nothing.
+        Ok(docs
+            .into_iter()
+            .next()
+            .filter(|doc| !doc.is_badvalue())
+            .unwrap_or(Yaml::Null))
+    }
+}
+
+struct Loader<'a> {
+    docs: Vec<Yaml>,
+    // (current node, anchor_id) tuple
+    doc_stack: Vec<(Yaml, usize)>,
+    key_stack: Vec<Yaml>,
+    anchor_map: BTreeMap<usize, (Yaml, usize)>,
+    resolvers: &'a Resolvers,
+    includes: Option<&'a Includes<'a>>,
+    // The number of nodes produced so far, including copies made by aliases.
+    nodes: usize,
+    // How many levels of collections are being skipped because they are nested too deeply.
+    skipped: usize,
+    // The first failure. Loading continues so the parser can finish, but the resulting
+    // documents are discarded.
+    error: Option<ParseError>,
+}
+
+impl Loader<'_> {
+    fn fail(&mut self, message: String) {
+        if self.error.is_none() {
+            self.error = Some(ParseError::new("config::loader", message));
+        }
+    }
+
+    /// Accounts for `count` new nodes, failing once the budget is exhausted.
+    fn spend(&mut self, count: usize) -> bool {
+        self.nodes = self.nodes.saturating_add(count);
+        if self.nodes > MAX_NODES {
+            self.fail(format!(
+                "Document expands to more than {} nodes.",
+                MAX_NODES
+            ));
+            return false;
+        }
+        true
+    }
+
+    /// Returns `false` and starts skipping when a new collection would be nested too deeply.
+    fn enter(&mut self) -> bool {
+        if self.skipped > 0 || self.doc_stack.len() >= MAX_DEPTH {
+            self.skipped += 1;
+            self.fail(format!(
+                "Document is nested deeper than {} levels.",
+                MAX_DEPTH
+            ));
+            return false;
+        }
+        self.spend(1);
+        true
+    }
+
+    /// Returns `false` while the end of a skipped collection is being consumed.
+    fn leave(&mut self) -> bool {
+        if self.skipped > 0 {
+            self.skipped -= 1;
+            if self.skipped == 0 {
+                // Stand in for the skipped collection so its parent stays consistent.
+                self.insert_new_node((Yaml::BadValue, 0));
+            }
+            return false;
+        }
+        true
+    }
+
+    /// Returns `true` if a scalar tagged `!tag` is resolved rather than kept as written.
+    fn resolves(&self, tag: &str) -> bool {
+        self.resolvers.is_strict()
+            || self.resolvers.contains(tag)
+            || (tag == "include" && self.includes.is_some())
+    }
+
+    fn resolve(&mut self, tag: &str, v: &str) -> Yaml {
+        if self.error.is_some() {
+            return Yaml::BadValue;
+        }
+        let included = match self.includes {
+            Some(includes) if tag == "include" && !self.resolvers.contains(tag) => {
+                Some(includes.load(v))
+            }
+            _ => None,
+        };
+        if let Some(included) = included {
+            return match included {
+                Ok(doc) if self.spend(node_count(&doc)) => doc,
+                Ok(_) => Yaml::BadValue,
+                Err(e) => {
+                    self.error = Some(e);
+                    Yaml::BadValue
+                }
+            };
+        }
+        match self.resolvers.resolve(tag, v) {
+            Ok(val) => value_to_yaml(val),
+            Err(e) => {
+                self.error = Some(e);
+                Yaml::BadValue
+            }
+        }
+    }
+
+    fn insert_new_node(&mut self, node: (Yaml, usize)) {
+        // valid anchor id starts from 1
+        if node.1 > 0 {
+            let size = node_count(&node.0);
+            self.anchor_map.insert(node.1, (node.0.clone(), size));
+        }
+        if self.doc_stack.is_empty() {
+            self.doc_stack.push(node);
+        } else {
+            let parent = self.doc_stack.last_mut().unwrap();
+            match *parent {
+                (Yaml::Array(ref mut v), _) => v.push(node.0),
+                (Yaml::Hash(ref mut h), _) => {
+                    let cur_key = self.key_stack.last_mut().unwrap();
+                    // current node is a key
+                    if cur_key.is_badvalue() {
+                        *cur_key = node.0;
+                    // current node is a value
+                    } else {
+                        let mut newkey = Yaml::BadValue;
+                        mem::swap(&mut newkey, cur_key);
+                        h.insert(newkey, node.0);
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+impl MarkedEventReceiver for Loader<'_> {
+    fn on_event(&mut self, ev: Event, _: Marker) {
+        match ev {
+            Event::DocumentEnd => match self.doc_stack.len() {
+                // empty document
+                0 => self.docs.push(Yaml::BadValue),
+                _ => self.docs.push(self.doc_stack.pop().unwrap().0),
+            },
+            // Collections nested too deeply fall through to the catch-all and are skipped.
+            Event::SequenceStart(aid) if self.enter() => {
+                self.doc_stack.push((Yaml::Array(Vec::new()), aid));
+            }
+            Event::SequenceEnd => {
+                if !self.leave() {
+                    return;
+                }
+                let node = self.doc_stack.pop().unwrap();
+                self.insert_new_node(node);
+            }
+            Event::MappingStart(aid) if self.enter() => {
+                self.doc_stack.push((Yaml::Hash(Hash::new()), aid));
+                self.key_stack.push(Yaml::BadValue);
+            }
+            Event::MappingEnd => {
+                if !self.leave() {
+                    return;
+                }
+                self.key_stack.pop().unwrap();
+                let node = self.doc_stack.pop().unwrap();
+                self.insert_new_node(node);
+            }
+            Event::Scalar(_, _, _, _) | Event::Alias(_) if self.skipped > 0 => {}
+            Event::Scalar(v, style, aid, tag) => {
+                self.spend(1);
+                let node = match tag {
+                    Some(TokenType::Tag(ref handle, ref suffix))
+                        if handle == "!" && self.resolves(suffix) =>
+                    {
+                        self.resolve(suffix, &v)
+                    }
+                    _ if style != TScalarStyle::Plain => Yaml::String(v),
+                    Some(TokenType::Tag(ref handle, ref suffix)) if handle == "!!" => {
+                        core_tagged_scalar(v, suffix)
+                    }
+                    // A custom tag nothing resolves is ignored, as by `YamlLoader`.
+                    Some(TokenType::Tag(ref handle, _)) if handle == "!" => Yaml::from_str(&v),
+                    Some(_) => Yaml::String(v),
+                    // Datatype is not specified
+                    None => Yaml::from_str(&v),
+                };
+
+                self.insert_new_node((node, aid));
+            }
+            Event::Alias(id) => {
+                // Check the budget before copying so a hostile alias chain is never expanded.
+                let n = match self.anchor_map.get(&id).map(|(_, size)| *size) {
+                    Some(size) if self.spend(size) => self.anchor_map[&id].0.clone(),
+                    _ => Yaml::BadValue,
+                };
+                self.insert_new_node((n, 0));
+            }
+            _ => { /* ignore */ }
+        }
+    }
+}
+
+/// Parses every document in `source`, resolving custom tags with `resolvers`.
+pub(crate) fn load_from_str(source: &str, resolvers: &Resolvers) -> Result<Vec<Yaml>, ParseError> {
+    load(source, resolvers, None)
+}
+
+/// Parses every document in `source` with the resolvers of `options`. When `path` names the file
+/// `source` was read from, `!include` tags are resolved relative to it, and its extension gives
+/// the format unless `options` set one. Included files are added to `reads`.
+pub(crate) fn load_document(
+    source: &str,
+    options: &LoadOptions,
+    path: Option<&Path>,
+    reads: &mut Reads,
+) -> Result<Vec<Yaml>, ParseError> {
+    let format = options
+        .format
+        .or_else(|| path.map(Format::from_path))
+        .unwrap_or_default();
+    let path = match path {
+        Some(path) => path,
+        None => return parse(source, format, &options.resolvers, None),
+    };
+    let canonical = paths::check(path, options.symlinks, options.base_dir.as_deref())?;
+    let shared = RefCell::new(mem::take(reads));
+    let includes = Includes::new(options, vec![canonical], &shared);
+    let docs = parse(source, format, &options.resolvers, Some(&includes));
+    *reads = shared.into_inner();
+    docs
+}
+
+/// A collection open while looking for a node.
+enum Frame {
+    Seq {
+        in_key: bool,
+        index: usize,
+    },
+    Map {
+        in_key: bool,
+        // The key whose value comes next, `None` for a key that isn't a scalar.
+        key: Option<String>,
+        expecting_key: bool,
+    },
+}
+
+/// Finds the position of the node at a YAML path in the first document.
+struct Locator<'a> {
+    target: &'a [String],
+    frames: Vec<Frame>,
+    // The path of the innermost open collection outside of any key.
+    path: Vec<String>,
+    found: Option<Found>,
+    done: bool,
+}
+
+/// The deepest node found on the way to a YAML path.
+struct Found {
+    /// How many segments of the path lead to the node.
+    depth: usize,
+    mark: Marker,
+    /// The file the node includes, if it is an `!include`.
+    include: Option<String>,
+}
+
+impl Locator<'_> {
+    /// Handles the start of a node, returning its path segment, or `None` if it is a key or
+    /// inside one, along with whether it is inside a key.
+    fn begin(
+        &mut self,
+        scalar: Option<&str>,
+        include: bool,
+        mark: Marker,
+    ) -> (Option<String>, bool) {
+        let (segment, in_key) = match self.frames.last_mut() {
+            None => return (None, false),
+            Some(Frame::Seq { in_key, index }) => {
+                *index += 1;
+                ((!*in_key).then(|| (*index - 1).to_string()), *in_key)
+            }
+            Some(Frame::Map {
+                key, expecting_key, ..
+            }) if *expecting_key => {
+                *expecting_key = false;
+                *key = scalar.map(str::to_string);
+                return (None, true);
+            }
+            Some(Frame::Map {
+                in_key,
+                key,
+                expecting_key,
+            }) => {
+                *expecting_key = true;
+                (key.take().filter(|_| !*in_key), *in_key)
+            }
+        };
+        if let Some(segment) = &segment {
+            let depth = self.path.len() + 1;
+            let on_path = self.target.get(..depth).is_some_and(|prefix| {
+                prefix[..depth - 1] == self.path[..] && &prefix[depth - 1] == segment
+            });
+            if on_path && self.found.as_ref().is_none_or(|found| depth > found.depth) {
+                self.found = Some(Found {
+                    depth,
+                    mark,
+                    include: scalar.filter(|_| include).map(str::to_string),
+                });
+            }
+        }
+        (segment, in_key)
+    }
+
+    fn open(&mut self, mark: Marker, map: bool) {
+        let (segment, in_key) = self.begin(None, false, mark);
+        if !self.frames.is_empty() {
+            self.path.push(segment.unwrap_or_default());
+        }
+        self.frames.push(if map {
+            Frame::Map {
+                in_key,
+                key: None,
+                expecting_key: true,
+            }
+        } else {
+            Frame::Seq { in_key, index: 0 }
+        });
+    }
+
+    fn close(&mut self) {
+        self.frames.pop();
+        // The root has no segment.
+        if !self.frames.is_empty() {
+            self.path.pop();
+        }
+    }
+}
+
+impl MarkedEventReceiver for Locator<'_> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        if self.done {
+            return;
+        }
+        match ev {
+            Event::DocumentEnd => self.done = true,
+            Event::SequenceStart(_) => self.open(mark, false),
+            Event::MappingStart(_) => self.open(mark, true),
+            Event::SequenceEnd | Event::MappingEnd => self.close(),
+            Event::Scalar(v, _, _, tag) => {
+                let include = matches!(
+                    tag,
+                    Some(TokenType::Tag(ref handle, ref suffix)) if handle == "!" && suffix == "include"
+                );
+                self.begin(Some(&v), include, mark);
+            }
+            Event::Alias(_) => {
+                self.begin(None, false, mark);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the position in `source` of the node at the YAML `path`, or of the deepest node on
+/// the way to it, such as the `!include` its value came from. Only documents that read as YAML
+/// have positions, so `None` is returned for others.
+pub(crate) fn position(source: &str, path: &[String]) -> Option<Marker> {
+    find(source, path).map(|found| found.mark)
+}
+
+fn find(source: &str, path: &[String]) -> Option<Found> {
+    let mut locator = Locator {
+        target: path,
+        frames: Vec::new(),
+        path: Vec::new(),
+        found: None,
+        done: false,
+    };
+    Parser::new(source.chars()).load(&mut locator, false).ok()?;
+    locator.found
+}
+
+/// Where the node at a YAML path is written.
+#[derive(Debug, Default)]
+pub(crate) struct Definition {
+    /// The file the node is in, if the document was read from one.
+    pub file: Option<String>,
+    /// The line of the node, starting at 1.
+    pub line: Option<usize>,
+    /// Whether the node is in a file included by the document.
+    pub included: bool,
+}
+
+/// Finds where the node at `path` in `source`, read from `file`, is written, following the
+/// `!include`s on the way to it.
+pub(crate) fn definition(
+    source: &str,
+    path: &[String],
+    file: Option<&str>,
+    options: &LoadOptions,
+) -> Definition {
+    let mut definition = Definition {
+        file: file.map(str::to_string),
+        ..Default::default()
+    };
+    let mut source = source.to_string();
+    let mut path = path.to_vec();
+    for _ in 0..MAX_INCLUDE_DEPTH {
+        let found = match find(&source, &path) {
+            Some(found) => found,
+            None => break,
+        };
+        definition.line = Some(found.mark.line());
+        // Documents held in memory don't resolve includes.
+        let (target, including) = match (found.include, &definition.file) {
+            (Some(target), Some(including)) if found.depth < path.len() => (target, including),
+            _ => break,
+        };
+        // Includes are relative to the file itself rather than to a link to it.
+        let base = paths::check(
+            Path::new(including),
+            options.symlinks,
+            options.base_dir.as_deref(),
+        );
+        let included = match base {
+            Ok(base) => base.with_file_name(&target),
+            Err(_) => break,
+        };
+        source = match read_document(&included.to_string_lossy(), options) {
+            Ok(source) => source,
+            Err(_) => break,
+        };
+        definition = Definition {
+            file: Some(included.display().to_string()),
+            line: None,
+            included: true,
+        };
+        path.drain(..found.depth);
+    }
+    definition
+}
+
+/// Parses `source` as `format`. Only YAML has tags and more than one document.
+fn parse(
+    source: &str,
+    format: Format,
+    resolvers: &Resolvers,
+    includes: Option<&Includes>,
+) -> Result<Vec<Yaml>, ParseError> {
+    match format {
+        Format::Yaml => load(source, resolvers, includes),
+        Format::Json => Ok(vec![json::parse(source)?]),
+        Format::Toml => Ok(vec![format::toml_to_yaml(source)?]),
+    }
+}
+
+fn load(
+    source: &str,
+    resolvers: &Resolvers,
+    includes: Option<&Includes>,
+) -> Result<Vec<Yaml>, ParseError> {
+    let mut loader = Loader {
+        docs: Vec::new(),
+        doc_stack: Vec::new(),
+        key_stack: Vec::new(),
+        anchor_map: BTreeMap::new(),
+        resolvers,
+        includes,
+        nodes: 0,
+        skipped: 0,
+        error: None,
+    };
+    let mut parser = Parser::new(source.chars());
+    parser.load(&mut loader, true)?;
+
+    match loader.error {
+        Some(e) => Err(e),
+        None => Ok(loader.docs),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::loader::load_from_str;
+    use crate::resolvers::{Resolver, Resolvers};
+    use crate::{load, ErrorCode, ParseError, Value};
+    use std::fs;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+    use yaml_rust::{Yaml, YamlLoader};
+
+    struct Upper;
+
+    impl Resolver for Upper {
+        fn resolve(&self, value: &str) -> Result<Value, ParseError> {
+            Ok(Value::String(value.to_uppercase()))
+        }
+    }
+
+    #[test]
+    fn matches_yaml_rust_without_tags() {
+        let source = "a: 1\nb: [2.5, \"x\", ~]\nc: &anchor\n  d: !!int 3\ne: *anchor\n";
+
+        let docs = load_from_str(source, &Resolvers::default()).unwrap();
+
+        assert_eq!(docs, YamlLoader::load_from_str(source).unwrap());
+    }
+
+    #[test]
+    fn custom_tags_are_resolved() {
+        let mut resolvers = Resolvers::default();
+        resolvers.register("upper", Arc::new(Upper));
+
+        let docs = load_from_str("a: !upper value\nb: !upper \"quoted\"\n", &resolvers).unwrap();
+
+        assert_eq!(docs[0]["a"], Yaml::String("VALUE".to_string()));
+        assert_eq!(docs[0]["b"], Yaml::String("QUOTED".to_string()));
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let source = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+
+        assert!(load_from_str(&source, &Resolvers::default()).is_err());
+        assert!(load_from_str("[[[[1]]]]", &Resolvers::default()).is_ok());
+    }
+
+    #[test]
+    fn alias_expansion_is_bounded() {
+        let mut source = "a0: &a0 [x, x, x, x, x, x, x, x, x, x]\n".to_string();
+        for i in 1..10 {
+            let prev = format!("*a{}", i - 1);
+            source.push_str(&format!(
+                "a{}: &a{} [{}]\n",
+                i,
+                i,
+                vec![prev; 10].join(", ")
+            ));
+        }
+
+        assert!(load_from_str(&source, &Resolvers::default()).is_err());
+    }
+
+    #[test]
+    fn includes_are_spliced_relative_to_the_including_file() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("db")).unwrap();
+        fs::write(
+            dir.path().join("app.yaml"),
+            "name: app\ndb: !include db/main.yaml\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("db/main.yaml"),
+            "host: localhost\npool: !include pool.yaml\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("db/pool.yaml"), "size: 4\n").unwrap();
+        fs::write(dir.path().join("a.yaml"), "b: !include b.yaml\n").unwrap();
+        fs::write(dir.path().join("b.yaml"), "a: !include a.yaml\n").unwrap();
+
+        let values = load(dir.path().join("app.yaml").to_str().unwrap(), None).unwrap();
+        assert_eq!(values["DB_HOST"], Value::from("localhost"));
+        assert_eq!(values["DB_POOL_SIZE"], Value::I64(4));
+
+        let err = load(dir.path().join("a.yaml").to_str().unwrap(), None).unwrap_err();
+        assert!(err.message().starts_with("Circular include: "));
+        assert_eq!(err.code(), ErrorCode::CircularInclude);
+        assert!(err.message().ends_with("a.yaml."));
+        let mut strict = Resolvers::default();
+        strict.set_strict(true);
+        assert!(load_from_str("a: !include a.yaml\n", &strict).is_err());
+    }
+
+    #[test]
+    fn unknown_tags_are_kept_unless_strict() {
+        let docs = load_from_str("a: !unknown 42\n", &Resolvers::default()).unwrap();
+        assert_eq!(docs[0]["a"], Yaml::Integer(42));
+
+        let mut strict = Resolvers::default();
+        strict.set_strict(true);
+        let err = load_from_str("a: !unknown value\n", &strict).unwrap_err();
+        assert_eq!(err.message(), "No resolver is registered for tag !unknown.");
+    }
+}
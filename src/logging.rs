@@ -0,0 +1,303 @@
+//! Logging settings read from a `logging:` section.
+//!
+//! ```yaml
+//! logging:
+//!   level: info
+//!   format: json
+//!   target: stderr
+//!   modules:
+//!     my_app::db: debug
+//!     hyper: warn
+//! ```
+//!
+//! `LoggingConfig::directives` renders the levels as an `EnvFilter` directive string such as
+//! `info,my_app::db=debug,hyper=warn`. With the `logging` feature enabled, `env_filter` and
+//! `init` build and install a `tracing_subscriber` from the section directly.
+//!
+//! Module names are flattened like any other key, so `my_app::db` is stored as
+//! `LOGGING_MODULES_MY_APP__DB`. It is turned back into a target by lower-casing it and
+//! replacing each `__` with `::`.
+use crate::{Config, ParseError, Value};
+use std::path::PathBuf;
+
+/// The levels accepted for the section and for every module.
+pub const LEVELS: [&str; 6] = ["trace", "debug", "info", "warn", "error", "off"];
+
+/// How log lines are formatted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The default `tracing_subscriber` format.
+    #[default]
+    Full,
+    /// A shorter single line format.
+    Compact,
+    /// A multi-line format meant for humans.
+    Pretty,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Where log lines are written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LogTarget {
+    #[default]
+    Stdout,
+    Stderr,
+    /// Appended to the file at this path, set with `file:`.
+    File(PathBuf),
+}
+
+/// The settings of a `logging:` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggingConfig {
+    /// The level for targets without a level of their own.
+    pub level: String,
+    /// Per-target levels in the order they were loaded.
+    pub modules: Vec<(String, String)>,
+    pub format: LogFormat,
+    pub target: LogTarget,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".to_string(),
+            modules: Vec::new(),
+            format: LogFormat::default(),
+            target: LogTarget::default(),
+        }
+    }
+}
+
+fn logging_error(message: String) -> ParseError {
+    ParseError::new("config::logging", message)
+}
+
+/// Returns the string value of `key`, if set.
+fn string_of<'a>(config: &'a Config, key: &str) -> Result<Option<&'a str>, ParseError> {
+    match config.get(key) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.trim())),
+        Some(val) => Err(logging_error(format!(
+            "{} must be a string, found {:?}.",
+            key, val
+        ))),
+    }
+}
+
+/// Lower-cases `level`, rejecting anything `EnvFilter` wouldn't accept.
+fn level_of(key: &str, level: &str) -> Result<String, ParseError> {
+    let level = level.to_lowercase();
+    if LEVELS.contains(&level.as_str()) {
+        Ok(level)
+    } else {
+        Err(logging_error(format!(
+            "{}: \"{}\" is not one of {}.",
+            key,
+            level,
+            LEVELS.join(", ")
+        )))
+    }
+}
+
+/// Turns a flattened module name such as `MY_APP__DB` back into `my_app::db`.
+fn module_target(flattened: &str) -> String {
+    flattened.to_lowercase().replace("__", "::")
+}
+
+impl LoggingConfig {
+    /// Reads the section flattened under `prefix`, e.g. `LOGGING`. Missing keys keep their
+    /// defaults: level `info`, the full format, and standard output.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::logging::LoggingConfig;
+    /// use yaml_config::ConfigBuilder;
+    /// let config = ConfigBuilder::new().build().unwrap();
+    /// let logging = LoggingConfig::from_config(&config, "LOGGING").unwrap();
+    /// assert_eq!(logging.directives(), "info");
+    /// ```
+    pub fn from_config(config: &Config, prefix: &str) -> Result<Self, ParseError> {
+        let mut logging = LoggingConfig::default();
+
+        let key = format!("{}_LEVEL", prefix);
+        if let Some(level) = string_of(config, &key)? {
+            logging.level = level_of(&key, level)?;
+        }
+
+        let key = format!("{}_FORMAT", prefix);
+        if let Some(format) = string_of(config, &key)? {
+            logging.format = match format.to_lowercase().as_str() {
+                "full" => LogFormat::Full,
+                "compact" => LogFormat::Compact,
+                "pretty" => LogFormat::Pretty,
+                "json" => LogFormat::Json,
+                _ => {
+                    return Err(logging_error(format!(
+                        "{}: \"{}\" is not one of full, compact, pretty, json.",
+                        key, format
+                    )))
+                }
+            };
+        }
+
+        let key = format!("{}_TARGET", prefix);
+        let file = string_of(config, &format!("{}_FILE", prefix))?;
+        logging.target = match (string_of(config, &key)?, file) {
+            (None | Some("file"), Some(file)) => LogTarget::File(PathBuf::from(file)),
+            (Some("file"), None) => {
+                return Err(logging_error(format!(
+                    "{}: a file target needs {}_FILE.",
+                    key, prefix
+                )))
+            }
+            (None | Some("stdout"), None) => LogTarget::Stdout,
+            (Some("stderr"), None) => LogTarget::Stderr,
+            (Some(target), _) => {
+                return Err(logging_error(format!(
+                    "{}: \"{}\" is not one of stdout, stderr, file.",
+                    key, target
+                )))
+            }
+        };
+
+        let modules = format!("{}_MODULES_", prefix);
+        for key in config.values().keys() {
+            if let Some(module) = key.strip_prefix(&modules) {
+                let level = string_of(config, key)?.unwrap_or_default();
+                logging
+                    .modules
+                    .push((module_target(module), level_of(key, level)?));
+            }
+        }
+
+        Ok(logging)
+    }
+
+    /// Renders the levels as an `EnvFilter` directive string, e.g. `info,hyper=warn`.
+    pub fn directives(&self) -> String {
+        let mut out = self.level.clone();
+        for (module, level) in &self.modules {
+            out.push(',');
+            out.push_str(module);
+            out.push('=');
+            out.push_str(level);
+        }
+        out
+    }
+
+    /// Builds the `EnvFilter` described by the section.
+    #[cfg(feature = "logging")]
+    pub fn env_filter(&self) -> Result<tracing_subscriber::EnvFilter, ParseError> {
+        tracing_subscriber::EnvFilter::try_new(self.directives())
+            .map_err(|e| logging_error(format!("Invalid directives: {}", e)))
+    }
+
+    /// Installs a `tracing_subscriber` formatting and filtering events as the section
+    /// describes. Fails when the file target can't be opened or a global subscriber has
+    /// already been installed.
+    #[cfg(feature = "logging")]
+    pub fn init(&self) -> Result<(), ParseError> {
+        use std::fs::OpenOptions;
+        use std::sync::Mutex;
+        use tracing_subscriber::fmt::writer::BoxMakeWriter;
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::util::SubscriberInitExt;
+        use tracing_subscriber::{fmt, Layer, Registry};
+
+        let writer = match &self.target {
+            LogTarget::Stdout => BoxMakeWriter::new(std::io::stdout),
+            LogTarget::Stderr => BoxMakeWriter::new(std::io::stderr),
+            LogTarget::File(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| {
+                        logging_error(format!("Could not open {}: {}", path.display(), e))
+                    })?;
+                BoxMakeWriter::new(Mutex::new(file))
+            }
+        };
+        let ansi = matches!(self.target, LogTarget::Stdout | LogTarget::Stderr);
+        let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+        let layer: Box<dyn Layer<Registry> + Send + Sync> = match self.format {
+            LogFormat::Full => layer.boxed(),
+            LogFormat::Compact => layer.compact().boxed(),
+            LogFormat::Pretty => layer.pretty().boxed(),
+            LogFormat::Json => layer.json().boxed(),
+        };
+
+        tracing_subscriber::registry()
+            .with(layer)
+            .with(self.env_filter()?)
+            .try_init()
+            .map_err(|e| logging_error(format!("Could not install the subscriber: {}", e)))
+    }
+}
+
+impl Config {
+    /// Reads the `logging:` section. See the `logging` module.
+    pub fn logging(&self) -> Result<LoggingConfig, ParseError> {
+        LoggingConfig::from_config(self, "LOGGING")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::logging::{LogFormat, LogTarget, LoggingConfig};
+    use crate::{ConfigBuilder, Value};
+    use std::path::PathBuf;
+
+    #[test]
+    fn sections_become_directives() {
+        let config = ConfigBuilder::new().build().unwrap().overridden([
+            ("LOGGING_LEVEL", "WARN"),
+            ("LOGGING_FORMAT", "json"),
+            ("LOGGING_TARGET", "stderr"),
+            ("LOGGING_MODULES_MY_APP__DB", "debug"),
+            ("LOGGING_MODULES_HYPER", "error"),
+        ]);
+
+        let logging = config.logging().unwrap();
+
+        assert_eq!(logging.directives(), "warn,my_app::db=debug,hyper=error");
+        assert_eq!(logging.format, LogFormat::Json);
+        assert_eq!(logging.target, LogTarget::Stderr);
+    }
+
+    #[test]
+    fn file_targets_need_a_path() {
+        let config = ConfigBuilder::new()
+            .build()
+            .unwrap()
+            .overridden([("LOGGING_FILE", "/var/log/app.log")]);
+        assert_eq!(
+            config.logging().unwrap().target,
+            LogTarget::File(PathBuf::from("/var/log/app.log"))
+        );
+
+        let config = ConfigBuilder::new()
+            .build()
+            .unwrap()
+            .overridden([("LOGGING_TARGET", "file")]);
+        assert!(config.logging().is_err());
+    }
+
+    #[test]
+    fn invalid_levels_name_the_key() {
+        let config = ConfigBuilder::new()
+            .build()
+            .unwrap()
+            .overridden([("LOGGING_MODULES_HYPER", "loud")]);
+        let err = config.logging().unwrap_err();
+        assert!(err.message().starts_with("LOGGING_MODULES_HYPER: \"loud\""));
+
+        let config = ConfigBuilder::new()
+            .build()
+            .unwrap()
+            .overridden([("LOGGING_LEVEL", Value::I64(3))]);
+        assert!(LoggingConfig::from_config(&config, "LOGGING").is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! A catalog of the messages shown to users, for localizing or re-branding them.
+//!
+//! Error messages, the missing-keys report, schema and policy violations, lint findings, the
+//! replies of setup wizards, and the output of the `yaml-config` tool are each built from a
+//! template identified by a stable id, such as
+//! `error.missing_env` for `"Error parsing OS environment variable for {key}"`. A catalog
+//! installed with `set_catalog` is asked for every template first, so an application can
+//! translate the messages or word them in its own terms:
+//!
+//! ```rust
+//! use yaml_config::messages::{self, Messages};
+//! use yaml_config::load_from_str;
+//! messages::set_catalog(
+//!     Messages::new().set("error.missing_env", "Die Umgebungsvariable {key} fehlt."),
+//! );
+//! let error = load_from_str("db:\n  password: null\n", None).unwrap_err();
+//! assert_eq!(error.message(), "Die Umgebungsvariable DB_PASSWORD fehlt.");
+//! # messages::clear_catalog();
+//! ```
+//!
+//! Templates refer to their arguments as `{name}`. Write `{{` and `}}` for literal braces.
+//! `ids` lists every id with its English template. Other messages of `ParseError::Other`, the
+//! problems reported by validators and policies, and the messages of the errors
+//! `ParseError::Io`, `ParseError::Scan`, and `ParseError::Env` wrap, are not in the catalog.
+use fxhash::FxHashMap;
+use std::sync::{Arc, RwLock};
+
+/// Every message id with its English template.
+const TEMPLATES: [(&str, &str); 32] = [
+    ("error.at", " at {location}"),
+    (
+        "error.missing_env",
+        "Error parsing OS environment variable for {key}",
+    ),
+    (
+        "error.env_not_unicode",
+        "Environment variable {key} is not valid unicode.",
+    ),
+    (
+        "error.env_unavailable",
+        "The process environment can't be read. Set `LoadOptions::on_env_unavailable` to \
+         `OnEnvUnavailable::YamlOnly` to resolve from the YAML alone.",
+    ),
+    (
+        "error.invalid_env",
+        "Environment variable {key} = \"{value}\" is not {expected} like its YAML value{at}.",
+    ),
+    (
+        "error.type_mismatch",
+        "{key}: expected {expected}, found {found}.",
+    ),
+    ("error.not_set", "{key} is not set."),
+    (
+        "error.depth_exceeded",
+        "{key} is nested deeper than {max_depth} levels{at}.",
+    ),
+    (
+        "error.unsupported_structure",
+        "Failed to convert type for {key}{at}",
+    ),
+    ("error.empty_document", "YAML document is empty."),
+    ("report.missing_keys", "{count} required keys are missing."),
+    ("report.set_env", "set {var}"),
+    ("report.example", "e.g. {example}"),
+    ("schema.required", "{key} is required."),
+    ("schema.invalid", "{key}: {problem}"),
+    ("policy.violated", "Policies violated: {violations}."),
+    ("policy.violation", "{name} ({problem})"),
+    ("reload.rejected", "Reload rejected: {problems}"),
+    (
+        "reload.policy_violated",
+        "Policy {name} is violated: {problem}",
+    ),
+    ("lint.info", "info"),
+    ("lint.warning", "warning"),
+    ("lint.error", "error"),
+    (
+        "lint.duplicate_key",
+        "{key} is defined more than once; only the last value is kept.",
+    ),
+    (
+        "lint.plaintext_secret",
+        "{key} holds a literal secret; leave it null to read it from the environment or use a \
+         tag such as !keyring.",
+    ),
+    ("lint.unused_anchor", "The anchor is never referenced."),
+    (
+        "lint.shadowed_value",
+        "{key} overrides the value set by {owner}.",
+    ),
+    ("wizard.not_integer", "\"{reply}\" is not an integer."),
+    ("wizard.not_float", "\"{reply}\" is not a float."),
+    ("wizard.not_boolean", "\"{reply}\" is not a boolean."),
+    ("wizard.secret", "{prompt}: set {var} in the environment."),
+    (
+        "wizard.input_ended",
+        "The input ended before {key} was answered.",
+    ),
+    ("cli.usage", "usage: yaml-config lint FILE..."),
+];
+
+/// Provides the templates of messages.
+pub trait Catalog: Send + Sync {
+    /// Returns the template for `id`, or `None` to keep the English one.
+    fn template(&self, id: &str) -> Option<String>;
+}
+
+impl<F> Catalog for F
+where
+    F: Fn(&str) -> Option<String> + Send + Sync,
+{
+    fn template(&self, id: &str) -> Option<String> {
+        self(id)
+    }
+}
+
+/// A catalog holding a fixed set of templates.
+#[derive(Debug, Clone, Default)]
+pub struct Messages {
+    templates: FxHashMap<String, String>,
+}
+
+impl Messages {
+    /// Creates an empty catalog, which keeps every English template.
+    pub fn new() -> Self {
+        Messages::default()
+    }
+
+    /// Replaces the template of `id`.
+    pub fn set<I: Into<String>, T: Into<String>>(mut self, id: I, template: T) -> Self {
+        self.templates.insert(id.into(), template.into());
+        self
+    }
+}
+
+impl Catalog for Messages {
+    fn template(&self, id: &str) -> Option<String> {
+        self.templates.get(id).cloned()
+    }
+}
+
+static CATALOG: RwLock<Option<Arc<dyn Catalog>>> = RwLock::new(None);
+
+/// Installs `catalog` for every message rendered from now on, in every thread.
+pub fn set_catalog<C: Catalog + 'static>(catalog: C) {
+    *CATALOG.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(catalog));
+}
+
+/// Removes the installed catalog, restoring the English templates.
+pub fn clear_catalog() {
+    *CATALOG.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Lists every message id with its English template.
+pub fn ids() -> impl Iterator<Item = (&'static str, &'static str)> {
+    TEMPLATES.iter().copied()
+}
+
+/// Fills the `{name}` placeholders of `template` from `args`. Unknown placeholders are kept.
+fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let end = match tail.find('}') {
+            Some(end) if tail.starts_with('{') => end,
+            _ => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+                continue;
+            }
+        };
+        let name = &tail[1..end];
+        match args.iter().find(|(arg, _)| *arg == name) {
+            Some((_, val)) => out.push_str(val),
+            None => out.push_str(&tail[..=end]),
+        }
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Renders the message `id` with `args`, from the installed catalog or else in English.
+pub fn text(id: &str, args: &[(&str, &str)]) -> String {
+    let catalog = CATALOG.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let template = catalog.and_then(|catalog| catalog.template(id));
+    match template {
+        Some(template) => fill(&template, args),
+        None => {
+            let english = TEMPLATES
+                .iter()
+                .find(|(known, _)| *known == id)
+                .map_or(id, |(_, template)| template);
+            fill(english, args)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::messages::{fill, ids, text};
+
+    #[test]
+    fn templates_are_filled() {
+        assert_eq!(
+            fill(
+                "{key} = {{{value}}} {unknown} }",
+                &[("key", "A"), ("value", "1")]
+            ),
+            "A = {1} {unknown} }"
+        );
+        assert_eq!(
+            text("report.set_env", &[("var", "APP_PORT")]),
+            "set APP_PORT"
+        );
+        assert!(ids().all(|(id, _)| id.contains('.')));
+    }
+}
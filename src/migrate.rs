@@ -0,0 +1,226 @@
+//! Configuration versioning and migrations.
+//!
+//! A configuration file may declare its format version with a top-level `config_version` key.
+//! Migrations registered in `LoadOptions::migrations` upgrade older files one version at a time
+//! right after they are loaded, so an application can evolve its keys while still accepting
+//! files written for earlier releases.
+//!
+//! Migrations operate on the flattened values. Environment lookups for `null` values happen
+//! before migrating and therefore use the keys as written in the file.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::migrate::rename;
+//! use yaml_config::LoadOptions;
+//! let mut options = LoadOptions::default();
+//! // Version 1 called the key `DB_URL`; version 2 calls it `DATABASE_URL`.
+//! options.migrations.register(1, |values| {
+//!     rename(values, "DB_URL", "DATABASE_URL");
+//!     Ok(())
+//! });
+//! ```
+use crate::{ParseError, Value};
+use fxhash::{FxBuildHasher, FxHashMap};
+use indexmap::IndexMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// The flattened key holding the version of a configuration file.
+pub const VERSION_KEY: &str = "CONFIG_VERSION";
+
+/// A migration upgrading flattened values by one version.
+pub type Migration = Arc<
+    dyn Fn(&mut IndexMap<String, Value, FxBuildHasher>) -> Result<(), ParseError> + Send + Sync,
+>;
+
+/// The registered migrations, keyed by the version they upgrade from.
+#[derive(Clone, Default)]
+pub struct Migrations {
+    steps: FxHashMap<i64, Migration>,
+}
+
+impl fmt::Debug for Migrations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut versions: Vec<&i64> = self.steps.keys().collect();
+        versions.sort();
+        f.debug_struct("Migrations")
+            .field("versions", &versions)
+            .finish()
+    }
+}
+
+fn migrate_error(message: String) -> ParseError {
+    ParseError::new("config::migrate", message)
+}
+
+/// Moves the value of `from` to `to`, keeping its position. Returns `false` if `from` is not
+/// present. An existing value for `to` is replaced.
+pub fn rename(values: &mut IndexMap<String, Value, FxBuildHasher>, from: &str, to: &str) -> bool {
+    let index = match values.get_index_of(from) {
+        Some(index) => index,
+        None => return false,
+    };
+    if from == to {
+        return true;
+    }
+    // Shifting rather than swapping keeps every other key in place.
+    values.shift_remove(to);
+    // Removing `to` may have shifted `from` down by one.
+    let index = values.get_index_of(from).unwrap_or(index);
+    let (_, val) = values.shift_remove_index(index).unwrap();
+    values.insert(to.to_string(), val);
+    let last = values.len() - 1;
+    values.move_index(last, index);
+    true
+}
+
+impl Migrations {
+    /// Registers `migration`, upgrading files from version `from` to version `from + 1`.
+    pub fn register<F>(&mut self, from: i64, migration: F)
+    where
+        F: Fn(&mut IndexMap<String, Value, FxBuildHasher>) -> Result<(), ParseError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.steps.insert(from, Arc::new(migration));
+    }
+
+    /// The version files are migrated to: one past the newest registered migration.
+    pub fn current_version(&self) -> Option<i64> {
+        self.steps.keys().max().map(|v| v + 1)
+    }
+
+    /// Upgrades `values` to the current version. Files without a `config_version` are treated
+    /// as version 1. Nothing happens when no migrations are registered.
+    pub(crate) fn apply(
+        &self,
+        values: &mut IndexMap<String, Value, FxBuildHasher>,
+    ) -> Result<(), ParseError> {
+        let current = match self.current_version() {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        let mut version = match values.get(VERSION_KEY) {
+            None => 1,
+            Some(Value::I64(v)) => *v,
+            Some(Value::I32(v)) => i64::from(*v),
+            Some(v) => {
+                return Err(migrate_error(format!(
+                    "{} must be an integer, found {:?}.",
+                    VERSION_KEY, v
+                )))
+            }
+        };
+        if version > current {
+            return Err(migrate_error(format!(
+                "Configuration version {} is newer than the supported version {}.",
+                version, current
+            )));
+        }
+
+        while version < current {
+            let step = self.steps.get(&version).ok_or_else(|| {
+                migrate_error(format!(
+                    "No migration is registered from version {}.",
+                    version
+                ))
+            })?;
+            step(values)?;
+            version += 1;
+        }
+        values.insert(VERSION_KEY.to_string(), Value::I64(current));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::migrate::{rename, Migrations};
+    use crate::Value;
+    use fxhash::FxBuildHasher;
+    use indexmap::IndexMap;
+
+    fn migrations() -> Migrations {
+        let mut migrations = Migrations::default();
+        migrations.register(1, |values| {
+            rename(values, "DB_URL", "DATABASE_URL");
+            Ok(())
+        });
+        migrations.register(2, |values| {
+            if let Some(Value::String(hostport)) = values.shift_remove("SERVER") {
+                let (host, port) = hostport.split_once(':').unwrap();
+                values.insert("SERVER_HOST".to_string(), Value::String(host.to_string()));
+                values.insert("SERVER_PORT".to_string(), Value::I64(port.parse().unwrap()));
+            }
+            Ok(())
+        });
+        migrations
+    }
+
+    #[test]
+    fn old_files_are_migrated_step_by_step() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert("DB_URL".to_string(), Value::String("pg://db".to_string()));
+        values.insert(
+            "SERVER".to_string(),
+            Value::String("0.0.0.0:80".to_string()),
+        );
+
+        migrations().apply(&mut values).unwrap();
+
+        let keys: Vec<&String> = values.keys().collect();
+        assert_eq!(
+            keys,
+            vec![
+                "DATABASE_URL",
+                "SERVER_HOST",
+                "SERVER_PORT",
+                "CONFIG_VERSION"
+            ]
+        );
+        assert_eq!(values["SERVER_PORT"], Value::I64(80));
+        assert_eq!(values["CONFIG_VERSION"], Value::I64(3));
+    }
+
+    #[test]
+    fn versions_are_checked() {
+        let mut current = IndexMap::with_hasher(FxBuildHasher::default());
+        current.insert("CONFIG_VERSION".to_string(), Value::I64(3));
+        current.insert("DB_URL".to_string(), Value::String("kept".to_string()));
+        migrations().apply(&mut current).unwrap();
+        assert!(current.contains_key("DB_URL"));
+
+        let mut newer = IndexMap::with_hasher(FxBuildHasher::default());
+        newer.insert("CONFIG_VERSION".to_string(), Value::I64(4));
+        assert!(migrations().apply(&mut newer).is_err());
+
+        let mut invalid = IndexMap::with_hasher(FxBuildHasher::default());
+        invalid.insert(
+            "CONFIG_VERSION".to_string(),
+            Value::String("two".to_string()),
+        );
+        assert!(migrations().apply(&mut invalid).is_err());
+    }
+
+    #[test]
+    fn renaming_keeps_the_order_of_keys() {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        for key in ["A", "B", "C", "D"] {
+            values.insert(key.to_string(), Value::from(key));
+        }
+
+        assert!(rename(&mut values, "C", "A"));
+        let keys: Vec<&str> = values.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["B", "A", "D"]);
+        assert_eq!(values["A"], Value::from("C"));
+
+        assert!(rename(&mut values, "D", "D"));
+        assert!(rename(&mut values, "B", "E"));
+        let keys: Vec<&str> = values.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["E", "A", "D"]);
+        assert!(!rename(&mut values, "Z", "A"));
+    }
+}
@@ -0,0 +1,109 @@
+//! The report of every required key missing from a configuration.
+//!
+//! Loading stops at the first `null` value no environment variable provides, which makes
+//! bringing up a new deployment a matter of fixing one key per attempt. When more than one key
+//! is missing the loader instead fails with `ParseError::MissingKeys`, whose report lists all of
+//! them at once, grouped by the top-level section they belong to:
+//!
+//! ```text
+//! 3 required keys are missing.
+//!
+//! db
+//!   DB_HOST at db.host: set DB_HOST, e.g. "db.internal"
+//!   DB_PASSWORD at db.password: set DB_PASSWORD
+//! server
+//!   SERVER_PORT: set APP_SERVER_PORT, e.g. 8080
+//! ```
+//!
+//! `ConfigBuilder::build` also lists keys the schema declares `required` that no layer
+//! provides, and shows the example value declared with `KeySpec::example`.
+use crate::json::write_value;
+use crate::messages;
+use crate::Value;
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::fmt;
+
+/// A required key without a value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingKey {
+    /// The flattened key.
+    pub key: String,
+    /// The YAML path of the key, if a document mentions it.
+    pub path: Option<Vec<String>>,
+    /// The environment variable that could provide the value, if any.
+    pub env_var: Option<String>,
+    /// An example value, from the schema.
+    pub example: Option<Value>,
+}
+
+impl MissingKey {
+    /// The top-level section the key belongs to: the first segment of its YAML path, or else
+    /// the first word of the key.
+    pub fn section(&self) -> String {
+        match self.path.as_ref().and_then(|path| path.first()) {
+            Some(section) => section.clone(),
+            None => self
+                .key
+                .split('_')
+                .next()
+                .unwrap_or_default()
+                .to_lowercase(),
+        }
+    }
+}
+
+/// Every required key missing from a configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingReport {
+    keys: Vec<MissingKey>,
+}
+
+impl MissingReport {
+    pub(crate) fn new(keys: Vec<MissingKey>) -> Self {
+        MissingReport { keys }
+    }
+
+    /// The missing keys, in the order they were found.
+    pub fn keys(&self) -> &[MissingKey] {
+        &self.keys
+    }
+}
+
+impl fmt::Display for MissingReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut sections: IndexMap<String, Vec<&MissingKey>, FxBuildHasher> = IndexMap::default();
+        for key in &self.keys {
+            sections.entry(key.section()).or_default().push(key);
+        }
+
+        let count = self.keys.len().to_string();
+        writeln!(
+            f,
+            "{}",
+            messages::text("report.missing_keys", &[("count", &count)])
+        )?;
+        for (section, keys) in sections {
+            write!(f, "\n{}", section)?;
+            for key in keys {
+                write!(f, "\n  {}", key.key)?;
+                if let Some(path) = &key.path {
+                    write!(f, " at {}", path.join("."))?;
+                }
+                let mut hints = Vec::new();
+                if let Some(env_var) = &key.env_var {
+                    hints.push(messages::text("report.set_env", &[("var", env_var)]));
+                }
+                if let Some(example) = &key.example {
+                    let mut text = String::new();
+                    write_value(example, &mut text);
+                    hints.push(messages::text("report.example", &[("example", &text)]));
+                }
+                if !hints.is_empty() {
+                    write!(f, ": {}", hints.join(", "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
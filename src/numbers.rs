@@ -0,0 +1,189 @@
+//! Parsing of numbers read from the environment.
+//!
+//! Environment values are parsed with Rust's number syntax, which has no thousands
+//! separators and uses `.` as the decimal point. Values copied from spreadsheets or typed by
+//! hand often don't follow it: `1,000` would silently become a string and `1.000,5` can't be
+//! parsed at all. With the default `NumberFormat::Strict` such values are rejected with an
+//! error instead. `NumberFormat::Localized` accepts them by declaring the separators in use.
+//!
+//! Only values that are well-formed numbers in a localized format, with digits grouped in
+//! threes between thousands separators and at most one decimal mark, are treated as numbers.
+//! Other values, such as `10.0.0.1` or `1.2.3`, are kept as written. A `null` key has no type to
+//! check against, so its value is also kept as written when it could be a comma-separated list,
+//! such as `80,443`.
+//!
+//! Note that `1.000` is a valid number in Rust syntax and is read as `1.0` in strict mode.
+use crate::ParseError;
+
+/// How numbers in environment values are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// Rust syntax only. Values that look like numbers written with thousands separators or a
+    /// decimal comma, e.g. `1,000`, `1 000`, `0,5`, or `1.000,5`, are an error.
+    #[default]
+    Strict,
+    /// Numbers may use the given thousands separator and decimal mark, e.g. `.` and `,` for
+    /// `1.000,5`. Rust syntax is still accepted when it doesn't conflict.
+    Localized {
+        /// The thousands separator, e.g. `,`, `.`, `'`, or ` `.
+        thousands: char,
+        /// The decimal mark, e.g. `.` or `,`.
+        decimal: char,
+    },
+}
+
+/// The separators people commonly write numbers with.
+const SEPARATORS: [char; 5] = [',', '.', '\'', ' ', '\u{a0}'];
+
+/// Parses `s` as a number written with the given separators, returning it in Rust syntax, or
+/// `None` if it isn't one. Digits before the decimal mark must be grouped in threes after the
+/// first group, e.g. `1.000.000,5` but not `10.0.0`.
+fn parse_localized(s: &str, thousands: char, decimal: char) -> Option<String> {
+    let (sign, digits) = match s.strip_prefix(['-', '+']) {
+        Some(digits) => (&s[..1], digits),
+        None => ("", s),
+    };
+    let (int, frac) = match digits.split_once(decimal) {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (digits, None),
+    };
+    let is_digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+
+    let mut groups = int.split(thousands);
+    let first = groups.next()?;
+    if !is_digits(first) {
+        return None;
+    }
+    let mut rewritten = format!("{}{}", sign, first);
+    let mut grouped = false;
+    for group in groups {
+        if first.len() > 3 || group.len() != 3 || !is_digits(group) {
+            return None;
+        }
+        rewritten.push_str(group);
+        grouped = true;
+    }
+    if let Some(frac) = frac {
+        if !is_digits(frac) {
+            return None;
+        }
+        rewritten.push('.');
+        rewritten.push_str(frac);
+    } else if !grouped {
+        return None;
+    }
+    Some(rewritten)
+}
+
+/// Returns `true` if `s` is a well-formed number with thousands separators or a decimal comma in
+/// some common convention, and is not a number in Rust syntax.
+fn looks_localized(s: &str) -> bool {
+    s.parse::<f64>().is_err()
+        && SEPARATORS.iter().any(|&thousands| {
+            ['.', ','].into_iter().any(|decimal| {
+                thousands != decimal && parse_localized(s, thousands, decimal).is_some()
+            })
+        })
+}
+
+/// Returns `true` if `s` is digits separated by commas, e.g. `80,443`, which may be a list.
+fn is_list_like(s: &str) -> bool {
+    s.strip_prefix(['-', '+'])
+        .unwrap_or(s)
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == ',')
+}
+
+/// Rewrites the environment value `raw` of `key` into Rust number syntax according to
+/// `format`. Values that aren't numbers are returned unchanged.
+pub(crate) fn normalize(
+    key: &str,
+    raw: String,
+    format: NumberFormat,
+) -> Result<String, ParseError> {
+    match format {
+        NumberFormat::Strict if looks_localized(&raw) => Err(ParseError::new(
+            "config::numbers",
+            format!(
+                "Environment variable {} = \"{}\" looks like a number with thousands separators \
+                 or a decimal comma. Write it as a plain number or configure a NumberFormat.",
+                key, raw
+            ),
+        )),
+        NumberFormat::Strict => Ok(raw),
+        NumberFormat::Localized { thousands, decimal } => {
+            Ok(parse_localized(&raw, thousands, decimal).unwrap_or(raw))
+        }
+    }
+}
+
+/// Like `normalize`, for the value of a `null` key, whose type is inferred from the value
+/// itself. Values that may be comma-separated lists are returned unchanged.
+pub(crate) fn normalize_untyped(
+    key: &str,
+    raw: String,
+    format: NumberFormat,
+) -> Result<String, ParseError> {
+    if is_list_like(&raw) {
+        return Ok(raw);
+    }
+    normalize(key, raw, format)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::numbers::{normalize, normalize_untyped, NumberFormat};
+
+    #[test]
+    fn strict_rejects_localized_numbers() {
+        let strict = NumberFormat::Strict;
+
+        for raw in ["1,000", "1.000,5", "1 000", "0,5", "-1'000'000"] {
+            assert!(normalize("N", raw.to_string(), strict).is_err(), "{}", raw);
+        }
+        for raw in [
+            "1000",
+            "1.5",
+            "-3",
+            "1e3",
+            "a,b",
+            "1,",
+            "localhost",
+            "10.0.0.1",
+            "1.2.3",
+        ] {
+            assert_eq!(normalize("N", raw.to_string(), strict).unwrap(), raw);
+        }
+        assert!(normalize("N", "80,443".to_string(), strict).is_err());
+        assert_eq!(
+            normalize_untyped("N", "80,443".to_string(), strict).unwrap(),
+            "80,443"
+        );
+    }
+
+    #[test]
+    fn localized_numbers_are_rewritten() {
+        let german = NumberFormat::Localized {
+            thousands: '.',
+            decimal: ',',
+        };
+        let english = NumberFormat::Localized {
+            thousands: ',',
+            decimal: '.',
+        };
+
+        assert_eq!(
+            normalize("N", "1.000,5".to_string(), german).unwrap(),
+            "1000.5"
+        );
+        assert_eq!(normalize("N", "1.000".to_string(), german).unwrap(), "1000");
+        assert_eq!(
+            normalize("N", "-1,000.5".to_string(), english).unwrap(),
+            "-1000.5"
+        );
+        assert_eq!(normalize("N", "a,b".to_string(), english).unwrap(), "a,b");
+        for raw in ["10.0.0.1", "1.2.3", "1.00,5"] {
+            assert_eq!(normalize("N", raw.to_string(), german).unwrap(), raw);
+        }
+    }
+}
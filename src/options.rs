@@ -0,0 +1,305 @@
+//! Options controlling how a configuration is loaded.
+//!
+//! `LoadOptions` collects every knob accepted by `load_with`. It implements `Default` so callers
+//! only need to spell out the options they care about.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::{LoadOptions, OnEmpty, Preference};
+//! let options = LoadOptions {
+//!     preference: Preference::PreferEnv,
+//!     on_empty: OnEmpty::EmptyConfig,
+//!     ..Default::default()
+//! };
+//! ```
+use crate::format::Format;
+pub use crate::loader::MAX_DEPTH;
+use crate::migrate::Migrations;
+use crate::numbers::NumberFormat;
+use crate::overlay::Overlays;
+use crate::paths::SymlinkPolicy;
+use crate::resolvers::Resolvers;
+use crate::{ParseError, Preference};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::panic;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Defines the behavior of the loader when the YAML document is empty or only contains comments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnEmpty {
+    /// Return a `ParseError` describing the empty document.
+    #[default]
+    Error,
+    /// Return a configuration with no keys.
+    EmptyConfig,
+    /// Build the configuration from the process environment alone. Every environment variable
+    /// becomes a key and its value is typed the same way as a `null` YAML value would be.
+    EnvOnly,
+}
+
+/// Defines the behavior of the loader when a YAML value is `null` and no environment variable
+/// provides it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnMissing {
+    /// Return a `ParseError` naming the variable.
+    #[default]
+    Error,
+    /// Keep the key with `Value::Null`.
+    Null,
+    /// Leave the key out.
+    Skip,
+}
+
+/// Defines the behavior of the loader when the process environment can't be read, such as in a
+/// WebAssembly runtime or a sandbox that doesn't provide one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnEnvUnavailable {
+    /// Return a `ParseError` saying the environment can't be read.
+    #[default]
+    Error,
+    /// Resolve from the YAML alone, as if the environment were empty, and record a warning.
+    /// `null` values are then handled as `LoadOptions::allow_missing` says.
+    YamlOnly,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Makes the process environment unavailable to loads on the current thread.
+    static ENV_UNAVAILABLE: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Returns `true` if the process environment can be read. Platforms without one either have
+/// none to read or panic when it is read, which is only caught when panics unwind.
+fn process_env_available() -> bool {
+    #[cfg(test)]
+    if ENV_UNAVAILABLE.with(|unavailable| unavailable.get()) {
+        return false;
+    }
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        !cfg!(all(target_family = "wasm", target_os = "unknown"))
+            && panic::catch_unwind(|| env::vars_os().next()).is_ok()
+    })
+}
+
+/// Limits on how much configuration is read, complementing the loader's limits on nesting
+/// and alias expansion so that a hostile configuration can't amplify into unbounded I/O.
+///
+/// `load_with` applies `max_bytes` to the file it reads. `ConfigBuilder::build` applies both
+/// limits to every file layer combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadBudget {
+    /// The most files read. `None` means no limit.
+    pub max_files: Option<usize>,
+    /// The most bytes read. `None` means no limit.
+    pub max_bytes: Option<u64>,
+}
+
+/// Options accepted by `load_with`.
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    /// The preference for handling values when a key exists in both the YAML and the environment.
+    pub preference: Preference,
+    /// What to do when the YAML document is empty.
+    pub on_empty: OnEmpty,
+    /// The synthetic key used when the document's top level is a sequence rather than a map.
+    /// Defaults to `ROOT`.
+    pub root_key: String,
+    /// Resolvers for custom tags such as `!keyring`. See the `resolvers` module.
+    pub resolvers: Resolvers,
+    /// Migrations upgrading files with an older `config_version`. See the `migrate` module.
+    pub migrations: Migrations,
+    /// The host and region selecting `per_host` and `per_region` overlays. See the `overlay`
+    /// module.
+    pub overlays: Overlays,
+    /// How numbers in environment values are written. Defaults to `NumberFormat::Strict`,
+    /// which rejects thousands separators and decimal commas. See the `numbers` module.
+    pub number_format: NumberFormat,
+    /// Whether a configuration file that is a symbolic link is followed. Defaults to
+    /// `SymlinkPolicy::Follow`. See the `paths` module.
+    pub symlinks: SymlinkPolicy,
+    /// The directory every file read must lie in, with relative paths taken from it. Defaults
+    /// to `None`, allowing any path. See the `paths` module.
+    pub base_dir: Option<PathBuf>,
+    /// How many files and bytes may be read. Defaults to no limits.
+    pub read_budget: ReadBudget,
+    /// Flattens every sequence element into its own indexed key, e.g. `SERVERS_0_HOST`, instead
+    /// of loading the sequence as a single `Value::Array`. Each element can then be overridden
+    /// from the environment like any other key. Empty sequences produce no key. Defaults to
+    /// `false`.
+    pub flatten_arrays: bool,
+    /// What to do with a `null` value no environment variable provides. Defaults to
+    /// `OnMissing::Error`. The keys concerned are listed by `Config::missing_keys`.
+    pub allow_missing: OnMissing,
+    /// Expands `${KEY}` and `${ENV_VAR:-default}` references inside string values. Defaults to
+    /// `false`, keeping such strings as written. See the `interpolate` module.
+    pub interpolate: bool,
+    /// The environment to resolve values against instead of the process environment, e.g. a
+    /// dump of production's environment to replay its configuration locally. Defaults to `None`,
+    /// reading the process environment.
+    pub env: Option<HashMap<String, String>>,
+    /// The format of the document. Defaults to `None`, detecting it from the file extension, or
+    /// YAML for documents held in memory. See the `format` module.
+    pub format: Option<Format>,
+    /// What to do when the process environment can't be read. Defaults to
+    /// `OnEnvUnavailable::Error`. Has no effect when `env` is set.
+    pub on_env_unavailable: OnEnvUnavailable,
+    /// The deepest nesting of maps flattened into keys, counting the top level as one. A
+    /// document nested deeper fails with `ParseError::DepthExceeded`. Defaults to `MAX_DEPTH`,
+    /// which the loader enforces on every document regardless.
+    pub max_depth: usize,
+    /// Reads the value of a key from the file named by `{VAR}_FILE` when its variable `VAR`
+    /// isn't set, e.g. `DB_PASSWORD_FILE=/run/secrets/db_password`, following the Docker and
+    /// Kubernetes convention for secrets. The file is read under the same `base_dir`, symlink
+    /// policy, and read budget as documents. Defaults to `false`.
+    pub secret_files: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            preference: Preference::default(),
+            on_empty: OnEmpty::default(),
+            root_key: "ROOT".to_string(),
+            resolvers: Resolvers::default(),
+            migrations: Migrations::default(),
+            overlays: Overlays::default(),
+            number_format: NumberFormat::default(),
+            symlinks: SymlinkPolicy::default(),
+            base_dir: None,
+            read_budget: ReadBudget::default(),
+            flatten_arrays: false,
+            allow_missing: OnMissing::default(),
+            interpolate: false,
+            env: None,
+            format: None,
+            on_env_unavailable: OnEnvUnavailable::default(),
+            max_depth: MAX_DEPTH,
+            secret_files: false,
+        }
+    }
+}
+
+impl LoadOptions {
+    /// Fails if the process environment is needed but can't be read, unless
+    /// `on_env_unavailable` degrades to the YAML alone, in which case the warning to record is
+    /// returned.
+    pub(crate) fn check_env(&self) -> Result<Option<String>, ParseError> {
+        self.check_env_with(self.env.is_some() || process_env_available())
+    }
+
+    fn check_env_with(&self, available: bool) -> Result<Option<String>, ParseError> {
+        if available {
+            return Ok(None);
+        }
+        match self.on_env_unavailable {
+            OnEnvUnavailable::Error => Err(ParseError::EnvUnavailable),
+            OnEnvUnavailable::YamlOnly => {
+                let warning =
+                    "The process environment can't be read, so values were resolved from the \
+                     YAML alone.";
+                #[cfg(feature = "tracing")]
+                tracing::warn!(target: "yaml_config", "{}", warning);
+                Ok(Some(warning.to_string()))
+            }
+        }
+    }
+
+    /// Reads the variable `key` from `env`, or from the process environment if it isn't set.
+    pub(crate) fn var_os(&self, key: &str) -> Option<OsString> {
+        match &self.env {
+            Some(vars) => vars.get(key).map(OsString::from),
+            None if !process_env_available() => None,
+            None => env::var_os(key),
+        }
+    }
+
+    /// Returns every variable of `env`, or of the process environment if it isn't set, that is
+    /// valid unicode. Other variables can't be represented as keys.
+    pub(crate) fn vars(&self) -> Vec<(String, String)> {
+        match &self.env {
+            Some(vars) => vars
+                .iter()
+                .map(|(key, val)| (key.clone(), val.clone()))
+                .collect(),
+            None if !process_env_available() => Vec::new(),
+            None => env::vars_os()
+                .filter_map(|(key, val)| Some((key.into_string().ok()?, val.into_string().ok()?)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::options::{LoadOptions, OnEnvUnavailable, ENV_UNAVAILABLE};
+    use crate::{parse_str, ConfigBuilder, ErrorCode, ParseError, Preference, Value};
+    use envtestkit::lock::lock_test;
+    use envtestkit::set_env;
+    use std::ffi::OsString;
+
+    /// Marks the environment of the current thread as unavailable until dropped, so a failing
+    /// test doesn't leak the flag into later tests on the same thread.
+    struct EnvUnavailable;
+
+    impl EnvUnavailable {
+        fn set() -> Self {
+            ENV_UNAVAILABLE.with(|unavailable| unavailable.set(true));
+            EnvUnavailable
+        }
+    }
+
+    impl Drop for EnvUnavailable {
+        fn drop(&mut self) {
+            ENV_UNAVAILABLE.with(|unavailable| unavailable.set(false));
+        }
+    }
+
+    #[test]
+    fn an_unavailable_environment_errors_or_degrades() {
+        let options = LoadOptions::default();
+        assert_eq!(options.check_env_with(true).unwrap(), None);
+        assert!(matches!(
+            options.check_env_with(false),
+            Err(ParseError::EnvUnavailable)
+        ));
+
+        let options = LoadOptions {
+            on_env_unavailable: OnEnvUnavailable::YamlOnly,
+            ..Default::default()
+        };
+        assert!(options.check_env_with(false).unwrap().is_some());
+    }
+
+    #[test]
+    fn yaml_only_loads_ignore_the_environment() {
+        let _lock = lock_test();
+        let _port = set_env(OsString::from("OPTIONS_TEST_PORT"), "9090");
+        let doc = "options_test:\n  port: 8080\n";
+        let options = LoadOptions {
+            preference: Preference::PreferEnv,
+            on_env_unavailable: OnEnvUnavailable::YamlOnly,
+            ..Default::default()
+        };
+        let unavailable = EnvUnavailable::set();
+
+        let values = parse_str(doc, &options).unwrap();
+        assert_eq!(values["OPTIONS_TEST_PORT"], Value::I64(8080));
+        let config = ConfigBuilder::new()
+            .with_options(options)
+            .add_str(doc)
+            .add_env_prefix("OPTIONS_TEST_")
+            .build()
+            .unwrap();
+        assert_eq!(config.get("OPTIONS_TEST_PORT"), Some(&Value::I64(8080)));
+        assert!(config.warnings()[0].starts_with("The process environment can't be read"));
+        let error = ConfigBuilder::new().add_str(doc).build().unwrap_err();
+
+        drop(unavailable);
+        assert_eq!(error.code(), ErrorCode::EnvUnavailable);
+    }
+}
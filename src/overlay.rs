@@ -0,0 +1,200 @@
+//! Host and region overlays.
+//!
+//! A document may carry `per_host:` and `per_region:` maps keyed by hostname or region name.
+//! The subtree matching the current selector is deep-merged over the rest of the document
+//! before it is flattened, so a handful of machines can receive special values without a file
+//! of their own:
+//!
+//! ```yaml
+//! database:
+//!   pool_size: 10
+//! per_region:
+//!   eu-west-1:
+//!     database:
+//!       host: db.eu-west-1.internal
+//! per_host:
+//!   batch-01:
+//!     database:
+//!       pool_size: 50
+//! ```
+//!
+//! The region overlay is applied first and the host overlay last, so the most specific value
+//! wins. Subtrees for other hosts and regions are discarded.
+use crate::ParseError;
+use yaml_rust::yaml::Hash;
+use yaml_rust::Yaml;
+
+/// The key holding overlays selected by hostname.
+pub const PER_HOST_KEY: &str = "per_host";
+
+/// The key holding overlays selected by region.
+pub const PER_REGION_KEY: &str = "per_region";
+
+/// The selectors choosing which overlays apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlays {
+    /// The host to select. Defaults to the machine's hostname with the `hostname` feature and
+    /// to `None` without it. An overlay matches either the full hostname or its first label,
+    /// ignoring ASCII case.
+    pub host: Option<String>,
+    /// The region to select. No region overlay applies when `None`, the default.
+    pub region: Option<String>,
+}
+
+/// The machine's hostname, if it is valid Unicode.
+#[cfg(feature = "hostname")]
+fn hostname() -> Option<String> {
+    gethostname::gethostname().into_string().ok()
+}
+
+#[cfg(not(feature = "hostname"))]
+fn hostname() -> Option<String> {
+    None
+}
+
+impl Default for Overlays {
+    fn default() -> Self {
+        Overlays {
+            host: hostname(),
+            region: None,
+        }
+    }
+}
+
+/// Returns `true` if the overlay key `name` selects `host`.
+fn host_matches(name: &str, host: &str) -> bool {
+    let short = host.split('.').next().unwrap_or(host);
+    name.eq_ignore_ascii_case(host) || name.eq_ignore_ascii_case(short)
+}
+
+/// Merges `overlay` into `base`. Maps are merged key by key and anything else replaces the
+/// base value.
+fn merge(base: &mut Hash, overlay: &Hash) {
+    for (key, val) in overlay {
+        match (base.get_mut(key), val) {
+            (Some(Yaml::Hash(base_child)), Yaml::Hash(overlay_child)) => {
+                merge(base_child, overlay_child)
+            }
+            _ => {
+                base.insert(key.clone(), val.clone());
+            }
+        }
+    }
+}
+
+/// Finds the overlay in the `per_*` map `overlays` selected by `matches`.
+fn select<'a>(
+    key: &str,
+    overlays: &'a Yaml,
+    matches: impl Fn(&str) -> bool,
+) -> Result<Option<&'a Hash>, ParseError> {
+    let overlays = match overlays {
+        Yaml::Hash(h) => h,
+        _ => {
+            return Err(ParseError::new(
+                "config::overlay",
+                format!("{} must be a map.", key),
+            ))
+        }
+    };
+
+    for (name, overlay) in overlays {
+        if name.as_str().is_some_and(&matches) {
+            return match overlay {
+                Yaml::Hash(h) => Ok(Some(h)),
+                Yaml::Null => Ok(None),
+                _ => Err(ParseError::new(
+                    "config::overlay",
+                    format!("{}/{} must be a map.", key, name.as_str().unwrap()),
+                )),
+            };
+        }
+    }
+    Ok(None)
+}
+
+/// Applies the overlays in `root` selected by `selectors`. Returns `None` if the document has
+/// no overlays, so the common case doesn't copy the document.
+pub(crate) fn apply(root: &Hash, selectors: &Overlays) -> Result<Option<Hash>, ParseError> {
+    let host_key = Yaml::String(PER_HOST_KEY.to_string());
+    let region_key = Yaml::String(PER_REGION_KEY.to_string());
+    if !root.contains_key(&host_key) && !root.contains_key(&region_key) {
+        return Ok(None);
+    }
+
+    let mut merged = root.clone();
+    let per_host = merged.remove(&host_key);
+    let per_region = merged.remove(&region_key);
+
+    if let (Some(overlays), Some(region)) = (&per_region, &selectors.region) {
+        if let Some(overlay) = select(PER_REGION_KEY, overlays, |name| name == region)? {
+            merge(&mut merged, overlay);
+        }
+    }
+    if let (Some(overlays), Some(host)) = (&per_host, &selectors.host) {
+        if let Some(overlay) = select(PER_HOST_KEY, overlays, |name| host_matches(name, host))? {
+            merge(&mut merged, overlay);
+        }
+    }
+
+    Ok(Some(merged))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::overlay::{apply, host_matches, Overlays};
+    use yaml_rust::YamlLoader;
+
+    const DOC: &str = "
+db:
+  host: db
+  pool: 10
+per_region:
+  eu:
+    db:
+      host: db.eu
+per_host:
+  batch-01:
+    db:
+      pool: 50
+";
+
+    #[test]
+    fn hosts_match_full_or_short_names() {
+        assert!(host_matches("batch-01", "BATCH-01.example.com"));
+        assert!(host_matches("batch-01.example.com", "batch-01.example.com"));
+        assert!(!host_matches("batch-02", "batch-01.example.com"));
+    }
+
+    #[test]
+    fn selected_overlays_are_merged() {
+        let doc = &YamlLoader::load_from_str(DOC).unwrap()[0];
+        let selectors = Overlays {
+            host: Some("batch-01.example.com".to_string()),
+            region: Some("eu".to_string()),
+        };
+
+        let merged = apply(doc.as_hash().unwrap(), &selectors).unwrap().unwrap();
+        let db = &merged[&yaml_rust::Yaml::from_str("db")];
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(db["host"].as_str(), Some("db.eu"));
+        assert_eq!(db["pool"].as_i64(), Some(50));
+    }
+
+    #[test]
+    fn unmatched_overlays_are_dropped() {
+        let doc = &YamlLoader::load_from_str(DOC).unwrap()[0];
+        let selectors = Overlays {
+            host: Some("web-01".to_string()),
+            region: None,
+        };
+
+        let merged = apply(doc.as_hash().unwrap(), &selectors).unwrap().unwrap();
+        let db = &merged[&yaml_rust::Yaml::from_str("db")];
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(db["host"].as_str(), Some("db"));
+        assert_eq!(db["pool"].as_i64(), Some(10));
+    }
+}
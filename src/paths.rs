@@ -0,0 +1,195 @@
+//! Rules for the paths configuration files are read from.
+//!
+//! `LoadOptions::symlinks` decides whether a configuration file that is a symbolic link is
+//! followed. Some security postures forbid following links at all, or links leading out of
+//! the directory the file is referenced from; see `SymlinkPolicy`. The canonical path of every
+//! file layer is reported in `Layer::canonical_path`.
+//!
+//! `LoadOptions::base_dir` confines every file read to one directory, for systems that load
+//! configuration provided by users. Relative paths are taken from that directory, and paths
+//! leaving it, whether through `..`, an absolute path, or a symbolic link, are rejected. The
+//! check is made by `load_with`, so it covers every file layer of a `ConfigBuilder`.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::paths::SymlinkPolicy;
+//! use yaml_config::LoadOptions;
+//! let options = LoadOptions {
+//!     symlinks: SymlinkPolicy::WithinDirectory,
+//!     ..Default::default()
+//! };
+//! ```
+use crate::{ErrorCode, ParseError};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether configuration files that are symbolic links are followed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Follow every link.
+    #[default]
+    Follow,
+    /// Refuse files that are links.
+    Deny,
+    /// Follow links whose target lies inside the directory containing the link.
+    WithinDirectory,
+}
+
+fn path_error(message: String) -> ParseError {
+    ParseError::with_code(ErrorCode::PathDenied, "config::paths", message)
+}
+
+/// Resolves `path` inside `base_dir`, rejecting paths that lead out of it.
+pub(crate) fn confine<P: AsRef<Path>>(base_dir: &Path, path: P) -> Result<PathBuf, ParseError> {
+    let path = path.as_ref();
+    let base = fs::canonicalize(base_dir)?;
+    let canonical = fs::canonicalize(base.join(path))?;
+    if canonical.starts_with(&base) {
+        Ok(canonical)
+    } else {
+        Err(path_error(format!(
+            "{} is outside of the base directory {}.",
+            path.display(),
+            base_dir.display()
+        )))
+    }
+}
+
+/// Checks `path` against `policy`, returning its canonical path.
+pub(crate) fn resolve<P: AsRef<Path>>(
+    path: P,
+    policy: SymlinkPolicy,
+) -> Result<PathBuf, ParseError> {
+    let link = path.as_ref();
+    let canonical = fs::canonicalize(link)?;
+    if !fs::symlink_metadata(link)?.file_type().is_symlink() {
+        return Ok(canonical);
+    }
+    match policy {
+        SymlinkPolicy::Follow => Ok(canonical),
+        SymlinkPolicy::Deny => Err(path_error(format!(
+            "{} is a symbolic link, which is not followed.",
+            link.display()
+        ))),
+        SymlinkPolicy::WithinDirectory => {
+            let parent = match link.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            if canonical.starts_with(fs::canonicalize(parent)?) {
+                Ok(canonical)
+            } else {
+                Err(path_error(format!(
+                    "{} links to {}, outside of its directory.",
+                    link.display(),
+                    canonical.display()
+                )))
+            }
+        }
+    }
+}
+
+/// Returns the canonical path of the file at `path`, taken relative to `base_dir` if set, after
+/// checking it against `policy` and confining it to `base_dir`. The symbolic link policy is
+/// checked on the path as given, before confinement resolves its links.
+pub(crate) fn check(
+    path: &Path,
+    policy: SymlinkPolicy,
+    base_dir: Option<&Path>,
+) -> Result<PathBuf, ParseError> {
+    let path = match base_dir {
+        Some(base_dir) => base_dir.join(path),
+        None => path.to_path_buf(),
+    };
+    let canonical = resolve(&path, policy)?;
+    match base_dir {
+        Some(base_dir) => confine(base_dir, canonical),
+        None => Ok(canonical),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use crate::paths::{check, confine, resolve, SymlinkPolicy};
+    use crate::ErrorCode;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+
+    #[test]
+    fn symlinks_follow_the_policy() {
+        let dir = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let conf = dir.path().join("conf");
+        fs::create_dir(&conf).unwrap();
+        let target = conf.join("app.yaml");
+        fs::write(&target, "a: 1\n").unwrap();
+        let escaped = outside.path().join("other.yaml");
+        fs::write(&escaped, "a: 2\n").unwrap();
+        let inner = conf.join("current.yaml");
+        symlink(&target, &inner).unwrap();
+        let outer = conf.join("escape.yaml");
+        symlink(&escaped, &outer).unwrap();
+        let (inner, outer) = (inner.to_str().unwrap(), outer.to_str().unwrap());
+
+        let canonical = fs::canonicalize(&target).unwrap();
+        assert_eq!(resolve(inner, SymlinkPolicy::Follow).unwrap(), canonical);
+        assert_eq!(
+            resolve(inner, SymlinkPolicy::WithinDirectory).unwrap(),
+            canonical
+        );
+        assert!(resolve(outer, SymlinkPolicy::Follow).is_ok());
+        assert!(resolve(outer, SymlinkPolicy::WithinDirectory)
+            .unwrap_err()
+            .message()
+            .contains("outside of its directory"));
+        assert_eq!(
+            resolve(inner, SymlinkPolicy::Deny).unwrap_err().code(),
+            ErrorCode::PathDenied
+        );
+        assert!(resolve(target.to_str().unwrap(), SymlinkPolicy::Deny).is_ok());
+    }
+
+    #[test]
+    fn paths_are_confined_to_the_base_directory() {
+        let dir = tempdir().unwrap();
+        let base = dir.path().join("tenant");
+        fs::create_dir(&base).unwrap();
+        fs::write(base.join("app.yaml"), "a: 1\n").unwrap();
+        fs::write(dir.path().join("other.yaml"), "a: 2\n").unwrap();
+        symlink(dir.path().join("other.yaml"), base.join("link.yaml")).unwrap();
+        let inside = fs::canonicalize(base.join("app.yaml")).unwrap();
+
+        assert_eq!(confine(&base, "app.yaml").unwrap(), inside);
+        assert_eq!(confine(&base, inside.to_str().unwrap()).unwrap(), inside);
+        assert!(confine(&base, "../other.yaml").is_err());
+        assert!(confine(&base, "link.yaml").is_err());
+
+        // The symlink policy still applies to links inside the base directory.
+        symlink(base.join("app.yaml"), base.join("current.yaml")).unwrap();
+        let current = std::path::Path::new("current.yaml");
+        assert_eq!(
+            check(current, SymlinkPolicy::Follow, Some(&base)).unwrap(),
+            inside
+        );
+        assert!(check(current, SymlinkPolicy::Deny, Some(&base)).is_err());
+        assert!(check(
+            std::path::Path::new("link.yaml"),
+            SymlinkPolicy::Follow,
+            Some(&base)
+        )
+        .is_err());
+        let absolute = dir.path().join("other.yaml");
+        assert_eq!(
+            confine(&base, absolute.to_str().unwrap())
+                .unwrap_err()
+                .message(),
+            format!(
+                "{} is outside of the base directory {}.",
+                absolute.display(),
+                base.display()
+            )
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Permission and ownership checks for configuration files.
+//!
+//! A `PermissionPolicy` set with `ConfigBuilder::with_permission_policy` checks every file
+//! layer before it is loaded. A file that is writable by its group or by every user, or owned
+//! by another user than the expected one, is reported as a warning in `Config::warnings` or,
+//! with `PermissionPolicy::deny`, fails the build.
+//!
+//! The checks only apply on Unix; elsewhere every file passes.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::permissions::PermissionPolicy;
+//! use yaml_config::ConfigBuilder;
+//! let config = ConfigBuilder::new()
+//!     .with_permission_policy(PermissionPolicy::deny().owned_by(0))
+//!     .add_file("/etc/app/config.yaml")
+//!     .build();
+//! ```
+use crate::{ErrorCode, ParseError};
+use std::path::Path;
+
+/// What happens when a file fails the checks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Enforcement {
+    /// Record a warning in `Config::warnings` and load the file anyway.
+    #[default]
+    Warn,
+    /// Fail the build.
+    Deny,
+}
+
+/// The permissions and ownership configuration files must have.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PermissionPolicy {
+    enforcement: Enforcement,
+    owner: Option<u32>,
+}
+
+impl PermissionPolicy {
+    /// A policy whose violations are recorded as warnings.
+    pub fn warn() -> Self {
+        Self::default()
+    }
+
+    /// A policy whose violations fail the build.
+    pub fn deny() -> Self {
+        PermissionPolicy {
+            enforcement: Enforcement::Deny,
+            ..Self::default()
+        }
+    }
+
+    /// Requires files to be owned by the user with id `uid`.
+    pub fn owned_by(mut self, uid: u32) -> Self {
+        self.owner = Some(uid);
+        self
+    }
+
+    /// What happens when a file fails the checks.
+    pub fn enforcement(&self) -> Enforcement {
+        self.enforcement
+    }
+
+    /// Describes every way the file at `path` violates the policy. A file whose permissions
+    /// can't be read, including one that doesn't exist, violates it.
+    #[cfg(unix)]
+    pub fn violations<P: AsRef<Path>>(&self, path: P) -> Vec<String> {
+        use std::os::unix::fs::MetadataExt;
+        let meta = match std::fs::metadata(path) {
+            Ok(meta) => meta,
+            Err(e) => return vec![format!("its permissions can't be read ({})", e)],
+        };
+        let mut problems = Vec::new();
+        if meta.mode() & 0o020 != 0 {
+            problems.push("it is writable by its group".to_string());
+        }
+        if meta.mode() & 0o002 != 0 {
+            problems.push("it is writable by every user".to_string());
+        }
+        match self.owner {
+            Some(owner) if meta.uid() != owner => problems.push(format!(
+                "it is owned by uid {} instead of {}",
+                meta.uid(),
+                owner
+            )),
+            _ => {}
+        }
+        problems
+    }
+
+    /// Describes every way the file at `path` violates the policy.
+    #[cfg(not(unix))]
+    pub fn violations<P: AsRef<Path>>(&self, _path: P) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Checks the file at `path`, returning the warning to record, if any.
+    pub(crate) fn check(&self, path: &Path) -> Result<Option<String>, ParseError> {
+        let problems = self.violations(path);
+        if problems.is_empty() {
+            return Ok(None);
+        }
+        let message = format!(
+            "Unsafe permissions on {}: {}.",
+            path.display(),
+            problems.join("; ")
+        );
+        match self.enforcement {
+            Enforcement::Warn => Ok(Some(message)),
+            Enforcement::Deny => Err(ParseError::with_code(
+                ErrorCode::UnsafePermissions,
+                "config::permissions",
+                message,
+            )),
+        }
+    }
+}
+
+/// Returns `true` when anyone on the host may read the file at `path`, or when its
+/// permissions can't be read.
+#[cfg(unix)]
+pub(crate) fn world_readable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).map_or(true, |meta| meta.permissions().mode() & 0o004 != 0)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn world_readable(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use crate::permissions::PermissionPolicy;
+    use crate::{ConfigBuilder, ErrorCode, LoadOptions};
+    use std::fs;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use tempfile::tempdir;
+
+    #[test]
+    fn unsafe_files_are_reported() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(&path, "a: 1\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o666)).unwrap();
+        let path = path.to_str().unwrap();
+        let uid = fs::metadata(path).unwrap().uid();
+
+        assert_eq!(
+            PermissionPolicy::warn().owned_by(uid + 1).violations(path),
+            vec![
+                "it is writable by its group".to_string(),
+                "it is writable by every user".to_string(),
+                format!("it is owned by uid {} instead of {}", uid, uid + 1),
+            ]
+        );
+
+        let config = ConfigBuilder::new()
+            .with_permission_policy(PermissionPolicy::warn())
+            .add_file(path)
+            .build()
+            .unwrap();
+        assert!(config.warnings()[0].starts_with("Unsafe permissions on"));
+
+        let err = ConfigBuilder::new()
+            .with_permission_policy(PermissionPolicy::deny())
+            .add_file(path)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.module(), "config::permissions");
+        assert_eq!(err.code(), ErrorCode::UnsafePermissions);
+
+        // Files are checked where they are read from.
+        let err = ConfigBuilder::new()
+            .with_options(LoadOptions {
+                base_dir: Some(dir.path().to_path_buf()),
+                ..Default::default()
+            })
+            .with_permission_policy(PermissionPolicy::deny())
+            .add_file("config.yaml")
+            .build()
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::UnsafePermissions);
+        assert!(
+            PermissionPolicy::warn().violations(dir.path().join("gone.yaml"))[0]
+                .starts_with("its permissions can't be read")
+        );
+
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(PermissionPolicy::deny()
+            .owned_by(uid)
+            .violations(path)
+            .is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+//! Compliance policies over the resolved configuration.
+//!
+//! A policy is a named predicate over the whole `Config`, such as "TLS must be enabled in
+//! prod". Policies registered with `ConfigBuilder::with_policies` are evaluated when the
+//! configuration is built, and the resulting `ComplianceReport` is available from
+//! `Config::compliance`. Enforced policies additionally fail the build when violated.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::policy::Policies;
+//! use yaml_config::{ConfigBuilder, Value};
+//! let policies = Policies::new()
+//!     .policy("tls-in-prod", |config| {
+//!         let prod = config.get("APP_ENV") == Some(&Value::from("prod"));
+//!         match config.get("TLS_ENABLED") {
+//!             Some(Value::Bool(true)) => Ok(()),
+//!             _ if !prod => Ok(()),
+//!             _ => Err("TLS_ENABLED must be true in prod".to_string()),
+//!         }
+//!     })
+//!     .enforce();
+//! let config = ConfigBuilder::new().with_policies(policies).build().unwrap();
+//! assert!(config.compliance().is_compliant());
+//! ```
+use crate::messages;
+use crate::{Config, ErrorCode, ParseError};
+use std::fmt;
+use std::sync::Arc;
+
+/// Checks the whole configuration, describing the violation when it fails.
+pub type Predicate = Arc<dyn Fn(&Config) -> Result<(), String> + Send + Sync>;
+
+/// A set of named policies.
+#[derive(Clone, Default)]
+pub struct Policies {
+    policies: Vec<(String, Predicate)>,
+    enforce: bool,
+}
+
+impl fmt::Debug for Policies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&String> = self.policies.iter().map(|(name, _)| name).collect();
+        f.debug_struct("Policies")
+            .field("policies", &names)
+            .field("enforce", &self.enforce)
+            .finish()
+    }
+}
+
+impl Policies {
+    /// Creates an empty, report-only set of policies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a policy named `name`.
+    pub fn policy<F>(mut self, name: &str, check: F) -> Self
+    where
+        F: Fn(&Config) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.policies.push((name.to_string(), Arc::new(check)));
+        self
+    }
+
+    /// Makes violations fail the build instead of only being reported.
+    pub fn enforce(mut self) -> Self {
+        self.enforce = true;
+        self
+    }
+
+    /// Whether violations fail the build.
+    pub fn is_enforced(&self) -> bool {
+        self.enforce
+    }
+
+    /// Whether no policy is registered.
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// Evaluates every policy against `config`, in registration order.
+    pub fn evaluate(&self, config: &Config) -> ComplianceReport {
+        ComplianceReport {
+            results: self
+                .policies
+                .iter()
+                .map(|(name, check)| PolicyResult {
+                    name: name.clone(),
+                    violation: check(config).err(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Evaluates every policy and, when they are enforced, fails on a violation.
+    pub(crate) fn check(&self, config: &Config) -> Result<ComplianceReport, ParseError> {
+        let report = self.evaluate(config);
+        if self.enforce && !report.is_compliant() {
+            let violations: Vec<String> = report
+                .violations()
+                .map(|r| {
+                    messages::text(
+                        "policy.violation",
+                        &[
+                            ("name", &r.name),
+                            ("problem", r.violation.as_deref().unwrap_or("")),
+                        ],
+                    )
+                })
+                .collect();
+            return Err(ParseError::with_code(
+                ErrorCode::PolicyViolation,
+                "config::policy",
+                messages::text("policy.violated", &[("violations", &violations.join(", "))]),
+            ));
+        }
+        Ok(report)
+    }
+}
+
+/// The outcome of a single policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyResult {
+    pub name: String,
+    /// Why the policy failed, or `None` when it passed.
+    pub violation: Option<String>,
+}
+
+/// The outcome of every policy, in registration order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComplianceReport {
+    pub results: Vec<PolicyResult>,
+}
+
+impl ComplianceReport {
+    /// Whether every policy passed.
+    pub fn is_compliant(&self) -> bool {
+        self.results.iter().all(|r| r.violation.is_none())
+    }
+
+    /// The policies that failed.
+    pub fn violations(&self) -> impl Iterator<Item = &PolicyResult> {
+        self.results.iter().filter(|r| r.violation.is_some())
+    }
+}
+
+impl fmt::Display for ComplianceReport {
+    /// Writes one line per policy, e.g. `FAIL tls-in-prod: TLS_ENABLED must be true`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for result in &self.results {
+            match &result.violation {
+                None => writeln!(f, "PASS {}", result.name)?,
+                Some(violation) => writeln!(f, "FAIL {}: {}", result.name, violation)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::policy::Policies;
+    use crate::{Config, ConfigBuilder, Value};
+
+    fn debug_disabled(config: &Config) -> Result<(), String> {
+        let dev = config.get("APP_ENV") == Some(&Value::from("dev"));
+        match config.get("DEBUG_ENDPOINTS") {
+            Some(Value::Bool(true)) if !dev => {
+                Err("debug endpoints are only allowed when APP_ENV=dev".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[test]
+    fn violations_are_reported() {
+        let policies = Policies::new()
+            .policy("debug-disabled", debug_disabled)
+            .policy("always", |_| Ok(()));
+        let config = ConfigBuilder::new().build().unwrap().overridden([
+            ("APP_ENV", Value::from("prod")),
+            ("DEBUG_ENDPOINTS", Value::Bool(true)),
+        ]);
+
+        let report = policies.evaluate(&config);
+
+        assert!(!report.is_compliant());
+        assert_eq!(report.violations().count(), 1);
+        assert_eq!(
+            report.to_string(),
+            "FAIL debug-disabled: debug endpoints are only allowed when APP_ENV=dev\nPASS always\n"
+        );
+    }
+
+    #[test]
+    fn enforced_policies_fail_the_build() {
+        let policies = Policies::new().policy("has-name", |config| match config.get("NAME") {
+            Some(_) => Ok(()),
+            None => Err("NAME is missing".to_string()),
+        });
+
+        let config = ConfigBuilder::new()
+            .with_policies(policies.clone())
+            .build()
+            .unwrap();
+        assert_eq!(config.compliance().violations().count(), 1);
+
+        let err = ConfigBuilder::new()
+            .with_policies(policies.enforce())
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Policies violated: has-name (NAME is missing)."
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Proxy settings merged from a `proxy:` section and the conventional environment variables.
+//!
+//! ```yaml
+//! proxy:
+//!   http: http://proxy.internal:3128
+//!   https: http://proxy.internal:3128
+//!   no_proxy: localhost,.internal
+//! ```
+//!
+//! Each setting may also come from `HTTP_PROXY`, `HTTPS_PROXY`, and `NO_PROXY` (or their
+//! lower-case spellings). When both provide a setting, the `Preference` decides which one
+//! wins, the same way it does for every other key. Empty variables are treated as unset.
+use crate::{Config, ParseError, Preference, Value};
+use std::env;
+
+/// The proxies to use for outgoing requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxySettings {
+    /// The proxy for `http://` URLs.
+    pub http: Option<String>,
+    /// The proxy for `https://` URLs.
+    pub https: Option<String>,
+    /// Hosts reached directly. An entry matches the host itself and its subdomains, and `*`
+    /// matches every host.
+    pub no_proxy: Vec<String>,
+}
+
+/// Reads the environment variable `name`, falling back to its lower-case spelling.
+fn env_var(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .or_else(|| env::var(name.to_lowercase()).ok())
+        .filter(|v| !v.trim().is_empty())
+}
+
+/// Returns the string value of `key`, if set.
+fn string_of(config: &Config, key: &str) -> Result<Option<String>, ParseError> {
+    match config.get(key) {
+        None => Ok(None),
+        Some(Value::String(s)) if s.trim().is_empty() => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.trim().to_string())),
+        // `no_proxy` may also be written as a list.
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(s) => Ok(s.trim().to_string()),
+                _ => Err(proxy_error(key, item)),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|items| Some(items.join(","))),
+        Some(val) => Err(proxy_error(key, val)),
+    }
+}
+
+fn proxy_error(key: &str, val: &Value) -> ParseError {
+    ParseError::new(
+        "config::proxy",
+        format!("{} must be a string, found {:?}.", key, val),
+    )
+}
+
+impl ProxySettings {
+    /// Merges the section flattened under `prefix`, e.g. `PROXY`, with the environment.
+    pub fn from_config(
+        config: &Config,
+        prefix: &str,
+        preference: Preference,
+    ) -> Result<Self, ParseError> {
+        Self::resolve(config, prefix, preference, env_var)
+    }
+
+    fn resolve<F>(
+        config: &Config,
+        prefix: &str,
+        preference: Preference,
+        env: F,
+    ) -> Result<Self, ParseError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let setting = |part: &str, var: &str| -> Result<Option<String>, ParseError> {
+            let yaml = string_of(config, &format!("{}_{}", prefix, part))?;
+            Ok(match preference {
+                Preference::PreferYaml => yaml.or_else(|| env(var)),
+                Preference::PreferEnv | Preference::PreferEnvFallback => env(var).or(yaml),
+            })
+        };
+
+        let no_proxy = setting("NO_PROXY", "NO_PROXY")?.unwrap_or_default();
+        Ok(ProxySettings {
+            http: setting("HTTP", "HTTP_PROXY")?,
+            https: setting("HTTPS", "HTTPS_PROXY")?,
+            no_proxy: no_proxy
+                .split(',')
+                .map(str::trim)
+                .filter(|host| !host.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+
+    /// Returns `true` when `host` is reached without a proxy.
+    pub fn bypasses(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.no_proxy.iter().any(|entry| {
+            let entry = entry.trim_start_matches('.').to_ascii_lowercase();
+            entry == "*"
+                || host == entry
+                || host
+                    .strip_suffix(&entry)
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+    }
+
+    /// Returns the proxy to use for a request to `host` over `scheme` (`http` or `https`).
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::proxy::ProxySettings;
+    /// let proxy = ProxySettings {
+    ///     https: Some("http://proxy:3128".to_string()),
+    ///     no_proxy: vec![".internal".to_string()],
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(proxy.for_host("https", "example.com"), Some("http://proxy:3128"));
+    /// assert_eq!(proxy.for_host("https", "db.internal"), None);
+    /// ```
+    pub fn for_host(&self, scheme: &str, host: &str) -> Option<&str> {
+        if self.bypasses(host) {
+            return None;
+        }
+        match scheme {
+            "http" => self.http.as_deref(),
+            "https" => self.https.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    /// Merges the `proxy:` section with the proxy environment variables. See the `proxy`
+    /// module.
+    pub fn proxy(&self, preference: Preference) -> Result<ProxySettings, ParseError> {
+        ProxySettings::from_config(self, "PROXY", preference)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proxy::ProxySettings;
+    use crate::{Config, ConfigBuilder, Preference, Value};
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "HTTPS_PROXY" => Some("http://env-proxy:8080".to_string()),
+            "NO_PROXY" => Some("localhost, .corp".to_string()),
+            _ => None,
+        }
+    }
+
+    fn config() -> Config {
+        ConfigBuilder::new().build().unwrap().overridden([
+            ("PROXY_HTTP", Value::from("http://yaml-proxy:3128")),
+            ("PROXY_HTTPS", Value::from("http://yaml-proxy:3128")),
+        ])
+    }
+
+    #[test]
+    fn preference_picks_the_winner() {
+        let yaml = ProxySettings::resolve(&config(), "PROXY", Preference::PreferYaml, env).unwrap();
+        assert_eq!(yaml.https.as_deref(), Some("http://yaml-proxy:3128"));
+        assert_eq!(yaml.no_proxy, vec!["localhost", ".corp"]);
+
+        let env = ProxySettings::resolve(&config(), "PROXY", Preference::PreferEnv, env).unwrap();
+        assert_eq!(env.https.as_deref(), Some("http://env-proxy:8080"));
+        assert_eq!(env.http.as_deref(), Some("http://yaml-proxy:3128"));
+    }
+
+    #[test]
+    fn no_proxy_matches_subdomains() {
+        let config = config().overridden([(
+            "PROXY_NO_PROXY",
+            Value::Array(vec![Value::from("internal"), Value::from("10.0.0.1")]),
+        )]);
+        let proxy = ProxySettings::resolve(&config, "PROXY", Preference::PreferYaml, env).unwrap();
+
+        assert!(proxy.bypasses("db.internal"));
+        assert!(proxy.bypasses("INTERNAL"));
+        assert!(!proxy.bypasses("notinternal"));
+        assert_eq!(proxy.for_host("http", "10.0.0.1"), None);
+        assert_eq!(
+            proxy.for_host("http", "example.com"),
+            Some("http://yaml-proxy:3128")
+        );
+    }
+}
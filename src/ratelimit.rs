@@ -0,0 +1,150 @@
+//! Rate limits read from a `rate_limit:` section.
+//!
+//! ```yaml
+//! rate_limit:
+//!   requests: 100
+//!   per: 1m
+//!   burst: 20
+//! ```
+//!
+//! `requests` is required. `per` is a duration (see `types::duration`) and defaults to one
+//! second. `burst` defaults to `requests`.
+use crate::types::duration;
+use crate::{Config, ParseError, Value};
+use std::time::Duration;
+
+/// Allows `requests` requests every `per`, with up to `burst` sent at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+    pub burst: u32,
+}
+
+fn rate_limit_error(message: String) -> ParseError {
+    ParseError::new("config::ratelimit", message)
+}
+
+/// Reads a positive count from `key`.
+fn count(config: &Config, key: &str) -> Result<Option<u32>, String> {
+    match config.get(key) {
+        None => Ok(None),
+        Some(Value::I32(v)) if *v >= 1 => Ok(Some(*v as u32)),
+        Some(Value::I64(v)) if (1..=i64::from(u32::MAX)).contains(v) => Ok(Some(*v as u32)),
+        Some(val) => Err(format!("{}: {:?} is not a positive count", key, val)),
+    }
+}
+
+impl RateLimit {
+    /// Reads the section flattened under `prefix`, e.g. `RATE_LIMIT`. Every problem found is
+    /// reported.
+    pub fn from_config(config: &Config, prefix: &str) -> Result<Self, ParseError> {
+        let mut problems = Vec::new();
+        let key = |part: &str| format!("{}_{}", prefix, part);
+
+        let requests = match count(config, &key("REQUESTS")) {
+            Ok(Some(requests)) => requests,
+            Ok(None) => {
+                problems.push(format!("{} is not set", key("REQUESTS")));
+                0
+            }
+            Err(e) => {
+                problems.push(e);
+                0
+            }
+        };
+
+        let per = key("PER");
+        let per = match config.get(&per).map(duration::parse) {
+            None => Duration::from_secs(1),
+            Some(Ok(d)) if !d.is_zero() => d,
+            Some(Ok(_)) => {
+                problems.push(format!("{}: the period can't be zero", per));
+                Duration::ZERO
+            }
+            Some(Err(e)) => {
+                problems.push(format!("{}: {}", per, e));
+                Duration::ZERO
+            }
+        };
+
+        let burst = count(config, &key("BURST")).unwrap_or_else(|e| {
+            problems.push(e);
+            None
+        });
+
+        if !problems.is_empty() {
+            return Err(rate_limit_error(format!("{}.", problems.join("; "))));
+        }
+        Ok(RateLimit {
+            requests,
+            per,
+            burst: burst.unwrap_or(requests),
+        })
+    }
+
+    /// The sustained rate in requests per second.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use yaml_config::ratelimit::RateLimit;
+    /// let limit = RateLimit { requests: 120, per: Duration::from_secs(60), burst: 10 };
+    /// assert_eq!(limit.per_second(), 2.0);
+    /// assert_eq!(limit.interval(), Duration::from_millis(500));
+    /// ```
+    pub fn per_second(&self) -> f64 {
+        f64::from(self.requests) / self.per.as_secs_f64()
+    }
+
+    /// The time it takes to earn one request, the refill interval of a token bucket.
+    pub fn interval(&self) -> Duration {
+        self.per / self.requests
+    }
+}
+
+impl Config {
+    /// Reads the `rate_limit:` section. See the `ratelimit` module.
+    pub fn rate_limit(&self) -> Result<RateLimit, ParseError> {
+        RateLimit::from_config(self, "RATE_LIMIT")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ratelimit::RateLimit;
+    use crate::{ConfigBuilder, Value};
+    use std::time::Duration;
+
+    #[test]
+    fn sections_are_read() {
+        let config = ConfigBuilder::new().build().unwrap().overridden([
+            ("RATE_LIMIT_REQUESTS", Value::I64(100)),
+            ("RATE_LIMIT_PER", Value::from("1m")),
+        ]);
+
+        assert_eq!(
+            config.rate_limit().unwrap(),
+            RateLimit {
+                requests: 100,
+                per: Duration::from_secs(60),
+                burst: 100,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_limits_are_rejected() {
+        let config = ConfigBuilder::new().build().unwrap().overridden([
+            ("RATE_LIMIT_PER", Value::from("0s")),
+            ("RATE_LIMIT_BURST", Value::I64(-1)),
+        ]);
+
+        assert_eq!(
+            config.rate_limit().unwrap_err().message(),
+            "RATE_LIMIT_REQUESTS is not set; RATE_LIMIT_PER: the period can't be zero; \
+             RATE_LIMIT_BURST: I64(-1) is not a positive count."
+        );
+    }
+}
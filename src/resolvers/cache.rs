@@ -0,0 +1,139 @@
+//! Caching for resolvers backed by external secret stores.
+//!
+//! Wrapping a resolver in a `CachedResolver` means reloading a configuration only reaches the
+//! backend once a cached value is older than the resolver's TTL. `Config::refresh_secrets`
+//! refetches every cached value on demand, for example after a secret has been rotated. The
+//! refreshed values reach a configuration once it is rebuilt.
+use crate::resolvers::Resolver;
+use crate::{ParseError, Value};
+use fxhash::FxHashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// A resolver remembering the values resolved by another resolver for a fixed time.
+///
+/// **Examples**
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use yaml_config::resolvers::cache::CachedResolver;
+/// use yaml_config::resolvers::exec::{ExecResolver, TAG};
+/// use yaml_config::LoadOptions;
+/// let resolver = ExecResolver::new().allow("op");
+/// let mut options = LoadOptions::default();
+/// options.resolvers.register(
+///     TAG,
+///     Arc::new(CachedResolver::new(resolver, Duration::from_secs(300))),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct CachedResolver<R> {
+    inner: R,
+    ttl: Duration,
+    // Maps the tagged text to the resolved value and when it was resolved.
+    entries: Mutex<FxHashMap<String, (Value, Instant)>>,
+}
+
+impl<R: Resolver> CachedResolver<R> {
+    /// Caches the values resolved by `inner` for `ttl`.
+    pub fn new(inner: R, ttl: Duration) -> Self {
+        CachedResolver {
+            inner,
+            ttl,
+            entries: Mutex::new(FxHashMap::default()),
+        }
+    }
+
+    /// Drops every cached value, so the next resolve of each reaches the backend.
+    pub fn clear(&self) {
+        self.entries().clear();
+    }
+
+    fn entries(&self) -> MutexGuard<'_, FxHashMap<String, (Value, Instant)>> {
+        // A panic while holding the lock can't leave the map half-updated.
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<R: Resolver> Resolver for CachedResolver<R> {
+    fn resolve(&self, value: &str) -> Result<Value, ParseError> {
+        if let Some((cached, resolved_at)) = self.entries().get(value) {
+            if resolved_at.elapsed() < self.ttl {
+                return Ok(cached.clone());
+            }
+        }
+
+        // The lock isn't held while resolving, so a slow backend doesn't block cache hits.
+        let resolved = self.inner.resolve(value)?;
+        self.entries()
+            .insert(value.to_string(), (resolved.clone(), Instant::now()));
+        Ok(resolved)
+    }
+
+    fn refresh(&self) -> Result<(), ParseError> {
+        let references: Vec<String> = self.entries().keys().cloned().collect();
+        let mut first_error = None;
+
+        for reference in references {
+            match self.inner.resolve(&reference) {
+                Ok(resolved) => {
+                    self.entries().insert(reference, (resolved, Instant::now()));
+                }
+                // Keep serving the previous value, but report the failure.
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resolvers::cache::CachedResolver;
+    use crate::resolvers::Resolver;
+    use crate::{ParseError, Value};
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Counter {
+        calls: AtomicI64,
+    }
+
+    impl Resolver for Counter {
+        fn resolve(&self, _value: &str) -> Result<Value, ParseError> {
+            Ok(Value::I64(self.calls.fetch_add(1, Ordering::SeqCst) + 1))
+        }
+    }
+
+    #[test]
+    fn values_are_cached_until_they_expire() {
+        let cached = CachedResolver::new(Counter::default(), Duration::from_secs(60));
+        assert_eq!(cached.resolve("a").unwrap(), Value::I64(1));
+        assert_eq!(cached.resolve("a").unwrap(), Value::I64(1));
+        assert_eq!(cached.resolve("b").unwrap(), Value::I64(2));
+
+        let expired = CachedResolver::new(Counter::default(), Duration::ZERO);
+        assert_eq!(expired.resolve("a").unwrap(), Value::I64(1));
+        assert_eq!(expired.resolve("a").unwrap(), Value::I64(2));
+    }
+
+    #[test]
+    fn refresh_refetches_cached_values() {
+        let cached = CachedResolver::new(Counter::default(), Duration::from_secs(60));
+        cached.resolve("a").unwrap();
+
+        cached.refresh().unwrap();
+        assert_eq!(cached.resolve("a").unwrap(), Value::I64(2));
+
+        cached.clear();
+        assert_eq!(cached.resolve("a").unwrap(), Value::I64(3));
+    }
+}
@@ -0,0 +1,292 @@
+//! A resolver substituting the output of a command.
+//!
+//! `!exec "op read op://vault/item/field"` runs the command and uses its standard output, with
+//! the trailing newline removed, as the value. Many secret managers only offer a CLI, which makes
+//! this the lowest common denominator. The command is run directly rather than through a shell,
+//! and only programs on the resolver's allowlist may be run.
+use crate::resolvers::Resolver;
+use crate::{ParseError, Value};
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The tag `ExecResolver` is conventionally registered under.
+pub const TAG: &str = "exec";
+
+/// How long a command may run before it is killed, unless configured otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs allowlisted commands and resolves to their standard output.
+///
+/// Nothing is allowed by default; every program has to be allowed explicitly by name.
+///
+/// **Examples**
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use yaml_config::resolvers::exec::{ExecResolver, TAG};
+/// use yaml_config::LoadOptions;
+/// let mut options = LoadOptions::default();
+/// options.resolvers.register(
+///     TAG,
+///     Arc::new(ExecResolver::new().allow("op").timeout(Duration::from_secs(5))),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExecResolver {
+    allowed: Vec<String>,
+    timeout: Duration,
+}
+
+impl Default for ExecResolver {
+    fn default() -> Self {
+        ExecResolver {
+            allowed: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+fn exec_error(message: String) -> ParseError {
+    ParseError::new("config::resolvers::exec", message)
+}
+
+/// Splits a command line into words. Single quotes preserve everything literally, double quotes
+/// allow `\"` and `\\` escapes, and unquoted whitespace separates words.
+fn split_command(command: &str) -> Result<Vec<String>, ParseError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => {
+                            return Err(exec_error(format!("Unterminated quote in {}", command)))
+                        }
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => break,
+                        },
+                        Some(c) => word.push(c),
+                        None => {
+                            return Err(exec_error(format!("Unterminated quote in {}", command)))
+                        }
+                    }
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+impl ExecResolver {
+    /// Creates a resolver with an empty allowlist and the default timeout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows `program` to be run. It is matched against the first word of the command exactly,
+    /// so an allowed `op` does not allow `/usr/bin/op` or vice versa.
+    pub fn allow(mut self, program: &str) -> Self {
+        self.allowed.push(program.to_string());
+        self
+    }
+
+    /// Sets how long a command may run before it is killed and resolving fails.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Reads `pipe` to the end on its own thread, sending the result once it is closed.
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> Receiver<io::Result<Vec<u8>>> {
+    let (sender, receiver) = mpsc::channel();
+    if let Some(mut pipe) = pipe {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = sender.send(pipe.read_to_end(&mut buf).map(|_| buf));
+        });
+    }
+    receiver
+}
+
+impl Resolver for ExecResolver {
+    fn resolve(&self, value: &str) -> Result<Value, ParseError> {
+        let words = split_command(value)?;
+        let (program, args) = match words.split_first() {
+            Some(split) => split,
+            None => return Err(exec_error("Empty command.".to_string())),
+        };
+        if !self.allowed.iter().any(|allowed| allowed == program) {
+            return Err(exec_error(format!(
+                "{} is not an allowed command.",
+                program
+            )));
+        }
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| exec_error(format!("Could not run {}: {}", program, e)))?;
+
+        // Drain both pipes on their own threads so a chatty command can't block on a full pipe
+        // while we wait for it to exit.
+        let out = drain(child.stdout.take());
+        let err = drain(child.stderr.take());
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(exec_error(format!(
+                    "{} did not finish within {:?}.",
+                    program, self.timeout
+                )));
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        // A process the command left running in the background can keep the pipes open after
+        // the command exits, so reading them is bounded by the same deadline.
+        let collect = |pipe: Receiver<io::Result<Vec<u8>>>, name: &str| match pipe
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            Ok(read) => read.map_err(ParseError::from),
+            Err(RecvTimeoutError::Timeout) => Err(exec_error(format!(
+                "The {} of {} was not closed within {:?}.",
+                name, program, self.timeout
+            ))),
+            Err(RecvTimeoutError::Disconnected) => Err(exec_error(format!(
+                "The {} of {} could not be read.",
+                name, program
+            ))),
+        };
+        let out = collect(out, "output")?;
+        let err = collect(err, "error output")?;
+        if !status.success() {
+            return Err(exec_error(format!(
+                "{} failed with {}: {}",
+                program,
+                status,
+                String::from_utf8_lossy(&err).trim()
+            )));
+        }
+
+        let mut out = String::from_utf8(out)
+            .map_err(|_| exec_error(format!("{} did not print valid UTF-8.", program)))?;
+        if out.ends_with('\n') {
+            out.pop();
+            if out.ends_with('\r') {
+                out.pop();
+            }
+        }
+
+        Ok(Value::String(out))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resolvers::exec::{split_command, ExecResolver};
+    use crate::resolvers::Resolver;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn commands_are_split() {
+        assert_eq!(
+            split_command(r#"op read "op://vault/my item/field" 'a b' c\d"#).unwrap(),
+            vec!["op", "read", "op://vault/my item/field", "a b", r"c\d"]
+        );
+        assert_eq!(
+            split_command(r#"echo "a \"b\"""#).unwrap(),
+            vec!["echo", r#"a "b""#]
+        );
+        assert!(split_command("echo 'a").is_err());
+    }
+
+    #[test]
+    fn only_allowed_programs_run() {
+        let resolver = ExecResolver::new().allow("echo");
+
+        assert_eq!(
+            resolver
+                .resolve("echo hello world")
+                .unwrap()
+                .as_string()
+                .unwrap(),
+            "hello world"
+        );
+        assert!(resolver.resolve("sh -c 'echo hi'").is_err());
+        assert!(resolver.resolve("").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn failures_and_timeouts_are_errors() {
+        let resolver = ExecResolver::new()
+            .allow("false")
+            .allow("sleep")
+            .timeout(Duration::from_millis(100));
+
+        assert!(resolver.resolve("false").is_err());
+        assert!(resolver.resolve("sleep 5").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn pipes_held_open_by_background_processes_time_out() {
+        let resolver = ExecResolver::new()
+            .allow("sh")
+            .timeout(Duration::from_millis(200));
+
+        let started = Instant::now();
+        let error = resolver.resolve("sh -c 'sleep 5 &'").unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(4));
+        assert!(error.message().ends_with("was not closed within 200ms."));
+    }
+}
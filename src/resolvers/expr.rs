@@ -0,0 +1,345 @@
+//! A resolver evaluating arithmetic over system facts.
+//!
+//! `workers: !expr max(2, cpu_count - 1)` is evaluated at load time, so one deployment file
+//! scales across instance sizes. Expressions support numbers, the operators `+ - * / %`,
+//! parentheses, and the functions `min`, `max`, `abs`, `floor`, `ceil`, `round`, and `clamp`.
+//! Names refer to variables: `ExprResolver::new` detects `cpu_count`, and with the `facts`
+//! feature also provides `memory_bytes` and `container` from `facts::Facts`. More can be added
+//! with `ExprResolver::var`.
+//!
+//! Whole results are integers and anything else is a float, so `cpu_count / 2` may need
+//! `floor`.
+use crate::resolvers::Resolver;
+use crate::{ParseError, Value};
+use fxhash::FxHashMap;
+#[cfg(not(feature = "facts"))]
+use std::thread;
+
+/// The tag `ExprResolver` is conventionally registered under.
+pub const TAG: &str = "expr";
+
+/// The deepest nesting of parentheses and calls accepted.
+const MAX_DEPTH: usize = 64;
+
+/// Evaluates `!expr` scalars against a set of variables.
+///
+/// **Examples**
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use yaml_config::resolvers::expr::{ExprResolver, TAG};
+/// use yaml_config::resolvers::Resolver;
+/// use yaml_config::{LoadOptions, Value};
+/// let resolver = ExprResolver::empty().var("cpu_count", 8.0);
+/// assert_eq!(resolver.resolve("max(2, cpu_count - 1)").unwrap(), Value::I64(7));
+///
+/// let mut options = LoadOptions::default();
+/// options.resolvers.register(TAG, Arc::new(ExprResolver::new()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ExprResolver {
+    vars: FxHashMap<String, f64>,
+}
+
+fn expr_error(expr: &str, message: String) -> ParseError {
+    ParseError::new(
+        "config::resolvers::expr",
+        format!("!expr {:?}: {}.", expr, message),
+    )
+}
+
+impl ExprResolver {
+    /// Creates a resolver with `cpu_count` detected.
+    #[cfg(not(feature = "facts"))]
+    pub fn new() -> Self {
+        let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::empty().var("cpu_count", cpus as f64)
+    }
+
+    /// Creates a resolver with every numeric fact of `Facts::current` as a variable.
+    #[cfg(feature = "facts")]
+    pub fn new() -> Self {
+        Self::from_facts(crate::facts::Facts::current())
+    }
+
+    /// Creates a resolver with the numeric facts of `facts` as variables.
+    #[cfg(feature = "facts")]
+    pub fn from_facts(facts: &crate::facts::Facts) -> Self {
+        facts
+            .vars()
+            .into_iter()
+            .fold(Self::empty(), |resolver, (name, value)| {
+                resolver.var(name, value)
+            })
+    }
+
+    /// Creates a resolver without any variables.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Sets the variable `name`, replacing a detected value of the same name.
+    pub fn var(mut self, name: &str, value: f64) -> Self {
+        self.vars.insert(name.to_string(), value);
+        self
+    }
+
+    /// Evaluates `expr`.
+    pub fn eval(&self, expr: &str) -> Result<f64, ParseError> {
+        let mut parser = ExprParser {
+            src: expr.as_bytes(),
+            pos: 0,
+            depth: 0,
+            vars: &self.vars,
+        };
+        let val = parser.expr().map_err(|e| expr_error(expr, e))?;
+        parser.skip_ws();
+        if parser.pos < parser.src.len() {
+            return Err(expr_error(
+                expr,
+                format!("unexpected input at position {}", parser.pos),
+            ));
+        }
+        if !val.is_finite() {
+            return Err(expr_error(
+                expr,
+                "the result is not a finite number".to_string(),
+            ));
+        }
+        Ok(val)
+    }
+}
+
+impl Resolver for ExprResolver {
+    fn resolve(&self, value: &str) -> Result<Value, ParseError> {
+        let val = self.eval(value)?;
+        if val.fract() == 0.0 && val.abs() < i64::MAX as f64 {
+            Ok(Value::I64(val as i64))
+        } else {
+            Ok(Value::F64(val))
+        }
+    }
+}
+
+/// A recursive descent parser evaluating as it goes.
+struct ExprParser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    depth: usize,
+    vars: &'a FxHashMap<String, f64>,
+}
+
+impl ExprParser<'_> {
+    fn skip_ws(&mut self) {
+        while self.pos < self.src.len() && self.src[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `c` if it is the next non-blank character.
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_ws();
+        if self.src.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<f64, String> {
+        let mut val = self.term()?;
+        loop {
+            if self.eat(b'+') {
+                val += self.term()?;
+            } else if self.eat(b'-') {
+                val -= self.term()?;
+            } else {
+                return Ok(val);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let mut val = self.unary()?;
+        loop {
+            if self.eat(b'*') {
+                val *= self.unary()?;
+            } else if self.eat(b'/') {
+                val /= self.unary()?;
+            } else if self.eat(b'%') {
+                val %= self.unary()?;
+            } else {
+                return Ok(val);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.eat(b'-') {
+            return self.nested(|p| p.unary()).map(|v| -v);
+        }
+        self.atom()
+    }
+
+    /// Runs `f` one level deeper, failing once the nesting limit is reached.
+    fn nested<F>(&mut self, f: F) -> Result<f64, String>
+    where
+        F: FnOnce(&mut Self) -> Result<f64, String>,
+    {
+        if self.depth >= MAX_DEPTH {
+            return Err(format!("nested deeper than {} levels", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let val = f(self);
+        self.depth -= 1;
+        val
+    }
+
+    fn atom(&mut self) -> Result<f64, String> {
+        self.skip_ws();
+        let start = self.pos;
+        match self.src.get(self.pos) {
+            Some(b'(') => {
+                self.pos += 1;
+                let val = self.nested(|p| p.expr())?;
+                if !self.eat(b')') {
+                    return Err(format!("expected ')' at position {}", self.pos));
+                }
+                Ok(val)
+            }
+            Some(c) if c.is_ascii_digit() || *c == b'.' => {
+                while self
+                    .src
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == b'.')
+                {
+                    self.pos += 1;
+                }
+                let number = String::from_utf8_lossy(&self.src[start..self.pos]);
+                number
+                    .parse()
+                    .map_err(|_| format!("\"{}\" is not a number", number))
+            }
+            Some(c) if c.is_ascii_alphabetic() || *c == b'_' => {
+                while self
+                    .src
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_')
+                {
+                    self.pos += 1;
+                }
+                let name = String::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+                if self.eat(b'(') {
+                    return self.nested(|p| p.args().and_then(|args| call(&name, &args)));
+                }
+                self.vars
+                    .get(&name)
+                    .copied()
+                    .ok_or_else(|| format!("unknown variable {}", name))
+            }
+            Some(_) => Err(format!("unexpected input at position {}", self.pos)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+
+    /// Parses the arguments of a call whose `(` has been consumed.
+    fn args(&mut self) -> Result<Vec<f64>, String> {
+        let mut args = Vec::new();
+        if self.eat(b')') {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            if self.eat(b')') {
+                return Ok(args);
+            }
+            if !self.eat(b',') {
+                return Err(format!("expected ',' or ')' at position {}", self.pos));
+            }
+        }
+    }
+}
+
+/// Applies the function `name` to `args`.
+fn call(name: &str, args: &[f64]) -> Result<f64, String> {
+    let arity = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} takes {} arguments, got {}",
+                name,
+                n,
+                args.len()
+            ))
+        }
+    };
+    match name {
+        "min" | "max" if args.is_empty() => Err(format!("{} needs an argument", name)),
+        "min" => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+        "max" => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        "abs" => arity(1).map(|_| args[0].abs()),
+        "floor" => arity(1).map(|_| args[0].floor()),
+        "ceil" => arity(1).map(|_| args[0].ceil()),
+        "round" => arity(1).map(|_| args[0].round()),
+        "clamp" => arity(3).and_then(|_| {
+            if args[1] > args[2] {
+                return Err("clamp's lower bound is above its upper bound".to_string());
+            }
+            Ok(args[0].clamp(args[1], args[2]))
+        }),
+        _ => Err(format!("unknown function {}", name)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resolvers::expr::ExprResolver;
+    use crate::resolvers::Resolver;
+    use crate::Value;
+
+    fn resolver() -> ExprResolver {
+        ExprResolver::empty()
+            .var("cpu_count", 4.0)
+            .var("memory_bytes", 8.0 * 1024.0 * 1024.0 * 1024.0)
+    }
+
+    #[test]
+    fn expressions_are_evaluated() {
+        let r = resolver();
+
+        assert_eq!(r.resolve("max(2, cpu_count - 1)").unwrap(), Value::I64(3));
+        assert_eq!(r.resolve("cpu_count / 8").unwrap(), Value::F64(0.5));
+        assert_eq!(r.resolve("-(1 + 2) * 3 % 4").unwrap(), Value::I64(-1));
+        assert_eq!(
+            r.resolve("floor(memory_bytes / 1024 / 1024 / 4)").unwrap(),
+            Value::I64(2048)
+        );
+        assert_eq!(
+            r.resolve("clamp(cpu_count * 4, 2, 8)").unwrap(),
+            Value::I64(8)
+        );
+    }
+
+    #[test]
+    fn errors_describe_the_problem() {
+        let r = resolver();
+
+        assert_eq!(
+            r.resolve("gpu_count + 1").unwrap_err().message(),
+            "!expr \"gpu_count + 1\": unknown variable gpu_count."
+        );
+        assert!(r.resolve("sqrt(4)").is_err());
+        assert!(r.resolve("1 +").is_err());
+        assert!(r.resolve("(1").is_err());
+        assert!(r.resolve("1 / 0").is_err());
+        assert!(r.resolve("abs(1, 2)").is_err());
+        assert!(r.resolve(&"(".repeat(10_000)).is_err());
+    }
+
+    #[test]
+    fn detected_facts_are_available() {
+        assert!(ExprResolver::new().eval("cpu_count").unwrap() >= 1.0);
+    }
+}
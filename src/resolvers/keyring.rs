@@ -0,0 +1,64 @@
+//! A resolver reading secrets from the platform credential store.
+//!
+//! `!keyring service/account` is resolved to the password stored for `account` under `service`
+//! in the macOS Keychain, the Windows Credential Manager, or the Secret Service on Linux.
+use crate::resolvers::Resolver;
+use crate::{ParseError, Value};
+
+/// The tag `KeyringResolver` is conventionally registered under.
+pub const TAG: &str = "keyring";
+
+/// Resolves `service/account` references through the `keyring` crate.
+///
+/// **Examples**
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use yaml_config::resolvers::keyring::{KeyringResolver, TAG};
+/// use yaml_config::LoadOptions;
+/// let mut options = LoadOptions::default();
+/// options.resolvers.register(TAG, Arc::new(KeyringResolver));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeyringResolver;
+
+/// Splits a `service/account` reference. The account is everything after the first `/`.
+fn parse_reference(value: &str) -> Result<(&str, &str), ParseError> {
+    match value.trim().split_once('/') {
+        Some((service, account)) if !service.is_empty() && !account.is_empty() => {
+            Ok((service, account))
+        }
+        _ => Err(ParseError::new(
+            "config::resolvers::keyring",
+            format!("Expected a service/account reference, found \"{}\".", value),
+        )),
+    }
+}
+
+impl Resolver for KeyringResolver {
+    fn resolve(&self, value: &str) -> Result<Value, ParseError> {
+        let (service, account) = parse_reference(value)?;
+        keyring::Entry::new(service, account)
+            .and_then(|entry| entry.get_password())
+            .map(Value::String)
+            .map_err(|e| {
+                ParseError::new(
+                    "keyring",
+                    format!("Could not read {}/{}: {}", service, account, e),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resolvers::keyring::parse_reference;
+
+    #[test]
+    fn references_are_split() {
+        assert_eq!(parse_reference("billing/db").unwrap(), ("billing", "db"));
+        assert_eq!(parse_reference("svc/a/b").unwrap(), ("svc", "a/b"));
+        assert!(parse_reference("billing").is_err());
+        assert!(parse_reference("/db").is_err());
+    }
+}
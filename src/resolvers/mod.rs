@@ -0,0 +1,115 @@
+//! Resolvers for custom YAML tags.
+//!
+//! A scalar tagged with a custom tag, e.g. `password: !keyring billing/db`, is passed to the
+//! `Resolver` registered for that tag in `LoadOptions::resolvers`, and the returned value
+//! replaces the scalar before the document is flattened. Tags without a registered resolver
+//! are ignored and the scalar is kept as written, unless `Resolvers::set_strict` makes them an
+//! error so that a reference is never mistaken for the value itself.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use yaml_config::resolvers::Resolver;
+//! use yaml_config::{LoadOptions, ParseError, Value};
+//!
+//! struct Upper;
+//!
+//! impl Resolver for Upper {
+//!     fn resolve(&self, value: &str) -> Result<Value, ParseError> {
+//!         Ok(Value::String(value.to_uppercase()))
+//!     }
+//! }
+//!
+//! let mut options = LoadOptions::default();
+//! options.resolvers.register("upper", Arc::new(Upper));
+//! ```
+pub mod cache;
+pub mod exec;
+pub mod expr;
+#[cfg(feature = "keyring")]
+pub mod keyring;
+
+use crate::{ErrorCode, ParseError, Value};
+use fxhash::FxHashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Resolves the value of a tagged scalar.
+pub trait Resolver: Send + Sync {
+    /// Resolves `value`, the text following the tag.
+    fn resolve(&self, value: &str) -> Result<Value, ParseError>;
+
+    /// Refetches any values the resolver has cached. Resolvers without a cache do nothing.
+    fn refresh(&self) -> Result<(), ParseError> {
+        Ok(())
+    }
+}
+
+/// The resolvers available while loading, keyed by tag name (without the leading `!`).
+#[derive(Clone, Default)]
+pub struct Resolvers {
+    resolvers: FxHashMap<String, Arc<dyn Resolver>>,
+    strict: bool,
+}
+
+impl fmt::Debug for Resolvers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut tags: Vec<&String> = self.resolvers.keys().collect();
+        tags.sort();
+        f.debug_struct("Resolvers")
+            .field("tags", &tags)
+            .field("strict", &self.strict)
+            .finish()
+    }
+}
+
+impl Resolvers {
+    /// Registers `resolver` for `!tag`, replacing any previous resolver for the tag.
+    pub fn register(&mut self, tag: &str, resolver: Arc<dyn Resolver>) {
+        self.resolvers.insert(tag.to_string(), resolver);
+    }
+
+    /// Makes tags without a registered resolver an error instead of keeping their scalar as
+    /// written. Off by default.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Returns `true` if a resolver is registered for `!tag`.
+    pub(crate) fn contains(&self, tag: &str) -> bool {
+        self.resolvers.contains_key(tag)
+    }
+
+    /// Returns `true` if tags without a registered resolver are an error.
+    pub(crate) fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Resolves `value` tagged with `!tag`.
+    pub fn resolve(&self, tag: &str, value: &str) -> Result<Value, ParseError> {
+        match self.resolvers.get(tag) {
+            Some(resolver) => resolver.resolve(value),
+            None => Err(ParseError::with_code(
+                ErrorCode::UnknownTag,
+                "config::resolvers",
+                format!("No resolver is registered for tag !{}.", tag),
+            )),
+        }
+    }
+
+    /// Refreshes every registered resolver, returning the first failure.
+    pub fn refresh(&self) -> Result<(), ParseError> {
+        let mut first_error = None;
+        for resolver in self.resolvers.values() {
+            if let Err(e) = resolver.refresh() {
+                first_error.get_or_insert(e);
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Retry policies read from a `retry:` section.
+//!
+//! ```yaml
+//! retry:
+//!   max_attempts: 5
+//!   base_delay: 100ms
+//!   max_delay: 10s
+//!   jitter: 20%
+//! ```
+//!
+//! Every key is optional. Delays are durations (see `types::duration`) and `jitter` is the
+//! share of each delay that may be randomly shaved off, written as a percentage or ratio
+//! (see `types::percent`). `true` means full jitter.
+use crate::types::{duration, percent};
+use crate::{Config, ParseError, Value};
+use std::time::Duration;
+
+/// An exponential backoff policy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// How many times an operation is attempted in total, at least 1.
+    pub max_attempts: u32,
+    /// The delay before the first retry. Each following retry waits twice as long.
+    pub base_delay: Duration,
+    /// The longest delay between two attempts.
+    pub max_delay: Duration,
+    /// The share of each delay, between 0 and 1, that may be randomly removed.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.0,
+        }
+    }
+}
+
+fn retry_error(message: String) -> ParseError {
+    ParseError::new("config::retry", message)
+}
+
+impl RetryPolicy {
+    /// Reads the section flattened under `prefix`, e.g. `RETRY`. Missing keys keep their
+    /// defaults: 3 attempts, 100ms base delay, 10s maximum delay, and no jitter.
+    ///
+    /// Every problem found is reported, including a base delay longer than the maximum.
+    pub fn from_config(config: &Config, prefix: &str) -> Result<Self, ParseError> {
+        let mut policy = RetryPolicy::default();
+        let mut problems = Vec::new();
+        let key = |part: &str| format!("{}_{}", prefix, part);
+
+        let attempts = key("MAX_ATTEMPTS");
+        match config.get(&attempts) {
+            None => {}
+            Some(Value::I32(v)) if *v >= 1 => policy.max_attempts = *v as u32,
+            Some(Value::I64(v)) if (1..=i64::from(u32::MAX)).contains(v) => {
+                policy.max_attempts = *v as u32
+            }
+            Some(val) => problems.push(format!(
+                "{}: {:?} is not a positive number of attempts",
+                attempts, val
+            )),
+        }
+
+        for (part, field) in [
+            ("BASE_DELAY", &mut policy.base_delay),
+            ("MAX_DELAY", &mut policy.max_delay),
+        ] {
+            let delay = key(part);
+            if let Some(val) = config.get(&delay) {
+                match duration::parse(val) {
+                    Ok(d) => *field = d,
+                    Err(e) => problems.push(format!("{}: {}", delay, e)),
+                }
+            }
+        }
+
+        let jitter = key("JITTER");
+        match config.get(&jitter) {
+            None => {}
+            Some(Value::Bool(full)) => policy.jitter = if *full { 1.0 } else { 0.0 },
+            Some(val) => match percent::parse(val) {
+                Ok(ratio) => policy.jitter = ratio,
+                Err(e) => problems.push(format!("{}: {}", jitter, e)),
+            },
+        }
+
+        if problems.is_empty() && policy.base_delay > policy.max_delay {
+            problems.push(format!(
+                "{} ({:?}) is longer than {} ({:?})",
+                key("BASE_DELAY"),
+                policy.base_delay,
+                key("MAX_DELAY"),
+                policy.max_delay
+            ));
+        }
+        if !problems.is_empty() {
+            return Err(retry_error(format!("{}.", problems.join("; "))));
+        }
+        Ok(policy)
+    }
+
+    /// The delay before retry number `retry`, counting from 1, without jitter.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use yaml_config::retry::RetryPolicy;
+    /// let policy = RetryPolicy::default();
+    /// assert_eq!(policy.delay(1), Duration::from_millis(100));
+    /// assert_eq!(policy.delay(3), Duration::from_millis(400));
+    /// assert_eq!(policy.delay(30), Duration::from_secs(10));
+    /// ```
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(retry.saturating_sub(1));
+        self.base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |d| d.min(self.max_delay))
+    }
+
+    /// The delay before retry number `retry` with jitter applied. `random` is a number in
+    /// `[0, 1)` supplied by the caller's random number generator.
+    pub fn delay_with_jitter(&self, retry: u32, random: f64) -> Duration {
+        self.delay(retry)
+            .mul_f64(1.0 - self.jitter * random.clamp(0.0, 1.0))
+    }
+}
+
+impl Config {
+    /// Reads the `retry:` section. See the `retry` module.
+    pub fn retry_policy(&self) -> Result<RetryPolicy, ParseError> {
+        RetryPolicy::from_config(self, "RETRY")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::retry::RetryPolicy;
+    use crate::{ConfigBuilder, Value};
+    use std::time::Duration;
+
+    #[test]
+    fn sections_are_read() {
+        let config = ConfigBuilder::new().build().unwrap().overridden([
+            ("RETRY_MAX_ATTEMPTS", Value::I64(5)),
+            ("RETRY_BASE_DELAY", Value::from("250ms")),
+            ("RETRY_MAX_DELAY", Value::from("1s")),
+            ("RETRY_JITTER", Value::from("50%")),
+        ]);
+
+        let policy = config.retry_policy().unwrap();
+
+        assert_eq!(policy.max_attempts, 5);
+        assert_eq!(policy.delay(2), Duration::from_millis(500));
+        assert_eq!(policy.delay(4), Duration::from_secs(1));
+        assert_eq!(
+            policy.delay_with_jitter(1, 0.5),
+            Duration::from_micros(187_500)
+        );
+        assert_eq!(
+            ConfigBuilder::new()
+                .build()
+                .unwrap()
+                .retry_policy()
+                .unwrap(),
+            RetryPolicy::default()
+        );
+    }
+
+    #[test]
+    fn insane_policies_are_rejected() {
+        let config = ConfigBuilder::new()
+            .build()
+            .unwrap()
+            .overridden([("RETRY_BASE_DELAY", "1m"), ("RETRY_MAX_DELAY", "10s")]);
+        assert_eq!(
+            config.retry_policy().unwrap_err().message(),
+            "RETRY_BASE_DELAY (60s) is longer than RETRY_MAX_DELAY (10s)."
+        );
+
+        let config = ConfigBuilder::new().build().unwrap().overridden([
+            ("RETRY_MAX_ATTEMPTS", Value::I64(0)),
+            ("RETRY_JITTER", Value::from("150%")),
+        ]);
+        let message = config.retry_policy().unwrap_err().message().into_owned();
+        assert!(message.starts_with("RETRY_MAX_ATTEMPTS: I64(0)"));
+        assert!(message.contains("RETRY_JITTER: "));
+    }
+}
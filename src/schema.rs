@@ -0,0 +1,292 @@
+//! Declarations describing the keys an application expects.
+//!
+//! A `Schema` maps flattened keys to a `KeySpec`. It is attached to a `ConfigBuilder` and
+//! travels with the resulting `Config`.
+//!
+//! Components embedded in an application can declare the keys they need, with defaults and
+//! validators, in a schema of their own and mount it on the application's `Config` with
+//! `Config::mount_schema`. The single configuration file is then checked against every
+//! component's needs.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::schema::{KeySpec, Schema, Visibility};
+//! let schema = Schema::new()
+//!     .key("LOGGING_LEVEL", KeySpec::new().visibility(Visibility::Public))
+//!     .key("DATABASE_PASSWORD", KeySpec::new().visibility(Visibility::Secret));
+//! ```
+use crate::messages;
+use crate::units::Unit;
+use crate::Value;
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Checks a value, describing the problem when it is rejected.
+pub type Validator = Arc<dyn Fn(&Value) -> Result<(), String> + Send + Sync>;
+
+/// How widely a key's value may be shown.
+///
+/// Levels are ordered from least to most sensitive, so a view at a given level includes every
+/// key at that level or below.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Visibility {
+    /// Safe to expose to anyone, e.g. over a debug endpoint.
+    Public,
+    /// Only for operators of the service. Keys missing from the schema default to this level.
+    #[default]
+    Internal,
+    /// Credentials and other values that must never be shown.
+    Secret,
+}
+
+/// The type a key's value is declared with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Integer,
+    Float,
+    Boolean,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ValueKind::String => "string",
+            ValueKind::Integer => "integer",
+            ValueKind::Float => "float",
+            ValueKind::Boolean => "boolean",
+        })
+    }
+}
+
+/// The declaration for a single key.
+#[derive(Clone, Default)]
+pub struct KeySpec {
+    visibility: Visibility,
+    required: bool,
+    default: Option<Value>,
+    validator: Option<Validator>,
+    unit: Option<Unit>,
+    restart_required: bool,
+    example: Option<Value>,
+    prompt: Option<String>,
+    kind: Option<ValueKind>,
+}
+
+impl fmt::Debug for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeySpec")
+            .field("visibility", &self.visibility)
+            .field("required", &self.required)
+            .field("default", &self.default)
+            .field("validator", &self.validator.is_some())
+            .field("unit", &self.unit)
+            .field("restart_required", &self.restart_required)
+            .field("example", &self.example)
+            .field("prompt", &self.prompt)
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+impl KeySpec {
+    /// Creates a spec with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the visibility of the key.
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Requires the key to be present, either loaded or through its default.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Sets the value used when the key isn't loaded.
+    pub fn default_value<V: Into<Value>>(mut self, val: V) -> Self {
+        self.default = Some(val.into());
+        self
+    }
+
+    /// Sets a check the key's value has to pass.
+    pub fn validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&Value) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Declares the unit the key is expected in. Quantities such as `30s` are converted to a
+    /// number of this unit, and quantities of another kind are rejected. See the `units` module.
+    pub fn unit(mut self, unit: Unit) -> Self {
+        self.unit = Some(unit);
+        self
+    }
+
+    /// Marks the key as only taking effect on restart. `SharedConfig::reload` keeps its
+    /// current value and reports the change as pending instead of applying it. Keys are
+    /// hot-reloadable by default.
+    pub fn restart_required(mut self) -> Self {
+        self.restart_required = true;
+        self
+    }
+
+    /// Sets an example of a valid value, shown when the key is missing. See the `missing`
+    /// module.
+    pub fn example<V: Into<Value>>(mut self, val: V) -> Self {
+        self.example = Some(val.into());
+        self
+    }
+
+    /// Sets the question asking for the key's value in a setup wizard. See the `wizard`
+    /// module.
+    pub fn prompt(mut self, prompt: &str) -> Self {
+        self.prompt = Some(prompt.to_string());
+        self
+    }
+
+    /// Declares the type of the key's value, which answers in a setup wizard are parsed as.
+    pub fn kind(mut self, kind: ValueKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// The visibility of the key.
+    pub fn get_visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    /// Whether the key is required.
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    /// The default value of the key, if any.
+    pub fn get_default(&self) -> Option<&Value> {
+        self.default.as_ref()
+    }
+
+    /// The example value of the key, if any.
+    pub fn get_example(&self) -> Option<&Value> {
+        self.example.as_ref()
+    }
+
+    /// The question asking for the key's value, if any.
+    pub fn get_prompt(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+
+    /// The declared type of the key's value, if any.
+    pub fn get_kind(&self) -> Option<ValueKind> {
+        self.kind
+    }
+
+    /// The unit the key is expected in, if declared.
+    pub fn get_unit(&self) -> Option<Unit> {
+        self.unit
+    }
+
+    /// Whether a change to the key only takes effect on restart.
+    pub fn requires_restart(&self) -> bool {
+        self.restart_required
+    }
+
+    /// Checks `val`, the value of `key` or `None` when it is missing.
+    pub fn check(&self, key: &str, val: Option<&Value>) -> Result<(), String> {
+        match (val, &self.validator) {
+            (None, _) if self.required => Err(messages::text("schema.required", &[("key", key)])),
+            (Some(val), Some(validator)) => validator(val)
+                .map_err(|e| messages::text("schema.invalid", &[("key", key), ("problem", &e)])),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A set of key declarations.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    keys: IndexMap<String, KeySpec, FxBuildHasher>,
+}
+
+impl Schema {
+    /// Creates an empty schema.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a flattened key, replacing any previous declaration.
+    pub fn key(mut self, key: &str, spec: KeySpec) -> Self {
+        self.keys.insert(key.to_string(), spec);
+        self
+    }
+
+    /// Adds every declaration in `other`, replacing declarations for the same keys.
+    pub fn merge(mut self, other: Schema) -> Self {
+        self.keys.extend(other.keys);
+        self
+    }
+
+    /// Returns the declaration for `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&KeySpec> {
+        self.keys.get(key)
+    }
+
+    /// Iterates over every declared key in declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &KeySpec)> {
+        self.keys.iter()
+    }
+
+    /// The visibility of `key`. Undeclared keys are `Visibility::Internal`.
+    pub fn visibility_of(&self, key: &str) -> Visibility {
+        self.get(key)
+            .map(KeySpec::get_visibility)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::schema::{KeySpec, Schema, Visibility};
+    use crate::Value;
+
+    #[test]
+    fn visibility_defaults_to_internal() {
+        let schema = Schema::new().key("A", KeySpec::new().visibility(Visibility::Public));
+
+        assert_eq!(schema.visibility_of("A"), Visibility::Public);
+        assert_eq!(schema.visibility_of("B"), Visibility::Internal);
+    }
+
+    #[test]
+    fn specs_check_presence_and_validity() {
+        let spec = KeySpec::new()
+            .required()
+            .validator(|val| match val.as_i64() {
+                Some(port) if *port > 0 => Ok(()),
+                _ => Err("must be a positive integer".to_string()),
+            });
+
+        assert!(spec.check("PORT", Some(&Value::I64(80))).is_ok());
+        assert_eq!(
+            spec.check("PORT", Some(&Value::I64(0))).unwrap_err(),
+            "PORT: must be a positive integer"
+        );
+        assert_eq!(spec.check("PORT", None).unwrap_err(), "PORT is required.");
+        assert!(KeySpec::new().check("PORT", None).is_ok());
+    }
+
+    #[test]
+    fn visibility_is_ordered() {
+        assert!(Visibility::Public < Visibility::Internal);
+        assert!(Visibility::Internal < Visibility::Secret);
+    }
+}
@@ -0,0 +1,851 @@
+//! A shared, swappable handle to the live configuration.
+//!
+//! `SharedConfig` is cheap to clone and can be handed to every part of an application. Readers
+//! take a snapshot of the current `Config` while writers atomically replace it.
+//!
+//! `SharedConfig::reload` only swaps in a new configuration that passes the live snapshot's
+//! schema and enforced policies. A rejected reload leaves the previous snapshot in place and is
+//! recorded, along with the diff it would have applied, for `SharedConfig::last_reload_failure`
+//! and the health endpoint of `debug::router`.
+//!
+//! Keys declared with `KeySpec::restart_required` are never changed by a reload. Their new
+//! values are reported as pending until the process restarts or a later reload restores the
+//! running value, so a reload is never applied partially without anyone noticing.
+//!
+//! Keys pinned with `SharedConfig::pin`, such as a database URL whose change would drop every
+//! connection, keep their value across reloads until they are unpinned. Reloads carrying a new
+//! value for a pinned key report it in `ReloadOutcome::held_by_pin`.
+//!
+//! Every applied change is kept in a bounded `SharedConfig::history`, so a bad runtime change
+//! can be reverted with `SharedConfig::rollback` without restarting. Applied reloads,
+//! replacements, and rollbacks can also be appended to an audit log with
+//! `SharedConfig::with_audit_log`.
+use crate::audit::{AuditEntry, AuditLog};
+use crate::{messages, snapshot, Config, ErrorCode, ParseError, Value};
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::SystemTime;
+
+/// A thread-safe handle to the current configuration snapshot.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::{ConfigBuilder, SharedConfig};
+/// let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+/// let snapshot = shared.snapshot();
+/// assert!(snapshot.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedConfig {
+    inner: Arc<RwLock<Arc<Config>>>,
+    state: Arc<RwLock<ReloadState>>,
+}
+
+/// The number of snapshots kept in the history unless set with
+/// `SharedConfig::with_history_limit`.
+pub const HISTORY_LIMIT: usize = 16;
+
+/// What is remembered about past reloads.
+///
+/// Every change to the snapshot is made while holding the write lock on this state, so checking
+/// the current snapshot, recording the history, and swapping in the new one are a single step.
+#[derive(Debug)]
+struct ReloadState {
+    last_failure: Option<ReloadFailure>,
+    pending_restart: Vec<String>,
+    pinned: Vec<String>,
+    // Newest first; the front is the current snapshot.
+    history: VecDeque<HistoryEntry>,
+    history_limit: usize,
+    audit_log: Option<AuditLog>,
+}
+
+impl ReloadState {
+    fn record(&mut self, config: Arc<Config>) {
+        self.history.push_front(HistoryEntry::new(config));
+        self.history.truncate(self.history_limit);
+    }
+
+    /// Appends the change from `current` to `next` to the audit log, if there is one.
+    fn audit(&self, source: &str, current: &Config, next: &Config) -> Result<(), ParseError> {
+        let log = match &self.audit_log {
+            Some(log) => log,
+            None => return Ok(()),
+        };
+        log.append(&AuditEntry {
+            at: SystemTime::now(),
+            source: source.to_string(),
+            fingerprint: next.fingerprint(),
+            previous_fingerprint: current.fingerprint(),
+            diff: snapshot::changes(current, next),
+        })
+    }
+}
+
+/// A snapshot that was current at some point.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub config: Arc<Config>,
+    /// `Config::fingerprint` of the snapshot.
+    pub fingerprint: u64,
+    /// When the snapshot became current.
+    pub at: SystemTime,
+}
+
+impl HistoryEntry {
+    fn new(config: Arc<Config>) -> Self {
+        HistoryEntry {
+            fingerprint: config.fingerprint(),
+            config,
+            at: SystemTime::now(),
+        }
+    }
+}
+
+/// The result of an applied reload.
+#[derive(Debug, Clone)]
+pub struct ReloadOutcome {
+    /// The snapshot that was replaced.
+    pub previous: Arc<Config>,
+    /// Restart-required keys whose value in the reloaded configuration differs from the one
+    /// still in use, so a key reverted to its running value is no longer pending.
+    pub pending_restart: Vec<String>,
+    /// Pinned keys whose new value was not applied.
+    pub held_by_pin: Vec<String>,
+}
+
+/// A reload that was rejected, leaving the previous snapshot in place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadFailure {
+    /// Why the reload was rejected.
+    pub error: String,
+    /// The changes the reload would have applied, in the format of `snapshot::render` with
+    /// unchanged lines omitted. Empty when the new configuration couldn't be built at all.
+    pub diff: String,
+    /// When the reload was rejected.
+    pub at: SystemTime,
+}
+
+/// Checks `candidate` against the schema and enforced policies of `current`.
+fn check_candidate(current: &Config, candidate: &Config) -> Result<(), ParseError> {
+    let mut problems: Vec<String> = current
+        .schema()
+        .iter()
+        .filter_map(|(key, spec)| {
+            // Like `Config::mount_schema`, a key loaded as null counts as unset.
+            let val = candidate
+                .get(key)
+                .filter(|val| !val.is_null())
+                .or_else(|| spec.get_default());
+            spec.check(key, val).err()
+        })
+        .collect();
+    // A candidate breaking only policies is rejected as a policy violation.
+    let code = if problems.is_empty() {
+        ErrorCode::PolicyViolation
+    } else {
+        ErrorCode::SchemaViolation
+    };
+    if current.policies().is_enforced() {
+        let report = current.policies().evaluate(candidate);
+        problems.extend(report.violations().map(|r| {
+            messages::text(
+                "reload.policy_violated",
+                &[
+                    ("name", &r.name),
+                    ("problem", r.violation.as_deref().unwrap_or_default()),
+                ],
+            )
+        }));
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(ParseError::with_code(
+            code,
+            "config::shared",
+            messages::text("reload.rejected", &[("problems", &problems.join(" "))]),
+        ))
+    }
+}
+
+impl SharedConfig {
+    /// Wraps `config` in a new shared handle.
+    pub fn new(config: Config) -> Self {
+        let config = Arc::new(config);
+        SharedConfig {
+            state: Arc::new(RwLock::new(ReloadState {
+                last_failure: None,
+                pending_restart: Vec::new(),
+                pinned: Vec::new(),
+                history: VecDeque::from([HistoryEntry::new(Arc::clone(&config))]),
+                history_limit: HISTORY_LIMIT,
+                audit_log: None,
+            })),
+            inner: Arc::new(RwLock::new(config)),
+        }
+    }
+
+    /// Appends every change applied by `reload` and `rollback` to the audit log at `path`.
+    /// See the `audit` module.
+    pub fn with_audit_log<P: AsRef<Path>>(self, path: P) -> Self {
+        self.update_state(|state| state.audit_log = Some(AuditLog::new(path)));
+        self
+    }
+
+    /// Keeps at most `limit` snapshots, including the current one, in the history. A limit
+    /// of zero is treated as one.
+    pub fn with_history_limit(self, limit: usize) -> Self {
+        self.update_state(|state| {
+            state.history_limit = limit.max(1);
+            state.history.truncate(state.history_limit);
+        });
+        self
+    }
+
+    /// Returns the current snapshot. The snapshot is unaffected by later calls to `replace`.
+    pub fn snapshot(&self) -> Arc<Config> {
+        // A poisoned lock still holds a complete snapshot since replacing it is a single move.
+        match self.inner.read() {
+            Ok(guard) => Arc::clone(&guard),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    /// Returns the current snapshot. The same as `snapshot`, for callers that need to own it.
+    pub fn load_full(&self) -> Arc<Config> {
+        self.snapshot()
+    }
+
+    /// Borrows the current snapshot without cloning its `Arc`, for reads in tight loops.
+    ///
+    /// Replacing the snapshot waits until every guard is dropped, so guards should not be held
+    /// for long; use `load_full` to keep a snapshot around.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, SharedConfig};
+    /// let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+    /// let config = shared.read();
+    /// assert!(config.get("WORKERS").is_none());
+    /// ```
+    pub fn read(&self) -> ReadGuard<'_> {
+        ReadGuard {
+            guard: match self.inner.read() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            },
+        }
+    }
+
+    /// Replaces the current snapshot with `config`, returning the previous one. The new
+    /// snapshot is recorded in the history and appended to the audit log with the source
+    /// `replace`. Fails, keeping the current snapshot, only if the audit log can't be written.
+    pub fn replace(&self, config: Config) -> Result<Arc<Config>, ParseError> {
+        let mut state = self.lock_state();
+        state.audit("replace", &self.snapshot(), &config)?;
+        let config = Arc::new(config);
+        state.record(Arc::clone(&config));
+        Ok(self.swap(config))
+    }
+
+    /// Replaces the current snapshot with the configuration returned by `build`, unless it
+    /// fails to build or fails the current snapshot's schema or enforced policies.
+    ///
+    /// A rejected reload keeps the previous snapshot, is recorded as the
+    /// `last_reload_failure`, and returns the error. With the `tracing` feature it also emits
+    /// a warning carrying the diff that would have been applied. A successful reload clears
+    /// the recorded failure.
+    ///
+    /// Values of keys declared with `KeySpec::restart_required` or pinned with `pin` are carried
+    /// over from the current snapshot, and keys whose new value differs are listed in the
+    /// outcome.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, SharedConfig};
+    /// let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+    /// let outcome = shared.reload(|| ConfigBuilder::new().build()).unwrap();
+    /// assert!(outcome.pending_restart.is_empty());
+    /// assert!(shared.last_reload_failure().is_none());
+    /// ```
+    pub fn reload<F>(&self, build: F) -> Result<ReloadOutcome, ParseError>
+    where
+        F: FnOnce() -> Result<Config, ParseError>,
+    {
+        self.reload_from("reload", build)
+    }
+
+    /// Reloads like `reload`, naming who or what requested it, e.g. `sighup` or an operator,
+    /// as the source of the change in the audit log.
+    pub fn reload_from<F>(&self, source: &str, build: F) -> Result<ReloadOutcome, ParseError>
+    where
+        F: FnOnce() -> Result<Config, ParseError>,
+    {
+        // The candidate is built before taking the lock, since building may read files.
+        let built = build();
+        let mut state = self.lock_state();
+        let (result, diff) = match built {
+            Ok(candidate) => {
+                let current = self.snapshot();
+                let diff = snapshot::changes(&current, &candidate);
+                match check_candidate(&current, &candidate)
+                    .and_then(|()| self.apply(&mut state, source, &current, candidate))
+                {
+                    Ok(outcome) => return Ok(outcome),
+                    Err(e) => (e, diff),
+                }
+            }
+            Err(e) => (e, String::new()),
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            target: "yaml_config",
+            error = %result,
+            rejected_diff = %diff,
+            "configuration reload rejected, keeping the previous snapshot"
+        );
+        state.last_failure = Some(ReloadFailure {
+            error: result.to_string(),
+            diff,
+            at: SystemTime::now(),
+        });
+        Err(result)
+    }
+
+    /// Swaps in `candidate` with the restart-required and pinned values of `current` carried
+    /// over. `state` must be the locked state `current` was read under.
+    fn apply(
+        &self,
+        state: &mut ReloadState,
+        source: &str,
+        current: &Config,
+        mut candidate: Config,
+    ) -> Result<ReloadOutcome, ParseError> {
+        let mut pending_restart = Vec::new();
+        for (key, spec) in current.schema().iter() {
+            let old = current.values().get(key);
+            if spec.requires_restart() && candidate.values().get(key) != old {
+                pending_restart.push(key.clone());
+                candidate.set_value(key, old.cloned());
+            }
+        }
+        let mut held_by_pin = Vec::new();
+        for key in &state.pinned {
+            let old = current.values().get(key);
+            if candidate.values().get(key) != old {
+                candidate.set_value(key, old.cloned());
+                held_by_pin.push(key.clone());
+            }
+        }
+
+        state.audit(source, current, &candidate)?;
+        let candidate = Arc::new(candidate);
+        state.last_failure = None;
+        state.record(Arc::clone(&candidate));
+        // The running values were carried over, so the keys differing from them now are exactly
+        // the pending ones.
+        state.pending_restart = pending_restart.clone();
+        Ok(ReloadOutcome {
+            previous: self.swap(candidate),
+            pending_restart,
+            held_by_pin,
+        })
+    }
+
+    /// The snapshots that were current, newest first, so `history()[0]` is the current
+    /// snapshot. Snapshots applied by `replace`, `reload`, and `rollback` are kept, up to the
+    /// history limit.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.update_state(|state| state.history.iter().cloned().collect())
+    }
+
+    /// Makes the snapshot `n` changes back current again, returning the one it replaces. The
+    /// rollback is itself recorded as a change, so `rollback(1)` twice restores the original
+    /// snapshot.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, SharedConfig};
+    /// let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+    /// shared.replace(shared.snapshot().overridden([("WORKERS", 0_i64)])).unwrap();
+    /// shared.rollback(1).unwrap();
+    /// assert!(shared.snapshot().is_empty());
+    /// assert_eq!(shared.history().len(), 3);
+    /// ```
+    pub fn rollback(&self, n: usize) -> Result<Arc<Config>, ParseError> {
+        let mut state = self.lock_state();
+        let target = match state.history.get(n) {
+            Some(entry) => Arc::clone(&entry.config),
+            None => {
+                return Err(ParseError::new(
+                    "config::shared",
+                    format!(
+                        "Cannot roll back {} changes: the history only goes back {}.",
+                        n,
+                        state.history.len() - 1
+                    ),
+                ))
+            }
+        };
+        state.audit("rollback", &self.snapshot(), &target)?;
+        state.record(Arc::clone(&target));
+        Ok(self.swap(target))
+    }
+
+    /// The most recent rejected reload, or `None` if the last reload succeeded or none was
+    /// attempted.
+    pub fn last_reload_failure(&self) -> Option<ReloadFailure> {
+        self.update_state(|state| state.last_failure.clone())
+    }
+
+    /// The most recent rejected reload together with the snapshot served alongside it, read
+    /// under one lock so a concurrent reload can't pair a failure with a later snapshot.
+    pub(crate) fn failure_and_snapshot(&self) -> (Option<ReloadFailure>, Arc<Config>) {
+        self.update_state(|state| (state.last_failure.clone(), self.snapshot()))
+    }
+
+    /// Restart-required keys whose value in the last applied reload differs from the one still
+    /// in use.
+    pub fn pending_restart(&self) -> Vec<String> {
+        self.update_state(|state| state.pending_restart.clone())
+    }
+
+    /// Pins `key` to its current value: reloads keep that value, or keep the key unset, until
+    /// `unpin` is called. `replace` and `rollback` are explicit and still change it.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, SharedConfig, Value};
+    /// let config = ConfigBuilder::new().build().unwrap();
+    /// let shared = SharedConfig::new(config.overridden([("DATABASE_URL", "postgres://a")]));
+    /// shared.pin("DATABASE_URL");
+    /// let outcome = shared
+    ///     .reload(|| Ok(config.overridden([("DATABASE_URL", "postgres://b")])))
+    ///     .unwrap();
+    /// assert_eq!(outcome.held_by_pin, ["DATABASE_URL"]);
+    /// assert_eq!(shared.snapshot().get("DATABASE_URL"), Some(&Value::from("postgres://a")));
+    /// ```
+    pub fn pin(&self, key: &str) {
+        self.update_state(|state| {
+            if !state.pinned.iter().any(|pinned| pinned == key) {
+                state.pinned.push(key.to_string());
+            }
+        })
+    }
+
+    /// Unpins `key`, letting the next reload change it. Returns `false` if it wasn't pinned.
+    pub fn unpin(&self, key: &str) -> bool {
+        self.update_state(|state| {
+            let len = state.pinned.len();
+            state.pinned.retain(|pinned| pinned != key);
+            state.pinned.len() != len
+        })
+    }
+
+    /// The pinned keys, in the order they were pinned.
+    pub fn pinned(&self) -> Vec<String> {
+        self.update_state(|state| state.pinned.clone())
+    }
+
+    fn update_state<T, F: FnOnce(&mut ReloadState) -> T>(&self, f: F) -> T {
+        f(&mut self.lock_state())
+    }
+
+    /// Locks the reload state. Changes to the snapshot are made while holding this lock, and
+    /// the snapshot lock is only ever taken after it.
+    fn lock_state(&self) -> RwLockWriteGuard<'_, ReloadState> {
+        match self.state.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn swap(&self, config: Arc<Config>) -> Arc<Config> {
+        let mut guard = match self.inner.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        std::mem::replace(&mut *guard, config)
+    }
+
+    /// Applies `overrides` to the current snapshot until the returned guard is dropped, at
+    /// which point the snapshot that was current before is restored, even when unwinding from
+    /// a panic. Meant for tests of code that reads a `SharedConfig`.
+    ///
+    /// Replacements made while the guard is alive are discarded when it is dropped.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::{ConfigBuilder, SharedConfig};
+    /// let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+    /// {
+    ///     let _guard = shared.scoped_overrides([("RETRIES", 0_i64)]);
+    ///     assert!(shared.snapshot().contains_key("RETRIES"));
+    /// }
+    /// assert!(!shared.snapshot().contains_key("RETRIES"));
+    /// ```
+    pub fn scoped_overrides<I, K, V>(&self, overrides: I) -> OverrideGuard
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        let previous = self.snapshot();
+        self.swap(Arc::new(previous.overridden(overrides)));
+        OverrideGuard {
+            shared: self.clone(),
+            previous: Some(previous),
+        }
+    }
+}
+
+/// Borrows the current snapshot of a `SharedConfig`. Returned by `SharedConfig::read`.
+#[derive(Debug)]
+pub struct ReadGuard<'a> {
+    guard: RwLockReadGuard<'a, Arc<Config>>,
+}
+
+impl Deref for ReadGuard<'_> {
+    type Target = Config;
+
+    fn deref(&self) -> &Config {
+        &self.guard
+    }
+}
+
+/// Restores the overridden snapshot of a `SharedConfig` when dropped. Returned by
+/// `SharedConfig::scoped_overrides`.
+#[derive(Debug)]
+#[must_use = "the overrides are removed as soon as the guard is dropped"]
+pub struct OverrideGuard {
+    shared: SharedConfig,
+    previous: Option<Arc<Config>>,
+}
+
+impl Drop for OverrideGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            self.shared.swap(previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::policy::Policies;
+    use crate::schema::{KeySpec, Schema};
+    use crate::{ConfigBuilder, ParseError, SharedConfig, Value};
+    use std::fs::File;
+    use std::io::Write;
+    use std::panic;
+    use tempfile::tempdir;
+
+    #[test]
+    fn replace_swaps_the_snapshot() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.yaml");
+        let mut file = File::create(&file_path).unwrap();
+        writeln!(file, "a: 1").unwrap();
+
+        let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+        let before = shared.snapshot();
+        let config = ConfigBuilder::new()
+            .add_file(file_path.to_str().unwrap())
+            .build()
+            .unwrap();
+        shared.replace(config).unwrap();
+
+        assert!(before.is_empty());
+        assert_eq!(shared.snapshot().len(), 1);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn scoped_overrides_are_restored() {
+        let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+
+        {
+            let _guard = shared.scoped_overrides([("A", 1_i64), ("B", 2_i64)]);
+            assert_eq!(shared.snapshot().get("A"), Some(&Value::I64(1)));
+            assert_eq!(shared.snapshot().len(), 2);
+        }
+        assert!(shared.snapshot().is_empty());
+
+        let res = panic::catch_unwind(|| {
+            let _guard = shared.scoped_overrides([("A", "x")]);
+            panic!("test failure");
+        });
+        assert!(res.is_err());
+        assert!(shared.snapshot().is_empty());
+    }
+
+    #[test]
+    fn invalid_reloads_keep_the_previous_snapshot() {
+        let schema = Schema::new().key(
+            "PORT",
+            KeySpec::new().validator(|val| match val {
+                Value::I64(port) if *port > 0 => Ok(()),
+                _ => Err("must be a positive integer".to_string()),
+            }),
+        );
+        let policies = Policies::new()
+            .policy("no-debug", |config| match config.get("DEBUG") {
+                Some(Value::Bool(true)) => Err("DEBUG is on".to_string()),
+                _ => Ok(()),
+            })
+            .enforce();
+        let initial = ConfigBuilder::new()
+            .with_schema(schema)
+            .with_policies(policies)
+            .build()
+            .unwrap()
+            .overridden([("PORT", 80_i64)]);
+        let shared = SharedConfig::new(initial.clone());
+
+        let err = shared
+            .reload(|| Ok(initial.overridden([("PORT", Value::I64(0))])))
+            .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Reload rejected: PORT: must be a positive integer"
+        );
+        assert_eq!(shared.snapshot().get("PORT"), Some(&Value::I64(80)));
+        let failure = shared.last_reload_failure().unwrap();
+        assert_eq!(failure.diff, "- PORT = 80\n+ PORT = 0\n");
+
+        let err = shared
+            .reload(|| Ok(initial.overridden([("DEBUG", true)])))
+            .unwrap_err();
+        assert!(err
+            .message()
+            .contains("Policy no-debug is violated: DEBUG is on"));
+
+        shared
+            .reload(|| Err(ParseError::new("test", "unreadable")))
+            .unwrap_err();
+        assert_eq!(shared.last_reload_failure().unwrap().diff, "");
+
+        shared
+            .reload(|| Ok(initial.overridden([("PORT", 443_i64)])))
+            .unwrap();
+        assert_eq!(shared.snapshot().get("PORT"), Some(&Value::I64(443)));
+        assert!(shared.last_reload_failure().is_none());
+    }
+
+    #[test]
+    fn reloads_with_a_null_required_key_are_rejected() {
+        let schema = Schema::new().key("PORT", KeySpec::new().required());
+        let initial = ConfigBuilder::new()
+            .add_str("port: 80\n")
+            .with_schema(schema)
+            .build()
+            .unwrap();
+        let shared = SharedConfig::new(initial.clone());
+
+        assert!(shared
+            .reload(|| Ok(initial.overridden([("PORT", Value::Null)])))
+            .is_err());
+        assert_eq!(shared.snapshot().get("PORT"), Some(&Value::I64(80)));
+    }
+
+    #[test]
+    fn restart_required_keys_stay_pending() {
+        let schema = Schema::new()
+            .key("POOL_SIZE", KeySpec::new().restart_required())
+            .key("LOG_LEVEL", KeySpec::new());
+        let initial = ConfigBuilder::new()
+            .with_schema(schema)
+            .build()
+            .unwrap()
+            .overridden([
+                ("POOL_SIZE", Value::I64(4)),
+                ("LOG_LEVEL", Value::from("info")),
+            ]);
+        let shared = SharedConfig::new(initial.clone());
+
+        let outcome = shared
+            .reload(|| {
+                Ok(initial.overridden([
+                    ("POOL_SIZE", Value::I64(8)),
+                    ("LOG_LEVEL", Value::from("debug")),
+                ]))
+            })
+            .unwrap();
+
+        assert_eq!(outcome.pending_restart, vec!["POOL_SIZE"]);
+        assert_eq!(
+            outcome.previous.get("LOG_LEVEL"),
+            Some(&Value::from("info"))
+        );
+        assert_eq!(shared.snapshot().get("POOL_SIZE"), Some(&Value::I64(4)));
+        assert_eq!(
+            shared.snapshot().get("LOG_LEVEL"),
+            Some(&Value::from("debug"))
+        );
+
+        assert_eq!(shared.pending_restart(), vec!["POOL_SIZE"]);
+        let outcome = shared.reload(|| Ok(initial.clone())).unwrap();
+        assert!(outcome.pending_restart.is_empty());
+        assert!(shared.pending_restart().is_empty());
+    }
+
+    #[test]
+    fn pinned_keys_survive_reloads_until_unpinned() {
+        let initial = ConfigBuilder::new()
+            .build()
+            .unwrap()
+            .overridden([("DATABASE_URL", "postgres://a"), ("LOG_LEVEL", "info")]);
+        let shared = SharedConfig::new(initial.clone());
+        shared.pin("DATABASE_URL");
+        shared.pin("DATABASE_URL");
+        assert_eq!(shared.pinned(), vec!["DATABASE_URL"]);
+
+        let next = initial.overridden([("DATABASE_URL", "postgres://b"), ("LOG_LEVEL", "debug")]);
+        let outcome = shared.reload(|| Ok(next.clone())).unwrap();
+        assert_eq!(outcome.held_by_pin, vec!["DATABASE_URL"]);
+        let snapshot = shared.snapshot();
+        assert_eq!(
+            snapshot.get("DATABASE_URL"),
+            Some(&Value::from("postgres://a"))
+        );
+        assert_eq!(snapshot.get("LOG_LEVEL"), Some(&Value::from("debug")));
+
+        assert!(shared.unpin("DATABASE_URL"));
+        assert!(!shared.unpin("DATABASE_URL"));
+        let outcome = shared.reload(|| Ok(next)).unwrap();
+        assert!(outcome.held_by_pin.is_empty());
+        assert_eq!(
+            shared.snapshot().get("DATABASE_URL"),
+            Some(&Value::from("postgres://b"))
+        );
+    }
+
+    #[test]
+    fn history_is_bounded_and_rolled_back() {
+        let initial = ConfigBuilder::new().build().unwrap();
+        let shared = SharedConfig::new(initial.clone()).with_history_limit(3);
+        for workers in 1..=3_i64 {
+            shared
+                .reload(|| Ok(initial.overridden([("WORKERS", workers)])))
+                .unwrap();
+        }
+
+        let history = shared.history();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].fingerprint, shared.snapshot().fingerprint());
+        assert_eq!(history[2].config.get("WORKERS"), Some(&Value::I64(1)));
+
+        let replaced = shared.rollback(2).unwrap();
+        assert_eq!(replaced.get("WORKERS"), Some(&Value::I64(3)));
+        assert_eq!(shared.snapshot().get("WORKERS"), Some(&Value::I64(1)));
+        assert_eq!(
+            shared.rollback(3).unwrap_err().message(),
+            "Cannot roll back 3 changes: the history only goes back 2."
+        );
+    }
+
+    #[test]
+    fn applied_changes_are_audited() {
+        let dir = tempdir().unwrap();
+        let log = dir.path().join("audit.jsonl");
+        let initial = ConfigBuilder::new().build().unwrap();
+        let shared = SharedConfig::new(initial.clone()).with_audit_log(&log);
+
+        shared
+            .reload_from("sighup", || Ok(initial.overridden([("PORT", 8080_i64)])))
+            .unwrap();
+        shared.rollback(1).unwrap();
+        shared
+            .replace(initial.overridden([("PORT", 9090_i64)]))
+            .unwrap();
+
+        let written = std::fs::read_to_string(&log).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("\"source\":\"sighup\""));
+        assert!(lines[0].ends_with("\"diff\":\"+ PORT = 8080\\n\"}"));
+        assert!(lines[1].contains("\"source\":\"rollback\""));
+        assert!(lines[1].contains(&format!(
+            "\"fingerprint\":\"{:016x}\"",
+            initial.fingerprint()
+        )));
+        assert!(lines[2].contains("\"source\":\"replace\""));
+
+        let blocked = SharedConfig::new(initial.clone()).with_audit_log(dir.path());
+        assert!(blocked
+            .reload(|| Ok(initial.overridden([("PORT", 1_i64)])))
+            .is_err());
+        assert!(blocked.snapshot().is_empty());
+    }
+
+    #[test]
+    fn concurrent_changes_are_applied_one_at_a_time() {
+        let dir = tempdir().unwrap();
+        let log = dir.path().join("audit.jsonl");
+        let initial = ConfigBuilder::new().build().unwrap();
+        let shared = SharedConfig::new(initial.clone()).with_audit_log(&log);
+
+        let threads: Vec<_> = (0..4_i64)
+            .map(|thread| {
+                let shared = shared.clone();
+                let initial = initial.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25_i64 {
+                        if i % 3 == 2 {
+                            shared.rollback(1).unwrap();
+                        } else {
+                            shared
+                                .reload(|| Ok(initial.overridden([("WORKERS", thread * 100 + i)])))
+                                .unwrap();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Each change replaced exactly the snapshot recorded by the one before it.
+        let written = std::fs::read_to_string(&log).unwrap();
+        let field = |line: &str, name: &str| {
+            let start = line.find(&format!("\"{}\":\"", name)).unwrap() + name.len() + 4;
+            u64::from_str_radix(&line[start..start + 16], 16).unwrap()
+        };
+        let mut fingerprint = initial.fingerprint();
+        for line in written.lines() {
+            assert_eq!(field(line, "previous_fingerprint"), fingerprint);
+            fingerprint = field(line, "fingerprint");
+        }
+        assert_eq!(written.lines().count(), 100);
+        assert_eq!(shared.snapshot().fingerprint(), fingerprint);
+        assert_eq!(shared.history()[0].fingerprint, fingerprint);
+    }
+
+    #[test]
+    fn read_guards_borrow_the_current_snapshot() {
+        let shared = SharedConfig::new(ConfigBuilder::new().build().unwrap());
+        let owned = shared.load_full();
+
+        {
+            let config = shared.read();
+            assert!(std::ptr::eq(&*config, &*owned));
+        }
+        shared.replace(owned.overridden([("A", 1_i64)])).unwrap();
+
+        assert_eq!(shared.read().get("A"), Some(&Value::I64(1)));
+        assert!(owned.is_empty());
+    }
+}
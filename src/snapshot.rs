@@ -0,0 +1,165 @@
+//! Golden-file snapshot testing.
+//!
+//! `Config::assert_matches_snapshot` compares the resolved configuration against a checked-in
+//! snapshot file and panics with a line diff when they differ. The snapshot lists one
+//! `KEY = value` line per key, sorted by key, with values written as JSON and secret values
+//! masked, so it is safe to commit and easy to review.
+//!
+//! Set `UPDATE_SNAPSHOTS=1` to write the current configuration to the snapshot instead. A
+//! missing snapshot is always written.
+use crate::debug::MASK;
+use crate::json::{write_str, write_value};
+use crate::schema::Visibility;
+use crate::Config;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// The environment variable that makes snapshot assertions rewrite their snapshot.
+pub const UPDATE_VAR: &str = "UPDATE_SNAPSHOTS";
+
+/// Renders `config` in the snapshot format.
+pub fn render(config: &Config) -> String {
+    let mut entries: Vec<(&String, String)> = config
+        .values()
+        .iter()
+        .map(|(key, val)| {
+            let mut out = String::new();
+            if config.schema().visibility_of(key) == Visibility::Secret {
+                write_str(MASK, &mut out);
+            } else {
+                write_value(val, &mut out);
+            }
+            (key, out)
+        })
+        .collect();
+    entries.sort();
+
+    let mut out = String::new();
+    for (key, val) in entries {
+        out.push_str(&format!("{} = {}\n", key, val));
+    }
+    out
+}
+
+/// The lines of `render` that differ between `current` and `next`.
+pub(crate) fn changes(current: &Config, next: &Config) -> String {
+    diff(&render(current), &render(next))
+        .lines()
+        .filter(|line| !line.starts_with(' '))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+/// Produces a line diff of `expected` and `actual`. Removed lines start with `-`, added lines
+/// with `+`, and unchanged lines with a space.
+pub(crate) fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push_str(&format!("  {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("- {}\n", old[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+ {}\n", new[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+/// Compares `config` against the snapshot at `path`. See `Config::assert_matches_snapshot`.
+pub(crate) fn assert_matches(config: &Config, path: &Path) {
+    let actual = render(config);
+    let update = env::var_os(UPDATE_VAR).is_some_and(|v| v != "0");
+
+    match fs::read_to_string(path) {
+        Ok(expected) if !update => {
+            if expected != actual {
+                panic!(
+                    "Configuration does not match snapshot {}. Run with {}=1 to update it.\n{}",
+                    path.display(),
+                    UPDATE_VAR,
+                    diff(&expected, &actual)
+                );
+            }
+        }
+        _ => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).unwrap();
+            }
+            fs::write(path, actual)
+                .unwrap_or_else(|e| panic!("Could not write snapshot {}: {}", path.display(), e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::schema::{KeySpec, Schema, Visibility};
+    use crate::snapshot::{diff, render};
+    use crate::Value;
+    use fxhash::FxBuildHasher;
+    use indexmap::IndexMap;
+    use std::fs;
+    use std::panic;
+    use tempfile::tempdir;
+
+    fn config(port: i64) -> Config {
+        let mut values = IndexMap::with_hasher(FxBuildHasher::default());
+        values.insert("PORT".to_string(), Value::I64(port));
+        values.insert("HOST".to_string(), Value::String("db".to_string()));
+        values.insert("PASSWORD".to_string(), Value::String("hunter2".to_string()));
+        let schema = Schema::new().key("PASSWORD", KeySpec::new().visibility(Visibility::Secret));
+        Config::new(values, Vec::new(), schema)
+    }
+
+    #[test]
+    fn snapshots_are_sorted_and_masked() {
+        assert_eq!(
+            render(&config(80)),
+            "HOST = \"db\"\nPASSWORD = \"********\"\nPORT = 80\n"
+        );
+    }
+
+    #[test]
+    fn diffs_show_changed_lines() {
+        assert_eq!(diff("a\nb\nc\n", "a\nx\nc\n"), "  a\n- b\n+ x\n  c\n");
+    }
+
+    #[test]
+    fn snapshots_are_written_then_compared() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("snapshots/config.snap");
+
+        config(80).assert_matches_snapshot(&path);
+        assert!(fs::read_to_string(&path).unwrap().contains("PORT = 80"));
+        config(80).assert_matches_snapshot(&path);
+
+        let res = panic::catch_unwind(|| config(81).assert_matches_snapshot(&path));
+        let message = res.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("- PORT = 80\n+ PORT = 81"));
+
+        dir.close().unwrap();
+    }
+}
@@ -0,0 +1,205 @@
+//! A source backed by a Ctrip Apollo config service.
+//!
+//! Properties namespaces map each Apollo key through the property naming rules, while
+//! namespaces with a `.yaml`, `.yml`, or `.json` suffix are parsed as a whole document.
+//! `wait_for_change` long-polls the notification endpoint so callers know when to rebuild.
+use crate::builder::{Source, SourceKind};
+use crate::json::write_str;
+use crate::sources::http::{encode_component, HttpClient, UreqClient};
+use crate::sources::properties::property_key;
+use crate::{infer_value, parse_str, LoadOptions, ParseError, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::sync::Mutex;
+use yaml_rust::YamlLoader;
+
+/// An Apollo config source.
+///
+/// **Examples**
+///
+/// ```rust,no_run
+/// use yaml_config::sources::apollo::ApolloSource;
+/// use yaml_config::ConfigBuilder;
+/// let config = ConfigBuilder::new()
+///     .add_source(ApolloSource::new("http://apollo:8080", "billing").namespace("application"))
+///     .build();
+/// ```
+pub struct ApolloSource<C = UreqClient> {
+    client: C,
+    server: String,
+    app_id: String,
+    cluster: String,
+    namespace: String,
+    /// The last notification id seen, `-1` before the first notification.
+    notification_id: Mutex<i64>,
+}
+
+impl ApolloSource<UreqClient> {
+    /// Creates a source for `app_id` reading the `default` cluster and `application` namespace.
+    pub fn new(server: &str, app_id: &str) -> Self {
+        Self::with_client(UreqClient::new(), server, app_id)
+    }
+}
+
+impl<C: HttpClient> ApolloSource<C> {
+    /// Creates a source using a custom `HttpClient`.
+    pub fn with_client(client: C, server: &str, app_id: &str) -> Self {
+        ApolloSource {
+            client,
+            server: server.trim_end_matches('/').to_string(),
+            app_id: app_id.to_string(),
+            cluster: "default".to_string(),
+            namespace: "application".to_string(),
+            notification_id: Mutex::new(-1),
+        }
+    }
+
+    /// Sets the cluster to read from.
+    pub fn cluster(mut self, cluster: &str) -> Self {
+        self.cluster = cluster.to_string();
+        self
+    }
+
+    /// Sets the namespace to read from.
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = namespace.to_string();
+        self
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "{}/configs/{}/{}/{}",
+            self.server,
+            encode_component(&self.app_id),
+            encode_component(&self.cluster),
+            encode_component(&self.namespace)
+        )
+    }
+
+    /// Blocks until Apollo reports a new release of the namespace or the long-poll times out.
+    /// Returns `true` when the namespace changed and should be reloaded.
+    pub fn wait_for_change(&self) -> Result<bool, ParseError> {
+        let mut notification_id = self
+            .notification_id
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let mut notifications = String::from("[{\"namespaceName\":");
+        write_str(&self.namespace, &mut notifications);
+        notifications.push_str(&format!(",\"notificationId\":{}}}]", notification_id));
+
+        let response = self.client.get(&format!(
+            "{}/notifications/v2?appId={}&cluster={}&notifications={}",
+            self.server,
+            encode_component(&self.app_id),
+            encode_component(&self.cluster),
+            encode_component(&notifications)
+        ))?;
+
+        // The server answers 304 with an empty body when the poll times out.
+        if response.trim().is_empty() {
+            return Ok(false);
+        }
+
+        let docs = YamlLoader::load_from_str(&response)?;
+        let latest = docs
+            .first()
+            .and_then(|doc| doc.as_vec())
+            .and_then(|entries| {
+                entries
+                    .iter()
+                    .find_map(|entry| entry["notificationId"].as_i64())
+            });
+        match latest {
+            Some(id) => {
+                *notification_id = id;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl<C: HttpClient> Source for ApolloSource<C> {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Custom("apollo".to_string())
+    }
+
+    fn location(&self) -> String {
+        self.url()
+    }
+
+    fn load(
+        &self,
+        options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        let docs = YamlLoader::load_from_str(&self.client.get(&self.url())?)?;
+        let configurations = match docs.first().and_then(|doc| doc["configurations"].as_hash()) {
+            Some(configurations) => configurations,
+            None => {
+                return Err(ParseError::new(
+                    "config::sources::apollo",
+                    "Response does not contain configurations.",
+                ))
+            }
+        };
+
+        let is_document = [".yaml", ".yml", ".json"]
+            .iter()
+            .any(|suffix| self.namespace.ends_with(suffix));
+        if is_document {
+            // Non-properties namespaces deliver the whole file under a single `content` key.
+            let content = configurations
+                .iter()
+                .find(|(key, _)| key.as_str() == Some("content"))
+                .and_then(|(_, val)| val.as_str())
+                .unwrap_or_default();
+            return parse_str(content, options);
+        }
+
+        let mut config = IndexMap::with_hasher(FxBuildHasher::default());
+        for (key, val) in configurations {
+            if let (Some(key), Some(val)) = (key.as_str(), val.as_str()) {
+                config.insert(property_key(key), infer_value(val.to_string()));
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::Source;
+    use crate::sources::apollo::ApolloSource;
+    use crate::sources::http::HttpClient;
+    use crate::{LoadOptions, ParseError};
+
+    struct StaticClient;
+
+    impl HttpClient for StaticClient {
+        fn get(&self, url: &str) -> Result<String, ParseError> {
+            if url.starts_with("http://apollo:8080/notifications/v2") {
+                assert!(url.contains("%22notificationId%22%3A-1"));
+                return Ok(r#"[{"namespaceName":"application","notificationId":7}]"#.to_string());
+            }
+            assert_eq!(
+                url,
+                "http://apollo:8080/configs/billing/default/application"
+            );
+            Ok(
+                r#"{"appId":"billing","configurations":{"server.port":"8080","name":"billing"}}"#
+                    .to_string(),
+            )
+        }
+    }
+
+    #[test]
+    fn fetches_and_listens() {
+        let source = ApolloSource::with_client(StaticClient, "http://apollo:8080", "billing");
+        let config = source.load(&LoadOptions::default()).unwrap();
+
+        assert_eq!(*config["SERVER_PORT"].as_i64().unwrap(), 8080);
+        assert_eq!(*config["NAME"].as_string().unwrap(), "billing");
+        assert!(source.wait_for_change().unwrap());
+        assert_eq!(*source.notification_id.lock().unwrap(), 7);
+    }
+}
@@ -0,0 +1,174 @@
+//! A source backed by a gRPC config service.
+//!
+//! The service contract lives in `proto/config_service.proto`. The message types here derive
+//! `prost::Message` so they can be used directly with a `tonic` channel; the transport itself is
+//! supplied by implementing `ConfigServiceClient`, which keeps this crate free of an async runtime.
+use crate::builder::{Source, SourceKind};
+use crate::{infer_value, normalize_key, LoadOptions, ParseError, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+
+/// The `FetchRequest` message of the config service contract.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FetchRequest {
+    #[prost(string, tag = "1")]
+    pub application: String,
+    #[prost(string, tag = "2")]
+    pub environment: String,
+}
+
+/// The `FetchResponse` message of the config service contract.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FetchResponse {
+    #[prost(string, tag = "1")]
+    pub version: String,
+    #[prost(map = "string, string", tag = "2")]
+    pub values: HashMap<String, String>,
+}
+
+/// A stream of responses returned by `ConfigServiceClient::watch`.
+pub type ResponseStream = Box<dyn Iterator<Item = Result<FetchResponse, ParseError>>>;
+
+/// The transport used to talk to the config service.
+pub trait ConfigServiceClient {
+    /// Performs the `Fetch` RPC.
+    fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse, ParseError>;
+
+    /// Performs the `Watch` RPC.
+    fn watch(&self, request: &FetchRequest) -> Result<ResponseStream, ParseError>;
+}
+
+/// Converts a response into a flattened map. Keys are normalized and values are typed the same
+/// way as environment values.
+fn response_to_map(response: FetchResponse) -> IndexMap<String, Value, FxBuildHasher> {
+    let mut values: Vec<(String, String)> = response.values.into_iter().collect();
+    // Protobuf maps are unordered; sort so the resulting config is deterministic.
+    values.sort();
+    values
+        .into_iter()
+        .map(|(key, val)| (normalize_key(&key), infer_value(val)))
+        .collect()
+}
+
+/// A source that fetches its values from a config service.
+///
+/// **Examples**
+///
+/// ```rust,ignore
+/// use yaml_config::sources::grpc::GrpcSource;
+/// use yaml_config::ConfigBuilder;
+/// let config = ConfigBuilder::new()
+///     .add_file("path/to/base.yaml")
+///     .add_source(GrpcSource::new(my_client, "billing", "prod"))
+///     .build();
+/// ```
+pub struct GrpcSource<C> {
+    client: C,
+    request: FetchRequest,
+}
+
+impl<C: ConfigServiceClient> GrpcSource<C> {
+    /// Creates a source fetching the configuration of `application` in `environment`.
+    pub fn new(client: C, application: &str, environment: &str) -> Self {
+        GrpcSource {
+            client,
+            request: FetchRequest {
+                application: application.to_string(),
+                environment: environment.to_string(),
+            },
+        }
+    }
+
+    /// Subscribes to configuration changes. Every item is the complete flattened map of a
+    /// new version.
+    pub fn updates(
+        &self,
+    ) -> Result<
+        impl Iterator<Item = Result<IndexMap<String, Value, FxBuildHasher>, ParseError>>,
+        ParseError,
+    > {
+        let stream = self.client.watch(&self.request)?;
+        Ok(stream.map(|response| response.map(response_to_map)))
+    }
+}
+
+impl<C: ConfigServiceClient> Source for GrpcSource<C> {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Custom("grpc".to_string())
+    }
+
+    fn location(&self) -> String {
+        format!(
+            "grpc:{}/{}",
+            self.request.application, self.request.environment
+        )
+    }
+
+    fn load(
+        &self,
+        _options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        Ok(response_to_map(self.client.fetch(&self.request)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sources::grpc::{
+        ConfigServiceClient, FetchRequest, FetchResponse, GrpcSource, ResponseStream,
+    };
+    use crate::{ConfigBuilder, ParseError};
+    use std::collections::HashMap;
+
+    struct StaticClient;
+
+    fn response(version: &str, port: &str) -> FetchResponse {
+        let mut values = HashMap::new();
+        values.insert("database.port".to_string(), port.to_string());
+        values.insert("database.host".to_string(), "db".to_string());
+        FetchResponse {
+            version: version.to_string(),
+            values,
+        }
+    }
+
+    impl ConfigServiceClient for StaticClient {
+        fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse, ParseError> {
+            assert_eq!(request.application, "billing");
+            Ok(response("1", "5432"))
+        }
+
+        fn watch(&self, _request: &FetchRequest) -> Result<ResponseStream, ParseError> {
+            Ok(Box::new(vec![Ok(response("2", "6543"))].into_iter()))
+        }
+    }
+
+    #[test]
+    fn fetches_flattened_values() {
+        let config = ConfigBuilder::new()
+            .add_source(GrpcSource::new(StaticClient, "billing", "prod"))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            *config.get("DATABASE_PORT").unwrap().as_i64().unwrap(),
+            5432
+        );
+        assert_eq!(
+            *config.get("DATABASE_HOST").unwrap().as_string().unwrap(),
+            "db"
+        );
+        assert_eq!(config.layers()[0].location, "grpc:billing/prod");
+    }
+
+    #[test]
+    fn streams_updates() {
+        let source = GrpcSource::new(StaticClient, "billing", "prod");
+        let updates: Vec<_> = source.updates().unwrap().collect();
+
+        assert_eq!(updates.len(), 1);
+        let update = updates[0].as_ref().unwrap();
+        assert_eq!(*update["DATABASE_PORT"].as_i64().unwrap(), 6543);
+    }
+}
@@ -0,0 +1,140 @@
+//! The HTTP transport shared by HTTP based sources.
+//!
+//! Sources take any `HttpClient`, which makes them easy to test and lets applications reuse
+//! their own client. `UreqClient` is a ready-made blocking implementation.
+use crate::{ErrorCode, ParseError};
+
+/// A minimal blocking HTTP client.
+pub trait HttpClient {
+    /// Performs a `GET` request and returns the response body. Non-success statuses are errors.
+    /// Failing to reach the server is an error with the code `ErrorCode::SourceUnreachable`, which
+    /// optional layers skip.
+    fn get(&self, url: &str) -> Result<String, ParseError>;
+
+    /// Performs a `POST` request with a form encoded `body` and the extra `headers`, returning
+    /// the response body. Only needed by sources that long-poll with `POST`.
+    fn post_form(
+        &self,
+        url: &str,
+        _headers: &[(&str, &str)],
+        _body: &str,
+    ) -> Result<String, ParseError> {
+        Err(ParseError::new(
+            "config::sources::http",
+            format!("POST {} is not supported by this client.", url),
+        ))
+    }
+}
+
+/// Converts a failed request into a `ParseError`, telling a server that can't be reached apart
+/// from one that answered with an error.
+fn request_error(method: &str, url: &str, error: ureq::Error) -> ParseError {
+    let code = match error {
+        ureq::Error::Io(_)
+        | ureq::Error::Timeout(_)
+        | ureq::Error::HostNotFound
+        | ureq::Error::ConnectionFailed => ErrorCode::SourceUnreachable,
+        _ => ErrorCode::Other,
+    };
+    ParseError::with_code(
+        code,
+        "ureq",
+        format!("{} {} failed: {}", method, url, error),
+    )
+}
+
+/// Percent-encodes `s` for use as a query or form component.
+pub fn encode_component(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// An `HttpClient` backed by `ureq`.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::sources::http::UreqClient;
+/// let client = UreqClient::new().with_header("Authorization", "Basic dXNlcjpwYXNz");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UreqClient {
+    agent: ureq::Agent,
+    headers: Vec<(String, String)>,
+}
+
+impl Default for UreqClient {
+    fn default() -> Self {
+        UreqClient {
+            agent: ureq::Agent::new_with_defaults(),
+            headers: Vec::new(),
+        }
+    }
+}
+
+impl UreqClient {
+    /// Creates a client with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a header sent with every request, e.g. for authentication.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl HttpClient for UreqClient {
+    fn get(&self, url: &str) -> Result<String, ParseError> {
+        let mut request = self.agent.get(url);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .map_err(|e| request_error("GET", url, e))
+    }
+
+    fn post_form(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> Result<String, ParseError> {
+        let mut request = self
+            .agent
+            .post(url)
+            .header("Content-Type", "application/x-www-form-urlencoded");
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request
+            .send(body)
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .map_err(|e| request_error("POST", url, e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sources::http::encode_component;
+
+    #[test]
+    fn components_are_percent_encoded() {
+        assert_eq!(encode_component("a b/c~d"), "a%20b%2Fc~d");
+        assert_eq!(encode_component("\u{1}"), "%01");
+    }
+}
@@ -0,0 +1,217 @@
+//! A source reading attributes of a single LDAP / Active Directory entry.
+//!
+//! Some directories store a handful of settings (often rotated credentials) as attributes of a
+//! service account. Each attribute is explicitly mapped to a flattened key. Single valued
+//! attributes are typed the same way as environment values while multi-valued attributes
+//! become a `Value::Array`.
+use crate::builder::{Source, SourceKind};
+use crate::{infer_value, ErrorCode, LoadOptions, ParseError, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+
+/// The LDAP operation needed by `LdapSource`.
+pub trait LdapClient {
+    /// Reads the attributes `names` of the entry `dn`. Attributes missing from the entry are
+    /// absent from the result.
+    fn attributes(
+        &self,
+        dn: &str,
+        names: &[&str],
+    ) -> Result<HashMap<String, Vec<String>>, ParseError>;
+}
+
+/// Converts an `ldap3` error into a `ParseError`.
+fn ldap_error(error: ldap3::LdapError) -> ParseError {
+    ParseError::new("ldap3", error.to_string())
+}
+
+/// An `LdapClient` backed by `ldap3`. A connection is opened for every read.
+#[derive(Debug, Clone)]
+pub struct Ldap3Client {
+    url: String,
+    bind: Option<(String, String)>,
+}
+
+impl Ldap3Client {
+    /// Creates a client for the server at `url`, e.g. `ldaps://ldap.example.com`.
+    pub fn new(url: &str) -> Self {
+        Ldap3Client {
+            url: url.to_string(),
+            bind: None,
+        }
+    }
+
+    /// Authenticates with a simple bind before reading.
+    pub fn bind(mut self, dn: &str, password: &str) -> Self {
+        self.bind = Some((dn.to_string(), password.to_string()));
+        self
+    }
+}
+
+impl LdapClient for Ldap3Client {
+    fn attributes(
+        &self,
+        dn: &str,
+        names: &[&str],
+    ) -> Result<HashMap<String, Vec<String>>, ParseError> {
+        let mut conn = ldap3::LdapConn::new(&self.url).map_err(|e| {
+            ParseError::with_code(ErrorCode::SourceUnreachable, "ldap3", e.to_string())
+        })?;
+        if let Some((bind_dn, password)) = &self.bind {
+            conn.simple_bind(bind_dn, password)
+                .and_then(|res| res.success())
+                .map_err(ldap_error)?;
+        }
+        let (entries, _) = conn
+            .search(dn, ldap3::Scope::Base, "(objectClass=*)", names.to_vec())
+            .and_then(|res| res.success())
+            .map_err(ldap_error)?;
+        conn.unbind().map_err(ldap_error)?;
+
+        Ok(entries
+            .into_iter()
+            .next()
+            .map(|entry| ldap3::SearchEntry::construct(entry).attrs)
+            .unwrap_or_default())
+    }
+}
+
+/// A source mapping LDAP attributes to keys.
+///
+/// **Examples**
+///
+/// ```rust,no_run
+/// use yaml_config::sources::ldap::{Ldap3Client, LdapSource};
+/// use yaml_config::ConfigBuilder;
+/// let client = Ldap3Client::new("ldaps://ldap.example.com").bind("cn=reader", "secret");
+/// let config = ConfigBuilder::new()
+///     .add_source(
+///         LdapSource::with_client(client, "cn=billing,ou=services,dc=example,dc=com")
+///             .attribute("userPassword", "DATABASE_PASSWORD"),
+///     )
+///     .build();
+/// ```
+pub struct LdapSource<C = Ldap3Client> {
+    client: C,
+    dn: String,
+    mappings: Vec<(String, String)>,
+}
+
+impl LdapSource<Ldap3Client> {
+    /// Creates a source reading the entry `dn` anonymously from the server at `url`.
+    pub fn new(url: &str, dn: &str) -> Self {
+        Self::with_client(Ldap3Client::new(url), dn)
+    }
+}
+
+impl<C: LdapClient> LdapSource<C> {
+    /// Creates a source reading the entry `dn`.
+    pub fn with_client(client: C, dn: &str) -> Self {
+        LdapSource {
+            client,
+            dn: dn.to_string(),
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Maps the attribute `name` to the flattened `key`.
+    pub fn attribute(mut self, name: &str, key: &str) -> Self {
+        self.mappings.push((name.to_string(), key.to_string()));
+        self
+    }
+}
+
+impl<C: LdapClient> Source for LdapSource<C> {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Custom("ldap".to_string())
+    }
+
+    fn location(&self) -> String {
+        self.dn.clone()
+    }
+
+    fn load(
+        &self,
+        _options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        let names: Vec<&str> = self
+            .mappings
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let attributes = self.client.attributes(&self.dn, &names)?;
+
+        let mut config = IndexMap::with_hasher(FxBuildHasher::default());
+        for (name, key) in &self.mappings {
+            // Attribute names are case-insensitive in LDAP.
+            let values = attributes
+                .iter()
+                .find(|(attr, _)| attr.eq_ignore_ascii_case(name))
+                .map(|(_, values)| values);
+            let val = match values.map(Vec::as_slice) {
+                None | Some([]) => continue,
+                Some([single]) => infer_value(single.clone()),
+                Some(many) => Value::Array(many.iter().cloned().map(infer_value).collect()),
+            };
+            config.insert(key.clone(), val);
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sources::ldap::{LdapClient, LdapSource};
+    use crate::{ConfigBuilder, ParseError};
+    use std::collections::HashMap;
+
+    struct StaticClient;
+
+    impl LdapClient for StaticClient {
+        fn attributes(
+            &self,
+            dn: &str,
+            names: &[&str],
+        ) -> Result<HashMap<String, Vec<String>>, ParseError> {
+            assert_eq!(dn, "cn=billing");
+            assert_eq!(names, ["userPassword", "mail", "missing"]);
+            let mut attributes = HashMap::new();
+            attributes.insert("userpassword".to_string(), vec!["hunter2".to_string()]);
+            attributes.insert(
+                "mail".to_string(),
+                vec!["a@example.com".to_string(), "b@example.com".to_string()],
+            );
+            Ok(attributes)
+        }
+    }
+
+    #[test]
+    fn maps_attributes_to_keys() {
+        let source = LdapSource::with_client(StaticClient, "cn=billing")
+            .attribute("userPassword", "DATABASE_PASSWORD")
+            .attribute("mail", "ALERT_EMAILS")
+            .attribute("missing", "MISSING");
+        let config = ConfigBuilder::new().add_source(source).build().unwrap();
+
+        assert_eq!(config.len(), 2);
+        assert_eq!(
+            *config
+                .get("DATABASE_PASSWORD")
+                .unwrap()
+                .as_string()
+                .unwrap(),
+            "hunter2"
+        );
+        assert_eq!(
+            config
+                .get("ALERT_EMAILS")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,24 @@
+//! Additional sources for `ConfigBuilder`.
+//!
+//! Each source implements `builder::Source` and is gated behind its own feature so the default
+//! build stays lean.
+#[cfg(feature = "apollo")]
+pub mod apollo;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "ldap")]
+pub mod ldap;
+#[cfg(feature = "nacos")]
+pub mod nacos;
+#[cfg(any(feature = "spring-cloud", feature = "nacos", feature = "apollo"))]
+mod properties;
+#[cfg(feature = "windows")]
+pub mod registry;
+#[cfg(feature = "spring-cloud")]
+pub mod spring;
+#[cfg(feature = "systemd")]
+pub mod systemd;
+#[cfg(feature = "zookeeper")]
+pub mod zookeeper;
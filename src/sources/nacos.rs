@@ -0,0 +1,202 @@
+//! A source backed by an Alibaba Nacos config center.
+//!
+//! The config identified by `data_id` and `group` is fetched through the Nacos open API. Data
+//! ids ending in `.properties` are parsed as properties, anything else as YAML (which includes
+//! JSON). `wait_for_change` long-polls the listener endpoint so callers know when to rebuild.
+use crate::builder::{Source, SourceKind};
+use crate::sources::http::{encode_component, HttpClient, UreqClient};
+use crate::sources::properties::property_key;
+use crate::{infer_value, parse_str, LoadOptions, ParseError, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::sync::Mutex;
+
+/// How long the server may hold a long-poll request, in milliseconds.
+const LONG_POLL_TIMEOUT_MS: &str = "30000";
+
+/// Parses a `.properties` document. Values are typed the same way as environment values.
+///
+/// Blank lines and lines starting with `#` or `!` are ignored. Keys and values are separated by
+/// the first `=` or `:`.
+fn parse_properties(content: &str) -> IndexMap<String, Value, FxBuildHasher> {
+    let mut config = IndexMap::with_hasher(FxBuildHasher::default());
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        let (name, val) = match line.find(['=', ':']) {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => (line, ""),
+        };
+        config.insert(
+            property_key(name.trim()),
+            infer_value(val.trim().to_string()),
+        );
+    }
+    config
+}
+
+/// A Nacos config source.
+///
+/// **Examples**
+///
+/// ```rust,no_run
+/// use yaml_config::sources::nacos::NacosSource;
+/// use yaml_config::ConfigBuilder;
+/// let config = ConfigBuilder::new()
+///     .add_source(NacosSource::new("http://nacos:8848", "billing.yaml", "DEFAULT_GROUP"))
+///     .build();
+/// ```
+pub struct NacosSource<C = UreqClient> {
+    client: C,
+    server: String,
+    data_id: String,
+    group: String,
+    tenant: Option<String>,
+    /// MD5 of the last fetched content, used by the listener to detect changes.
+    md5: Mutex<String>,
+}
+
+impl NacosSource<UreqClient> {
+    /// Creates a source for the config `data_id` in `group`.
+    pub fn new(server: &str, data_id: &str, group: &str) -> Self {
+        Self::with_client(UreqClient::new(), server, data_id, group)
+    }
+}
+
+impl<C: HttpClient> NacosSource<C> {
+    /// Creates a source using a custom `HttpClient`.
+    pub fn with_client(client: C, server: &str, data_id: &str, group: &str) -> Self {
+        NacosSource {
+            client,
+            server: server.trim_end_matches('/').to_string(),
+            data_id: data_id.to_string(),
+            group: group.to_string(),
+            tenant: None,
+            md5: Mutex::new(String::new()),
+        }
+    }
+
+    /// Sets the tenant (namespace id) to read from.
+    pub fn tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self
+    }
+
+    fn url(&self) -> String {
+        let mut url = format!(
+            "{}/nacos/v1/cs/configs?dataId={}&group={}",
+            self.server,
+            encode_component(&self.data_id),
+            encode_component(&self.group)
+        );
+        if let Some(tenant) = &self.tenant {
+            url.push_str("&tenant=");
+            url.push_str(&encode_component(tenant));
+        }
+        url
+    }
+
+    /// Blocks until Nacos reports a change to the config or the long-poll times out. Returns
+    /// `true` when the config changed and should be reloaded.
+    pub fn wait_for_change(&self) -> Result<bool, ParseError> {
+        let md5 = self.md5.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        // Fields are separated by 0x02 and the entry is terminated by 0x01.
+        let mut listening = format!("{}\u{2}{}\u{2}{}", self.data_id, self.group, md5);
+        if let Some(tenant) = &self.tenant {
+            listening.push('\u{2}');
+            listening.push_str(tenant);
+        }
+        listening.push('\u{1}');
+
+        let response = self.client.post_form(
+            &format!("{}/nacos/v1/cs/configs/listener", self.server),
+            &[("Long-Pulling-Timeout", LONG_POLL_TIMEOUT_MS)],
+            &format!("Listening-Configs={}", encode_component(&listening)),
+        )?;
+
+        Ok(!response.trim().is_empty())
+    }
+}
+
+impl<C: HttpClient> Source for NacosSource<C> {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Custom("nacos".to_string())
+    }
+
+    fn location(&self) -> String {
+        self.url()
+    }
+
+    fn load(
+        &self,
+        options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        let content = self.client.get(&self.url())?;
+        *self.md5.lock().unwrap_or_else(|e| e.into_inner()) =
+            format!("{:x}", md5::compute(content.as_bytes()));
+
+        if self.data_id.ends_with(".properties") {
+            Ok(parse_properties(&content))
+        } else {
+            parse_str(&content, options)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::Source;
+    use crate::sources::http::HttpClient;
+    use crate::sources::nacos::{parse_properties, NacosSource};
+    use crate::{LoadOptions, ParseError};
+
+    struct StaticClient;
+
+    impl HttpClient for StaticClient {
+        fn get(&self, url: &str) -> Result<String, ParseError> {
+            assert_eq!(
+                url,
+                "http://nacos:8848/nacos/v1/cs/configs?dataId=billing.yaml&group=DEFAULT_GROUP&tenant=prod"
+            );
+            Ok("database:\n  port: 5432\n".to_string())
+        }
+
+        fn post_form(
+            &self,
+            _url: &str,
+            headers: &[(&str, &str)],
+            body: &str,
+        ) -> Result<String, ParseError> {
+            assert_eq!(headers[0].0, "Long-Pulling-Timeout");
+            // The md5 of the content returned by `get`.
+            assert!(body.contains("%025b2e5dfc2eb2ec6f5468e2f351a23408%02prod%01"));
+            Ok("billing.yaml%02DEFAULT_GROUP%02prod%01".to_string())
+        }
+    }
+
+    #[test]
+    fn properties_are_parsed() {
+        let config = parse_properties("# comment\nserver.port = 8080\nname: billing\n\n");
+
+        assert_eq!(config.len(), 2);
+        assert_eq!(*config["SERVER_PORT"].as_i64().unwrap(), 8080);
+        assert_eq!(*config["NAME"].as_string().unwrap(), "billing");
+    }
+
+    #[test]
+    fn fetches_and_listens() {
+        let source = NacosSource::with_client(
+            StaticClient,
+            "http://nacos:8848/",
+            "billing.yaml",
+            "DEFAULT_GROUP",
+        )
+        .tenant("prod");
+        let config = source.load(&LoadOptions::default()).unwrap();
+
+        assert_eq!(*config["DATABASE_PORT"].as_i64().unwrap(), 5432);
+        assert!(source.wait_for_change().unwrap());
+    }
+}
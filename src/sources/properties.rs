@@ -0,0 +1,21 @@
+//! Helpers for sources that deliver Java-style flat properties.
+use crate::normalize_key;
+
+/// Converts a property name such as `spring.datasource.url` or `hosts[0]` into a flattened key.
+pub(crate) fn property_key(name: &str) -> String {
+    normalize_key(&name.replace('[', ".").replace(']', ""))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sources::properties::property_key;
+
+    #[test]
+    fn property_names_are_flattened() {
+        assert_eq!(
+            property_key("spring.datasource.url"),
+            "SPRING_DATASOURCE_URL"
+        );
+        assert_eq!(property_key("hosts[10]"), "HOSTS_10");
+    }
+}
@@ -0,0 +1,258 @@
+//! A source reading a Windows Registry subtree.
+//!
+//! Every value below the root key becomes a flattened key built from the subkey path and the
+//! value name, so `Overrides\Database` value `Port` under root `Software\Billing` becomes
+//! `OVERRIDES_DATABASE_PORT`. A subkey's default (unnamed) value maps to the subkey's own key.
+//! Strings are typed the same way as environment values, `REG_DWORD` and `REG_QWORD` become
+//! integers and `REG_MULTI_SZ` becomes a `Value::Array`. Other value types are ignored.
+//!
+//! `RegistrySource::new` reads the real registry on Windows. The walk itself is defined over
+//! `RegistryReader` so it can be exercised on any platform.
+use crate::builder::{Source, SourceKind};
+use crate::{infer_value, normalize_key, LoadOptions, ParseError, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+
+/// A registry value of a supported type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryValue {
+    /// `REG_SZ` or `REG_EXPAND_SZ`.
+    String(String),
+    /// `REG_DWORD`.
+    U32(u32),
+    /// `REG_QWORD`.
+    U64(u64),
+    /// `REG_MULTI_SZ`.
+    MultiString(Vec<String>),
+}
+
+/// The registry operations needed by `RegistrySource`. Paths are relative to the hive.
+pub trait RegistryReader {
+    /// Returns the names of the subkeys of `path`.
+    fn subkeys(&self, path: &str) -> Result<Vec<String>, ParseError>;
+
+    /// Returns the supported values of `path`. The default value has an empty name.
+    fn values(&self, path: &str) -> Result<Vec<(String, RegistryValue)>, ParseError>;
+}
+
+/// The registry hive to read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hive {
+    /// `HKEY_LOCAL_MACHINE`.
+    LocalMachine,
+    /// `HKEY_CURRENT_USER`.
+    CurrentUser,
+}
+
+/// A `RegistryReader` over the real Windows Registry.
+#[cfg(windows)]
+pub struct WinRegReader {
+    hive: winreg::RegKey,
+}
+
+#[cfg(windows)]
+impl WinRegReader {
+    /// Creates a reader for `hive`.
+    pub fn new(hive: Hive) -> Self {
+        let hkey = match hive {
+            Hive::LocalMachine => winreg::enums::HKEY_LOCAL_MACHINE,
+            Hive::CurrentUser => winreg::enums::HKEY_CURRENT_USER,
+        };
+        WinRegReader {
+            hive: winreg::RegKey::predef(hkey),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl RegistryReader for WinRegReader {
+    fn subkeys(&self, path: &str) -> Result<Vec<String>, ParseError> {
+        let key = self.hive.open_subkey(path)?;
+        Ok(key.enum_keys().collect::<Result<Vec<String>, _>>()?)
+    }
+
+    fn values(&self, path: &str) -> Result<Vec<(String, RegistryValue)>, ParseError> {
+        use winreg::enums::RegType;
+        use winreg::types::FromRegValue;
+
+        let key = self.hive.open_subkey(path)?;
+        let mut values = Vec::new();
+        for entry in key.enum_values() {
+            let (name, raw) = entry?;
+            let val = match raw.vtype {
+                RegType::REG_SZ | RegType::REG_EXPAND_SZ => {
+                    RegistryValue::String(String::from_reg_value(&raw)?)
+                }
+                RegType::REG_DWORD => RegistryValue::U32(u32::from_reg_value(&raw)?),
+                RegType::REG_QWORD => RegistryValue::U64(u64::from_reg_value(&raw)?),
+                RegType::REG_MULTI_SZ => {
+                    RegistryValue::MultiString(Vec::<String>::from_reg_value(&raw)?)
+                }
+                _ => continue,
+            };
+            values.push((name, val));
+        }
+        Ok(values)
+    }
+}
+
+/// Converts a registry value into a `Value`.
+fn to_value(key: &str, val: RegistryValue) -> Result<Value, ParseError> {
+    Ok(match val {
+        RegistryValue::String(s) => infer_value(s),
+        RegistryValue::U32(v) => Value::I64(i64::from(v)),
+        RegistryValue::U64(v) => Value::I64(i64::try_from(v).map_err(|_| {
+            ParseError::new(
+                "config::sources::registry",
+                format!("Value of {} does not fit in an i64.", key),
+            )
+        })?),
+        RegistryValue::MultiString(items) => {
+            Value::Array(items.into_iter().map(infer_value).collect())
+        }
+    })
+}
+
+/// A source built from a registry subtree.
+///
+/// **Examples**
+///
+/// ```rust,ignore
+/// use yaml_config::sources::registry::{Hive, RegistrySource};
+/// use yaml_config::ConfigBuilder;
+/// let config = ConfigBuilder::new()
+///     .add_file("path/to/yaml/file.yaml")
+///     .add_source(RegistrySource::new(Hive::LocalMachine, r"Software\Billing"))
+///     .build();
+/// ```
+pub struct RegistrySource<R> {
+    reader: R,
+    root: String,
+}
+
+#[cfg(windows)]
+impl RegistrySource<WinRegReader> {
+    /// Creates a source reading the subtree at `root` in `hive`.
+    pub fn new(hive: Hive, root: &str) -> Self {
+        Self::with_reader(WinRegReader::new(hive), root)
+    }
+}
+
+impl<R: RegistryReader> RegistrySource<R> {
+    /// Creates a source using a custom `RegistryReader`.
+    pub fn with_reader(reader: R, root: &str) -> Self {
+        RegistrySource {
+            reader,
+            root: root.trim_end_matches('\\').to_string(),
+        }
+    }
+
+    fn visit(
+        &self,
+        path: &str,
+        key: Option<&str>,
+        config: &mut IndexMap<String, Value, FxBuildHasher>,
+    ) -> Result<(), ParseError> {
+        for (name, val) in self.reader.values(path)? {
+            let val_key = match (key, name.is_empty()) {
+                (Some(key), true) => key.to_string(),
+                // The root's default value has no key of its own.
+                (None, true) => continue,
+                (Some(key), false) => format!("{}_{}", key, normalize_key(&name)),
+                (None, false) => normalize_key(&name),
+            };
+            let val = to_value(&val_key, val)?;
+            config.insert(val_key, val);
+        }
+
+        let mut subkeys = self.reader.subkeys(path)?;
+        subkeys.sort();
+        for subkey in subkeys {
+            let sub_path = format!("{}\\{}", path, subkey);
+            let sub_key = match key {
+                Some(key) => format!("{}_{}", key, normalize_key(&subkey)),
+                None => normalize_key(&subkey),
+            };
+            self.visit(&sub_path, Some(&sub_key), config)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: RegistryReader> Source for RegistrySource<R> {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Custom("registry".to_string())
+    }
+
+    fn location(&self) -> String {
+        self.root.clone()
+    }
+
+    fn load(
+        &self,
+        _options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        let mut config = IndexMap::with_hasher(FxBuildHasher::default());
+        self.visit(&self.root, None, &mut config)?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sources::registry::{RegistryReader, RegistrySource, RegistryValue};
+    use crate::{ConfigBuilder, ParseError};
+
+    struct StaticReader;
+
+    impl RegistryReader for StaticReader {
+        fn subkeys(&self, path: &str) -> Result<Vec<String>, ParseError> {
+            Ok(match path {
+                r"Software\Billing" => vec!["Database".to_string()],
+                _ => Vec::new(),
+            })
+        }
+
+        fn values(&self, path: &str) -> Result<Vec<(String, RegistryValue)>, ParseError> {
+            Ok(match path {
+                r"Software\Billing" => vec![
+                    (
+                        "Log Level".to_string(),
+                        RegistryValue::String("INFO".to_string()),
+                    ),
+                    (
+                        "Hosts".to_string(),
+                        RegistryValue::MultiString(vec!["a".to_string(), "b".to_string()]),
+                    ),
+                ],
+                r"Software\Billing\Database" => vec![
+                    (String::new(), RegistryValue::String("primary".to_string())),
+                    ("Port".to_string(), RegistryValue::U32(5432)),
+                ],
+                _ => Vec::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn reads_the_subtree() {
+        let source = RegistrySource::with_reader(StaticReader, r"Software\Billing\");
+        let config = ConfigBuilder::new().add_source(source).build().unwrap();
+
+        assert_eq!(config.len(), 4);
+        assert_eq!(
+            *config.get("LOG_LEVEL").unwrap().as_string().unwrap(),
+            "INFO"
+        );
+        assert_eq!(config.get("HOSTS").unwrap().as_array().unwrap().len(), 2);
+        assert_eq!(
+            *config.get("DATABASE").unwrap().as_string().unwrap(),
+            "primary"
+        );
+        assert_eq!(
+            *config.get("DATABASE_PORT").unwrap().as_i64().unwrap(),
+            5432
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! A source speaking the Spring Cloud Config Server HTTP API.
+//!
+//! The server is queried at `/{application}/{profile}[/{label}]`. It answers with a list of
+//! property sources ordered from highest to lowest precedence, which are merged accordingly.
+//! Property names such as `spring.datasource.url` or `hosts[0]` are flattened into
+//! `SPRING_DATASOURCE_URL` and `HOSTS_0`.
+use crate::builder::{Source, SourceKind};
+use crate::sources::http::{HttpClient, UreqClient};
+use crate::sources::properties::property_key;
+use crate::{yaml_to_value, LoadOptions, ParseError, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use yaml_rust::YamlLoader;
+
+/// A Spring Cloud Config Server source.
+///
+/// **Examples**
+///
+/// ```rust,no_run
+/// use yaml_config::sources::spring::SpringCloudSource;
+/// use yaml_config::ConfigBuilder;
+/// let config = ConfigBuilder::new()
+///     .add_source(SpringCloudSource::new("http://config:8888", "billing", "prod").label("main"))
+///     .build();
+/// ```
+pub struct SpringCloudSource<C = UreqClient> {
+    client: C,
+    base_url: String,
+    application: String,
+    profile: String,
+    label: Option<String>,
+}
+
+impl SpringCloudSource<UreqClient> {
+    /// Creates a source for `application` with the given comma separated `profile`.
+    pub fn new(base_url: &str, application: &str, profile: &str) -> Self {
+        Self::with_client(UreqClient::new(), base_url, application, profile)
+    }
+}
+
+impl<C: HttpClient> SpringCloudSource<C> {
+    /// Creates a source using a custom `HttpClient`.
+    pub fn with_client(client: C, base_url: &str, application: &str, profile: &str) -> Self {
+        SpringCloudSource {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            application: application.to_string(),
+            profile: profile.to_string(),
+            label: None,
+        }
+    }
+
+    /// Sets the label (usually a git branch) to request.
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    fn url(&self) -> String {
+        let mut url = format!("{}/{}/{}", self.base_url, self.application, self.profile);
+        if let Some(label) = &self.label {
+            // The server expects slashes in labels to be written as `(_)`.
+            url.push('/');
+            url.push_str(&label.replace('/', "(_)"));
+        }
+        url
+    }
+}
+
+/// Parses a Spring Cloud Config environment response into a flattened map.
+fn parse_environment(body: &str) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+    let docs = YamlLoader::load_from_str(body)?;
+    let sources = match docs.first().map(|doc| &doc["propertySources"]) {
+        Some(sources) if sources.is_array() => sources.as_vec().unwrap(),
+        _ => {
+            return Err(ParseError::new(
+                "config::sources::spring",
+                "Response does not contain propertySources.",
+            ))
+        }
+    };
+
+    let mut config = IndexMap::with_hasher(FxBuildHasher::default());
+    // The first property source has the highest precedence, so apply them in reverse.
+    for source in sources.iter().rev() {
+        let properties = match source["source"].as_hash() {
+            Some(properties) => properties,
+            None => continue,
+        };
+        for (name, val) in properties {
+            let name = match name.as_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            if val.is_null() {
+                continue;
+            }
+            let key = property_key(name);
+            let val = yaml_to_value(&key, val)?;
+            config.insert(key, val);
+        }
+    }
+
+    Ok(config)
+}
+
+impl<C: HttpClient> Source for SpringCloudSource<C> {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Custom("spring-cloud-config".to_string())
+    }
+
+    fn location(&self) -> String {
+        self.url()
+    }
+
+    fn load(
+        &self,
+        _options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        parse_environment(&self.client.get(&self.url())?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sources::http::HttpClient;
+    use crate::sources::spring::SpringCloudSource;
+    use crate::{ConfigBuilder, ParseError};
+
+    struct StaticClient;
+
+    impl HttpClient for StaticClient {
+        fn get(&self, url: &str) -> Result<String, ParseError> {
+            assert_eq!(url, "http://config:8888/billing/prod/feature(_)x");
+            Ok(r#"{
+                "name": "billing",
+                "profiles": ["prod"],
+                "propertySources": [
+                    {"name": "billing-prod.yml", "source": {"server.port": 9090}},
+                    {"name": "billing.yml", "source": {"server.port": 8080, "hosts[0]": "a"}}
+                ]
+            }"#
+            .to_string())
+        }
+    }
+
+    #[test]
+    fn property_sources_are_merged_by_precedence() {
+        let source =
+            SpringCloudSource::with_client(StaticClient, "http://config:8888/", "billing", "prod")
+                .label("feature/x");
+        let config = ConfigBuilder::new().add_source(source).build().unwrap();
+
+        assert_eq!(*config.get("SERVER_PORT").unwrap().as_i64().unwrap(), 9090);
+        assert_eq!(*config.get("HOSTS_0").unwrap().as_string().unwrap(), "a");
+    }
+}
@@ -0,0 +1,135 @@
+//! A source reading systemd service credentials.
+//!
+//! Credentials passed with `LoadCredential=` or `SetCredential=` are exposed by systemd as files
+//! in the directory named by `$CREDENTIALS_DIRECTORY`. Each file becomes a key named after the
+//! file (normalized, so `db-password` becomes `DB_PASSWORD`) whose value is the file's content
+//! as a string, without a trailing newline. Credentials are never type-inferred since secrets
+//! such as `0123` must keep their exact spelling.
+use crate::builder::{Source, SourceKind};
+use crate::{normalize_key, LoadOptions, ParseError, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::env;
+use std::fs::{read_dir, read_to_string};
+use std::path::PathBuf;
+
+/// The environment variable systemd uses to publish the credentials directory.
+pub const CREDENTIALS_DIRECTORY: &str = "CREDENTIALS_DIRECTORY";
+
+/// A source built from a systemd credentials directory.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::sources::systemd::CredentialsSource;
+/// use yaml_config::ConfigBuilder;
+/// let config = ConfigBuilder::new()
+///     .add_file("path/to/yaml/file.yaml")
+///     .add_source(CredentialsSource::new())
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CredentialsSource {
+    dir: Option<PathBuf>,
+}
+
+impl CredentialsSource {
+    /// Creates a source reading `$CREDENTIALS_DIRECTORY`. When the variable is not set, for
+    /// example when running outside of systemd, the source provides no keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a source reading an explicit directory.
+    pub fn from_dir(dir: &str) -> Self {
+        CredentialsSource {
+            dir: Some(PathBuf::from(dir)),
+        }
+    }
+
+    fn dir(&self) -> Option<PathBuf> {
+        self.dir
+            .clone()
+            .or_else(|| env::var_os(CREDENTIALS_DIRECTORY).map(PathBuf::from))
+    }
+}
+
+impl Source for CredentialsSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Custom("systemd-credentials".to_string())
+    }
+
+    fn location(&self) -> String {
+        match self.dir() {
+            Some(dir) => dir.display().to_string(),
+            None => format!("${}", CREDENTIALS_DIRECTORY),
+        }
+    }
+
+    fn load(
+        &self,
+        _options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        let mut config = IndexMap::with_hasher(FxBuildHasher::default());
+        let dir = match self.dir() {
+            Some(dir) => dir,
+            None => return Ok(config),
+        };
+
+        let mut paths = Vec::new();
+        for entry in read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+        // Directory order is unspecified; sort so the resulting config is deterministic.
+        paths.sort();
+
+        for path in paths {
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) if !name.starts_with('.') => name,
+                _ => continue,
+            };
+            let content = read_to_string(&path)?;
+            let content = content.strip_suffix('\n').unwrap_or(&content);
+            config.insert(normalize_key(name), Value::String(content.to_string()));
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sources::systemd::CredentialsSource;
+    use crate::ConfigBuilder;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn reads_credentials() {
+        let dir = tempdir().unwrap();
+        let mut password = File::create(dir.path().join("db-password")).unwrap();
+        writeln!(password, "0123").unwrap();
+        let mut token = File::create(dir.path().join("api_token")).unwrap();
+        write!(token, "abc").unwrap();
+
+        let config = ConfigBuilder::new()
+            .add_source(CredentialsSource::from_dir(dir.path().to_str().unwrap()))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            *config.get("DB_PASSWORD").unwrap().as_string().unwrap(),
+            "0123"
+        );
+        assert_eq!(
+            *config.get("API_TOKEN").unwrap().as_string().unwrap(),
+            "abc"
+        );
+
+        dir.close().unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+//! A source reading a ZooKeeper znode subtree.
+//!
+//! Every znode below the root with data becomes a key: the path relative to the root is
+//! flattened like a YAML path (`/app/db/port` under root `/app` becomes `DB_PORT`) and the data,
+//! read as UTF-8, is typed the same way as environment values. The ZooKeeper session itself is
+//! supplied by implementing `ZnodeClient` over the client library of your choice.
+use crate::builder::{Source, SourceKind};
+use crate::{infer_value, normalize_key, LoadOptions, ParseError, Value};
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+
+/// The ZooKeeper operations needed by `ZooKeeperSource`.
+pub trait ZnodeClient {
+    /// Returns the names of the children of the znode at `path`.
+    fn children(&self, path: &str) -> Result<Vec<String>, ParseError>;
+
+    /// Returns the data of the znode at `path`, or `None` if it has no data.
+    fn data(&self, path: &str) -> Result<Option<Vec<u8>>, ParseError>;
+
+    /// Sets watches on the subtree at `path` and blocks until one of them fires.
+    fn wait_for_change(&self, path: &str) -> Result<(), ParseError>;
+}
+
+/// A source built from a znode subtree.
+///
+/// **Examples**
+///
+/// ```rust,ignore
+/// use yaml_config::sources::zookeeper::ZooKeeperSource;
+/// use yaml_config::ConfigBuilder;
+/// let config = ConfigBuilder::new()
+///     .add_source(ZooKeeperSource::new(my_client, "/config/billing"))
+///     .build();
+/// ```
+pub struct ZooKeeperSource<C> {
+    client: C,
+    root: String,
+}
+
+impl<C: ZnodeClient> ZooKeeperSource<C> {
+    /// Creates a source reading every znode below `root`.
+    pub fn new(client: C, root: &str) -> Self {
+        let root = root.trim_end_matches('/');
+        ZooKeeperSource {
+            client,
+            root: if root.is_empty() { "/" } else { root }.to_string(),
+        }
+    }
+
+    /// Blocks until a watch on the subtree fires, signalling that the source should be reloaded.
+    pub fn wait_for_change(&self) -> Result<(), ParseError> {
+        self.client.wait_for_change(&self.root)
+    }
+
+    fn visit(
+        &self,
+        path: &str,
+        key: Option<&str>,
+        config: &mut IndexMap<String, Value, FxBuildHasher>,
+    ) -> Result<(), ParseError> {
+        if let Some(key) = key {
+            if let Some(data) = self.client.data(path)? {
+                if !data.is_empty() {
+                    let val = String::from_utf8(data).map_err(|e| {
+                        ParseError::new(
+                            "config::sources::zookeeper",
+                            format!("Data of {} is not valid UTF-8: {}", path, e),
+                        )
+                    })?;
+                    config.insert(key.to_string(), infer_value(val));
+                }
+            }
+        }
+
+        let mut children = self.client.children(path)?;
+        // Sort so the resulting config does not depend on the server's ordering.
+        children.sort();
+        for child in children {
+            let child_path = format!("{}/{}", path.trim_end_matches('/'), child);
+            let child_key = match key {
+                Some(key) => format!("{}_{}", key, normalize_key(&child)),
+                None => normalize_key(&child),
+            };
+            self.visit(&child_path, Some(&child_key), config)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<C: ZnodeClient> Source for ZooKeeperSource<C> {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Custom("zookeeper".to_string())
+    }
+
+    fn location(&self) -> String {
+        format!("zk:{}", self.root)
+    }
+
+    fn load(
+        &self,
+        _options: &LoadOptions,
+    ) -> Result<IndexMap<String, Value, FxBuildHasher>, ParseError> {
+        let mut config = IndexMap::with_hasher(FxBuildHasher::default());
+        self.visit(&self.root, None, &mut config)?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sources::zookeeper::{ZnodeClient, ZooKeeperSource};
+    use crate::{ConfigBuilder, ParseError};
+
+    struct StaticClient;
+
+    impl ZnodeClient for StaticClient {
+        fn children(&self, path: &str) -> Result<Vec<String>, ParseError> {
+            Ok(match path {
+                "/app" => vec!["db".to_string(), "name".to_string()],
+                "/app/db" => vec!["port".to_string(), "host".to_string()],
+                _ => Vec::new(),
+            })
+        }
+
+        fn data(&self, path: &str) -> Result<Option<Vec<u8>>, ParseError> {
+            Ok(match path {
+                "/app/db/port" => Some(b"5432".to_vec()),
+                "/app/db/host" => Some(b"db".to_vec()),
+                "/app/name" => Some(b"billing".to_vec()),
+                _ => None,
+            })
+        }
+
+        fn wait_for_change(&self, path: &str) -> Result<(), ParseError> {
+            assert_eq!(path, "/app");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reads_the_subtree() {
+        let source = ZooKeeperSource::new(StaticClient, "/app/");
+        source.wait_for_change().unwrap();
+        let config = ConfigBuilder::new().add_source(source).build().unwrap();
+
+        assert_eq!(config.len(), 3);
+        assert_eq!(*config.get("DB_PORT").unwrap().as_i64().unwrap(), 5432);
+        assert_eq!(*config.get("DB_HOST").unwrap().as_string().unwrap(), "db");
+        assert_eq!(*config.get("NAME").unwrap().as_string().unwrap(), "billing");
+        assert_eq!(config.layers()[0].location, "zk:/app");
+    }
+}
@@ -1,27 +1,42 @@
-use crate::{env_or_error, load, maybe_yaml_to_value, Value};
+#![allow(clippy::approx_constant, clippy::bool_assert_comparison)]
+
+use crate::{
+    env_or_error, key_string, load, load_from_reader, load_from_str, load_layered, load_nested,
+    load_with, maybe_yaml_to_value, normalize_key, numbers::NumberFormat, parse_str,
+    try_parse_bytes, types, units::Unit, ConfigBuilder, ErrorCode, KeySpec, LoadOptions, OnEmpty,
+    OnMissing, ParseError, Preference, Schema, Value,
+};
 use envtestkit::lock::{lock_read, lock_test};
 use envtestkit::set_env;
 use fxhash::{FxBuildHasher, FxHasher};
 use indexmap::IndexMap;
 use std::ffi::OsString;
-use std::fs::File;
+use std::fs::{self, File};
 use std::hash::BuildHasherDefault;
 use std::io::Write;
 use tempfile::tempdir;
 use yaml_rust::Yaml;
 
+fn prefer_env() -> LoadOptions {
+    LoadOptions {
+        preference: Preference::PreferEnv,
+        ..Default::default()
+    }
+}
+
 #[test]
 fn successfully_gets_environment_variable() {
     let _lock = lock_test();
     let _test = set_env(OsString::from("TEST_ENV_VAR"), "1");
-    let res = env_or_error("TEST_ENV_VAR").expect("failed to find environment variable.");
+    let res = env_or_error("TEST_ENV_VAR", &LoadOptions::default())
+        .expect("failed to find environment variable.");
     assert_eq!(res, "1");
 }
 
 #[test]
 fn error_when_environment_variable_is_not_found() {
     let _lock = lock_read();
-    let res = env_or_error("TEST_ENV_VAR");
+    let res = env_or_error("TEST_ENV_VAR", &LoadOptions::default());
     assert!(res.is_err());
 }
 
@@ -39,7 +54,13 @@ fn maybe_yaml_null_gets_environment_variable_i64() {
 
     let maybe_val = Yaml::from_str("null");
 
-    maybe_yaml_to_value("TEST_ENV_VAR", &maybe_val, false, &mut config).unwrap();
+    maybe_yaml_to_value(
+        "TEST_ENV_VAR",
+        &maybe_val,
+        &LoadOptions::default(),
+        &mut config,
+    )
+    .unwrap();
 
     assert_eq!(*config["TEST_ENV_VAR"].as_i64().unwrap(), 1);
 }
@@ -58,7 +79,13 @@ fn maybe_yaml_null_gets_environment_variable_f64() {
 
     let maybe_val = Yaml::from_str("null");
 
-    maybe_yaml_to_value("TEST_ENV_VAR", &maybe_val, false, &mut config).unwrap();
+    maybe_yaml_to_value(
+        "TEST_ENV_VAR",
+        &maybe_val,
+        &LoadOptions::default(),
+        &mut config,
+    )
+    .unwrap();
 
     assert_eq!(*config["TEST_ENV_VAR"].as_f64().unwrap(), 3.14);
 }
@@ -77,7 +104,13 @@ fn maybe_yaml_null_gets_environment_variable_bool() {
 
     let maybe_val = Yaml::from_str("null");
 
-    maybe_yaml_to_value("TEST_ENV_VAR", &maybe_val, false, &mut config).unwrap();
+    maybe_yaml_to_value(
+        "TEST_ENV_VAR",
+        &maybe_val,
+        &LoadOptions::default(),
+        &mut config,
+    )
+    .unwrap();
 
     assert_eq!(*config["TEST_ENV_VAR"].as_bool().unwrap(), true);
 }
@@ -96,7 +129,13 @@ fn maybe_yaml_null_gets_environment_variable_string() {
 
     let maybe_val = Yaml::from_str("null");
 
-    maybe_yaml_to_value("TEST_ENV_VAR", &maybe_val, false, &mut config).unwrap();
+    maybe_yaml_to_value(
+        "TEST_ENV_VAR",
+        &maybe_val,
+        &LoadOptions::default(),
+        &mut config,
+    )
+    .unwrap();
 
     assert_eq!(*config["TEST_ENV_VAR"].as_string().unwrap(), "string");
 }
@@ -115,7 +154,7 @@ fn maybe_yaml_null_gets_environment_variable_string_with_prefer_yaml() {
 
     let maybe_val = Yaml::from_str("null");
 
-    maybe_yaml_to_value("TEST_ENV_VAR", &maybe_val, true, &mut config).unwrap();
+    maybe_yaml_to_value("TEST_ENV_VAR", &maybe_val, &prefer_env(), &mut config).unwrap();
 
     assert_eq!(*config["TEST_ENV_VAR"].as_string().unwrap(), "string");
 }
@@ -132,7 +171,7 @@ fn maybe_yaml_gets_i64() {
 
     let maybe_val = Yaml::Integer(10);
 
-    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, true, &mut config).unwrap();
+    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, &prefer_env(), &mut config).unwrap();
 
     assert_eq!(*config["TEST_VAR_VAL"].as_i64().unwrap(), 10);
 }
@@ -153,7 +192,7 @@ fn maybe_yaml_gets_i64_env_var_match() {
 
     let maybe_val = Yaml::Integer(10);
 
-    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, true, &mut config).unwrap();
+    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, &prefer_env(), &mut config).unwrap();
 
     assert_eq!(*config["TEST_VAR_VAL"].as_i64().unwrap(), 10);
 }
@@ -170,7 +209,7 @@ fn maybe_yaml_gets_f64() {
 
     let maybe_val = Yaml::from_str("3.14");
 
-    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, true, &mut config).unwrap();
+    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, &prefer_env(), &mut config).unwrap();
 
     assert_eq!(*config["TEST_VAR_VAL"].as_f64().unwrap(), 3.14);
 }
@@ -191,7 +230,7 @@ fn maybe_yaml_gets_f64_env_var_match() {
 
     let maybe_val = Yaml::from_str("3.14");
 
-    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, true, &mut config).unwrap();
+    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, &prefer_env(), &mut config).unwrap();
 
     assert_eq!(*config["TEST_VAR_VAL"].as_f64().unwrap(), 3.14);
 }
@@ -208,7 +247,7 @@ fn maybe_yaml_gets_bool() {
 
     let maybe_val = Yaml::Boolean(true);
 
-    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, true, &mut config).unwrap();
+    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, &prefer_env(), &mut config).unwrap();
 
     assert_eq!(*config["TEST_VAR_VAL"].as_bool().unwrap(), true);
 }
@@ -229,7 +268,7 @@ fn maybe_yaml_gets_bool_env_var_match() {
 
     let maybe_val = Yaml::Boolean(true);
 
-    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, true, &mut config).unwrap();
+    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, &prefer_env(), &mut config).unwrap();
 
     assert_eq!(*config["TEST_VAR_VAL"].as_bool().unwrap(), true);
 }
@@ -246,7 +285,7 @@ fn maybe_yaml_gets_string() {
 
     let maybe_val = Yaml::String("test".to_string());
 
-    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, true, &mut config).unwrap();
+    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, &prefer_env(), &mut config).unwrap();
 
     assert_eq!(*config["TEST_VAR_VAL"].as_string().unwrap(), "test");
 }
@@ -267,13 +306,13 @@ fn maybe_yaml_gets_string_env_var_match() {
 
     let maybe_val = Yaml::from_str("test");
 
-    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, true, &mut config).unwrap();
+    maybe_yaml_to_value("TEST_VAR_VAL", &maybe_val, &prefer_env(), &mut config).unwrap();
 
     assert_eq!(*config["TEST_VAR_VAL"].as_string().unwrap(), "test");
 }
 
 #[test]
-fn arrays_are_not_allowed() {
+fn arrays_are_loaded() {
     let dir = tempdir().unwrap();
     let file_path = dir.path().join("test.yaml");
     let mut file = File::create(&file_path).unwrap();
@@ -281,19 +320,29 @@ fn arrays_are_not_allowed() {
         file,
         "
         test_key_1: 1
-        test_key_2: \"test\"
+        test_key_2: [8080, 8081]
         test_key_3:
             - test_1: 0
+              test-2: [a]
             - test_3: 2
-            - test_4: 'a'
         test_key_4: true
         ",
     )
     .unwrap();
 
-    let res = load(file_path.to_str().unwrap(), None);
+    let res = load(file_path.to_str().unwrap(), None).expect("temp file not loaded.");
 
-    assert!(res.is_err());
+    assert_eq!(
+        res["TEST_KEY_2"],
+        Value::Array(vec![Value::I64(8080), Value::I64(8081)])
+    );
+    let maps = res["TEST_KEY_3"].as_array().unwrap();
+    assert_eq!(maps.len(), 2);
+    let first = maps[0].as_map().unwrap();
+    assert_eq!(first["TEST_1"], Value::I64(0));
+    assert_eq!(first["TEST_2"], Value::Array(vec![Value::from("a")]));
+    assert_eq!(maps[1].as_map().unwrap()["TEST_3"], Value::I64(2));
+    assert_eq!(res["TEST_KEY_4"], Value::Bool(true));
 
     drop(file);
     dir.close().unwrap();
@@ -399,3 +448,798 @@ fn three_layer() {
     drop(file);
     dir.close().unwrap();
 }
+
+#[test]
+fn empty_document_errors_by_default() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "# only a comment").unwrap();
+
+    let res = load(file_path.to_str().unwrap(), None);
+
+    assert!(matches!(res, Err(ParseError::EmptyDocument)));
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "[YC2005] config: YAML document is empty."
+    );
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn empty_document_with_empty_config() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let file = File::create(&file_path).unwrap();
+
+    let options = LoadOptions {
+        on_empty: OnEmpty::EmptyConfig,
+        ..Default::default()
+    };
+    let res = load_with(file_path.to_str().unwrap(), &options).expect("temp file not loaded.");
+
+    assert!(res.is_empty());
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn empty_document_with_env_only() {
+    let _lock = lock_test();
+    let _test = set_env(OsString::from("TEST_ENV_ONLY_VAR"), "42");
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "---").unwrap();
+
+    let options = LoadOptions {
+        on_empty: OnEmpty::EnvOnly,
+        ..Default::default()
+    };
+    let res = load_with(file_path.to_str().unwrap(), &options).expect("temp file not loaded.");
+
+    assert_eq!(*res["TEST_ENV_ONLY_VAR"].as_i64().unwrap(), 42);
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn top_level_sequence() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(
+        file,
+        "
+        - \"rule_1\"
+        - 2
+        - - 3.14
+          - true
+        ",
+    )
+    .unwrap();
+
+    let res = load(file_path.to_str().unwrap(), None).expect("temp file not loaded.");
+    let root = res["ROOT"].as_array().unwrap();
+
+    assert_eq!(*root[0].as_string().unwrap(), "rule_1");
+    assert_eq!(*root[1].as_i64().unwrap(), 2);
+    assert_eq!(*root[2].as_array().unwrap()[0].as_f64().unwrap(), 3.14);
+    assert_eq!(*root[2].as_array().unwrap()[1].as_bool().unwrap(), true);
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn top_level_sequence_with_root_key() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "[1, 2, 3]").unwrap();
+
+    let options = LoadOptions {
+        root_key: "rules".to_string(),
+        ..Default::default()
+    };
+    let res = load_with(file_path.to_str().unwrap(), &options).expect("temp file not loaded.");
+
+    assert_eq!(res["RULES"].as_array().unwrap().len(), 3);
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn null_sequence_elements_are_loaded_as_null() {
+    let res = load_from_str("rules: [1, ~, 3]\nhosts:\n  - a\n  -\n", None).unwrap();
+
+    assert_eq!(
+        res["RULES"],
+        Value::Array(vec![Value::I64(1), Value::Null, Value::I64(3)])
+    );
+    assert_eq!(
+        res["HOSTS"],
+        Value::Array(vec![Value::from("a"), Value::Null])
+    );
+}
+
+#[test]
+fn top_level_sequence_is_post_processed_like_a_map() {
+    let _lock = lock_read();
+    let mut options = LoadOptions {
+        root_key: "rules.list".to_string(),
+        interpolate: true,
+        ..Default::default()
+    };
+    let res = parse_str("[\"${RULES_DEFAULT:-allow}\", deny]\n", &options).unwrap();
+
+    assert_eq!(
+        res["RULES_LIST"],
+        Value::Array(vec![Value::from("allow"), Value::from("deny")])
+    );
+
+    options.migrations.register(1, |values| {
+        crate::migrate::rename(values, "RULES_LIST", "POLICIES");
+        Ok(())
+    });
+    let res = parse_str("[allow]\n", &options).unwrap();
+
+    assert_eq!(res["POLICIES"], Value::Array(vec![Value::from("allow")]));
+    assert!(!res.contains_key("RULES_LIST"));
+}
+
+#[test]
+fn arrays_are_flattened_by_index() {
+    let _lock = lock_test();
+    let _test = set_env(OsString::from("SERVERS_1_PORT"), "9090");
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(
+        file,
+        "servers:\n  - host: a\n    port: 80\n  - host: b\n    port: null\ntags: [x, [y]]"
+    )
+    .unwrap();
+
+    let options = LoadOptions {
+        flatten_arrays: true,
+        ..Default::default()
+    };
+    let res = load_with(file_path.to_str().unwrap(), &options).expect("temp file not loaded.");
+
+    assert_eq!(
+        res.keys().collect::<Vec<_>>(),
+        vec![
+            "SERVERS_0_HOST",
+            "SERVERS_0_PORT",
+            "SERVERS_1_HOST",
+            "SERVERS_1_PORT",
+            "TAGS_0",
+            "TAGS_1_0"
+        ]
+    );
+    assert_eq!(res["SERVERS_1_PORT"], Value::I64(9090));
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn documents_are_loaded_from_strings_and_readers() {
+    let _lock = lock_test();
+    let _test = set_env(OsString::from("FROM_STR_PORT"), "9090");
+    let doc = "from_str:\n  host: localhost\n  port: 8080\n";
+
+    let res = load_from_str(doc, None).unwrap();
+    assert_eq!(res["FROM_STR_PORT"], Value::I64(8080));
+    let res = load_from_str(doc, Some(Preference::PreferEnv)).unwrap();
+    assert_eq!(res["FROM_STR_PORT"], Value::I64(9090));
+
+    let res = load_from_reader(doc.as_bytes(), None).unwrap();
+    assert_eq!(res["FROM_STR_HOST"], Value::String("localhost".to_string()));
+    assert!(load_from_reader(&b"\xff"[..], None).is_err());
+}
+
+#[test]
+fn layered_files_override_key_by_key() {
+    let _lock = lock_test();
+    let _test = set_env(OsString::from("LAYERED_DB_USER"), "admin");
+    let dir = tempdir().unwrap();
+    let base = dir.path().join("base.yaml");
+    let mut file = File::create(&base).unwrap();
+    writeln!(
+        file,
+        "layered:\n  db:\n    host: localhost\n    port: 5432\n    user: null\n  zones: [a, b]"
+    )
+    .unwrap();
+    let prod = dir.path().join("prod.yaml");
+    let mut file = File::create(&prod).unwrap();
+    writeln!(file, "layered:\n  db:\n    host: db.internal\n  zones: [c]").unwrap();
+
+    let res = load_layered(&[base.to_str().unwrap(), prod.to_str().unwrap()], None)
+        .expect("layered files not loaded.");
+
+    assert_eq!(
+        res["LAYERED_DB_HOST"],
+        Value::String("db.internal".to_string())
+    );
+    assert_eq!(res["LAYERED_DB_PORT"], Value::I64(5432));
+    assert_eq!(res["LAYERED_DB_USER"], Value::String("admin".to_string()));
+    assert_eq!(
+        res["LAYERED_ZONES"],
+        Value::Array(vec![Value::String("c".to_string())])
+    );
+    assert!(load_layered(&[base.to_str().unwrap(), "missing.yaml"], None).is_err());
+}
+
+#[test]
+fn later_layers_fill_null_placeholders() {
+    let dir = tempdir().unwrap();
+    let base = dir.path().join("base.yaml");
+    fs::write(&base, "placeholder:\n  token: null\n  host: localhost\n").unwrap();
+    let prod = dir.path().join("prod.yaml");
+    fs::write(&prod, "placeholder:\n  token: s3cret\n").unwrap();
+
+    let res = load_layered(&[base.to_str().unwrap(), prod.to_str().unwrap()], None).unwrap();
+
+    assert_eq!(res["PLACEHOLDER_TOKEN"], Value::from("s3cret"));
+    assert_eq!(res["PLACEHOLDER_HOST"], Value::from("localhost"));
+}
+
+#[test]
+fn missing_values_can_be_null_or_skipped() {
+    let _lock = lock_test();
+    let _test = set_env(OsString::from("MISSING_TEST_HOST"), "db");
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(
+        file,
+        "missing_test:\n  host: null\n  password: null\n  port: 5432"
+    )
+    .unwrap();
+    let file_path = file_path.to_str().unwrap();
+
+    assert!(load_with(file_path, &LoadOptions::default()).is_err());
+
+    let options = LoadOptions {
+        allow_missing: OnMissing::Null,
+        ..Default::default()
+    };
+    let res = load_with(file_path, &options).unwrap();
+    assert_eq!(res["MISSING_TEST_HOST"], Value::String("db".to_string()));
+    assert_eq!(res["MISSING_TEST_PASSWORD"], Value::Null);
+    assert_eq!(res.len(), 3);
+
+    let options = LoadOptions {
+        allow_missing: OnMissing::Skip,
+        ..Default::default()
+    };
+    let res = load_with(file_path, &options).unwrap();
+    assert!(!res.contains_key("MISSING_TEST_PASSWORD"));
+    assert_eq!(res.len(), 2);
+
+    let report = crate::validate(file_path, &options).unwrap();
+    assert_eq!(report.missing_keys, vec!["MISSING_TEST_PASSWORD"]);
+}
+
+#[test]
+fn nested_load_keeps_the_tree() {
+    let _lock = lock_test();
+    let _test = set_env(OsString::from("DB_PORT"), "6543");
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(
+        file,
+        "db:\n  host: localhost\n  port: null\n  replicas:\n    - host: a\ndebug: true"
+    )
+    .unwrap();
+
+    let res = load_nested(file_path.to_str().unwrap(), &LoadOptions::default())
+        .expect("temp file not loaded.");
+
+    assert_eq!(res.keys().collect::<Vec<_>>(), vec!["DB", "DEBUG"]);
+    let db = res["DB"].as_map().unwrap();
+    assert_eq!(
+        db.keys().collect::<Vec<_>>(),
+        vec!["HOST", "PORT", "REPLICAS"]
+    );
+    assert_eq!(db["HOST"], Value::String("localhost".to_string()));
+    assert_eq!(db["PORT"], Value::I64(6543));
+    let replicas = db["REPLICAS"].as_array().unwrap();
+    assert_eq!(
+        replicas[0].as_map().unwrap()["HOST"],
+        Value::String("a".to_string())
+    );
+    assert_eq!(res["DEBUG"], Value::Bool(true));
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn top_level_sequence_of_maps_is_loaded() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "- name: a").unwrap();
+
+    let res = load(file_path.to_str().unwrap(), None).expect("temp file not loaded.");
+
+    let items = res["ROOT"].as_array().unwrap();
+    assert_eq!(items[0].as_map().unwrap()["NAME"], Value::from("a"));
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn non_string_keys_are_stringified() {
+    assert_eq!(key_string(&Yaml::Integer(8080)).unwrap(), "8080");
+    assert_eq!(key_string(&Yaml::Boolean(true)).unwrap(), "true");
+    assert_eq!(key_string(&Yaml::Real("1.50".to_string())).unwrap(), "1.50");
+    assert!(key_string(&Yaml::Null).is_err());
+}
+
+#[test]
+fn non_string_keys() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(
+        file,
+        "
+        ports:
+          8080: \"http\"
+          443: \"https\"
+        flags:
+          true: 1
+        ",
+    )
+    .unwrap();
+
+    let res = load(file_path.to_str().unwrap(), None).expect("temp file not loaded.");
+
+    assert_eq!(*res["PORTS_8080"].as_string().unwrap(), "http");
+    assert_eq!(*res["PORTS_443"].as_string().unwrap(), "https");
+    assert_eq!(*res["FLAGS_TRUE"].as_i64().unwrap(), 1);
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn keys_are_normalized() {
+    assert_eq!(normalize_key("my-key.name"), "MY_KEY_NAME");
+    assert_eq!(normalize_key("with space"), "WITH_SPACE");
+    assert_eq!(normalize_key("größe"), "GRÖSSE");
+    assert_eq!(normalize_key("already_OK_1"), "ALREADY_OK_1");
+}
+
+#[test]
+fn special_character_keys() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(
+        file,
+        "
+        my-service:
+          host.name: \"localhost\"
+          max connections: 10
+        ",
+    )
+    .unwrap();
+
+    let res = load(file_path.to_str().unwrap(), None).expect("temp file not loaded.");
+
+    assert_eq!(
+        *res["MY_SERVICE_HOST_NAME"].as_string().unwrap(),
+        "localhost"
+    );
+    assert_eq!(*res["MY_SERVICE_MAX_CONNECTIONS"].as_i64().unwrap(), 10);
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn sibling_keys_normalizing_to_the_same_name_are_not_allowed() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(
+        file,
+        "
+        my-key: 1
+        my_key: 2
+        ",
+    )
+    .unwrap();
+
+    let res = load(file_path.to_str().unwrap(), None);
+
+    assert!(res.is_err());
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn flattened_key_collisions_are_not_allowed() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(
+        file,
+        "
+        a_b: 1
+        a:
+          b: 2
+        ",
+    )
+    .unwrap();
+
+    let res = load(file_path.to_str().unwrap(), None);
+
+    let error = res.unwrap_err();
+    assert_eq!(error.message(), "Key A_B is defined by both /a_b and /a/b.");
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn try_parse_bytes_never_panics() {
+    let _lock = lock_read();
+    let options = LoadOptions::default();
+    let mut inputs: Vec<Vec<u8>> = vec![
+        b"\xff\xfe\x00".to_vec(),
+        b"a: [1, {b: 2}]".to_vec(),
+        b"a: *missing".to_vec(),
+        b"? [a, b]\n: 1".to_vec(),
+        b"a: !!float nope".to_vec(),
+        b"a:\n  - [1, [2]]".to_vec(),
+        b"--- 1\n--- 2".to_vec(),
+        b"a: \"unterminated".to_vec(),
+        b"\ta: 1".to_vec(),
+        "{".repeat(10_000).into_bytes(),
+    ];
+
+    // A fixed xorshift sequence over YAML-ish characters stands in for a fuzzer.
+    let alphabet = b"ab01:-[]{}&*!?,'\"#|> \n\t\\.~";
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    for _ in 0..2000 {
+        let mut input = Vec::new();
+        for _ in 0..(state % 64) {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            input.push(alphabet[(state % alphabet.len() as u64) as usize]);
+        }
+        inputs.push(input);
+    }
+
+    for input in inputs {
+        let _ = try_parse_bytes(&input, &options);
+    }
+}
+
+#[test]
+fn env_values_of_the_wrong_type_are_an_error() {
+    let _lock = lock_test();
+    let _test = set_env(OsString::from("PORT"), "eighty");
+
+    let options = LoadOptions {
+        preference: Preference::PreferEnv,
+        ..Default::default()
+    };
+    let error = try_parse_bytes(b"port: 80", &options).unwrap_err();
+
+    assert_eq!(
+        error.message(),
+        "Environment variable PORT = \"eighty\" is not an integer like its YAML value at line 1, \
+         column 7."
+    );
+}
+
+#[test]
+fn env_values_of_the_wrong_type_can_fall_back_to_yaml() {
+    let _lock = lock_test();
+    let _port = set_env(OsString::from("PORT"), "eighty");
+    let _debug = set_env(OsString::from("DEBUG"), "false");
+
+    let options = LoadOptions {
+        preference: Preference::PreferEnvFallback,
+        ..Default::default()
+    };
+    let res = try_parse_bytes(b"port: 80\ndebug: true", &options).unwrap();
+
+    assert_eq!(res["PORT"], Value::I64(80));
+    assert_eq!(res["DEBUG"], Value::Bool(false));
+}
+
+#[test]
+fn maps_nested_deeper_than_max_depth_are_an_error() {
+    let options = LoadOptions {
+        max_depth: 3,
+        ..Default::default()
+    };
+    let error = try_parse_bytes(b"a:\n  b:\n    c:\n      d: 1\n", &options).unwrap_err();
+    assert!(matches!(
+        &error,
+        ParseError::DepthExceeded { key, max_depth: 3, .. } if key == "A_B_C"
+    ));
+    assert_eq!(error.location().unwrap().line, 4);
+
+    // Documents as deep as the loader allows are flattened without recursion.
+    let mut doc = String::new();
+    for depth in 0..500 {
+        doc.push_str(&format!("{}k:\n", " ".repeat(depth)));
+    }
+    doc.push_str(&format!("{}v: 1\n", " ".repeat(500)));
+    let values = try_parse_bytes(doc.as_bytes(), &LoadOptions::default()).unwrap();
+    assert_eq!(values.len(), 1);
+}
+
+#[test]
+fn conversion_errors_report_the_position_of_the_node() {
+    let _lock = lock_test();
+    let _port = set_env(OsString::from("SERVER_PORT"), "eighty");
+    let _host = set_env(OsString::from("DB_PORT"), "eighty");
+
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("app.yaml");
+    fs::write(
+        &file_path,
+        "name: app\nserver:\n  host: [a, b]\n  port: 8080\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("db.yaml"), "port: 5432\n").unwrap();
+    let included = dir.path().join("main.yaml");
+    fs::write(&included, "db: !include db.yaml\n").unwrap();
+
+    let options = LoadOptions {
+        preference: Preference::PreferEnv,
+        ..Default::default()
+    };
+    let error = load_with(file_path.to_str().unwrap(), &options).unwrap_err();
+    let location = error.location().unwrap();
+    assert_eq!((location.line, location.column), (4, 9));
+    assert!(error
+        .message()
+        .ends_with(&format!("at {}:4:9.", file_path.display())));
+
+    // A value from another file points at the `!include` it came from.
+    let error = load_with(included.to_str().unwrap(), &options).unwrap_err();
+    assert_eq!(error.location().unwrap().line, 1);
+    assert_eq!(error.location().unwrap().column, 14);
+}
+
+#[test]
+fn unit_annotations_are_converted_to_the_declared_unit() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(
+        file,
+        "
+        http:
+          timeout: {{value: 30, unit: seconds}}
+          retry_delay: 250ms
+        cache: 64 MiB
+        ",
+    )
+    .unwrap();
+    let schema = Schema::new()
+        .key("HTTP_TIMEOUT", KeySpec::new().unit(Unit::Milliseconds))
+        .key("HTTP_RETRY_DELAY", KeySpec::new().unit(Unit::Milliseconds))
+        .key("CACHE", KeySpec::new().unit(Unit::Kibibytes));
+
+    let config = ConfigBuilder::new()
+        .add_file(file_path.to_str().unwrap())
+        .with_schema(schema)
+        .build()
+        .unwrap();
+
+    assert_eq!(config.get("HTTP_TIMEOUT"), Some(&Value::I64(30000)));
+    assert_eq!(config.get("HTTP_RETRY_DELAY"), Some(&Value::I64(250)));
+    assert_eq!(config.get("CACHE"), Some(&Value::I64(65536)));
+
+    let schema = Schema::new().key("CACHE", KeySpec::new().unit(Unit::Seconds));
+    let res = ConfigBuilder::new()
+        .add_file(file_path.to_str().unwrap())
+        .with_schema(schema)
+        .build();
+    assert!(res.is_err());
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn localized_env_numbers_are_rejected_unless_configured() {
+    let _lock = lock_test();
+    let _test = set_env(OsString::from("PRICE"), "1.000,5");
+
+    assert!(try_parse_bytes(b"price: ~", &LoadOptions::default()).is_err());
+
+    let options = LoadOptions {
+        number_format: NumberFormat::Localized {
+            thousands: '.',
+            decimal: ',',
+        },
+        ..Default::default()
+    };
+    let config = try_parse_bytes(b"price: ~", &options).unwrap();
+    assert_eq!(config["PRICE"], Value::F64(1000.5));
+}
+
+#[test]
+fn env_values_that_are_not_localized_numbers_are_kept() {
+    let _lock = lock_test();
+    let _host = set_env(OsString::from("NUMBERS_HOST"), "10.0.0.1");
+    let _version = set_env(OsString::from("NUMBERS_VERSION"), "1.2.3");
+    let _ports = set_env(OsString::from("NUMBERS_PORTS"), "80,443");
+    let doc = b"numbers:\n  host: ~\n  version: ~\n  ports: ~\n";
+
+    let german = LoadOptions {
+        number_format: NumberFormat::Localized {
+            thousands: '.',
+            decimal: ',',
+        },
+        ..Default::default()
+    };
+    for options in [LoadOptions::default(), german] {
+        let config = try_parse_bytes(doc, &options).unwrap();
+        assert_eq!(config["NUMBERS_HOST"], Value::from("10.0.0.1"));
+        assert_eq!(config["NUMBERS_VERSION"], Value::from("1.2.3"));
+        assert_eq!(config["NUMBERS_PORTS"], Value::from("80,443"));
+    }
+}
+
+#[test]
+fn percentages_are_validated_at_build_time() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "sampling:\n  rate: 5%\n  burst: 150%").unwrap();
+
+    let config = ConfigBuilder::new()
+        .add_file(file_path.to_str().unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(config.get_percent("SAMPLING_RATE").unwrap(), 0.05);
+    assert!(config.get_percent("SAMPLING_BURST").is_err());
+    assert!(config.get_percent("SAMPLING_MISSING").is_err());
+
+    let schema = Schema::new().key(
+        "SAMPLING_BURST",
+        KeySpec::new().validator(types::percent::validate),
+    );
+    let res = ConfigBuilder::new()
+        .add_file(file_path.to_str().unwrap())
+        .with_schema(schema)
+        .build();
+    assert!(res.is_err());
+
+    drop(file);
+    dir.close().unwrap();
+}
+
+#[test]
+fn values_resolve_against_an_environment_snapshot() {
+    let _lock = lock_test();
+    let _test = set_env(OsString::from("SNAPSHOT_TEST_HOST"), "local");
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.yaml");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "snapshot_test:\n  host: null\n  port: 5432").unwrap();
+    let file_path = file_path.to_str().unwrap();
+
+    let env = [
+        ("SNAPSHOT_TEST_HOST", "db.prod"),
+        ("SNAPSHOT_TEST_PORT", "6432"),
+    ];
+    let options = LoadOptions {
+        preference: Preference::PreferEnv,
+        env: Some(
+            env.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        ),
+        ..Default::default()
+    };
+    let res = load_with(file_path, &options).unwrap();
+    assert_eq!(res["SNAPSHOT_TEST_HOST"], Value::from("db.prod"));
+    assert_eq!(res["SNAPSHOT_TEST_PORT"], Value::I64(6432));
+
+    // Variables missing from the snapshot are missing, even if the process has them.
+    let options = LoadOptions {
+        env: Some(Default::default()),
+        ..Default::default()
+    };
+    assert!(load_with(file_path, &options).is_err());
+}
+
+#[test]
+fn json_files_are_detected_by_extension() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.json");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "{{\"server\": {{\"port\": 8080, \"tls\": true}}}}").unwrap();
+
+    let res = load(file_path.to_str().unwrap(), None).unwrap();
+
+    assert_eq!(res["SERVER_PORT"], Value::I64(8080));
+    assert_eq!(res["SERVER_TLS"], Value::Bool(true));
+}
+
+#[test]
+fn secrets_are_read_from_files_named_by_file_variables() {
+    let dir = tempdir().unwrap();
+    let secret_path = dir.path().join("db_password");
+    fs::write(&secret_path, "hunter2\n").unwrap();
+    let doc = "secret_test:\n  password: null\n  user: app\n";
+
+    let env = |var: &str, val: &str| LoadOptions {
+        preference: Preference::PreferEnv,
+        env: Some([(var.to_string(), val.to_string())].into_iter().collect()),
+        secret_files: true,
+        ..Default::default()
+    };
+    let options = env("SECRET_TEST_PASSWORD_FILE", secret_path.to_str().unwrap());
+    let res = parse_str(doc, &options).unwrap();
+    assert_eq!(res["SECRET_TEST_PASSWORD"], Value::from("hunter2"));
+
+    // A file variable overrides a YAML value like the variable itself.
+    let options = LoadOptions {
+        env: Some(
+            [
+                ("SECRET_TEST_PASSWORD".to_string(), "s3cret".to_string()),
+                (
+                    "SECRET_TEST_USER_FILE".to_string(),
+                    secret_path.to_str().unwrap().to_string(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        ),
+        secret_files: true,
+        ..prefer_env()
+    };
+    let res = parse_str(doc, &options).unwrap();
+    assert_eq!(res["SECRET_TEST_PASSWORD"], Value::from("s3cret"));
+    assert_eq!(res["SECRET_TEST_USER"], Value::from("hunter2"));
+
+    let options = env("SECRET_TEST_PASSWORD_FILE", "does/not/exist");
+    let error = parse_str(doc, &options).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::SecretFileUnreadable);
+    assert!(error
+        .message()
+        .starts_with("SECRET_TEST_PASSWORD_FILE names"));
+
+    // The file is confined to `base_dir` like a document.
+    let options = LoadOptions {
+        base_dir: Some(dir.path().join("config")),
+        ..env("SECRET_TEST_PASSWORD_FILE", secret_path.to_str().unwrap())
+    };
+    fs::create_dir(dir.path().join("config")).unwrap();
+    assert!(parse_str(doc, &options).is_err());
+
+    // The lookup is opt-in.
+    let options = LoadOptions {
+        secret_files: false,
+        ..env("SECRET_TEST_PASSWORD_FILE", secret_path.to_str().unwrap())
+    };
+    assert!(matches!(
+        parse_str(doc, &options),
+        Err(ParseError::MissingEnv { .. })
+    ));
+}
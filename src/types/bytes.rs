@@ -0,0 +1,110 @@
+//! Byte sizes such as `10MiB` or `2GB`.
+//!
+//! A size is a quantity with a data unit (see the `units` module), so both decimal (`kB`, `MB`,
+//! `GB`) and binary (`KiB`, `MiB`, `GiB`) units are accepted. Bare numbers are bytes.
+use crate::types::{required, type_error};
+use crate::units::{parse_quantity, Dimension};
+use crate::{Config, ParseError, Value};
+
+/// Converts an amount of bytes, rejecting negative, fractional, and out of range amounts.
+/// Amounts within rounding error of a whole number, such as `1.005kB` scaled in binary floating
+/// point, are rounded to it.
+fn from_bytes(bytes: f64, shown: &str) -> Result<u64, String> {
+    if !bytes.is_finite() {
+        return Err(format!("\"{}\" is not a number", shown));
+    }
+    if bytes < 0.0 {
+        return Err(format!("\"{}\" is negative", shown));
+    }
+    let whole = bytes.round();
+    if (bytes - whole).abs() > whole.max(1.0) * 1e-9 {
+        return Err(format!("\"{}\" is not a whole number of bytes", shown));
+    }
+    let bytes = whole;
+    if bytes >= u64::MAX as f64 {
+        return Err(format!("\"{}\" is out of range", shown));
+    }
+    Ok(bytes as u64)
+}
+
+/// Parses `val` as a number of bytes.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::types::bytes::parse;
+/// use yaml_config::Value;
+/// assert_eq!(parse(&Value::String("10MiB".to_string())), Ok(10 * 1024 * 1024));
+/// assert_eq!(parse(&Value::String("2 GB".to_string())), Ok(2_000_000_000));
+/// assert_eq!(parse(&Value::I64(512)), Ok(512));
+/// ```
+pub fn parse(val: &Value) -> Result<u64, String> {
+    let s = match val {
+        Value::I32(v) => return from_bytes(f64::from(*v), &v.to_string()),
+        Value::I64(v) if *v < 0 => return Err(format!("\"{}\" is negative", v)),
+        Value::I64(v) => return Ok(*v as u64),
+        Value::F32(v) => return from_bytes(f64::from(*v), &v.to_string()),
+        Value::F64(v) => return from_bytes(*v, &v.to_string()),
+        Value::String(s) => s.trim(),
+        _ => return Err("expected a size such as \"10MiB\" or \"2GB\"".to_string()),
+    };
+    if let Ok(bytes) = s.parse::<u64>() {
+        return Ok(bytes);
+    }
+
+    let (amount, unit) = parse_quantity(s)?;
+    if unit.dimension() != Dimension::Data {
+        return Err(format!("\"{}\" is not a byte size", s));
+    }
+    from_bytes(amount * unit.factor(), s)
+}
+
+/// Checks that `val` is a valid byte size. Suitable for `KeySpec::validator`.
+pub fn validate(val: &Value) -> Result<(), String> {
+    parse(val).map(|_| ())
+}
+
+impl Config {
+    /// Returns the value of `key` as a number of bytes. Bare numbers are bytes.
+    pub fn get_bytes(&self, key: &str) -> Result<u64, ParseError> {
+        let val = required(self, "bytes", key)?;
+        parse(val).map_err(|e| type_error("bytes", key, &e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::bytes::parse;
+    use crate::Value;
+
+    fn bytes(s: &str) -> Result<u64, String> {
+        parse(&Value::String(s.to_string()))
+    }
+
+    #[test]
+    fn sizes_are_parsed() {
+        assert_eq!(bytes("10MiB"), Ok(10_485_760));
+        assert_eq!(bytes("2GB"), Ok(2_000_000_000));
+        assert_eq!(bytes("1.5 KiB"), Ok(1536));
+        assert_eq!(bytes("64 kilobytes"), Ok(64_000));
+        assert_eq!(bytes("4096"), Ok(4096));
+        assert_eq!(parse(&Value::I32(1)), Ok(1));
+        assert_eq!(bytes("1.005kB"), Ok(1005));
+        assert_eq!(
+            bytes("0.1 KiB"),
+            Err("\"0.1 KiB\" is not a whole number of bytes".to_string())
+        );
+        assert_eq!(parse(&Value::F64(1048576.0)), Ok(1_048_576));
+    }
+
+    #[test]
+    fn invalid_sizes_are_rejected() {
+        assert!(bytes("30s").is_err());
+        assert!(bytes("-1MB").is_err());
+        assert!(bytes("0.5B").is_err());
+        assert!(bytes("lots").is_err());
+        assert!(parse(&Value::I64(-1)).is_err());
+        assert!(parse(&Value::F64(1.5)).is_err());
+        assert!(parse(&Value::F64(f64::NAN)).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Colors for UI and theming configuration.
+//!
+//! Accepts hex notation (`#RGB`, `#RRGGBB`, `#RRGGBBAA`) and the CSS functions `rgb(r, g, b)`
+//! and `rgba(r, g, b, a)`, where `a` is between 0 and 1.
+use crate::types::{required, type_error};
+use crate::{Config, ParseError, Value};
+use std::fmt;
+
+/// An RGBA color with 8 bits per channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    /// Opacity, where 255 is fully opaque.
+    pub a: u8,
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.a == u8::MAX {
+            write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            write!(
+                f,
+                "#{:02x}{:02x}{:02x}{:02x}",
+                self.r, self.g, self.b, self.a
+            )
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize, width: usize| {
+        let v = u8::from_str_radix(&hex[i * width..(i + 1) * width], 16).ok()?;
+        // `#abc` is shorthand for `#aabbcc`.
+        Some(if width == 1 { v * 17 } else { v })
+    };
+    match hex.len() {
+        3 => Some(Color {
+            r: channel(0, 1)?,
+            g: channel(1, 1)?,
+            b: channel(2, 1)?,
+            a: u8::MAX,
+        }),
+        6 | 8 => Some(Color {
+            r: channel(0, 2)?,
+            g: channel(1, 2)?,
+            b: channel(2, 2)?,
+            a: if hex.len() == 8 {
+                channel(3, 2)?
+            } else {
+                u8::MAX
+            },
+        }),
+        _ => None,
+    }
+}
+
+fn parse_function(args: &str, alpha: bool) -> Option<Color> {
+    let parts: Vec<&str> = args.split(',').map(str::trim).collect();
+    if parts.len() != if alpha { 4 } else { 3 } {
+        return None;
+    }
+    let a = match parts.get(3) {
+        Some(a) => {
+            let a = a.parse::<f64>().ok()?;
+            if !(0.0..=1.0).contains(&a) {
+                return None;
+            }
+            (a * 255.0).round() as u8
+        }
+        None => u8::MAX,
+    };
+    Some(Color {
+        r: parts[0].parse().ok()?,
+        g: parts[1].parse().ok()?,
+        b: parts[2].parse().ok()?,
+        a,
+    })
+}
+
+/// Parses `val` as a color.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::types::color::{parse, Color};
+/// use yaml_config::Value;
+/// let orange = Color { r: 255, g: 136, b: 0, a: 255 };
+/// assert_eq!(parse(&Value::String("#ff8800".to_string())), Ok(orange));
+/// assert_eq!(parse(&Value::String("rgb(255, 136, 0)".to_string())), Ok(orange));
+/// ```
+pub fn parse(val: &Value) -> Result<Color, String> {
+    let s = match val {
+        Value::String(s) => s.trim(),
+        _ => return Err("expected a color such as \"#RRGGBB\" or \"rgb(r, g, b)\"".to_string()),
+    };
+    let lower = s.to_ascii_lowercase();
+
+    let color = if let Some(hex) = lower.strip_prefix('#') {
+        parse_hex(hex)
+    } else if let Some(args) = lower
+        .strip_prefix("rgba(")
+        .and_then(|s| s.strip_suffix(')'))
+    {
+        parse_function(args, true)
+    } else if let Some(args) = lower.strip_prefix("rgb(").and_then(|s| s.strip_suffix(')')) {
+        parse_function(args, false)
+    } else {
+        None
+    };
+
+    color.ok_or_else(|| format!("\"{}\" is not a color", s))
+}
+
+/// Checks that `val` is a valid color. Suitable for `KeySpec::validator`.
+pub fn validate(val: &Value) -> Result<(), String> {
+    parse(val).map(|_| ())
+}
+
+impl Config {
+    /// Returns the value of `key` parsed as a color.
+    pub fn get_color(&self, key: &str) -> Result<Color, ParseError> {
+        let val = required(self, "color", key)?;
+        parse(val).map_err(|e| type_error("color", key, &e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::color::{parse, Color};
+    use crate::Value;
+
+    fn color(s: &str) -> Result<Color, String> {
+        parse(&Value::String(s.to_string()))
+    }
+
+    #[test]
+    fn colors_are_parsed() {
+        let white = Color {
+            r: 255,
+            g: 255,
+            b: 255,
+            a: 255,
+        };
+        assert_eq!(color("#FFF"), Ok(white));
+        assert_eq!(color("#ffffff"), Ok(white));
+        assert_eq!(color("RGB(255,255,255)"), Ok(white));
+        assert_eq!(color("rgba(255, 255, 255, 0.5)").unwrap().a, 128);
+        assert_eq!(color("#11223380").unwrap().to_string(), "#11223380");
+        assert_eq!(color("rgb(0, 128, 255)").unwrap().to_string(), "#0080ff");
+    }
+
+    #[test]
+    fn invalid_colors_are_rejected() {
+        for s in [
+            "red",
+            "#ff",
+            "#gggggg",
+            "rgb(256, 0, 0)",
+            "rgb(1, 2)",
+            "rgba(1, 2, 3, 2)",
+        ] {
+            assert!(color(s).is_err(), "{}", s);
+        }
+        assert!(parse(&Value::I64(0xffffff)).is_err());
+    }
+}
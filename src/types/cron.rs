@@ -0,0 +1,122 @@
+//! Cron schedules.
+//!
+//! Schedules use the `cron` crate's syntax with a leading seconds field, e.g.
+//! `0 30 9 * * Mon-Fri`, and an optional trailing year. Classic five-field crontab
+//! expressions such as `*/5 * * * *` are accepted too and fire at second zero. Their numeric
+//! days of the week keep their crontab meaning, 0 or 7 for Sunday through 6 for Saturday,
+//! although the `cron` crate numbers them 1 for Sunday through 7 for Saturday.
+use crate::types::{required, type_error};
+use crate::{Config, ParseError, Value};
+use std::str::FromStr;
+
+pub use ::cron::Schedule;
+
+/// Parses `val` as a cron schedule.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::types::cron::parse;
+/// use yaml_config::Value;
+/// assert!(parse(&Value::String("*/5 * * * *".to_string())).is_ok());
+/// assert!(parse(&Value::String("every tuesday".to_string())).is_err());
+/// ```
+pub fn parse(val: &Value) -> Result<Schedule, String> {
+    let expr = match val {
+        Value::String(s) => s.trim(),
+        _ => return Err("expected a cron expression".to_string()),
+    };
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    let expr = match fields[..] {
+        [minute, hour, day, month, weekday] => format!(
+            "0 {} {} {} {} {}",
+            minute,
+            hour,
+            day,
+            month,
+            crontab_weekdays(weekday)
+        ),
+        _ => expr.to_string(),
+    };
+
+    Schedule::from_str(&expr).map_err(|e| format!("\"{}\" is not a cron expression: {}", expr, e))
+}
+
+/// Translates the day-of-week field of a crontab expression to the numbering of the `cron`
+/// crate. Numeric days, ranges, and lists are rewritten as the list of days they select; `*`,
+/// `*/step`, and day names mean the same in both and are kept.
+fn crontab_weekdays(field: &str) -> String {
+    let mut days = Vec::new();
+    let mut kept = Vec::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok()),
+            None => (item, Some(1)),
+        };
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        match (first.parse::<u32>(), last.parse::<u32>(), step) {
+            (Ok(first), Ok(last), Some(step)) if first <= last && last <= 7 && step > 0 => {
+                days.extend((first..=last).step_by(step as usize).map(|day| day % 7 + 1));
+            }
+            // Left for the `cron` crate to accept or report.
+            _ => kept.push(item.to_string()),
+        }
+    }
+    days.sort_unstable();
+    days.dedup();
+    days.iter()
+        .map(u32::to_string)
+        .chain(kept)
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Checks that `val` is a valid cron schedule. Suitable for `KeySpec::validator`.
+pub fn validate(val: &Value) -> Result<(), String> {
+    parse(val).map(|_| ())
+}
+
+impl Config {
+    /// Returns the value of `key` parsed as a cron schedule.
+    pub fn get_cron(&self, key: &str) -> Result<Schedule, ParseError> {
+        let val = required(self, "cron", key)?;
+        parse(val).map_err(|e| type_error("cron", key, &e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::cron::parse;
+    use crate::Value;
+
+    fn schedule(s: &str) -> Result<String, String> {
+        parse(&Value::String(s.to_string())).map(|s| s.to_string())
+    }
+
+    #[test]
+    fn schedules_are_parsed() {
+        assert_eq!(
+            schedule("0 30 9 * * Mon-Fri").unwrap(),
+            "0 30 9 * * Mon-Fri"
+        );
+        assert_eq!(schedule("*/5 * * * *").unwrap(), "0 */5 * * * *");
+    }
+
+    #[test]
+    fn crontab_days_of_week_are_translated() {
+        // Monday to Friday, then Sunday given as 0 and as 7, then Friday to Sunday.
+        assert_eq!(schedule("0 9 * * 1-5").unwrap(), "0 0 9 * * 2,3,4,5,6");
+        assert_eq!(schedule("0 9 * * 0,7").unwrap(), "0 0 9 * * 1");
+        assert_eq!(schedule("0 9 * * 5-7").unwrap(), "0 0 9 * * 1,6,7");
+        assert_eq!(schedule("0 9 * * 0-6/2").unwrap(), "0 0 9 * * 1,3,5,7");
+        assert_eq!(schedule("0 9 * * Mon-Fri").unwrap(), "0 0 9 * * Mon-Fri");
+        assert!(schedule("0 9 * * 8").is_err());
+    }
+
+    #[test]
+    fn invalid_schedules_are_rejected() {
+        assert!(schedule("61 * * * * *").is_err());
+        assert!(schedule("* *").is_err());
+        assert!(parse(&Value::I64(5)).is_err());
+    }
+}
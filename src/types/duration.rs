@@ -0,0 +1,159 @@
+//! Durations such as `30s`, `5m`, or `1h30m`.
+//!
+//! Each part of a duration is a quantity with a time unit (see the `units` module), so
+//! `1.5 hours` and `1h 30m` are equivalent. Bare numbers are seconds.
+use crate::types::{required, type_error};
+use crate::units::{parse_quantity, Dimension};
+use crate::{Config, ParseError, Value};
+use std::time::Duration;
+
+/// Converts a number of seconds, rejecting negative and out of range amounts.
+fn from_secs(secs: f64, shown: &str) -> Result<Duration, String> {
+    if secs < 0.0 {
+        return Err(format!("\"{}\" is negative", shown));
+    }
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("\"{}\" is out of range", shown))
+}
+
+/// Splits `s` before every number that follows a unit, so `1h30m` becomes `1h` and `30m`.
+fn parts(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_unit = false;
+    for (i, c) in s.char_indices() {
+        if c.is_ascii_digit() && in_unit {
+            parts.push(s[start..i].trim());
+            start = i;
+        }
+        if !c.is_whitespace() {
+            in_unit = c.is_alphabetic();
+        }
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
+/// Writes `duration` the way it is parsed, in whole hours, minutes, seconds, and milliseconds,
+/// with any remainder in nanoseconds.
+///
+/// **Examples**
+///
+/// ```rust
+/// use std::time::Duration;
+/// use yaml_config::types::duration::format;
+/// assert_eq!(format(Duration::from_secs(5415)), "1h30m15s");
+/// assert_eq!(format(Duration::from_millis(250)), "250ms");
+/// assert_eq!(format(Duration::ZERO), "0s");
+/// ```
+pub fn format(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let nanos = duration.subsec_nanos();
+    let parts = [
+        (secs / 3600, "h"),
+        (secs / 60 % 60, "m"),
+        (secs % 60, "s"),
+        (u64::from(nanos / 1_000_000), "ms"),
+        (u64::from(nanos % 1_000_000), "ns"),
+    ];
+    let out: String = parts
+        .iter()
+        .filter(|(amount, _)| *amount > 0)
+        .map(|(amount, unit)| format!("{}{}", amount, unit))
+        .collect();
+    if out.is_empty() {
+        "0s".to_string()
+    } else {
+        out
+    }
+}
+
+/// Parses `val` as a duration.
+///
+/// **Examples**
+///
+/// ```rust
+/// use std::time::Duration;
+/// use yaml_config::types::duration::parse;
+/// use yaml_config::Value;
+/// assert_eq!(parse(&Value::String("1h30m".to_string())), Ok(Duration::from_secs(5400)));
+/// assert_eq!(parse(&Value::String("250ms".to_string())), Ok(Duration::from_millis(250)));
+/// assert_eq!(parse(&Value::I64(30)), Ok(Duration::from_secs(30)));
+/// ```
+pub fn parse(val: &Value) -> Result<Duration, String> {
+    let s = match val {
+        Value::Duration(v) => return Ok(*v),
+        Value::I32(v) => return from_secs(f64::from(*v), &v.to_string()),
+        Value::I64(v) => return from_secs(*v as f64, &v.to_string()),
+        Value::F32(v) => return from_secs(f64::from(*v), &v.to_string()),
+        Value::F64(v) => return from_secs(*v, &v.to_string()),
+        Value::String(s) => s.trim(),
+        _ => return Err("expected a duration such as \"30s\" or \"1h30m\"".to_string()),
+    };
+    if let Ok(secs) = s.parse::<f64>() {
+        return from_secs(secs, s);
+    }
+
+    let mut total = 0.0;
+    for part in parts(s) {
+        let (amount, unit) = parse_quantity(part)?;
+        if unit.dimension() != Dimension::Time {
+            return Err(format!("\"{}\" is not a duration", s));
+        }
+        if amount < 0.0 {
+            return Err(format!("\"{}\" is negative", s));
+        }
+        total += amount * unit.factor() / 1e9;
+    }
+    from_secs(total, s)
+}
+
+/// Checks that `val` is a valid duration. Suitable for `KeySpec::validator`.
+pub fn validate(val: &Value) -> Result<(), String> {
+    parse(val).map(|_| ())
+}
+
+impl Config {
+    /// Returns the value of `key` as a `Duration`. Bare numbers are seconds.
+    pub fn get_duration(&self, key: &str) -> Result<Duration, ParseError> {
+        let val = required(self, "duration", key)?;
+        parse(val).map_err(|e| type_error("duration", key, &e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::duration::{format, parse};
+    use crate::Value;
+    use std::time::Duration;
+
+    fn duration(s: &str) -> Result<Duration, String> {
+        parse(&Value::String(s.to_string()))
+    }
+
+    #[test]
+    fn durations_are_parsed() {
+        assert_eq!(duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(duration("1h 30m 15s"), Ok(Duration::from_secs(5415)));
+        assert_eq!(duration("1.5 hours"), Ok(Duration::from_secs(5400)));
+        assert_eq!(duration("30 seconds"), Ok(Duration::from_secs(30)));
+        assert_eq!(duration("45"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse(&Value::F64(0.5)), Ok(Duration::from_millis(500)));
+        assert_eq!(
+            parse(&Value::from(Duration::from_secs(90))),
+            Ok(Duration::from_secs(90))
+        );
+        for d in [Duration::from_secs(5415), Duration::new(61, 1_500_000)] {
+            assert_eq!(duration(&format(d)), Ok(d));
+        }
+    }
+
+    #[test]
+    fn invalid_durations_are_rejected() {
+        assert!(duration("64 MiB").is_err());
+        assert!(duration("-5s").is_err());
+        assert!(duration("soon").is_err());
+        assert!(parse(&Value::I64(-1)).is_err());
+        assert!(parse(&Value::Bool(true)).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! Media types such as `application/json` or `text/html; charset=utf-8`.
+use crate::types::{required, type_error};
+use crate::{Config, ParseError, Value};
+
+pub use ::mime::Mime;
+
+/// Returns `true` for the characters allowed in an RFC 7230 token.
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+/// Returns the first part of `s` that isn't a valid token: the type, the subtype, or a
+/// parameter name. Quoted parameter values are left to the parser.
+fn invalid_token(s: &str) -> Option<&str> {
+    let mut parts = s.split(';');
+    let essence = parts.next().unwrap_or_default();
+    let mut tokens: Vec<&str> = essence.splitn(2, '/').map(str::trim).collect();
+    for param in parts {
+        let name = param.split('=').next().unwrap_or_default().trim();
+        tokens.push(name);
+        if let Some(val) = param.split_once('=').map(|(_, v)| v.trim()) {
+            if !val.starts_with('"') {
+                tokens.push(val);
+            }
+        }
+    }
+    tokens
+        .into_iter()
+        .find(|token| token.is_empty() || !token.chars().all(is_token_char))
+}
+
+/// Parses `val` as a media type.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::types::mime::parse;
+/// use yaml_config::Value;
+/// let mime = parse(&Value::String("text/html; charset=utf-8".to_string())).unwrap();
+/// assert_eq!(mime.essence_str(), "text/html");
+/// assert!(parse(&Value::String("text/ht ml".to_string())).is_err());
+/// ```
+pub fn parse(val: &Value) -> Result<Mime, String> {
+    let s = match val {
+        Value::String(s) => s.trim(),
+        _ => return Err("expected a media type such as \"application/json\"".to_string()),
+    };
+    if !s.contains('/') {
+        return Err(format!("\"{}\" is missing a subtype", s));
+    }
+    if let Some(token) = invalid_token(s) {
+        return Err(format!("\"{}\" is not a valid token in \"{}\"", token, s));
+    }
+
+    s.parse::<Mime>()
+        .map_err(|e| format!("\"{}\" is not a media type: {}", s, e))
+}
+
+/// Checks that `val` is a valid media type. Suitable for `KeySpec::validator`.
+pub fn validate(val: &Value) -> Result<(), String> {
+    parse(val).map(|_| ())
+}
+
+impl Config {
+    /// Returns the value of `key` parsed as a media type.
+    pub fn get_mime(&self, key: &str) -> Result<Mime, ParseError> {
+        let val = required(self, "mime", key)?;
+        parse(val).map_err(|e| type_error("mime", key, &e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::mime::parse;
+    use crate::Value;
+
+    fn mime(s: &str) -> Result<String, String> {
+        parse(&Value::String(s.to_string())).map(|m| m.to_string())
+    }
+
+    #[test]
+    fn media_types_are_parsed() {
+        assert_eq!(mime("application/json").unwrap(), "application/json");
+        assert_eq!(
+            mime("text/plain; charset=utf-8").unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            parse(&Value::String("text/plain; charset=UTF-8".to_string()))
+                .unwrap()
+                .get_param(mime::CHARSET)
+                .unwrap(),
+            "utf-8"
+        );
+    }
+
+    #[test]
+    fn errors_name_the_invalid_token() {
+        assert_eq!(
+            mime("tex@t/html").unwrap_err(),
+            "\"tex@t\" is not a valid token in \"tex@t/html\""
+        );
+        assert_eq!(
+            mime("text/html; char set=utf-8").unwrap_err(),
+            "\"char set\" is not a valid token in \"text/html; char set=utf-8\""
+        );
+        assert!(mime("text").is_err());
+        assert!(mime("text/html; charset=").is_err());
+        assert!(parse(&Value::I64(1)).is_err());
+    }
+}
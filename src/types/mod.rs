@@ -0,0 +1,36 @@
+//! Typed values parsed from strings.
+//!
+//! Each submodule parses one kind of value, e.g. `"75%"` as a ratio, and adds a matching
+//! accessor to `Config` such as `Config::get_percent`. Every module also provides a `validate`
+//! function that can be passed to `KeySpec::validator`, so a malformed value is reported when
+//! the configuration is built rather than when it is first read.
+pub mod bytes;
+#[cfg(feature = "color")]
+pub mod color;
+#[cfg(feature = "cron")]
+pub mod cron;
+pub mod duration;
+#[cfg(feature = "mime")]
+pub mod mime;
+pub mod percent;
+
+use crate::{Config, ParseError, Value};
+
+/// Builds the error reported by typed accessors.
+pub(crate) fn type_error(module: &str, key: &str, message: &str) -> ParseError {
+    ParseError::new(
+        format!("config::types::{}", module),
+        format!("{}: {}", key, message),
+    )
+}
+
+/// Returns the value of `key`, or an error naming it when it isn't set.
+pub(crate) fn required<'a>(
+    config: &'a Config,
+    module: &str,
+    key: &str,
+) -> Result<&'a Value, ParseError> {
+    config
+        .get(key)
+        .ok_or_else(|| type_error(module, key, "not set"))
+}
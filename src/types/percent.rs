@@ -0,0 +1,79 @@
+//! Percentages and ratios.
+//!
+//! `"75%"` and `0.75` both describe the ratio `0.75`. Ratios must lie between 0 and 1, which
+//! catches the common mistake of writing `75` for seventy-five percent.
+use crate::types::{required, type_error};
+use crate::{Config, ParseError, Value};
+
+/// Parses `val` as a ratio between 0 and 1. Strings may end in `%`; numbers are ratios.
+///
+/// **Examples**
+///
+/// ```rust
+/// use yaml_config::types::percent::parse;
+/// use yaml_config::Value;
+/// assert_eq!(parse(&Value::String("75%".to_string())), Ok(0.75));
+/// assert_eq!(parse(&Value::F64(0.1)), Ok(0.1));
+/// assert!(parse(&Value::I64(75)).is_err());
+/// ```
+pub fn parse(val: &Value) -> Result<f64, String> {
+    let ratio = match val {
+        Value::I32(v) => f64::from(*v),
+        Value::I64(v) => *v as f64,
+        Value::F32(v) => f64::from(*v),
+        Value::F64(v) => *v,
+        Value::String(s) => {
+            let s = s.trim();
+            let number = s.strip_suffix('%').map(str::trim_end);
+            let parsed = number.unwrap_or(s).parse::<f64>();
+            match (parsed, number.is_some()) {
+                (Ok(v), true) => v / 100.0,
+                (Ok(v), false) => v,
+                (Err(_), _) => return Err(format!("\"{}\" is not a percentage", s)),
+            }
+        }
+        _ => return Err("expected a percentage such as \"75%\" or a ratio".to_string()),
+    };
+
+    if !(0.0..=1.0).contains(&ratio) {
+        return Err(format!("{} is not between 0% and 100%", ratio * 100.0));
+    }
+    Ok(ratio)
+}
+
+/// Checks that `val` is a valid percentage. Suitable for `KeySpec::validator`.
+pub fn validate(val: &Value) -> Result<(), String> {
+    parse(val).map(|_| ())
+}
+
+impl Config {
+    /// Returns the value of `key` as a ratio between 0 and 1. `"75%"` and `0.75` are both
+    /// returned as `0.75`.
+    pub fn get_percent(&self, key: &str) -> Result<f64, ParseError> {
+        let val = required(self, "percent", key)?;
+        parse(val).map_err(|e| type_error("percent", key, &e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::percent::parse;
+    use crate::Value;
+
+    #[test]
+    fn percentages_are_normalized() {
+        assert_eq!(parse(&Value::String("75%".to_string())), Ok(0.75));
+        assert_eq!(parse(&Value::String(" 12.5 % ".to_string())), Ok(0.125));
+        assert_eq!(parse(&Value::String("0.3".to_string())), Ok(0.3));
+        assert_eq!(parse(&Value::I64(1)), Ok(1.0));
+    }
+
+    #[test]
+    fn percentages_are_range_checked() {
+        assert!(parse(&Value::String("101%".to_string())).is_err());
+        assert!(parse(&Value::String("-5%".to_string())).is_err());
+        assert!(parse(&Value::I64(75)).is_err());
+        assert!(parse(&Value::String("most".to_string())).is_err());
+        assert!(parse(&Value::Bool(true)).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! Unit-annotated values.
+//!
+//! A quantity can be written with its unit, either as a suffix (`timeout: 30s`,
+//! `cache: 64 MiB`) or as a map (`timeout: {value: 30, unit: seconds}`). Either form is loaded
+//! as the string `"30 seconds"`. Declaring the unit a key expects with `KeySpec::unit` converts
+//! the value to a plain number in that unit when the schema is applied, and rejects quantities
+//! of the wrong kind, so `30s` given for a key expecting milliseconds becomes `30000` rather
+//! than a thirty millisecond timeout.
+//!
+//! Plain numbers are taken to already be in the expected unit.
+//!
+//! **Examples**
+//!
+//! ```rust
+//! use yaml_config::units::Unit;
+//! use yaml_config::{KeySpec, Schema};
+//! let schema = Schema::new().key("HTTP_TIMEOUT", KeySpec::new().unit(Unit::Milliseconds));
+//! ```
+use crate::Value;
+use std::fmt;
+use yaml_rust::Yaml;
+
+/// What a unit measures. Only units of the same dimension can be converted into each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dimension {
+    /// Durations.
+    Time,
+    /// Amounts of data.
+    Data,
+}
+
+/// A unit of measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+    Minutes,
+    Hours,
+    Days,
+    Bytes,
+    Kilobytes,
+    Megabytes,
+    Gigabytes,
+    Kibibytes,
+    Mebibytes,
+    Gibibytes,
+}
+
+impl Unit {
+    /// What the unit measures.
+    pub fn dimension(self) -> Dimension {
+        match self {
+            Unit::Nanoseconds
+            | Unit::Microseconds
+            | Unit::Milliseconds
+            | Unit::Seconds
+            | Unit::Minutes
+            | Unit::Hours
+            | Unit::Days => Dimension::Time,
+            _ => Dimension::Data,
+        }
+    }
+
+    /// The size of the unit in nanoseconds or bytes.
+    pub(crate) fn factor(self) -> f64 {
+        match self {
+            Unit::Nanoseconds => 1.0,
+            Unit::Microseconds => 1e3,
+            Unit::Milliseconds => 1e6,
+            Unit::Seconds => 1e9,
+            Unit::Minutes => 60e9,
+            Unit::Hours => 3600e9,
+            Unit::Days => 86400e9,
+            Unit::Bytes => 1.0,
+            Unit::Kilobytes => 1e3,
+            Unit::Megabytes => 1e6,
+            Unit::Gigabytes => 1e9,
+            Unit::Kibibytes => 1024.0,
+            Unit::Mebibytes => 1024.0 * 1024.0,
+            Unit::Gibibytes => 1024.0 * 1024.0 * 1024.0,
+        }
+    }
+
+    /// Parses a unit name or symbol such as `ms`, `second`, `hours`, `KiB`, or `MB`.
+    pub fn parse(name: &str) -> Option<Unit> {
+        let unit = match name {
+            "ns" | "nanosecond" | "nanoseconds" => Unit::Nanoseconds,
+            "us" | "µs" | "microsecond" | "microseconds" => Unit::Microseconds,
+            "ms" | "millisecond" | "milliseconds" => Unit::Milliseconds,
+            "s" | "sec" | "secs" | "second" | "seconds" => Unit::Seconds,
+            "m" | "min" | "mins" | "minute" | "minutes" => Unit::Minutes,
+            "h" | "hr" | "hrs" | "hour" | "hours" => Unit::Hours,
+            "d" | "day" | "days" => Unit::Days,
+            "B" | "byte" | "bytes" => Unit::Bytes,
+            "kB" | "KB" | "kilobyte" | "kilobytes" => Unit::Kilobytes,
+            "MB" | "megabyte" | "megabytes" => Unit::Megabytes,
+            "GB" | "gigabyte" | "gigabytes" => Unit::Gigabytes,
+            "KiB" | "kibibyte" | "kibibytes" => Unit::Kibibytes,
+            "MiB" | "mebibyte" | "mebibytes" => Unit::Mebibytes,
+            "GiB" | "gibibyte" | "gibibytes" => Unit::Gibibytes,
+            _ => return None,
+        };
+        Some(unit)
+    }
+
+    /// Converts `val`, a plain number or a quantity such as `"30s"`, into a number of this
+    /// unit. Whole results are `Value::I64` and others `Value::F64`.
+    pub fn convert(self, val: &Value) -> Result<Value, String> {
+        let (amount, unit) = match val {
+            Value::I32(v) => return Ok(Value::I64(i64::from(*v))),
+            Value::I64(v) => return Ok(Value::I64(*v)),
+            Value::F32(v) => return Ok(Value::F64(f64::from(*v))),
+            Value::F64(v) => return Ok(Value::F64(*v)),
+            Value::String(s) => parse_quantity(s)?,
+            _ => return Err(format!("expected a quantity in {}", self)),
+        };
+        if unit.dimension() != self.dimension() {
+            return Err(format!("{} cannot be converted to {}", unit, self));
+        }
+
+        let converted = amount * unit.factor() / self.factor();
+        if converted.fract() == 0.0 && converted.abs() < i64::MAX as f64 {
+            Ok(Value::I64(converted as i64))
+        } else {
+            Ok(Value::F64(converted))
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Unit::Nanoseconds => "nanoseconds",
+            Unit::Microseconds => "microseconds",
+            Unit::Milliseconds => "milliseconds",
+            Unit::Seconds => "seconds",
+            Unit::Minutes => "minutes",
+            Unit::Hours => "hours",
+            Unit::Days => "days",
+            Unit::Bytes => "bytes",
+            Unit::Kilobytes => "kilobytes",
+            Unit::Megabytes => "megabytes",
+            Unit::Gigabytes => "gigabytes",
+            Unit::Kibibytes => "kibibytes",
+            Unit::Mebibytes => "mebibytes",
+            Unit::Gibibytes => "gibibytes",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Splits a quantity such as `"30s"`, `"1.5 hours"`, or `"64 MiB"` into its amount and unit.
+pub fn parse_quantity(s: &str) -> Result<(f64, Unit), String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(s.len());
+    let (amount, unit) = s.split_at(split);
+    let amount = amount
+        .parse::<f64>()
+        .map_err(|_| format!("\"{}\" is not a quantity", s))?;
+    let unit = unit.trim();
+    if unit.is_empty() {
+        return Err(format!("\"{}\" has no unit", s));
+    }
+    match Unit::parse(unit) {
+        Some(unit) => Ok((amount, unit)),
+        None => Err(format!("\"{}\" is not a known unit", unit)),
+    }
+}
+
+/// Returns the `{value, unit}` annotation form as a single quantity string, or `None` if
+/// `val` isn't an annotated value.
+pub(crate) fn annotated_scalar(val: &Yaml) -> Option<Yaml> {
+    let map = val.as_hash()?;
+    if map.len() != 2 {
+        return None;
+    }
+    let unit = map.get(&Yaml::String("unit".to_string()))?.as_str()?;
+    let amount = match map.get(&Yaml::String("value".to_string()))? {
+        Yaml::Integer(i) => i.to_string(),
+        Yaml::Real(r) => r.clone(),
+        _ => return None,
+    };
+    Some(Yaml::String(format!("{} {}", amount, unit)))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::units::{parse_quantity, Unit};
+    use crate::Value;
+
+    #[test]
+    fn quantities_are_parsed() {
+        assert_eq!(parse_quantity("30s").unwrap(), (30.0, Unit::Seconds));
+        assert_eq!(parse_quantity(" 1.5 hours ").unwrap(), (1.5, Unit::Hours));
+        assert_eq!(parse_quantity("64 MiB").unwrap(), (64.0, Unit::Mebibytes));
+        assert!(parse_quantity("30").is_err());
+        assert!(parse_quantity("30 fortnights").is_err());
+        assert!(parse_quantity("s").is_err());
+    }
+
+    #[test]
+    fn values_are_converted_to_the_expected_unit() {
+        let ms = Unit::Milliseconds;
+
+        assert_eq!(
+            ms.convert(&Value::String("30s".to_string())),
+            Ok(Value::I64(30000))
+        );
+        assert_eq!(
+            ms.convert(&Value::String("1500 us".to_string())),
+            Ok(Value::F64(1.5))
+        );
+        assert_eq!(ms.convert(&Value::I64(250)), Ok(Value::I64(250)));
+        assert_eq!(
+            Unit::Bytes.convert(&Value::String("2 KiB".to_string())),
+            Ok(Value::I64(2048))
+        );
+        assert!(ms.convert(&Value::String("30 MB".to_string())).is_err());
+        assert!(ms.convert(&Value::Bool(true)).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Dry-run validation.
+//!
+//! `validate` runs the same pipeline as `ConfigBuilder::build` — reading under the options'
+//! `base_dir`, symlink policy, and read budget, parsing, tag resolution, environment
+//! resolution, and, with `validate_with_schema`, schema checks — but reports what it found
+//! instead of returning the values. It is meant for `--check-config` flags and CI gates.
+use crate::builder::ValueSourceKind;
+use crate::{ConfigBuilder, LoadOptions, ParseError, Schema};
+
+/// A summary of a configuration that loaded successfully.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Every flattened key, in document order.
+    pub keys: Vec<String>,
+    /// The keys whose value would be taken from the environment, in document order.
+    pub env_keys: Vec<String>,
+    /// The keys left `null` with no environment variable to provide them, which only load
+    /// when `LoadOptions::allow_missing` allows it.
+    pub missing_keys: Vec<String>,
+}
+
+impl ValidationReport {
+    /// The number of keys the configuration would contain.
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+}
+
+/// Checks that the file at `file_path` loads with `options` without producing a configuration.
+///
+/// The problem found is returned as an error, exactly as `ConfigBuilder::build` would report it.
+///
+/// # Examples
+///
+/// ```rust
+/// use yaml_config::{validate, LoadOptions};
+/// match validate("path/to/yaml/file.yaml", &LoadOptions::default()) {
+///     Ok(report) => println!("configuration OK, {} keys", report.key_count()),
+///     Err(e) => eprintln!("invalid configuration: {}", e),
+/// }
+/// ```
+pub fn validate(file_path: &str, options: &LoadOptions) -> Result<ValidationReport, ParseError> {
+    validate_with_schema(file_path, options, Schema::new())
+}
+
+/// Checks that the file at `file_path` loads with `options` and satisfies `schema`, as
+/// `ConfigBuilder::with_schema` would check it, without producing a configuration.
+pub fn validate_with_schema(
+    file_path: &str,
+    options: &LoadOptions,
+    schema: Schema,
+) -> Result<ValidationReport, ParseError> {
+    let config = ConfigBuilder::new()
+        .with_options(options.clone())
+        .with_schema(schema)
+        .add_file(file_path)
+        .build()?;
+
+    let keys: Vec<String> = config.values().keys().cloned().collect();
+    let env_keys = keys
+        .iter()
+        .filter(|key| {
+            config
+                .source_of(key)
+                .is_some_and(|source| source.kind == ValueSourceKind::Env)
+        })
+        .cloned()
+        .collect();
+    Ok(ValidationReport {
+        keys,
+        env_keys,
+        missing_keys: config.missing_keys().to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{validate, validate_with_schema, KeySpec, LoadOptions, OnMissing, Schema};
+    use envtestkit::lock::lock_test;
+    use envtestkit::set_env;
+    use std::ffi::OsString;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn reports_keys_and_their_origin() {
+        let _lock = lock_test();
+        let _test = set_env(OsString::from("VALIDATE_PASSWORD"), "hunter2");
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.yaml");
+        let mut file = File::create(&file_path).unwrap();
+        writeln!(file, "validate:\n  host: db\n  password: null").unwrap();
+
+        let report = validate(file_path.to_str().unwrap(), &LoadOptions::default()).unwrap();
+
+        assert_eq!(report.keys, vec!["VALIDATE_HOST", "VALIDATE_PASSWORD"]);
+        assert_eq!(report.env_keys, vec!["VALIDATE_PASSWORD"]);
+        assert_eq!(report.key_count(), 2);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn reports_the_first_problem() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.yaml");
+        let mut file = File::create(&file_path).unwrap();
+        writeln!(file, "validate:\n  missing: null").unwrap();
+
+        assert!(validate(file_path.to_str().unwrap(), &LoadOptions::default()).is_err());
+        assert!(validate("does/not/exist.yaml", &LoadOptions::default()).is_err());
+
+        let options = LoadOptions {
+            base_dir: Some(dir.path().join("config")),
+            ..Default::default()
+        };
+        assert!(validate(file_path.to_str().unwrap(), &options).is_err());
+
+        writeln!(file, "  port: 8080").unwrap();
+        let options = LoadOptions {
+            allow_missing: OnMissing::Null,
+            ..Default::default()
+        };
+        let schema = Schema::new().key("VALIDATE_HOST", KeySpec::new().required());
+        assert!(validate(file_path.to_str().unwrap(), &options).is_ok());
+        let error =
+            validate_with_schema(file_path.to_str().unwrap(), &options, schema).unwrap_err();
+        assert!(error.to_string().contains("VALIDATE_HOST"));
+
+        dir.close().unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+//! JavaScript bindings for WebAssembly.
+//!
+//! With the `wasm` feature the crate exports `loadStr(yaml, options)`, which parses a document
+//! exactly as `load_with` does on the backend and returns the flattened configuration as a
+//! plain object, so a web UI can preview how a file will be resolved. Build it with e.g.
+//! `cargo rustc --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib`
+//! followed by `wasm-bindgen --target web`.
+//!
+//! ```js
+//! import init, { loadStr } from "./yaml_config.js";
+//! await init();
+//! const config = loadStr("db:\n  port: 5432\n", { preferEnv: true });
+//! console.log(config.DB_PORT); // 5432
+//! ```
+//!
+//! `options` may be omitted or hold any of:
+//!
+//! * `preferEnv` - a boolean, as `Preference::PreferEnv`.
+//! * `flattenArrays` - a boolean, as `LoadOptions::flatten_arrays`.
+//! * `rootKey` - a string, as `LoadOptions::root_key`.
+//! * `emptyConfig` - a boolean; when set an empty document is an empty object rather than an
+//!   error.
+//!
+//! There is no process environment in a browser, so values are resolved from the YAML alone, as
+//! with `OnEnvUnavailable::YamlOnly`: `null` values fail to resolve and no value is overridden.
+//! Integers are returned as numbers and so lose precision beyond 2^53. Errors are thrown as a
+//! JavaScript `Error` carrying the `ParseError` message.
+use crate::types::duration;
+use crate::{parse_str, LoadOptions, OnEmpty, OnEnvUnavailable, Preference, Value};
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+/// Reads the property `name` of `options`, if `options` is an object.
+fn option(options: &JsValue, name: &str) -> Option<JsValue> {
+    if !options.is_object() {
+        return None;
+    }
+    Reflect::get(options, &JsValue::from_str(name))
+        .ok()
+        .filter(|val| !val.is_undefined() && !val.is_null())
+}
+
+/// Converts the JavaScript options object into `LoadOptions`.
+fn load_options(options: &JsValue) -> LoadOptions {
+    let flag = |name| option(options, name).and_then(|val| val.as_bool());
+    let mut load_options = LoadOptions {
+        on_env_unavailable: OnEnvUnavailable::YamlOnly,
+        ..Default::default()
+    };
+    if flag("preferEnv") == Some(true) {
+        load_options.preference = Preference::PreferEnv;
+    }
+    if let Some(flatten_arrays) = flag("flattenArrays") {
+        load_options.flatten_arrays = flatten_arrays;
+    }
+    if let Some(root_key) = option(options, "rootKey").and_then(|val| val.as_string()) {
+        load_options.root_key = root_key;
+    }
+    if flag("emptyConfig") == Some(true) {
+        load_options.on_empty = OnEmpty::EmptyConfig;
+    }
+    load_options
+}
+
+/// Converts `val` into the equivalent JavaScript value.
+fn to_js(val: &Value) -> JsValue {
+    match val {
+        Value::I32(v) => JsValue::from(*v),
+        Value::I64(v) => JsValue::from(*v as f64),
+        Value::F32(v) => JsValue::from(*v),
+        Value::F64(v) => JsValue::from(*v),
+        Value::String(v) => JsValue::from_str(v),
+        Value::Bool(v) => JsValue::from_bool(*v),
+        Value::Array(items) => items.iter().map(to_js).collect::<Array>().into(),
+        Value::Map(map) => to_object(map).into(),
+        Value::Duration(v) => JsValue::from_str(&duration::format(*v)),
+        Value::Null => JsValue::NULL,
+    }
+}
+
+/// Builds an object holding `entries` in order.
+fn to_object<'a, I>(entries: I) -> Object
+where
+    I: IntoIterator<Item = (&'a String, &'a Value)>,
+{
+    let object = Object::new();
+    for (key, val) in entries {
+        // Setting a property on a plain object can't fail.
+        let _ = Reflect::set(&object, &JsValue::from_str(key), &to_js(val));
+    }
+    object
+}
+
+/// Parses the YAML document `yaml` and returns the flattened configuration as an object.
+#[wasm_bindgen(js_name = loadStr)]
+pub fn load_str(yaml: &str, options: JsValue) -> Result<Object, JsError> {
+    let config =
+        parse_str(yaml, &load_options(&options)).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(to_object(&config))
+}
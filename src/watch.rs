@@ -0,0 +1,217 @@
+//! Reloading a `SharedConfig` when its files change.
+//!
+//! A `Watcher` polls the modification time and length of a set of files from a background
+//! thread and reloads the shared configuration with `SharedConfig::reload_from`, naming `watch`
+//! as the source, when any of them changes. The reload is gated like any other, so a change
+//! that breaks the schema is rejected and the previous snapshot kept.
+//!
+//! Editors often write a file several times when saving it, so a change is only acted on once
+//! the files have stayed the same for the debounce period. Reloads are also applied at most once
+//! per minimum interval, so a file rewritten in a loop doesn't keep swapping the snapshot under
+//! its readers. Changes made during the interval are picked up as soon as it has passed.
+//!
+//! **Examples**
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use yaml_config::watch::Watcher;
+//! use yaml_config::{ConfigBuilder, SharedConfig};
+//! let build = || ConfigBuilder::new().add_file("config.yaml").build();
+//! let shared = SharedConfig::new(build().unwrap());
+//! let _watch = Watcher::new(shared.clone(), &["config.yaml"], build)
+//!     .debounce(Duration::from_millis(500))
+//!     .min_interval(Duration::from_secs(5))
+//!     .spawn();
+//! ```
+use crate::{Config, ParseError, SharedConfig};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the files are checked unless set with `Watcher::poll_interval`.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the files must stay the same unless set with `Watcher::debounce`.
+pub const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// The least time between applied reloads unless set with `Watcher::min_interval`.
+pub const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What identifies a version of a file, or `None` if it can't be read.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamps(paths: &[PathBuf]) -> Vec<Stamp> {
+    paths
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+/// Decides when changes to the files call for a reload.
+#[derive(Debug)]
+struct Pending {
+    debounce: Duration,
+    min_interval: Duration,
+    seen: Vec<Stamp>,
+    // When the files last changed, if that change hasn't been reloaded yet.
+    changed_at: Option<Instant>,
+    applied_at: Option<Instant>,
+}
+
+impl Pending {
+    /// Records the files as `current` at `now`, returning `true` when a reload is due.
+    fn tick(&mut self, current: Vec<Stamp>, now: Instant) -> bool {
+        if current != self.seen {
+            self.seen = current;
+            self.changed_at = Some(now);
+            return false;
+        }
+        let settled = self
+            .changed_at
+            .is_some_and(|at| now.duration_since(at) >= self.debounce);
+        let allowed = self
+            .applied_at
+            .is_none_or(|at| now.duration_since(at) >= self.min_interval);
+        if settled && allowed {
+            self.changed_at = None;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Reloads a `SharedConfig` with `build` when the files it reads change.
+pub struct Watcher<F> {
+    shared: SharedConfig,
+    paths: Vec<PathBuf>,
+    build: F,
+    poll_interval: Duration,
+    debounce: Duration,
+    min_interval: Duration,
+}
+
+impl<F> Watcher<F>
+where
+    F: FnMut() -> Result<Config, ParseError> + Send + 'static,
+{
+    /// Watches `paths`, reloading `shared` with `build` when they change.
+    pub fn new<P: AsRef<Path>>(shared: SharedConfig, paths: &[P], build: F) -> Self {
+        Watcher {
+            shared,
+            paths: paths
+                .iter()
+                .map(|path| path.as_ref().to_path_buf())
+                .collect(),
+            build,
+            poll_interval: POLL_INTERVAL,
+            debounce: DEBOUNCE,
+            min_interval: MIN_INTERVAL,
+        }
+    }
+
+    /// Checks the files every `interval`.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Waits until the files have stayed the same for `debounce` before reloading.
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Applies reloads at least `interval` apart. Reloads that are rejected don't count.
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Starts watching on a background thread, until the returned handle is dropped.
+    pub fn spawn(mut self) -> WatchHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            let mut pending = Pending {
+                debounce: self.debounce,
+                min_interval: self.min_interval,
+                seen: stamps(&self.paths),
+                changed_at: None,
+                applied_at: None,
+            };
+            while !stopped.load(Ordering::Relaxed) {
+                thread::sleep(self.poll_interval);
+                let now = Instant::now();
+                if !pending.tick(stamps(&self.paths), now) {
+                    continue;
+                }
+                // A rejected reload is recorded in `SharedConfig::last_reload_failure`.
+                if self.shared.reload_from("watch", &mut self.build).is_ok() {
+                    pending.applied_at = Some(now);
+                }
+            }
+        });
+        WatchHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Stops the watcher when dropped.
+#[derive(Debug)]
+pub struct WatchHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::watch::Pending;
+    use std::time::{Duration, Instant, SystemTime};
+
+    #[test]
+    fn bursts_of_writes_cause_one_reload_per_interval() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let version = |n| vec![Some((SystemTime::UNIX_EPOCH, n))];
+        let mut pending = Pending {
+            debounce: Duration::from_millis(100),
+            min_interval: Duration::from_millis(1000),
+            seen: version(0),
+            changed_at: None,
+            applied_at: None,
+        };
+
+        // Three quick writes are reloaded once, after they settle.
+        assert!(!pending.tick(version(1), at(0)));
+        assert!(!pending.tick(version(2), at(50)));
+        assert!(!pending.tick(version(3), at(80)));
+        assert!(!pending.tick(version(3), at(150)));
+        assert!(pending.tick(version(3), at(180)));
+        pending.applied_at = Some(at(180));
+        assert!(!pending.tick(version(3), at(300)));
+
+        // A write soon after waits for the minimum interval.
+        assert!(!pending.tick(version(4), at(400)));
+        assert!(!pending.tick(version(4), at(600)));
+        assert!(!pending.tick(version(4), at(1100)));
+        assert!(pending.tick(version(4), at(1180)));
+    }
+}
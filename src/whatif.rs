@@ -0,0 +1,140 @@
+//! Hypothetical resolution of a configuration.
+//!
+//! `Config::resolve_with` resolves a configuration again under a different environment or
+//! with different file contents and returns a `WhatIf`: the configuration that would result and
+//! how it differs from the current one, powering "what would change if I set X?" tooling
+//! without touching the live process.
+//!
+//! **Examples**
+//!
+//! ```rust,no_run
+//! use std::collections::HashMap;
+//! use yaml_config::ConfigBuilder;
+//! let config = ConfigBuilder::new()
+//!     .add_file("config/base.yaml")
+//!     .add_env_prefix("APP_")
+//!     .build()
+//!     .unwrap();
+//! let env = HashMap::from([("APP_DB_POOL_SIZE".to_string(), "64".to_string())]);
+//! let files = HashMap::from([(
+//!     "config/base.yaml".to_string(),
+//!     std::fs::read_to_string("config/base.next.yaml").unwrap(),
+//! )]);
+//! print!("{}", config.resolve_with(&env, &files).unwrap().diff);
+//! ```
+use crate::{snapshot, Config, Value};
+
+/// The value of a key before and after a hypothetical resolution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub key: String,
+    /// The current value, or `None` if the key isn't set.
+    pub current: Option<Value>,
+    /// The value the key would take, or `None` if it would no longer be set.
+    pub resolved: Option<Value>,
+}
+
+/// The outcome of `Config::resolve_with`.
+#[derive(Debug, Clone)]
+pub struct WhatIf {
+    /// The configuration that would result.
+    pub config: Config,
+    /// Every key whose value would change, in the order of the current configuration followed
+    /// by the keys that would be added. Values are not masked, so secrets are included.
+    pub changes: Vec<Change>,
+    /// The changes in the format of `snapshot::render`, with removed lines starting with `-`
+    /// and added lines with `+`. Secret values are masked.
+    pub diff: String,
+}
+
+/// Compares `current` with the hypothetical configuration `resolved`.
+pub(crate) fn compare(current: &Config, resolved: Config) -> WhatIf {
+    let mut changes: Vec<Change> = current
+        .values()
+        .iter()
+        .filter(|(key, val)| resolved.values().get(*key) != Some(*val))
+        .map(|(key, val)| Change {
+            key: key.clone(),
+            current: Some(val.clone()),
+            resolved: resolved.values().get(key).cloned(),
+        })
+        .collect();
+    changes.extend(
+        resolved
+            .values()
+            .iter()
+            .filter(|(key, _)| !current.contains_key(key))
+            .map(|(key, val)| Change {
+                key: key.clone(),
+                current: None,
+                resolved: Some(val.clone()),
+            }),
+    );
+    WhatIf {
+        diff: snapshot::changes(current, &resolved),
+        config: resolved,
+        changes,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::whatif::Change;
+    use crate::{ConfigBuilder, KeySpec, Schema, Value, Visibility};
+    use std::collections::HashMap;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn overrides_are_resolved_without_touching_the_environment_or_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.yaml");
+        fs::write(&path, "db:\n  host: localhost\n  password: hunter2\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        let config = ConfigBuilder::new()
+            .with_schema(
+                Schema::new().key("DB_PASSWORD", KeySpec::new().visibility(Visibility::Secret)),
+            )
+            .add_file(&path)
+            .add_env_prefix("WHATIF_TEST_")
+            .build()
+            .unwrap();
+
+        let env = HashMap::from([("WHATIF_TEST_DB_POOL".to_string(), "8".to_string())]);
+        let files = HashMap::from([(
+            path.clone(),
+            "db:\n  host: db.internal\n  password: swordfish\n".to_string(),
+        )]);
+        let what_if = config.resolve_with(&env, &files).unwrap();
+
+        assert_eq!(
+            what_if.changes,
+            vec![
+                Change {
+                    key: "DB_HOST".to_string(),
+                    current: Some(Value::from("localhost")),
+                    resolved: Some(Value::from("db.internal")),
+                },
+                Change {
+                    key: "DB_PASSWORD".to_string(),
+                    current: Some(Value::from("hunter2")),
+                    resolved: Some(Value::from("swordfish")),
+                },
+                Change {
+                    key: "DB_POOL".to_string(),
+                    current: None,
+                    resolved: Some(Value::I64(8)),
+                },
+            ]
+        );
+        assert_eq!(
+            what_if.diff,
+            "- DB_HOST = \"localhost\"\n+ DB_HOST = \"db.internal\"\n+ DB_POOL = 8\n"
+        );
+        assert_eq!(config.get("DB_HOST"), Some(&Value::from("localhost")));
+        assert!(fs::read_to_string(&path).unwrap().contains("localhost"));
+
+        let unknown = HashMap::from([("other.yaml".to_string(), String::new())]);
+        assert!(config.resolve_with(&HashMap::new(), &unknown).is_err());
+    }
+}
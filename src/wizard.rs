@@ -0,0 +1,264 @@
+//! Setup wizards built from a schema.
+//!
+//! `Schema::questions` lists one `Question` per declared key, with the prompt set by
+//! `KeySpec::prompt`, the declared `ValueKind`, the default, and whether an answer is required,
+//! so a CLI can walk a new installation through its settings. `Question::answer` parses and
+//! checks a reply against the spec.
+//!
+//! `Schema::collect_interactive` is a ready-made wizard asking every question on a reader and
+//! writer, such as stdin and stdout, and returning a YAML document holding the answers:
+//!
+//! ```text
+//! Database host [localhost]: db.internal
+//! Database port (integer) [5432]:
+//! Admin password: set ADMIN_PASSWORD in the environment.
+//! ```
+//!
+//! Empty replies take the default. Keys without a default that aren't required are left out
+//! of the document. Invalid replies are reported and the question asked again. The document
+//! holds one top-level key per flattened key, e.g. `db_host: "db.internal"`, so it loads back
+//! into the same keys. Keys with `Visibility::Secret` are never asked for nor shown: they are
+//! written as `~`, so their values come from the environment.
+use crate::json::{write_str, write_value};
+use crate::messages;
+use crate::schema::{KeySpec, Schema, ValueKind, Visibility};
+use crate::{infer_value, ParseError, Value};
+use std::io::{BufRead, Write};
+
+fn wizard_error(message: String) -> ParseError {
+    ParseError::new("config::wizard", message)
+}
+
+/// A key a setup wizard asks for.
+#[derive(Debug, Clone, Copy)]
+pub struct Question<'a> {
+    /// The flattened key.
+    pub key: &'a str,
+    spec: &'a KeySpec,
+}
+
+impl<'a> Question<'a> {
+    /// The text asking for the value: the spec's prompt, or else the key.
+    pub fn prompt(&self) -> &'a str {
+        self.spec.get_prompt().unwrap_or(self.key)
+    }
+
+    /// The declared type of the value, if any.
+    pub fn kind(&self) -> Option<ValueKind> {
+        self.spec.get_kind()
+    }
+
+    /// The value used when the reply is empty, if any.
+    pub fn default(&self) -> Option<&'a Value> {
+        self.spec.get_default()
+    }
+
+    /// Whether the question must be answered when it has no default.
+    pub fn is_required(&self) -> bool {
+        self.spec.is_required()
+    }
+
+    /// Whether the value is a secret, which a wizard shouldn't echo or write to a file.
+    pub fn is_secret(&self) -> bool {
+        self.spec.get_visibility() == Visibility::Secret
+    }
+
+    /// Parses `reply` as the declared type, or infers its type as for an environment
+    /// variable, and runs the spec's validator. An empty reply yields the default, or `None`
+    /// if there is none and the key isn't required. Infinite and NaN floats, which YAML can't
+    /// hold, are rejected, or kept as strings when no type is declared.
+    pub fn answer(&self, reply: &str) -> Result<Option<Value>, String> {
+        let reply = reply.trim();
+        if reply.is_empty() {
+            return match self.default() {
+                Some(default) => Ok(Some(default.clone())),
+                None => self.spec.check(self.key, None).map(|()| None),
+            };
+        }
+
+        let val = match self.kind() {
+            None => match infer_value(reply.to_string()) {
+                Value::F64(val) if !val.is_finite() => Value::String(reply.to_string()),
+                val => val,
+            },
+            Some(ValueKind::String) => Value::String(reply.to_string()),
+            Some(kind) => {
+                let (parsed, id) = match kind {
+                    ValueKind::Integer => {
+                        (reply.parse().ok().map(Value::I64), "wizard.not_integer")
+                    }
+                    ValueKind::Float => (
+                        reply
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|val| val.is_finite())
+                            .map(Value::F64),
+                        "wizard.not_float",
+                    ),
+                    _ => (reply.parse().ok().map(Value::Bool), "wizard.not_boolean"),
+                };
+                parsed.ok_or_else(|| messages::text(id, &[("reply", reply)]))?
+            }
+        };
+        self.spec.check(self.key, Some(&val))?;
+        Ok(Some(val))
+    }
+}
+
+impl Schema {
+    /// Lists a question for every declared key, in declaration order.
+    pub fn questions(&self) -> Vec<Question<'_>> {
+        self.iter()
+            .map(|(key, spec)| Question { key, spec })
+            .collect()
+    }
+
+    /// Asks every question on `writer`, reading the replies from `reader`, and returns a YAML
+    /// document holding the answers. Fails if `reader` ends before every question is answered.
+    ///
+    /// **Examples**
+    ///
+    /// ```rust
+    /// use yaml_config::schema::ValueKind;
+    /// use yaml_config::{KeySpec, Schema};
+    /// let schema = Schema::new()
+    ///     .key("DB_HOST", KeySpec::new().prompt("Database host").required())
+    ///     .key("DB_PORT", KeySpec::new().kind(ValueKind::Integer).default_value(5432_i64));
+    /// let mut prompts = Vec::new();
+    /// let yaml = schema
+    ///     .collect_interactive(&b"db.internal\n\n"[..], &mut prompts)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "db_host: \"db.internal\"\ndb_port: 5432\n");
+    /// ```
+    pub fn collect_interactive<R: BufRead, W: Write>(
+        &self,
+        mut reader: R,
+        mut writer: W,
+    ) -> Result<String, ParseError> {
+        let mut yaml = String::new();
+        for question in self.questions() {
+            if question.is_secret() {
+                let text = messages::text(
+                    "wizard.secret",
+                    &[("prompt", question.prompt()), ("var", question.key)],
+                );
+                writeln!(writer, "{}", text)?;
+                write_key(question.key, &mut yaml);
+                yaml.push_str("~\n");
+                continue;
+            }
+            let answer = loop {
+                write!(writer, "{}", question.prompt())?;
+                if let Some(kind) = question.kind() {
+                    write!(writer, " ({})", kind)?;
+                }
+                if let Some(default) = question.default() {
+                    let mut text = String::new();
+                    write_value(default, &mut text);
+                    write!(writer, " [{}]", text)?;
+                }
+                write!(writer, ": ")?;
+                writer.flush()?;
+
+                let mut reply = String::new();
+                if reader.read_line(&mut reply)? == 0 {
+                    return Err(wizard_error(messages::text(
+                        "wizard.input_ended",
+                        &[("key", question.key)],
+                    )));
+                }
+                match question.answer(&reply) {
+                    Ok(answer) => break answer,
+                    Err(e) => writeln!(writer, "{}", e)?,
+                }
+            };
+
+            if let Some(val) = answer {
+                write_key(question.key, &mut yaml);
+                write_value(&val, &mut yaml);
+                yaml.push('\n');
+            }
+        }
+        Ok(yaml)
+    }
+}
+
+/// Writes the top-level key of the flattened `key` and its colon to `yaml`.
+fn write_key(key: &str, yaml: &mut String) {
+    let key = key.to_lowercase();
+    if key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        yaml.push_str(&key);
+    } else {
+        write_str(&key, yaml);
+    }
+    yaml.push_str(": ");
+}
+
+#[cfg(test)]
+mod test {
+    use crate::schema::{ValueKind, Visibility};
+    use crate::{parse_str, KeySpec, LoadOptions, Schema, Value};
+
+    #[test]
+    fn invalid_replies_are_asked_again() {
+        let schema = Schema::new()
+            .key(
+                "SERVER_PORT",
+                KeySpec::new()
+                    .prompt("Port")
+                    .kind(ValueKind::Integer)
+                    .validator(|val| match val.as_i64() {
+                        Some(port) if *port > 0 => Ok(()),
+                        _ => Err("must be positive".to_string()),
+                    }),
+            )
+            .key("ADMIN_EMAIL", KeySpec::new().required())
+            .key("DEBUG", KeySpec::new().kind(ValueKind::Boolean));
+
+        let mut prompts = Vec::new();
+        let yaml = schema
+            .collect_interactive(
+                &b"eighty\n-1\n8080\n\nops@example.com\n\n"[..],
+                &mut prompts,
+            )
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(prompts).unwrap(),
+            "Port (integer): \"eighty\" is not an integer.\nPort (integer): SERVER_PORT: must be \
+             positive\nPort (integer): ADMIN_EMAIL: ADMIN_EMAIL is required.\nADMIN_EMAIL: \
+             DEBUG (boolean): "
+        );
+        let values = parse_str(&yaml, &LoadOptions::default()).unwrap();
+        assert_eq!(values["SERVER_PORT"], Value::I64(8080));
+        assert_eq!(values["ADMIN_EMAIL"], Value::from("ops@example.com"));
+        assert!(!values.contains_key("DEBUG"));
+
+        assert!(schema
+            .collect_interactive(&b"8080\n"[..], Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn secrets_and_non_finite_floats_are_never_written() {
+        let schema = Schema::new()
+            .key("RATIO", KeySpec::new().kind(ValueKind::Float))
+            .key(
+                "ADMIN_PASSWORD",
+                KeySpec::new()
+                    .visibility(Visibility::Secret)
+                    .default_value("hunter2"),
+            );
+
+        let mut prompts = Vec::new();
+        let yaml = schema
+            .collect_interactive(&b"inf\nNaN\n0.5\n"[..], &mut prompts)
+            .unwrap();
+
+        let prompts = String::from_utf8(prompts).unwrap();
+        assert!(prompts.contains("\"inf\" is not a float."));
+        assert!(prompts.ends_with("ADMIN_PASSWORD: set ADMIN_PASSWORD in the environment.\n"));
+        assert!(!prompts.contains("hunter2"));
+        assert_eq!(yaml, "ratio: 0.5\nadmin_password: ~\n");
+    }
+}